@@ -0,0 +1,184 @@
+use std::path::PathBuf;
+use lopdf::{Document, Object};
+
+use crate::sanitize::{remove_additional_actions, remove_open_action};
+use crate::report;
+use crate::utils::{load_for_update, save_changes, SaveOptions, SaveReport};
+
+/// How a destination fits its page into the window. Coordinates left as None keep whatever the
+/// viewer currently has.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fit {
+    /// The whole page
+    Fit,
+    /// The page's bounding box
+    FitB,
+    /// The page's width, with this coordinate at the top of the window
+    FitH(Option<f64>),
+    /// The page's height, with this coordinate at the left of the window
+    FitV(Option<f64>),
+    /// The width of the bounding box, with this coordinate at the top of the window
+    FitBH(Option<f64>),
+    /// The height of the bounding box, with this coordinate at the left of the window
+    FitBV(Option<f64>),
+    /// Left, top and zoom factor
+    XYZ(Option<f64>, Option<f64>, Option<f64>),
+    /// The rectangle [llx lly urx ury]
+    FitR([f64; 4]),
+}
+
+/// A page and how to show it, for an /OpenAction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpenDestination {
+    pub page: u32,
+    pub fit: Fit,
+}
+
+impl OpenDestination {
+    /// Parses "page=N,mode" where mode is one of fit, fitb, fith[=top], fitv[=left], fitbh[=top],
+    /// fitbv[=left], xyz[=left:top:zoom] or fitr=llx:lly:urx:ury. The mode defaults to fit, and
+    /// empty xyz values are left to the viewer, e.g. "page=3,xyz=::1.5".
+    pub fn parse(s: &str) -> Result<OpenDestination, String> {
+        let mut page = None;
+        let mut fit = None;
+
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = match part.split_once('=') {
+                Some((key, value)) => (key.trim().to_ascii_lowercase(), Some(value.trim())),
+                None => (part.to_ascii_lowercase(), None),
+            };
+            if key == "page" {
+                let value = value.ok_or("page needs a number, e.g. \"page=1\"")?;
+                let number: u32 = value.parse().map_err(|_| format!("`{}` is not a page number", value))?;
+                if number == 0 { return Err("pages are numbered from 1".to_string()); }
+                page = Some(number);
+                continue;
+            }
+            if fit.is_some() {
+                return Err(format!("`{}` is a second fit mode, only one is allowed", part));
+            }
+            fit = Some(parse_fit(&key, value)?);
+        }
+
+        let page = page.ok_or_else(|| format!("expected \"page=N,mode\", found `{}`", s))?;
+        Ok(OpenDestination { page, fit: fit.unwrap_or(Fit::Fit) })
+    }
+}
+
+/// Clears or sets the action run when a PDF is opened, either inplace or in a new file
+///
+/// * `infile` - a PathBuf of the file to change
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `set` - where the document should open, or None to remove the /OpenAction
+/// * `strip_additional_actions` - also remove the /AA actions of the catalog and pages
+/// * `compress` - a boolean flag to compress the outfile before saving
+/// * `save` - how to write the file, see SaveOptions
+///
+pub fn open_action(infile: PathBuf,
+                   outfile: Option<PathBuf>,
+                   set: Option<OpenDestination>,
+                   strip_additional_actions: bool,
+                   compress: bool,
+                   save: &SaveOptions) -> SaveReport {
+    let (mut doc, before) = load_for_update(&infile);
+
+    match set {
+        Some(destination) => {
+            if let Err(error) = set_open_action(&mut doc, &destination) {
+                panic!("Failed to set the open action: {}", error);
+            }
+            report::summary(format!("the document now opens at page {}", destination.page));
+        }
+        None => {
+            let report = remove_open_action(&mut doc);
+            if report.entries == 0 {
+                report::summary("the document has no open action");
+            } else {
+                report::summary("removed the open action");
+            }
+        }
+    }
+
+    if strip_additional_actions {
+        // entries counts each key and its value
+        let report = remove_additional_actions(&mut doc);
+        report::summary(format!("removed {} additional actions dictionaries", report.entries / 2));
+    }
+
+    if compress { report::timed("compress", || doc.compress()) }
+
+    save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Replaces the catalog's /OpenAction with an explicit destination
+///
+/// Returns an error if the page does not exist.
+pub fn set_open_action(doc: &mut Document, destination: &OpenDestination) -> Result<(), String> {
+    let pages = doc.get_pages();
+    let page_id = *pages.get(&destination.page)
+        .ok_or_else(|| format!("page {} does not exist, the document has {} pages", destination.page, pages.len()))?;
+
+    let coordinate = |value: Option<f64>| value.map_or(Object::Null, Object::Real);
+    let mut array: Vec<Object> = vec![page_id.into()];
+    match destination.fit {
+        Fit::Fit => array.push("Fit".into()),
+        Fit::FitB => array.push("FitB".into()),
+        Fit::FitH(top) => array.extend(["FitH".into(), coordinate(top)]),
+        Fit::FitV(left) => array.extend(["FitV".into(), coordinate(left)]),
+        Fit::FitBH(top) => array.extend(["FitBH".into(), coordinate(top)]),
+        Fit::FitBV(left) => array.extend(["FitBV".into(), coordinate(left)]),
+        Fit::XYZ(left, top, zoom) => array.extend(["XYZ".into(), coordinate(left), coordinate(top), coordinate(zoom)]),
+        Fit::FitR(rect) => {
+            array.push("FitR".into());
+            array.extend(rect.iter().map(|v| Object::Real(*v)));
+        }
+    }
+
+    let catalog_id = doc.trailer.get(b"Root")
+        .and_then(Object::as_reference)
+        .map_err(|_| "the document has no catalog".to_string())?;
+    doc.get_object_mut(catalog_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(|e| format!("the catalog is not a dictionary: {}", e))?
+        .set("OpenAction", array);
+
+    Ok(())
+}
+
+
+// ------- Helpers -------
+
+fn parse_fit(mode: &str, value: Option<&str>) -> Result<Fit, String> {
+    let numbers = |count: usize| -> Result<Vec<Option<f64>>, String> {
+        let values: Vec<&str> = value.map(|v| v.split(':').collect()).unwrap_or_default();
+        if values.len() > count {
+            return Err(format!("{} takes at most {} values, found {}", mode, count, values.len()));
+        }
+        (0..count)
+            .map(|i| match values.get(i).map(|v| v.trim()) {
+                None | Some("") => Ok(None),
+                Some(v) => v.parse::<f64>().map(Some).map_err(|_| format!("`{}` is not a number", v)),
+            })
+            .collect()
+    };
+
+    let fit = match mode {
+        "fit" | "fitb" if value.is_some() => return Err(format!("{} does not take a value", mode)),
+        "fit" => Fit::Fit,
+        "fitb" => Fit::FitB,
+        "fith" => Fit::FitH(numbers(1)?[0]),
+        "fitv" => Fit::FitV(numbers(1)?[0]),
+        "fitbh" => Fit::FitBH(numbers(1)?[0]),
+        "fitbv" => Fit::FitBV(numbers(1)?[0]),
+        "xyz" => {
+            let values = numbers(3)?;
+            Fit::XYZ(values[0], values[1], values[2])
+        }
+        "fitr" => match numbers(4)?[..] {
+            [Some(llx), Some(lly), Some(urx), Some(ury)] => Fit::FitR([llx, lly, urx, ury]),
+            _ => return Err("fitr needs a rectangle, e.g. \"fitr=0:0:300:400\"".to_string()),
+        },
+        _ => return Err(format!("`{}` is not a fit mode, expected one of fit, fitb, fith, fitv, fitbh, fitbv, xyz or fitr", mode)),
+    };
+    Ok(fit)
+}
@@ -0,0 +1,227 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime}
+};
+use lopdf::{Document, Object, ObjectId};
+
+use crate::links::page_annotations;
+use crate::names::name_tree_entries;
+use crate::report;
+use crate::utils::{decode_text_string, load_pdf};
+
+/// A file embedded in a PDF
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// The file name stored in the file specification
+    pub name: String,
+    /// Page of the file attachment annotation, None for document level attachments
+    pub page: Option<u32>,
+    /// The decoded file contents
+    pub data: Vec<u8>,
+    /// Modification date from the embedded file's /Params, in seconds since the Unix epoch
+    pub modified: Option<i64>,
+}
+
+/// Prints the name, size and location of every file attached to a PDF
+///
+/// * `infile` - a PathBuf of the file to list attachments from
+///
+pub fn print_attachments(infile: PathBuf) {
+    let doc = load_pdf(&infile);
+
+    for attachment in list_attachments(&doc) {
+        let location = match attachment.page {
+            Some(page) => format!("page {}", page),
+            None => "document".to_string(),
+        };
+        println!("{:<40} {:>10} bytes  {}", attachment.name, attachment.data.len(), location);
+    }
+}
+
+/// Writes every attached file of a PDF to a directory, printing where each one went. Path
+/// separators in stored names are replaced, names that are already taken get a numeric suffix,
+/// and modification dates are restored when the PDF records them.
+///
+/// * `infile` - a PathBuf of the file to extract attachments from
+/// * `output_dir` - the directory to write the files to, created if missing
+/// * `flatten_names` - drop any folders from stored names and keep only the final file name
+///
+pub fn extract_attachments(infile: PathBuf, output_dir: PathBuf, flatten_names: bool) {
+    let doc = load_pdf(&infile);
+
+    if let Err(error) = fs::create_dir_all(&output_dir) {
+        panic!("Failed to create {}: {}", output_dir.display(), error);
+    }
+
+    let mut taken = HashSet::new();
+    for attachment in list_attachments(&doc) {
+        let file_name = unique_name(&output_file_name(&attachment.name, flatten_names), &mut taken);
+        let path = output_dir.join(&file_name);
+        if let Err(error) = write_attachment(&path, &attachment) {
+            panic!("Failed to write {}: {}", path.display(), error);
+        }
+        report::summary(format!("{} -> {} ({} bytes)", attachment.name, path.display(), attachment.data.len()));
+    }
+}
+
+/// Collects the embedded files of the document: first those in the /EmbeddedFiles name tree, then
+/// those of file attachment annotations in page order. A file referenced from both is listed once.
+pub fn list_attachments(doc: &Document) -> Vec<Attachment> {
+    let mut attachments = Vec::new();
+    let mut seen: HashSet<ObjectId> = HashSet::new();
+
+    let tree = doc.catalog().ok()
+        .and_then(|catalog| catalog.get(b"Names").ok())
+        .and_then(|names| doc.dereference(names).ok())
+        .and_then(|(_, names)| names.as_dict().ok())
+        .and_then(|names| names.get(b"EmbeddedFiles").ok())
+        .cloned();
+    if let Some(tree) = tree {
+        for (key, filespec) in name_tree_entries(doc, &tree) {
+            if let Some(attachment) = read_filespec(doc, &filespec, &decode_text_string(&key), None, &mut seen) {
+                attachments.push(attachment);
+            }
+        }
+    }
+
+    for (page, page_id) in doc.get_pages() {
+        for annot in page_annotations(doc, page_id) {
+            let annot = match doc.dereference(&annot).and_then(|(_, a)| a.as_dict()) {
+                Ok(annot) => annot,
+                Err(_) => continue,
+            };
+            if annot.get(b"Subtype").and_then(Object::as_name).ok() != Some(b"FileAttachment") { continue; }
+            let filespec = match annot.get(b"FS") {
+                Ok(filespec) => filespec,
+                Err(_) => continue,
+            };
+            let fallback = format!("page{}-attachment", page);
+            if let Some(attachment) = read_filespec(doc, filespec, &fallback, Some(page), &mut seen) {
+                attachments.push(attachment);
+            }
+        }
+    }
+
+    attachments
+}
+
+
+// ------- Helpers -------
+
+fn read_filespec(doc: &Document,
+                 filespec: &Object,
+                 fallback_name: &str,
+                 page: Option<u32>,
+                 seen: &mut HashSet<ObjectId>) -> Option<Attachment> {
+    let filespec = doc.dereference(filespec).ok()?.1.as_dict().ok()?;
+    let embedded = filespec.get(b"EF").ok()
+        .and_then(|ef| doc.dereference(ef).ok())
+        .and_then(|(_, ef)| ef.as_dict().ok())
+        .and_then(|ef| ef.get(b"UF").or_else(|_| ef.get(b"F")).ok())?;
+    let stream_id = embedded.as_reference().ok()?;
+    if !seen.insert(stream_id) { return None; }
+    let stream = doc.get_object(stream_id).and_then(Object::as_stream).ok()?;
+
+    let name = [b"UF".as_slice(), b"F"].iter()
+        .find_map(|key| filespec.get(key).and_then(Object::as_str).ok())
+        .map(decode_text_string)
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| fallback_name.to_string());
+    let data = if stream.dict.has(b"Filter") {
+        stream.decompressed_content().ok()?
+    } else {
+        stream.content.clone()
+    };
+    let modified = stream.dict.get(b"Params").ok()
+        .and_then(|params| doc.dereference(params).ok())
+        .and_then(|(_, params)| params.as_dict().ok())
+        .and_then(|params| params.get(b"ModDate").and_then(Object::as_str).ok())
+        .and_then(|date| parse_date(&String::from_utf8_lossy(date)));
+
+    Some(Attachment { name, page, data, modified })
+}
+
+// the stored name made safe to use as a single file name
+fn output_file_name(name: &str, flatten: bool) -> String {
+    let name = if flatten {
+        name.rsplit(['/', '\\']).next().unwrap_or(name).to_string()
+    } else {
+        name.replace(['/', '\\'], "_")
+    };
+    let name: String = name.chars()
+        .map(|c| if c.is_control() || c == ':' { '_' } else { c })
+        .collect();
+    match name.trim() {
+        "" | "." | ".." => "attachment".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+// report.xml, report-1.xml, report-2.xml, ...
+fn unique_name(name: &str, taken: &mut HashSet<String>) -> String {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    let mut candidate = name.to_string();
+    let mut suffix = 0;
+    while !taken.insert(candidate.to_lowercase()) {
+        suffix += 1;
+        candidate = format!("{}-{}{}", stem, suffix, extension);
+    }
+    candidate
+}
+
+fn write_attachment(path: &Path, attachment: &Attachment) -> std::io::Result<()> {
+    fs::write(path, &attachment.data)?;
+    if let Some(seconds) = attachment.modified {
+        let time = match u64::try_from(seconds) {
+            Ok(seconds) => SystemTime::UNIX_EPOCH + Duration::from_secs(seconds),
+            Err(_) => SystemTime::UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs()),
+        };
+        fs::File::options().write(true).open(path)?.set_modified(time)?;
+    }
+    Ok(())
+}
+
+// a PDF date, D:YYYYMMDDHHmmSSOHH'mm', as seconds since the Unix epoch. Everything after the
+// year is optional.
+fn parse_date(date: &str) -> Option<i64> {
+    let date = date.trim().trim_start_matches("D:");
+    let digits: String = date.chars().take_while(char::is_ascii_digit).collect();
+    if digits.len() < 4 { return None; }
+    let field = |start: usize, len: usize, default: i64| -> i64 {
+        digits.get(start..start + len).and_then(|v| v.parse().ok()).unwrap_or(default)
+    };
+    let (year, month, day) = (field(0, 4, 0), field(4, 2, 1), field(6, 2, 1));
+    let (hour, minute, second) = (field(8, 2, 0), field(10, 2, 0), field(12, 2, 0));
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    // the offset from UT of the local time the date is written in
+    let rest = &date[digits.len()..];
+    let offset = match rest.chars().next() {
+        Some(sign @ ('+' | '-')) => {
+            let numbers: Vec<i64> = rest[1..].split('\'').filter_map(|v| v.trim().parse().ok()).collect();
+            let minutes = numbers.first().unwrap_or(&0) * 60 + numbers.get(1).unwrap_or(&0);
+            if sign == '-' { -minutes * 60 } else { minutes * 60 }
+        }
+        _ => 0,
+    };
+
+    Some(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+// days since 1970-01-01 in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
@@ -0,0 +1,816 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf
+};
+use lopdf::{Bookmark, Dictionary, Document, Object, ObjectId};
+
+use crate::content::page_layout;
+use crate::dests::{explicit_destination, goto_destination, resolve_destination};
+use crate::links::{page_annotations, set_page_annotations};
+use crate::report;
+use crate::utils::{decode_text_string, encode_text_string, load_for_update, save_changes, SaveOptions, SaveReport};
+
+// gaps wider than this fraction of the font size are read as a space between words
+const WORD_GAP: f64 = 0.15;
+
+/// Summary of a destination shift
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ShiftReport {
+    /// Number of outline items retargeted
+    pub bookmarks: usize,
+    /// Number of link annotations retargeted
+    pub links: usize,
+    /// Outline items that would have left the document and now point at the first or last page
+    pub clamped: usize,
+    /// Link annotations removed because their target would have left the document
+    pub dropped: usize,
+}
+
+/// What `remap_to_pages` did to destinations outside the page set
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RemapReport {
+    pub links_dropped: usize,
+    pub links_retargeted: usize,
+    pub bookmarks_dropped: usize,
+    pub bookmarks_retargeted: usize,
+}
+
+/// A line of large text that `generate_bookmarks` treats as a heading
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    /// 1 for the largest headings, 2 for the next size down and so on
+    pub level: usize,
+    pub page: u32,
+    /// Font size in points, rounded to the nearest half point
+    pub size: f64,
+    pub title: String,
+}
+
+/// Moves every bookmark (and optionally every internal link) by a number of pages, either inplace
+/// or in a new file
+///
+/// * `infile` - a PathBuf of the file to shift bookmarks in
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `offset` - number of pages to move destinations by, negative to move them back
+/// * `links` - also retarget GoTo link annotations
+/// * `compress` - a boolean flag to compress the outfile before saving
+/// * `save` - how to write the file, see SaveOptions
+///
+pub fn shift_bookmarks(infile: PathBuf, outfile: Option<PathBuf>, offset: i64, links: bool, compress: bool, save: &SaveOptions) -> SaveReport {
+    let (mut doc, before) = load_for_update(&infile);
+
+    let report = shift_destinations(&mut doc, offset, links);
+    if report.clamped > 0 {
+        report::warning(format!("{} bookmarks would point past the ends of the document and were clamped to the first or last page", report.clamped));
+    }
+    if report.dropped > 0 {
+        report::warning(format!("{} links would point past the ends of the document and were removed", report.dropped));
+    }
+    report::summary(format!("shifted {} bookmarks and {} links by {} pages", report.bookmarks, report.links, offset));
+
+    if compress { report::timed("compress", || doc.compress()) }
+
+    save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Retargets outline items, and GoTo link annotations when `links` is set, `offset` pages further
+/// on in page order. Outline items that would fall off either end are clamped to the first or last
+/// page; links are removed instead. Destinations are rewritten as explicit /Dest arrays on the item
+/// itself, so named destinations and shared actions are left untouched. Items whose destination
+/// does not resolve to a page are skipped.
+pub fn shift_destinations(doc: &mut Document, offset: i64, links: bool) -> ShiftReport {
+    let mut report = ShiftReport::default();
+    let order: Vec<ObjectId> = doc.get_pages().into_values().collect();
+    let positions: BTreeMap<ObjectId, usize> = order.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+    for item_id in outline_items(doc) {
+        let item = match doc.get_dictionary(item_id) {
+            Ok(item) => item,
+            Err(_) => continue,
+        };
+        let (shifted, clamped) = match shifted_target(doc, item, &order, &positions, offset) {
+            Some(Shift::Moved(dest)) => (dest, false),
+            Some(Shift::OutOfRange(dest)) => (dest, true),
+            None => continue,
+        };
+        let retargeted = retargeted(doc, item, shifted);
+        doc.objects.insert(item_id, Object::Dictionary(retargeted));
+        report.bookmarks += 1;
+        if clamped { report.clamped += 1; }
+    }
+
+    if !links { return report; }
+
+    for page_id in order.clone() {
+        let mut kept = Vec::new();
+        let mut updates = Vec::new();
+        let mut changed = false;
+
+        for annot in page_annotations(doc, page_id) {
+            let (annot_id, dict) = match doc.dereference(&annot) {
+                Ok((id, Object::Dictionary(dict))) => (id, dict.clone()),
+                _ => {
+                    kept.push(annot);
+                    continue;
+                }
+            };
+            if dict.get(b"Subtype").and_then(Object::as_name).ok() != Some(b"Link") {
+                kept.push(annot);
+                continue;
+            }
+            match shifted_target(doc, &dict, &order, &positions, offset) {
+                Some(Shift::Moved(dest)) => {
+                    let retargeted = retargeted(doc, &dict, dest);
+                    match annot_id {
+                        Some(id) => {
+                            updates.push((id, retargeted));
+                            kept.push(annot);
+                        }
+                        None => kept.push(Object::Dictionary(retargeted)),
+                    }
+                    report.links += 1;
+                    changed = true;
+                }
+                Some(Shift::OutOfRange(_)) => {
+                    report.dropped += 1;
+                    changed = true;
+                }
+                None => kept.push(annot),
+            }
+        }
+
+        for (id, annot) in updates {
+            doc.objects.insert(id, Object::Dictionary(annot));
+        }
+        if changed {
+            set_page_annotations(doc, page_id, kept);
+        }
+    }
+
+    report
+}
+
+/// Deals with internal links and outline items whose destination page is not in `keep`, ahead of
+/// the other pages being removed. By default such links are deleted and such outline items taken
+/// out of the outline (their children move up a level); with `retarget` both are pointed at the
+/// nearest page that is kept instead, preferring the earlier page on a tie.
+pub fn remap_to_pages(doc: &mut Document, keep: &HashSet<ObjectId>, retarget: bool) -> RemapReport {
+    let mut report = RemapReport::default();
+    let order: Vec<ObjectId> = doc.get_pages().into_values().collect();
+    let kept_positions: Vec<usize> = order.iter().enumerate()
+        .filter(|(_, id)| keep.contains(id))
+        .map(|(i, _)| i)
+        .collect();
+
+    // None when the destination is fine as it is, Some(None) when it should go
+    let remap = |doc: &Document, dict: &Dictionary| -> Option<Option<Vec<Object>>> {
+        let dest = goto_destination(doc, dict)?;
+        let target = resolve_destination(doc, &dest)?;
+        if keep.contains(&target) { return None; }
+        if !retarget { return Some(None); }
+
+        let position = order.iter().position(|id| *id == target)?;
+        let nearest = kept_positions.iter().min_by_key(|p| (p.abs_diff(position), **p))?;
+        let mut explicit = explicit_destination(doc, &dest)?;
+        explicit[0] = Object::Reference(order[*nearest]);
+        Some(Some(explicit))
+    };
+
+    let mut dropped_items = HashSet::new();
+    for item_id in outline_items(doc) {
+        let item = match doc.get_dictionary(item_id) {
+            Ok(item) => item,
+            Err(_) => continue,
+        };
+        match remap(doc, item) {
+            Some(Some(dest)) => {
+                let retargeted = retargeted(doc, item, dest);
+                doc.objects.insert(item_id, Object::Dictionary(retargeted));
+                report.bookmarks_retargeted += 1;
+            }
+            Some(None) => {
+                dropped_items.insert(item_id);
+            }
+            None => {}
+        }
+    }
+    if !dropped_items.is_empty() {
+        report.bookmarks_dropped = dropped_items.len();
+        remove_outline_items(doc, &dropped_items);
+    }
+
+    for page_id in order.iter().filter(|id| keep.contains(id)) {
+        let mut kept = Vec::new();
+        let mut updates = Vec::new();
+        let mut changed = false;
+
+        for annot in page_annotations(doc, *page_id) {
+            let (annot_id, dict) = match doc.dereference(&annot) {
+                Ok((id, Object::Dictionary(dict))) if dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Link") => (id, dict.clone()),
+                _ => {
+                    kept.push(annot);
+                    continue;
+                }
+            };
+            match remap(doc, &dict) {
+                Some(Some(dest)) => {
+                    let retargeted = retargeted(doc, &dict, dest);
+                    match annot_id {
+                        Some(id) => {
+                            updates.push((id, retargeted));
+                            kept.push(annot);
+                        }
+                        None => kept.push(Object::Dictionary(retargeted)),
+                    }
+                    report.links_retargeted += 1;
+                    changed = true;
+                }
+                Some(None) => {
+                    report.links_dropped += 1;
+                    changed = true;
+                }
+                None => kept.push(annot),
+            }
+        }
+
+        for (id, annot) in updates {
+            doc.objects.insert(id, Object::Dictionary(annot));
+        }
+        if changed {
+            set_page_annotations(doc, *page_id, kept);
+        }
+    }
+
+    report
+}
+
+/// Replaces the outline of a PDF with one built from its headings, either inplace or in a new file.
+/// Headings are guessed from font sizes, so check the candidates with `dry_run` first.
+///
+/// * `infile` - a PathBuf of the file to generate bookmarks for
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `min_size` - the smallest font size, in points, that counts as a heading
+/// * `levels` - how many heading sizes to use, each becomes one level of nesting
+/// * `dry_run` - print the headings that were found without changing the file
+/// * `compress` - a boolean flag to compress the outfile before saving
+/// * `save` - how to write the file, see SaveOptions
+///
+pub fn generate_bookmarks(infile: PathBuf, outfile: Option<PathBuf>, min_size: f64, levels: usize, dry_run: bool, compress: bool, save: &SaveOptions) -> Option<SaveReport> {
+    let (mut doc, before) = load_for_update(&infile);
+
+    let headings = find_headings(&doc, min_size, levels);
+    if dry_run {
+        for heading in &headings {
+            println!("{}{}  (page {}, {}pt)", "  ".repeat(heading.level - 1), heading.title, heading.page, heading.size);
+        }
+        return None;
+    }
+
+    if set_outline(&mut doc, &headings).is_none() {
+        report::warning(format!("no headings of {}pt or larger were found, the outline was not changed", min_size));
+    }
+    report::summary(format!("generated {} bookmarks", headings.len()));
+
+    if compress { report::timed("compress", || doc.compress()) }
+
+    Some(save_changes(&mut doc, &infile, outfile, save, before))
+}
+
+/// Scans the text of every page for runs set at `min_size` points or larger. Consecutive runs of
+/// the same size become one heading, and the `levels` largest sizes found become heading levels;
+/// text in any smaller size is ignored, as is text with no letters in it (page numbers, rules).
+pub fn find_headings(doc: &Document, min_size: f64, levels: usize) -> Vec<Heading> {
+    let mut candidates: Vec<Heading> = Vec::new();
+
+    for (page, page_id) in doc.get_pages() {
+        let page_layout = match page_layout(doc, page_id) {
+            Ok((_, page_layout)) => page_layout,
+            Err(_) => continue,
+        };
+        let mut current: Option<Heading> = None;
+        let mut last_right = f64::MAX;
+
+        for run in &page_layout.runs {
+            if run.glyphs.iter().all(|g| g.unicode.trim().is_empty()) { continue; }
+            let size = (run.size * 2.0).round() / 2.0;
+
+            if size < min_size || current.as_ref().is_some_and(|c| c.size != size) {
+                candidates.extend(current.take());
+            }
+            if size < min_size { continue; }
+
+            let heading = current.get_or_insert_with(|| {
+                last_right = f64::MAX;
+                Heading { level: 0, page, size, title: String::new() }
+            });
+            for glyph in &run.glyphs {
+                if glyph.bbox[0] - last_right > WORD_GAP * size {
+                    heading.title.push(' ');
+                }
+                heading.title.push_str(&glyph.unicode);
+                last_right = glyph.bbox[2];
+            }
+        }
+        candidates.extend(current);
+    }
+
+    let mut sizes: Vec<f64> = Vec::new();
+    for candidate in &mut candidates {
+        candidate.title = candidate.title.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !sizes.contains(&candidate.size) { sizes.push(candidate.size); }
+    }
+    sizes.sort_by(|a, b| b.total_cmp(a));
+    sizes.truncate(levels);
+
+    candidates.into_iter()
+        .filter(|c| c.title.chars().any(char::is_alphabetic))
+        .filter_map(|mut c| {
+            c.level = sizes.iter().position(|s| *s == c.size)? + 1;
+            Some(c)
+        })
+        .collect()
+}
+
+/// Replaces the document outline with one bookmark per heading, nested by level. Each bookmark
+/// shows the whole page its heading is on.
+///
+/// Returns the id of the new outline root, or None (leaving the outline alone) if there are no headings.
+pub fn set_outline(doc: &mut Document, headings: &[Heading]) -> Option<ObjectId> {
+    if headings.is_empty() { return None; }
+    let pages = doc.get_pages();
+
+    doc.bookmarks.clear();
+    doc.bookmark_table.clear();
+    let mut parents: Vec<(usize, u32)> = Vec::new();
+    for heading in headings {
+        while parents.last().is_some_and(|(level, _)| *level >= heading.level) {
+            parents.pop();
+        }
+        let page_id = *pages.get(&heading.page)?;
+        let bookmark = Bookmark::new(heading.title.clone(), [0.0, 0.0, 0.0], 0, page_id);
+        let id = doc.add_bookmark(bookmark, parents.last().map(|(_, id)| *id));
+        parents.push((heading.level, id));
+    }
+
+    let outline_id = doc.build_outline()?;
+    // save_pdf builds the outline again from whatever is left in the bookmark table
+    doc.bookmarks.clear();
+    doc.bookmark_table.clear();
+
+    if let Ok(Object::Dictionary(outline)) = doc.get_object_mut(outline_id) {
+        outline.set("Type", "Outlines");
+    }
+    let catalog_id = doc.trailer.get(b"Root").and_then(Object::as_reference).ok()?;
+    if let Ok(Object::Dictionary(catalog)) = doc.get_object_mut(catalog_id) {
+        catalog.set("Outlines", outline_id);
+    }
+
+    // lopdf writes titles as raw UTF-8, which readers take for PDFDocEncoding
+    for (item_id, heading) in outline_items(doc).into_iter().zip(headings) {
+        if let Ok(Object::Dictionary(item)) = doc.get_object_mut(item_id) {
+            item.set("Title", encode_text_string(&heading.title));
+        }
+    }
+
+    Some(outline_id)
+}
+
+/// Fixes broken outline structure, either inplace or in a new file, printing each correction
+///
+/// * `infile` - a PathBuf of the file to repair
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `compress` - a boolean flag to compress the outfile before saving
+/// * `save` - how to write the file, see SaveOptions
+///
+pub fn repair_bookmarks(infile: PathBuf, outfile: Option<PathBuf>, compress: bool, save: &SaveOptions) -> SaveReport {
+    let (mut doc, before) = load_for_update(&infile);
+
+    let fixes = repair_outline(&mut doc);
+    for fix in &fixes {
+        println!("{}", fix);
+    }
+    report::summary(format!("made {} corrections to the outline", fixes.len()));
+
+    if compress { report::timed("compress", || doc.compress()) }
+
+    save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Re-walks the outline from /Outlines /First along /First and /Next, then rewrites every item's
+/// /Parent, /Prev, /Next, /First and /Last to match what was walked and recomputes the /Count
+/// values. Items keep whether they are open or closed (the sign of /Count, closed when missing).
+/// Items that can only be reached through /Last or /Prev are removed.
+///
+/// Returns a description of every correction made.
+pub fn repair_outline(doc: &mut Document) -> Vec<String> {
+    let mut fixes = Vec::new();
+    let outlines_id = match doc.catalog().ok().and_then(|c| reference(c, b"Outlines")) {
+        Some(id) => id,
+        None => return fixes,
+    };
+    let claimed = outline_pointers(doc, outlines_id);
+
+    let first = doc.get_dictionary(outlines_id).ok().and_then(|o| reference(o, b"First"));
+    let mut visited = HashSet::from([outlines_id]);
+    let (children, visible) = repair_children(doc, outlines_id, first, &mut visited, &mut fixes);
+
+    if let Ok(root) = doc.get_object_mut(outlines_id).and_then(Object::as_dict_mut) {
+        fix_pointer(root, b"First", children.first().copied(), "outline root", &mut fixes);
+        fix_pointer(root, b"Last", children.last().copied(), "outline root", &mut fixes);
+        fix_count(root, Some(visible).filter(|v| *v > 0), "outline root", &mut fixes);
+    }
+
+    for id in claimed.difference(&visited) {
+        let title = item_label(doc, *id);
+        if doc.get_dictionary(*id).is_ok_and(|item| item.has(b"Title")) {
+            doc.objects.remove(id);
+            fixes.push(format!("{}: removed, it is not reachable from the outline root", title));
+        }
+    }
+
+    fixes
+}
+
+/// Removes the document outline, and the page mode that opens it. Returns how many items it had.
+pub fn remove_outline(doc: &mut Document) -> usize {
+    let count = outline_items(doc).len();
+    let root_id = doc.trailer.get(b"Root").and_then(Object::as_reference);
+    if let Ok(catalog) = root_id.and_then(|id| doc.get_object_mut(id)).and_then(Object::as_dict_mut) {
+        catalog.remove(b"Outlines");
+        if catalog.get(b"PageMode").and_then(Object::as_name).is_ok_and(|mode| mode == b"UseOutlines") {
+            catalog.remove(b"PageMode");
+        }
+    }
+    count
+}
+
+/// Every item in the document outline, depth first in reading order. Cycles in /First and /Next
+/// are ignored.
+pub fn outline_items(doc: &Document) -> Vec<ObjectId> {
+    let mut items = Vec::new();
+    let mut visited = HashSet::new();
+    let first = doc.catalog().ok()
+        .and_then(|catalog| catalog.get(b"Outlines").ok())
+        .and_then(|outlines| doc.dereference(outlines).ok())
+        .and_then(|(_, outlines)| outlines.as_dict().ok())
+        .and_then(|outlines| outlines.get(b"First").and_then(Object::as_reference).ok());
+
+    if let Some(first) = first {
+        collect_outline(doc, first, &mut items, &mut visited);
+    }
+    items
+}
+
+/// The number of top level outline items, None when the document has no outline
+pub fn top_level_count(doc: &Document) -> Option<usize> {
+    let outlines_id = doc.catalog().ok().and_then(|catalog| reference(catalog, b"Outlines"))?;
+    let first = doc.get_dictionary(outlines_id).ok().and_then(|outlines| reference(outlines, b"First"));
+    Some(outline_tree(doc, first, &mut HashSet::new()).len())
+}
+
+/// The title and page number of every top level outline item, in outline order. Items whose
+/// destination is not a page of this document, or that have none, are left out.
+pub fn top_level_bookmarks(doc: &Document) -> Vec<(String, u32)> {
+    let numbers: BTreeMap<ObjectId, u32> = doc.get_pages().into_iter().map(|(number, id)| (id, number)).collect();
+    let first = doc.catalog().ok()
+        .and_then(|catalog| reference(catalog, b"Outlines"))
+        .and_then(|outlines_id| doc.get_dictionary(outlines_id).ok())
+        .and_then(|outlines| reference(outlines, b"First"));
+
+    outline_tree(doc, first, &mut HashSet::new()).into_iter()
+        .filter_map(|node| {
+            let item = doc.get_dictionary(node.id).ok()?;
+            let page = *numbers.get(&resolve_destination(doc, &goto_destination(doc, item)?)?)?;
+            let title = item.get(b"Title").ok()
+                .and_then(|title| doc.dereference(title).ok())
+                .and_then(|(_, title)| title.as_str().ok())
+                .map(decode_text_string)
+                .unwrap_or_default();
+            Some((title, page))
+        })
+        .collect()
+}
+
+/// Readies the outline of a document to be moved into another one. Destinations are rewritten as
+/// explicit /Dest arrays pointing at the page object, as the names and page indices they may use
+/// mean nothing in the other document. Items whose destination does not lead to a page of this
+/// document are taken out of the outline (their children move up a level); items with other
+/// actions, or none, are left as they are.
+///
+/// Returns the top level items in order, empty when there is no outline.
+pub fn portable_outline(doc: &mut Document) -> Vec<ObjectId> {
+    let mut dropped_items = HashSet::new();
+    for item_id in outline_items(doc) {
+        let item = match doc.get_dictionary(item_id) {
+            Ok(item) => item,
+            Err(_) => continue,
+        };
+        let dest = match goto_destination(doc, item) {
+            Some(dest) => dest,
+            None => continue,
+        };
+        let explicit = resolve_destination(doc, &dest)
+            .zip(explicit_destination(doc, &dest));
+        match explicit {
+            Some((page_id, mut explicit)) => {
+                let portable = matches!(&dest, Object::Array(arr) if arr.first().and_then(|o| o.as_reference().ok()) == Some(page_id));
+                if !portable {
+                    explicit[0] = Object::Reference(page_id);
+                    let retargeted = retargeted(doc, item, explicit);
+                    doc.objects.insert(item_id, Object::Dictionary(retargeted));
+                }
+            }
+            None => {
+                dropped_items.insert(item_id);
+            }
+        }
+    }
+    if !dropped_items.is_empty() {
+        remove_outline_items(doc, &dropped_items);
+    }
+
+    let outlines_id = match doc.catalog().ok().and_then(|c| reference(c, b"Outlines")) {
+        Some(id) => id,
+        None => return Vec::new(),
+    };
+    let first = doc.get_dictionary(outlines_id).ok().and_then(|o| reference(o, b"First"));
+    outline_tree(doc, first, &mut HashSet::new()).into_iter().map(|node| node.id).collect()
+}
+
+/// Makes `items` the top level of a new outline, in order, and points the catalog at it. Each item
+/// keeps the items below it.
+///
+/// Returns the id of the new outline root, or None (leaving the outline alone) if there are no items.
+pub fn join_outlines(doc: &mut Document, items: &[ObjectId]) -> Option<ObjectId> {
+    if items.is_empty() { return None; }
+
+    let mut root = Dictionary::new();
+    root.set("Type", "Outlines");
+    root.set("First", items[0]);
+    let outline_id = doc.add_object(root);
+    for (i, item_id) in items.iter().enumerate() {
+        if let Ok(item) = doc.get_object_mut(*item_id).and_then(Object::as_dict_mut) {
+            item.set("Parent", outline_id);
+            match items.get(i + 1) {
+                Some(next) => item.set("Next", *next),
+                None => { item.remove(b"Next"); }
+            }
+        }
+    }
+
+    let catalog_id = doc.trailer.get(b"Root").and_then(Object::as_reference).ok()?;
+    if let Ok(Object::Dictionary(catalog)) = doc.get_object_mut(catalog_id) {
+        catalog.set("Outlines", outline_id);
+    }
+    // the sibling chain is right now, let the repair pass sort out the back links and counts
+    repair_outline(doc);
+
+    Some(outline_id)
+}
+
+
+// ------- Helpers -------
+
+struct OutlineNode {
+    id: ObjectId,
+    children: Vec<OutlineNode>,
+}
+
+// takes items out of the outline, moving their children up into their place
+fn remove_outline_items(doc: &mut Document, remove: &HashSet<ObjectId>) {
+    let outlines_id = match doc.catalog().ok().and_then(|c| reference(c, b"Outlines")) {
+        Some(id) => id,
+        None => return,
+    };
+    let first = doc.get_dictionary(outlines_id).ok().and_then(|o| reference(o, b"First"));
+    let tree = outline_tree(doc, first, &mut HashSet::new());
+    let tree = splice_out(tree, remove);
+
+    link_outline(doc, outlines_id, &tree);
+    // the sibling chains are right now, let the repair pass sort out the back links and counts
+    repair_outline(doc);
+}
+
+fn outline_tree(doc: &Document, first: Option<ObjectId>, visited: &mut HashSet<ObjectId>) -> Vec<OutlineNode> {
+    let mut nodes = Vec::new();
+    let mut current = first;
+    while let Some(id) = current {
+        if !visited.insert(id) { break; }
+        let item = match doc.get_dictionary(id) {
+            Ok(item) => item,
+            Err(_) => break,
+        };
+        current = reference(item, b"Next");
+        let children = outline_tree(doc, reference(item, b"First"), visited);
+        nodes.push(OutlineNode { id, children });
+    }
+    nodes
+}
+
+fn splice_out(nodes: Vec<OutlineNode>, remove: &HashSet<ObjectId>) -> Vec<OutlineNode> {
+    let mut kept = Vec::new();
+    for node in nodes {
+        let children = splice_out(node.children, remove);
+        if remove.contains(&node.id) {
+            kept.extend(children);
+        } else {
+            kept.push(OutlineNode { id: node.id, children });
+        }
+    }
+    kept
+}
+
+fn link_outline(doc: &mut Document, parent_id: ObjectId, nodes: &[OutlineNode]) {
+    if let Ok(parent) = doc.get_object_mut(parent_id).and_then(Object::as_dict_mut) {
+        match nodes.first() {
+            Some(first) => parent.set("First", first.id),
+            None => { parent.remove(b"First"); }
+        }
+    }
+    for (i, node) in nodes.iter().enumerate() {
+        if let Ok(item) = doc.get_object_mut(node.id).and_then(Object::as_dict_mut) {
+            match nodes.get(i + 1) {
+                Some(next) => item.set("Next", next.id),
+                None => { item.remove(b"Next"); }
+            }
+        }
+        link_outline(doc, node.id, &node.children);
+    }
+}
+
+enum Shift {
+    Moved(Vec<Object>),
+    /// the destination clamped to the first or last page
+    OutOfRange(Vec<Object>),
+}
+
+fn shifted_target(doc: &Document,
+                  dict: &Dictionary,
+                  order: &[ObjectId],
+                  positions: &BTreeMap<ObjectId, usize>,
+                  offset: i64) -> Option<Shift> {
+    let dest = goto_destination(doc, dict)?;
+    let position = *positions.get(&resolve_destination(doc, &dest)?)? as i64;
+    let mut explicit = explicit_destination(doc, &dest)?;
+
+    let target = position + offset;
+    let clamped = target.clamp(0, order.len() as i64 - 1);
+    explicit[0] = Object::Reference(order[clamped as usize]);
+
+    if clamped == target {
+        Some(Shift::Moved(explicit))
+    } else {
+        Some(Shift::OutOfRange(explicit))
+    }
+}
+
+// a GoTo action becomes a plain /Dest, other actions are never passed in here
+pub(crate) fn retargeted(doc: &Document, dict: &Dictionary, dest: Vec<Object>) -> Dictionary {
+    let mut dict = dict.clone();
+    if dict.get(b"Dest").is_err() {
+        let is_goto = dict.get(b"A").ok()
+            .and_then(|a| doc.dereference(a).ok())
+            .and_then(|(_, a)| a.as_dict().ok())
+            .and_then(|a| a.get(b"S").and_then(Object::as_name).ok())
+            == Some(b"GoTo");
+        if is_goto { dict.remove(b"A"); }
+    }
+    dict.set("Dest", dest);
+    dict
+}
+
+fn collect_outline(doc: &Document, first: ObjectId, items: &mut Vec<ObjectId>, visited: &mut HashSet<ObjectId>) {
+    let mut current = Some(first);
+    while let Some(id) = current {
+        if !visited.insert(id) { return; }
+        let item = match doc.get_dictionary(id) {
+            Ok(item) => item,
+            Err(_) => return,
+        };
+        items.push(id);
+        if let Ok(child) = item.get(b"First").and_then(Object::as_reference) {
+            collect_outline(doc, child, items, visited);
+        }
+        current = item.get(b"Next").and_then(Object::as_reference).ok();
+    }
+}
+
+fn reference(dict: &Dictionary, key: &[u8]) -> Option<ObjectId> {
+    dict.get(key).and_then(Object::as_reference).ok()
+}
+
+fn item_label(doc: &Document, id: ObjectId) -> String {
+    doc.get_dictionary(id).ok()
+        .and_then(|item| item.get(b"Title").ok())
+        .and_then(|title| doc.dereference(title).ok())
+        .and_then(|(_, title)| title.as_str().ok())
+        .map(|title| format!("\"{}\"", decode_text_string(title)))
+        .unwrap_or_else(|| format!("item {} {} R", id.0, id.1))
+}
+
+// everything the outline points at through any of its sibling or child pointers
+fn outline_pointers(doc: &Document, outlines_id: ObjectId) -> HashSet<ObjectId> {
+    let mut seen = HashSet::new();
+    let mut queue = vec![outlines_id];
+    while let Some(id) = queue.pop() {
+        if !seen.insert(id) { continue; }
+        if let Ok(item) = doc.get_dictionary(id) {
+            for key in [b"First".as_slice(), b"Last", b"Next", b"Prev"] {
+                queue.extend(reference(item, key));
+            }
+        }
+    }
+    seen.remove(&outlines_id);
+    seen
+}
+
+// repairs the children of `parent_id`, returning them and how many of their descendants are
+// visible when the parent is open
+fn repair_children(doc: &mut Document,
+                   parent_id: ObjectId,
+                   first: Option<ObjectId>,
+                   visited: &mut HashSet<ObjectId>,
+                   fixes: &mut Vec<String>) -> (Vec<ObjectId>, i64) {
+    let mut children = Vec::new();
+    let mut current = first;
+    while let Some(id) = current {
+        if !visited.insert(id) {
+            fixes.push(format!("{}: cut a loop back to {}", item_label(doc, parent_id), item_label(doc, id)));
+            break;
+        }
+        match doc.get_dictionary(id) {
+            Ok(item) => {
+                children.push(id);
+                current = reference(item, b"Next");
+            }
+            Err(_) => {
+                fixes.push(format!("{}: dropped a reference to missing item {} {} R", item_label(doc, parent_id), id.0, id.1));
+                break;
+            }
+        }
+    }
+
+    let mut visible = 0;
+    for (i, id) in children.iter().enumerate() {
+        let first_child = doc.get_dictionary(*id).ok().and_then(|item| reference(item, b"First"));
+        let (grandchildren, descendants) = repair_children(doc, *id, first_child, visited, fixes);
+
+        let title = item_label(doc, *id);
+        let mut item = match doc.get_dictionary(*id) {
+            Ok(item) => item.clone(),
+            Err(_) => continue,
+        };
+        let open = item.get(b"Count").and_then(Object::as_i64).is_ok_and(|count| count > 0);
+        let count = match (grandchildren.is_empty(), open) {
+            (true, _) => None,
+            (false, true) => Some(descendants),
+            (false, false) => Some(-descendants),
+        };
+
+        fix_pointer(&mut item, b"Parent", Some(parent_id), &title, fixes);
+        fix_pointer(&mut item, b"Prev", i.checked_sub(1).map(|prev| children[prev]), &title, fixes);
+        fix_pointer(&mut item, b"Next", children.get(i + 1).copied(), &title, fixes);
+        fix_pointer(&mut item, b"First", grandchildren.first().copied(), &title, fixes);
+        fix_pointer(&mut item, b"Last", grandchildren.last().copied(), &title, fixes);
+        fix_count(&mut item, count, &title, fixes);
+        doc.objects.insert(*id, Object::Dictionary(item));
+
+        visible += 1 + if open { descendants } else { 0 };
+    }
+
+    (children, visible)
+}
+
+fn fix_pointer(item: &mut Dictionary, key: &[u8], expected: Option<ObjectId>, title: &str, fixes: &mut Vec<String>) {
+    let current = reference(item, key);
+    if current == expected && (expected.is_some() || !item.has(key)) { return; }
+
+    let name = String::from_utf8_lossy(key);
+    match (current, expected) {
+        (_, Some(id)) => {
+            item.set(key.to_vec(), id);
+            fixes.push(format!("{}: set /{} to {} {} R", title, name, id.0, id.1));
+        }
+        (_, None) => {
+            item.remove(key);
+            fixes.push(format!("{}: removed /{}", title, name));
+        }
+    }
+}
+
+fn fix_count(item: &mut Dictionary, expected: Option<i64>, title: &str, fixes: &mut Vec<String>) {
+    let current = item.get(b"Count").and_then(Object::as_i64).ok();
+    if current == expected && (expected.is_some() || !item.has(b"Count")) { return; }
+
+    let was = current.map_or("missing".to_string(), |count| count.to_string());
+    match expected {
+        Some(count) => {
+            item.set("Count", count);
+            fixes.push(format!("{}: /Count {} -> {}", title, was, count));
+        }
+        None => {
+            item.remove(b"Count");
+            fixes.push(format!("{}: removed /Count {}", title, was));
+        }
+    }
+}
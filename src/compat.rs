@@ -0,0 +1,57 @@
+use std::ffi::OsString;
+
+/// Other tools whose command line habits tpdf accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Compat {
+    /// `cat`/`burst` verbs and the `output` keyword before the outfile
+    Pdftk,
+}
+
+// Subcommands, and their pdftk verbs, where pdftk writes `output <outfile>`
+const OUTPUT_KEYWORD_COMMANDS: [&str; 2] = ["merge", "split"];
+const PDFTK_VERBS: [&str; 2] = ["cat", "burst"];
+
+/// Drops pdftk's `output` keyword, e.g. `tpdf cat a.pdf b.pdf output out.pdf`, so the rest of the
+/// command line parses as usual. Only done for the pdftk verbs, or for merge and split with
+/// `--compat pdftk`, so a file really named `output` is not touched otherwise. Runs before clap
+/// sees the arguments.
+///
+/// # Arguments
+///
+/// * `args` - the raw command line, program name included
+///
+pub fn strip_output_keyword(mut args: Vec<OsString>) -> Vec<OsString> {
+    let mut compat = false;
+    let mut command = None;
+
+    let mut index = 1;
+    while index < args.len() {
+        let arg = args[index].to_str().unwrap_or("");
+        if arg == "--compat" {
+            compat |= args.get(index + 1).is_some_and(|value| value == "pdftk");
+            index += 1;
+        } else if arg == "--compat=pdftk" {
+            compat = true;
+        } else if command.is_none() && !arg.starts_with('-') {
+            command = Some(index);
+        }
+        index += 1;
+    }
+
+    let command = match command {
+        Some(command) => command,
+        None => return args,
+    };
+    let name = args[command].to_str().unwrap_or("");
+    let pdftk_style = PDFTK_VERBS.contains(&name) || (compat && OUTPUT_KEYWORD_COMMANDS.contains(&name));
+    if !pdftk_style { return args; }
+
+    // The last `output` that still has an outfile after it
+    let keyword = (command + 1..args.len()).rev()
+        .find(|&i| args[i] == "output"
+                   && args.get(i + 1).is_some_and(|next| !next.to_str().unwrap_or("").starts_with('-')));
+    if let Some(keyword) = keyword {
+        args.remove(keyword);
+    }
+    args
+}
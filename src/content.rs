@@ -0,0 +1,848 @@
+//! Content stream tokenizing and a basic model of where text and images land on a page.
+//!
+//! lopdf can decode content streams but silently stops at anything it does not understand (inline
+//! images in particular), which is fine for reading and dangerous for rewriting. The tokenizer here
+//! errors instead, and keeps inline images as a single `BI` operation so they survive a round trip.
+
+use std::collections::{BTreeMap, HashMap};
+use lopdf::{content::{Content, Operation}, Dictionary, Document, Object, ObjectId, StringFormat};
+
+use crate::utils::inherited_attribute;
+
+/// Identity transformation matrix [a b c d e f]
+pub(crate) const IDENTITY: [f64; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+// approximate glyph extents relative to the font size, used when building glyph boxes
+const DESCENT: f64 = -0.2;
+const ASCENT: f64 = 0.8;
+
+/// Splits a decoded content stream into operations. Inline images become one `BI` operation whose
+/// operands are the image dictionary and the raw image data.
+pub(crate) fn parse_content(data: &[u8]) -> Result<Vec<Operation>, String> {
+    let mut lexer = Lexer { data, pos: 0 };
+    let mut operations = Vec::new();
+    let mut operands = Vec::new();
+
+    while let Some(token) = lexer.next_token()? {
+        match token {
+            Token::Object(object) => operands.push(object),
+            Token::Keyword(keyword) if keyword == "BI" => {
+                if !operands.is_empty() {
+                    return Err(format!("unexpected operands before inline image at byte {}", lexer.pos));
+                }
+                let (dict, image) = lexer.inline_image()?;
+                operations.push(Operation::new("BI", vec![Object::Dictionary(dict), Object::String(image, StringFormat::Literal)]));
+            }
+            Token::Keyword(keyword) => {
+                operations.push(Operation::new(&keyword, std::mem::take(&mut operands)));
+            }
+            Token::ArrayEnd | Token::DictEnd => {
+                return Err(format!("unbalanced delimiter at byte {}", lexer.pos));
+            }
+        }
+    }
+
+    if !operands.is_empty() {
+        return Err("content stream ends with operands but no operator".to_string());
+    }
+
+    Ok(operations)
+}
+
+/// Writes operations back out as content stream bytes
+pub(crate) fn encode_content(operations: &[Operation]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    let mut start = 0;
+
+    for (i, operation) in operations.iter().enumerate() {
+        if operation.operator != "BI" { continue; }
+
+        buffer.extend(encode_plain(&operations[start..i]));
+        if let (Some(Object::Dictionary(dict)), Some(Object::String(image, _))) = (operation.operands.first(), operation.operands.get(1)) {
+            buffer.extend_from_slice(b"BI\n");
+            for (key, value) in dict.iter() {
+                buffer.extend(encode_plain(&[Operation::new("", vec![Object::Name(key.clone()), value.clone()])]));
+            }
+            buffer.extend_from_slice(b"ID ");
+            buffer.extend_from_slice(image);
+            buffer.extend_from_slice(b"\nEI\n");
+        }
+        start = i + 1;
+    }
+    buffer.extend(encode_plain(&operations[start..]));
+
+    buffer
+}
+
+/// Multiplies two transformation matrices, `m1` applied first
+pub(crate) fn multiply(m1: &[f64; 6], m2: &[f64; 6]) -> [f64; 6] {
+    [
+        m1[0] * m2[0] + m1[1] * m2[2],
+        m1[0] * m2[1] + m1[1] * m2[3],
+        m1[2] * m2[0] + m1[3] * m2[2],
+        m1[2] * m2[1] + m1[3] * m2[3],
+        m1[4] * m2[0] + m1[5] * m2[2] + m2[4],
+        m1[4] * m2[1] + m1[5] * m2[3] + m2[5],
+    ]
+}
+
+/// Transforms a point by a matrix
+pub(crate) fn transform(m: &[f64; 6], x: f64, y: f64) -> (f64, f64) {
+    (x * m[0] + y * m[2] + m[4], x * m[1] + y * m[3] + m[5])
+}
+
+/// The axis aligned bounding box [llx lly urx ury] of a rectangle after transformation
+pub(crate) fn transform_box(m: &[f64; 6], x0: f64, y0: f64, x1: f64, y1: f64) -> [f64; 4] {
+    let corners = [transform(m, x0, y0), transform(m, x1, y0), transform(m, x0, y1), transform(m, x1, y1)];
+    let mut bbox = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+    for (x, y) in corners {
+        bbox[0] = bbox[0].min(x);
+        bbox[1] = bbox[1].min(y);
+        bbox[2] = bbox[2].max(x);
+        bbox[3] = bbox[3].max(y);
+    }
+    bbox
+}
+
+/// The widths and Unicode mapping needed to lay out text in one font
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FontMetrics {
+    /// Composite (Type0) fonts use two byte character codes
+    pub two_byte: bool,
+    /// Glyph widths in thousandths of a text space unit, by character code
+    pub widths: HashMap<u32, f64>,
+    pub default_width: f64,
+    /// Character code to Unicode, from the font's /ToUnicode CMap
+    pub to_unicode: HashMap<u32, String>,
+    /// Name of a simple font's base encoding, used when there is no /ToUnicode
+    pub encoding: Option<String>,
+}
+
+impl FontMetrics {
+    pub(crate) fn width(&self, code: u32) -> f64 {
+        *self.widths.get(&code).unwrap_or(&self.default_width)
+    }
+
+    pub(crate) fn unicode(&self, code: u32, bytes: &[u8]) -> String {
+        match self.to_unicode.get(&code) {
+            Some(text) => text.clone(),
+            None if self.two_byte => "\u{FFFD}".to_string(),
+            None => Document::decode_text(self.encoding.as_deref(), bytes),
+        }
+    }
+
+    /// Splits a shown string into (code, bytes) pairs
+    pub(crate) fn codes<'a>(&self, bytes: &'a [u8]) -> Vec<(u32, &'a [u8])> {
+        if self.two_byte {
+            bytes.chunks(2)
+                .map(|c| (c.iter().fold(0u32, |acc, b| (acc << 8) | *b as u32), c))
+                .collect()
+        } else {
+            bytes.chunks(1).map(|c| (c[0] as u32, c)).collect()
+        }
+    }
+}
+
+/// Loads metrics for every font in a page's resources, keyed by resource name
+pub(crate) fn page_font_metrics(doc: &Document, page_id: ObjectId) -> BTreeMap<Vec<u8>, FontMetrics> {
+    doc.get_page_fonts(page_id)
+        .into_iter()
+        .map(|(name, font)| (name, font_metrics(doc, font)))
+        .collect()
+}
+
+/// Parses the content of a page and lays it out. Returns the operations along with the layout.
+pub(crate) fn page_layout(doc: &Document, page_id: ObjectId) -> Result<(Vec<Operation>, PageLayout), String> {
+    let operations = parse_content(&page_content(doc, page_id))?;
+    let page_layout = layout(&operations, &page_font_metrics(doc, page_id), &image_names(doc, page_id));
+    Ok((operations, page_layout))
+}
+
+/// Whether a page draws nothing: it shows no text, paints no XObject, inline image or shading,
+/// and either paints no path or has at most `threshold` bytes of content, like a white background
+/// or a stray mark. Setting the graphics state or clipping draws nothing. Annotations are not
+/// looked at, and a page whose content does not parse is not blank.
+pub(crate) fn is_blank_page(doc: &Document, page_id: ObjectId, threshold: usize) -> bool {
+    let data = page_content(doc, page_id);
+    let operations = match parse_content(&data) {
+        Ok(operations) => operations,
+        Err(_) => return false,
+    };
+
+    let mut paints_paths = false;
+    for operation in &operations {
+        match operation.operator.as_str() {
+            "Tj" | "TJ" | "'" | "\"" | "Do" | "BI" | "sh" => return false,
+            "S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" => paints_paths = true,
+            _ => {}
+        }
+    }
+    !paints_paths || data.len() <= threshold
+}
+
+// The decoded content streams of a page, one after the other
+fn page_content(doc: &Document, page_id: ObjectId) -> Vec<u8> {
+    // streams are concatenated with a separator, a token may not span two of them
+    let mut data = Vec::new();
+    for content_id in doc.get_page_contents(page_id) {
+        if let Ok(stream) = doc.get_object(content_id).and_then(Object::as_stream) {
+            data.extend(stream.decompressed_content().unwrap_or_else(|_| stream.content.clone()));
+            data.push(b'\n');
+        }
+    }
+    data
+}
+
+/// One glyph drawn by a text showing operator
+#[derive(Debug, Clone)]
+pub(crate) struct Glyph {
+    /// Index of the string within a TJ array (always 0 for Tj, ' and ")
+    pub item: usize,
+    /// The bytes of the character code within that string
+    pub bytes: Vec<u8>,
+    pub unicode: String,
+    /// Bounding box in default user space
+    pub bbox: [f64; 4],
+    /// Horizontal displacement in unscaled text space, including character and word spacing
+    pub advance: f64,
+}
+
+/// The glyphs drawn by one text showing operation
+#[derive(Debug, Clone)]
+pub(crate) struct TextRun {
+    pub op_index: usize,
+    /// Font size multiplied by the vertical scale of the text matrix and CTM, roughly the size in points
+    pub size: f64,
+    pub font_size: f64,
+    pub horizontal_scale: f64,
+    pub glyphs: Vec<Glyph>,
+}
+
+/// An image painted by a Do operation or inline image
+#[derive(Debug, Clone)]
+pub(crate) struct PlacedImage {
+    pub op_index: usize,
+    pub bbox: [f64; 4],
+}
+
+/// Where the text and images of a content stream end up on the page
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PageLayout {
+    pub runs: Vec<TextRun>,
+    pub images: Vec<PlacedImage>,
+}
+
+#[derive(Debug, Clone)]
+struct GraphicsState {
+    ctm: [f64; 6],
+    char_spacing: f64,
+    word_spacing: f64,
+    horizontal_scale: f64,
+    leading: f64,
+    rise: f64,
+    font: Vec<u8>,
+    font_size: f64,
+}
+
+/// Walks the operations of a content stream tracking the graphics and text state, and reports
+/// the position of every glyph and image. Form XObjects are not descended into.
+///
+/// * `operations` - the parsed content stream
+/// * `fonts` - metrics for the fonts in the page resources
+/// * `image_names` - names of the image XObjects in the page resources
+///
+pub(crate) fn layout(operations: &[Operation],
+                     fonts: &BTreeMap<Vec<u8>, FontMetrics>,
+                     image_names: &[Vec<u8>]) -> PageLayout {
+    let fallback = FontMetrics { default_width: 500.0, ..FontMetrics::default() };
+    let mut page = PageLayout::default();
+    let mut state = GraphicsState {
+        ctm: IDENTITY,
+        char_spacing: 0.0,
+        word_spacing: 0.0,
+        horizontal_scale: 1.0,
+        leading: 0.0,
+        rise: 0.0,
+        font: Vec::new(),
+        font_size: 0.0,
+    };
+    let mut stack: Vec<GraphicsState> = Vec::new();
+    let mut text_matrix = IDENTITY;
+    let mut line_matrix = IDENTITY;
+
+    for (op_index, operation) in operations.iter().enumerate() {
+        let nums: Vec<f64> = operation.operands.iter().filter_map(|o| o.as_float().ok()).collect();
+
+        match operation.operator.as_str() {
+            "q" => stack.push(state.clone()),
+            "Q" => {
+                if let Some(saved) = stack.pop() { state = saved; }
+            }
+            "cm" if nums.len() == 6 => {
+                let m = [nums[0], nums[1], nums[2], nums[3], nums[4], nums[5]];
+                state.ctm = multiply(&m, &state.ctm);
+            }
+            "BT" => {
+                text_matrix = IDENTITY;
+                line_matrix = IDENTITY;
+            }
+            "Tc" if nums.len() == 1 => state.char_spacing = nums[0],
+            "Tw" if nums.len() == 1 => state.word_spacing = nums[0],
+            "Tz" if nums.len() == 1 => state.horizontal_scale = nums[0] / 100.0,
+            "TL" if nums.len() == 1 => state.leading = nums[0],
+            "Ts" if nums.len() == 1 => state.rise = nums[0],
+            "Tf" => {
+                if let (Some(Object::Name(name)), Some(size)) = (operation.operands.first(), nums.first()) {
+                    state.font = name.clone();
+                    state.font_size = *size;
+                }
+            }
+            "Td" | "TD" if nums.len() == 2 => {
+                if operation.operator == "TD" { state.leading = -nums[1]; }
+                line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, nums[0], nums[1]], &line_matrix);
+                text_matrix = line_matrix;
+            }
+            "Tm" if nums.len() == 6 => {
+                line_matrix = [nums[0], nums[1], nums[2], nums[3], nums[4], nums[5]];
+                text_matrix = line_matrix;
+            }
+            "T*" => {
+                line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], &line_matrix);
+                text_matrix = line_matrix;
+            }
+            "Tj" | "TJ" | "'" | "\"" => {
+                let shown: Vec<Object> = match operation.operator.as_str() {
+                    "'" => {
+                        line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], &line_matrix);
+                        text_matrix = line_matrix;
+                        operation.operands.clone()
+                    }
+                    "\"" if operation.operands.len() == 3 => {
+                        state.word_spacing = operation.operands[0].as_float().unwrap_or(state.word_spacing);
+                        state.char_spacing = operation.operands[1].as_float().unwrap_or(state.char_spacing);
+                        line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], &line_matrix);
+                        text_matrix = line_matrix;
+                        vec![operation.operands[2].clone()]
+                    }
+                    "TJ" => match operation.operands.first() {
+                        Some(Object::Array(items)) => items.clone(),
+                        _ => Vec::new(),
+                    },
+                    _ => operation.operands.clone(),
+                };
+
+                let font = fonts.get(&state.font).unwrap_or(&fallback);
+                let run = show_text(op_index, &shown, font, &state, &mut text_matrix);
+                page.runs.push(run);
+            }
+            "Do" => {
+                if let Some(Object::Name(name)) = operation.operands.first() {
+                    if image_names.contains(name) {
+                        page.images.push(PlacedImage { op_index, bbox: transform_box(&state.ctm, 0.0, 0.0, 1.0, 1.0) });
+                    }
+                }
+            }
+            "BI" => page.images.push(PlacedImage { op_index, bbox: transform_box(&state.ctm, 0.0, 0.0, 1.0, 1.0) }),
+            _ => {}
+        }
+    }
+
+    page
+}
+
+
+// ------- Helpers -------
+
+fn show_text(op_index: usize,
+             shown: &[Object],
+             font: &FontMetrics,
+             state: &GraphicsState,
+             text_matrix: &mut [f64; 6]) -> TextRun {
+    let font_size = state.font_size;
+    let h_scale = state.horizontal_scale;
+    let scale = multiply(text_matrix, &state.ctm);
+    let size = font_size.abs() * (scale[2].powi(2) + scale[3].powi(2)).sqrt();
+    let mut glyphs = Vec::new();
+
+    for (item, object) in shown.iter().enumerate() {
+        match object {
+            Object::String(bytes, _) => {
+                for (code, code_bytes) in font.codes(bytes) {
+                    let width = font.width(code) / 1000.0;
+                    let word_spacing = if code_bytes == [b' '] { state.word_spacing } else { 0.0 };
+                    let advance = (width * font_size + state.char_spacing + word_spacing) * h_scale;
+
+                    // glyph space to user space: [Tfs*Th 0 0 Tfs 0 Trise] x Tm x CTM
+                    let rendering = multiply(
+                        &multiply(&[font_size * h_scale, 0.0, 0.0, font_size, 0.0, state.rise], text_matrix),
+                        &state.ctm);
+                    let bbox = transform_box(&rendering, 0.0, DESCENT, width, ASCENT);
+
+                    glyphs.push(Glyph {
+                        item,
+                        bytes: code_bytes.to_vec(),
+                        unicode: font.unicode(code, code_bytes),
+                        bbox,
+                        advance,
+                    });
+                    *text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, advance, 0.0], text_matrix);
+                }
+            }
+            number => {
+                if let Ok(adjustment) = number.as_float() {
+                    let tx = -adjustment / 1000.0 * font_size * h_scale;
+                    *text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, tx, 0.0], text_matrix);
+                }
+            }
+        }
+    }
+
+    TextRun { op_index, size, font_size, horizontal_scale: h_scale, glyphs }
+}
+
+fn image_names(doc: &Document, page_id: ObjectId) -> Vec<Vec<u8>> {
+    let resources = match inherited_attribute(doc, page_id, b"Resources") {
+        Some(resources) => resources,
+        None => return Vec::new(),
+    };
+    let xobjects = doc.dereference(&resources).ok()
+        .and_then(|(_, r)| r.as_dict().ok())
+        .and_then(|r| r.get(b"XObject").ok())
+        .and_then(|x| doc.dereference(x).ok())
+        .and_then(|(_, x)| x.as_dict().ok());
+
+    xobjects.map(|xobjects| {
+        xobjects.iter()
+            .filter(|(_, xobject)| {
+                doc.dereference(xobject).ok()
+                    .and_then(|(_, x)| x.as_stream().ok())
+                    .and_then(|x| x.dict.get(b"Subtype").and_then(Object::as_name).ok())
+                    == Some(b"Image")
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }).unwrap_or_default()
+}
+
+fn encode_plain(operations: &[Operation]) -> Vec<u8> {
+    // writing into a Vec cannot fail
+    Content { operations }.encode().unwrap_or_default()
+}
+
+fn font_metrics(doc: &Document, font: &Dictionary) -> FontMetrics {
+    let get = |dict: &Dictionary, key: &[u8]| -> Option<Object> {
+        dict.get(key).ok().and_then(|o| doc.dereference(o).ok()).map(|(_, o)| o.clone())
+    };
+    let mut metrics = FontMetrics::default();
+
+    if let Some(Object::Stream(stream)) = get(font, b"ToUnicode") {
+        if let Ok(cmap) = stream.decompressed_content() {
+            metrics.to_unicode = parse_to_unicode(&cmap);
+        }
+    }
+
+    if font.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Type0") {
+        metrics.two_byte = true;
+        metrics.default_width = 1000.0;
+        let descendant = match get(font, b"DescendantFonts") {
+            Some(Object::Array(fonts)) => fonts.first()
+                .and_then(|f| doc.dereference(f).ok())
+                .and_then(|(_, f)| f.as_dict().ok().cloned()),
+            _ => None,
+        };
+        if let Some(descendant) = descendant {
+            if let Some(dw) = get(&descendant, b"DW").and_then(|o| o.as_float().ok()) {
+                metrics.default_width = dw;
+            }
+            if let Some(Object::Array(w)) = get(&descendant, b"W") {
+                parse_cid_widths(doc, &w, &mut metrics.widths);
+            }
+        }
+        return metrics;
+    }
+
+    metrics.encoding = match get(font, b"Encoding") {
+        Some(Object::Name(name)) => Some(String::from_utf8_lossy(&name).into_owned()),
+        Some(Object::Dictionary(dict)) => dict.get(b"BaseEncoding").and_then(Object::as_name_str).ok().map(str::to_string),
+        _ => None,
+    };
+
+    let base_font = font.get(b"BaseFont").and_then(Object::as_name_str).unwrap_or("");
+    // the standard 14 fonts may omit /Widths, Courier is the only one with fixed widths
+    metrics.default_width = if base_font.starts_with("Courier") { 600.0 } else { 500.0 };
+    if let Some(missing) = get(font, b"FontDescriptor")
+        .and_then(|d| d.as_dict().ok().and_then(|d| d.get(b"MissingWidth").ok().cloned()))
+        .and_then(|w| w.as_float().ok()) {
+        metrics.default_width = missing;
+    }
+
+    let first_char = font.get(b"FirstChar").and_then(Object::as_i64).unwrap_or(0);
+    if let Some(Object::Array(widths)) = get(font, b"Widths") {
+        for (i, width) in widths.iter().enumerate() {
+            let width = doc.dereference(width).ok().and_then(|(_, w)| w.as_float().ok());
+            if let Some(width) = width {
+                metrics.widths.insert((first_char + i as i64) as u32, width);
+            }
+        }
+    }
+
+    metrics
+}
+
+// /W arrays mix `c [w1 w2 ...]` and `c_first c_last w` entries
+fn parse_cid_widths(doc: &Document, w: &[Object], widths: &mut HashMap<u32, f64>) {
+    let values: Vec<&Object> = w.iter().map(|o| doc.dereference(o).map(|(_, o)| o).unwrap_or(o)).collect();
+    let mut i = 0;
+    while i + 1 < values.len() {
+        let first = match values[i].as_i64() { Ok(first) => first as u32, Err(_) => break };
+        match values[i + 1] {
+            Object::Array(list) => {
+                for (offset, width) in list.iter().enumerate() {
+                    if let Ok(width) = width.as_float() {
+                        widths.insert(first + offset as u32, width);
+                    }
+                }
+                i += 2;
+            }
+            last => {
+                let (last, width) = match (last.as_i64(), values.get(i + 2).and_then(|w| w.as_float().ok())) {
+                    (Ok(last), Some(width)) => (last as u32, width),
+                    _ => break,
+                };
+                for code in first..=last.min(first.saturating_add(0xFFFF)) {
+                    widths.insert(code, width);
+                }
+                i += 3;
+            }
+        }
+    }
+}
+
+// understands the bfchar and bfrange sections of a ToUnicode CMap, which is all real files use
+fn parse_to_unicode(cmap: &[u8]) -> HashMap<u32, String> {
+    let mut map = HashMap::new();
+    let operations = match parse_content(cmap) {
+        Ok(operations) => operations,
+        Err(_) => return map,
+    };
+
+    let hex_value = |o: &Object| -> Option<u32> {
+        o.as_str().ok().map(|b| b.iter().fold(0u32, |acc, b| (acc << 8) | *b as u32))
+    };
+    let utf16 = |o: &Object| -> Option<String> {
+        let bytes = o.as_str().ok()?;
+        let units: Vec<u16> = bytes.chunks(2)
+            .map(|c| c.iter().fold(0u16, |acc, b| (acc << 8) | *b as u16))
+            .collect();
+        Some(String::from_utf16_lossy(&units))
+    };
+
+    for operation in operations {
+        match operation.operator.as_str() {
+            "endbfchar" => {
+                for pair in operation.operands.chunks_exact(2) {
+                    if let (Some(code), Some(text)) = (hex_value(&pair[0]), utf16(&pair[1])) {
+                        map.insert(code, text);
+                    }
+                }
+            }
+            "endbfrange" => {
+                for range in operation.operands.chunks_exact(3) {
+                    let (low, high) = match (hex_value(&range[0]), hex_value(&range[1])) {
+                        (Some(low), Some(high)) if high >= low && high - low <= 0xFFFF => (low, high),
+                        _ => continue,
+                    };
+                    match &range[2] {
+                        Object::Array(targets) => {
+                            for (code, target) in (low..=high).zip(targets.iter()) {
+                                if let Some(text) = utf16(target) { map.insert(code, text); }
+                            }
+                        }
+                        target => {
+                            let start = match utf16(target) { Some(start) => start, None => continue };
+                            let mut chars: Vec<char> = start.chars().collect();
+                            for code in low..=high {
+                                map.insert(code, chars.iter().collect());
+                                if let Some(last) = chars.last_mut() {
+                                    *last = char::from_u32(*last as u32 + 1).unwrap_or(*last);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    map
+}
+
+enum Token {
+    Object(Object),
+    Keyword(String),
+    ArrayEnd,
+    DictEnd,
+}
+
+struct Lexer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\r' | b'\n' | b'\x0C' | b'\0')
+}
+
+fn is_delimiter(b: u8) -> bool {
+    matches!(b, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+}
+
+impl<'a> Lexer<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b) = self.peek() {
+            if is_whitespace(b) {
+                self.pos += 1;
+            } else if b == b'%' {
+                while let Some(b) = self.peek() {
+                    if b == b'\r' || b == b'\n' { break; }
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, String> {
+        self.skip_whitespace();
+        let b = match self.peek() {
+            Some(b) => b,
+            None => return Ok(None),
+        };
+
+        let token = match b {
+            b'(' => Token::Object(Object::String(self.literal_string()?, StringFormat::Literal)),
+            b'<' if self.data.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                Token::Object(Object::Dictionary(self.dictionary()?))
+            }
+            b'<' => Token::Object(Object::String(self.hex_string()?, StringFormat::Hexadecimal)),
+            b'>' if self.data.get(self.pos + 1) == Some(&b'>') => {
+                self.pos += 2;
+                Token::DictEnd
+            }
+            b'[' => {
+                self.pos += 1;
+                Token::Object(Object::Array(self.array()?))
+            }
+            b']' => {
+                self.pos += 1;
+                Token::ArrayEnd
+            }
+            b'/' => Token::Object(Object::Name(self.name())),
+            b'+' | b'-' | b'.' | b'0'..=b'9' => Token::Object(self.number()?),
+            _ if is_delimiter(b) => return Err(format!("unexpected `{}` at byte {}", b as char, self.pos)),
+            _ => {
+                let start = self.pos;
+                while let Some(b) = self.peek() {
+                    if is_whitespace(b) || is_delimiter(b) { break; }
+                    self.pos += 1;
+                }
+                let keyword = String::from_utf8_lossy(&self.data[start..self.pos]).into_owned();
+                match keyword.as_str() {
+                    "true" => Token::Object(Object::Boolean(true)),
+                    "false" => Token::Object(Object::Boolean(false)),
+                    "null" => Token::Object(Object::Null),
+                    _ => Token::Keyword(keyword),
+                }
+            }
+        };
+
+        Ok(Some(token))
+    }
+
+    fn object(&mut self) -> Result<Option<Token>, String> {
+        match self.next_token()? {
+            Some(Token::Keyword(keyword)) => Err(format!("unexpected operator `{}` inside an array or dictionary", keyword)),
+            other => Ok(other),
+        }
+    }
+
+    fn array(&mut self) -> Result<Vec<Object>, String> {
+        let mut items = Vec::new();
+        loop {
+            match self.object()? {
+                Some(Token::Object(object)) => items.push(object),
+                Some(Token::ArrayEnd) => return Ok(items),
+                Some(_) | None => return Err("unterminated array".to_string()),
+            }
+        }
+    }
+
+    fn dictionary(&mut self) -> Result<Dictionary, String> {
+        let mut dict = Dictionary::new();
+        loop {
+            let key = match self.object()? {
+                Some(Token::Object(Object::Name(key))) => key,
+                Some(Token::DictEnd) => return Ok(dict),
+                _ => return Err("malformed dictionary".to_string()),
+            };
+            match self.object()? {
+                Some(Token::Object(value)) => dict.set(key, value),
+                _ => return Err("malformed dictionary".to_string()),
+            }
+        }
+    }
+
+    fn name(&mut self) -> Vec<u8> {
+        self.pos += 1;
+        let mut name = Vec::new();
+        while let Some(b) = self.peek() {
+            if is_whitespace(b) || is_delimiter(b) { break; }
+            if b == b'#' {
+                let hex = self.data.get(self.pos + 1..self.pos + 3)
+                    .and_then(|h| std::str::from_utf8(h).ok())
+                    .and_then(|h| u8::from_str_radix(h, 16).ok());
+                if let Some(value) = hex {
+                    name.push(value);
+                    self.pos += 3;
+                    continue;
+                }
+            }
+            name.push(b);
+            self.pos += 1;
+        }
+        name
+    }
+
+    fn number(&mut self) -> Result<Object, String> {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if !matches!(b, b'+' | b'-' | b'.' | b'0'..=b'9') { break; }
+            self.pos += 1;
+        }
+        let text = String::from_utf8_lossy(&self.data[start..self.pos]).into_owned();
+        if let Ok(integer) = text.parse::<i64>() {
+            return Ok(Object::Integer(integer));
+        }
+        // tolerate things like "--1" and "1.2.3" the same way most viewers do, by reading what parses
+        let cleaned = text.trim_start_matches('+');
+        cleaned.parse::<f64>()
+            .or_else(|_| cleaned.trim_start_matches('-').parse::<f64>().map(|n| -n))
+            .map(Object::Real)
+            .map_err(|_| format!("`{}` is not a number", text))
+    }
+
+    fn literal_string(&mut self) -> Result<Vec<u8>, String> {
+        self.pos += 1;
+        let mut depth = 1;
+        let mut bytes = Vec::new();
+
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'(' => {
+                    depth += 1;
+                    bytes.push(b);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 { return Ok(bytes); }
+                    bytes.push(b);
+                }
+                b'\\' => {
+                    let escaped = match self.peek() {
+                        Some(e) => e,
+                        None => break,
+                    };
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => bytes.push(b'\n'),
+                        b'r' => bytes.push(b'\r'),
+                        b't' => bytes.push(b'\t'),
+                        b'b' => bytes.push(b'\x08'),
+                        b'f' => bytes.push(b'\x0C'),
+                        b'\r' => {
+                            if self.peek() == Some(b'\n') { self.pos += 1; }
+                        }
+                        b'\n' => {}
+                        b'0'..=b'7' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(d @ b'0'..=b'7') => {
+                                        value = value * 8 + (d - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            bytes.push(value as u8);
+                        }
+                        other => bytes.push(other),
+                    }
+                }
+                _ => bytes.push(b),
+            }
+        }
+
+        Err("unterminated string".to_string())
+    }
+
+    fn hex_string(&mut self) -> Result<Vec<u8>, String> {
+        self.pos += 1;
+        let mut digits = Vec::new();
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'>' => {
+                    if digits.len() % 2 == 1 { digits.push(0); }
+                    return Ok(digits.chunks(2).map(|pair| pair[0] * 16 + pair[1]).collect());
+                }
+                _ if is_whitespace(b) => {}
+                _ => match (b as char).to_digit(16) {
+                    Some(d) => digits.push(d as u8),
+                    None => return Err(format!("invalid hex digit `{}`", b as char)),
+                },
+            }
+        }
+        Err("unterminated hex string".to_string())
+    }
+
+    // after BI: key/value pairs up to ID, one whitespace byte, then raw data up to an EI surrounded by whitespace
+    fn inline_image(&mut self) -> Result<(Dictionary, Vec<u8>), String> {
+        let mut dict = Dictionary::new();
+        loop {
+            match self.next_token()? {
+                Some(Token::Keyword(keyword)) if keyword == "ID" => break,
+                Some(Token::Object(Object::Name(key))) => match self.object()? {
+                    Some(Token::Object(value)) => dict.set(key, value),
+                    _ => return Err("malformed inline image dictionary".to_string()),
+                },
+                _ => return Err("malformed inline image dictionary".to_string()),
+            }
+        }
+        self.pos += 1;
+
+        let start = self.pos;
+        let mut i = start;
+        while i + 1 < self.data.len() {
+            let before_ok = i == start || is_whitespace(self.data[i - 1]);
+            let after_ok = self.data.get(i + 2).is_none_or(|b| is_whitespace(*b));
+            if &self.data[i..i + 2] == b"EI" && before_ok && after_ok {
+                let mut end = i;
+                if end > start && is_whitespace(self.data[end - 1]) { end -= 1; }
+                self.pos = i + 2;
+                return Ok((dict, self.data[start..end].to_vec()));
+            }
+            i += 1;
+        }
+
+        Err("inline image without EI".to_string())
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf
+};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::names::{build_name_tree, name_tree_entries, name_tree_lookup};
+use crate::report;
+use crate::utils::{decode_text_string, load_for_update, load_pdf, save_changes, SaveOptions, SaveReport};
+
+/// A named destination and the page it leads to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedDestination {
+    pub name: String,
+    /// None when the destination does not resolve to a page of this document
+    pub page: Option<u32>,
+}
+
+/// Prints every named destination in a PDF and the page it resolves to
+///
+/// * `infile` - a PathBuf of the file to list destinations from
+///
+pub fn print_dests(infile: PathBuf) {
+    let doc = load_pdf(&infile);
+
+    for dest in named_destinations(&doc) {
+        match dest.page {
+            Some(page) => println!("{:<40} page {}", dest.name, page),
+            None => println!("{:<40} dangling", dest.name),
+        }
+    }
+}
+
+/// Removes the named destinations that do not lead to a page, either inplace or in a new file
+///
+/// * `infile` - a PathBuf of the file to prune
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `compress` - a boolean flag to compress the outfile before saving
+/// * `save` - how to write the file, see SaveOptions
+///
+pub fn prune_dests(infile: PathBuf, outfile: Option<PathBuf>, compress: bool, save: &SaveOptions) -> SaveReport {
+    let (mut doc, before) = load_for_update(&infile);
+
+    let removed = remove_dangling_destinations(&mut doc);
+    report::summary(format!("removed {} dangling destinations", removed));
+
+    if compress { report::timed("compress", || doc.compress()) }
+
+    save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Resolves a destination to the ObjectId of the page it points at. Destinations can be explicit
+/// arrays (`[page /XYZ left top zoom]`), names looked up in the catalog's /Dests dictionary, or
+/// strings looked up in the /Names /Dests name tree.
+///
+/// Returns None when the destination does not lead to a page of this document.
+pub fn resolve_destination(doc: &Document, dest: &Object) -> Option<ObjectId> {
+    let explicit = explicit_destination(doc, dest)?;
+    let page_ids: HashSet<ObjectId> = doc.page_iter().collect();
+
+    match explicit.first()? {
+        Object::Reference(id) if page_ids.contains(id) => Some(*id),
+        // not allowed for local destinations, but some producers write a 0 based page index
+        Object::Integer(index) => doc.get_pages().get(&(u32::try_from(*index).ok()? + 1)).copied(),
+        _ => None,
+    }
+}
+
+/// Follows names, references and destination dictionaries down to the explicit destination array
+/// (`[page /XYZ left top zoom]` and friends)
+pub fn explicit_destination(doc: &Document, dest: &Object) -> Option<Vec<Object>> {
+    let dest = match dest {
+        Object::Reference(_) => doc.dereference(dest).ok()?.1,
+        _ => dest,
+    };
+
+    match dest {
+        Object::Array(arr) => Some(arr.clone()),
+        Object::Name(name) | Object::String(name, _) => match named_destination(doc, name)? {
+            // guard against a name resolving to another name
+            Object::Array(arr) => Some(arr),
+            _ => None,
+        },
+        // a destination dictionary is only valid as a value in /Dests, but tolerate it here too
+        Object::Dictionary(dict) => explicit_destination(doc, dict.get(b"D").ok()?),
+        _ => None,
+    }
+}
+
+/// The destination of an outline item or link annotation, taken from its /Dest entry or the /D
+/// of a GoTo action
+pub fn goto_destination(doc: &Document, dict: &Dictionary) -> Option<Object> {
+    if let Ok(dest) = dict.get(b"Dest") {
+        return Some(dest.clone());
+    }
+    let action = doc.dereference(dict.get(b"A").ok()?).ok()?.1.as_dict().ok()?;
+    match action.get(b"S").and_then(Object::as_name) {
+        Ok(b"GoTo") => action.get(b"D").ok().cloned(),
+        _ => None,
+    }
+}
+
+/// Lists the entries of the catalog's /Dests dictionary followed by those of the /Names /Dests
+/// name tree, with the page each one resolves to
+pub fn named_destinations(doc: &Document) -> Vec<NamedDestination> {
+    let page_numbers: BTreeMap<ObjectId, u32> = doc.get_pages()
+        .into_iter()
+        .map(|(num, id)| (id, num))
+        .collect();
+    let resolve = |dest: &Object| resolve_destination(doc, dest).and_then(|id| page_numbers.get(&id).copied());
+
+    let mut dests: Vec<NamedDestination> = dests_dict(doc)
+        .map(|dict| dict.iter()
+            .map(|(name, dest)| NamedDestination { name: decode_text_string(name), page: resolve(dest) })
+            .collect())
+        .unwrap_or_default();
+
+    if let Some(root) = dests_tree_root(doc) {
+        for (name, dest) in name_tree_entries(doc, &root) {
+            dests.push(NamedDestination { name: decode_text_string(&name), page: resolve(&dest) });
+        }
+    }
+
+    dests
+}
+
+/// Removes named destinations that do not resolve to a page from both the /Dests dictionary and
+/// the /Names /Dests tree. The name tree is rebuilt from what is left, and removed altogether
+/// (as is an empty /Dests dictionary) when nothing is.
+///
+/// Returns the number of destinations removed.
+pub fn remove_dangling_destinations(doc: &mut Document) -> usize {
+    let mut removed = 0;
+
+    if let Some(dict) = dests_dict(doc) {
+        let dangling: Vec<Vec<u8>> = dict.iter()
+            .filter(|(_, dest)| resolve_destination(doc, dest).is_none())
+            .map(|(name, _)| name.clone())
+            .collect();
+        if !dangling.is_empty() {
+            removed += dangling.len();
+            let mut dict = dict.clone();
+            for name in dangling {
+                dict.remove(&name);
+            }
+            set_catalog_entry(doc, b"Dests", dict.clone(), dict.is_empty());
+        }
+    }
+
+    if let Some(root) = dests_tree_root(doc) {
+        let entries = name_tree_entries(doc, &root);
+        let total = entries.len();
+        let kept: BTreeMap<Vec<u8>, Object> = entries.into_iter()
+            .filter(|(_, dest)| resolve_destination(doc, dest).is_some())
+            .collect();
+
+        if kept.len() < total {
+            removed += total - kept.len();
+            let mut names = doc.catalog().ok()
+                .and_then(|catalog| catalog.get(b"Names").ok())
+                .and_then(|names| doc.dereference(names).ok())
+                .and_then(|(_, names)| names.as_dict().ok())
+                .cloned()
+                .unwrap_or_default();
+            if kept.is_empty() {
+                names.remove(b"Dests");
+            } else {
+                let root_id = build_name_tree(doc, kept);
+                names.set("Dests", root_id);
+            }
+            set_catalog_entry(doc, b"Names", names.clone(), names.is_empty());
+        }
+    }
+
+    removed
+}
+
+/// Looks up a named destination, first in the PDF 1.1 style /Dests dictionary on the catalog and
+/// then in the /Names /Dests name tree. Returns the explicit destination array.
+pub fn named_destination(doc: &Document, name: &[u8]) -> Option<Object> {
+    let catalog = doc.catalog().ok()?;
+
+    let from_dict = catalog.get(b"Dests").ok()
+        .and_then(|dests| doc.dereference(dests).ok())
+        .and_then(|(_, dests)| dests.as_dict().ok())
+        .and_then(|dests| dests.get(name).ok())
+        .cloned();
+
+    let found = from_dict.or_else(|| {
+        let names = doc.dereference(catalog.get(b"Names").ok()?).ok()?.1.as_dict().ok()?;
+        name_tree_lookup(doc, names.get(b"Dests").ok()?, name)
+    })?;
+
+    entry_destination(doc, &found)
+}
+
+/// The named destinations of several documents, gathered to go into the one they are merged into.
+/// Each is kept as the explicit destination array it leads to, and a name already taken by an
+/// earlier document gets the number of the document it came from appended (`intro` becomes
+/// `intro-2`). The /Dests dictionary and the /Names /Dests tree are gathered separately.
+#[derive(Debug, Default)]
+pub struct MergedDestinations {
+    dict: BTreeMap<Vec<u8>, Object>,
+    tree: BTreeMap<Vec<u8>, Object>,
+    /// Number of names that were taken and got a suffix
+    pub renamed: usize,
+}
+
+impl MergedDestinations {
+    /// Adds the named destinations of a document. Names that do not lead to a destination array
+    /// are left out.
+    ///
+    /// * `doc` - the document, with its objects numbered as they will be in the merged one
+    /// * `index` - the position of the document among those merged, starting at 0
+    ///
+    pub fn add(&mut self, doc: &Document, index: usize) {
+        let from_dict: Vec<(Vec<u8>, Object)> = dests_dict(doc)
+            .map(|dict| dict.iter().map(|(name, dest)| (name.clone(), dest.clone())).collect())
+            .unwrap_or_default();
+        let from_tree = dests_tree_root(doc).map(|root| name_tree_entries(doc, &root)).unwrap_or_default();
+
+        for (entries, merged) in [(from_dict, &mut self.dict), (from_tree.into_iter().collect(), &mut self.tree)] {
+            for (name, dest) in entries {
+                let dest = match entry_destination(doc, &dest) {
+                    Some(dest @ Object::Array(_)) => dest,
+                    _ => continue,
+                };
+                let name = if merged.contains_key(&name) {
+                    self.renamed += 1;
+                    unused_name(merged, &name, index)
+                } else {
+                    name
+                };
+                merged.insert(name, dest);
+            }
+        }
+    }
+
+    /// Writes the gathered destinations into the catalog of the merged document, replacing its
+    /// /Dests dictionary and /Names /Dests tree. Leaves the catalog alone if none were gathered.
+    pub fn write(self, doc: &mut Document) {
+        if !self.dict.is_empty() {
+            set_catalog_entry(doc, b"Dests", Dictionary::from_iter(self.dict), false);
+        }
+        if !self.tree.is_empty() {
+            let mut names = doc.catalog().ok()
+                .and_then(|catalog| catalog.get(b"Names").ok())
+                .and_then(|names| doc.dereference(names).ok())
+                .and_then(|(_, names)| names.as_dict().ok())
+                .cloned()
+                .unwrap_or_default();
+            let root_id = build_name_tree(doc, self.tree);
+            names.set("Dests", root_id);
+            set_catalog_entry(doc, b"Names", names, false);
+        }
+    }
+}
+
+
+// ------- Helpers -------
+
+fn dests_dict(doc: &Document) -> Option<&Dictionary> {
+    let dests = doc.catalog().ok()?.get(b"Dests").ok()?;
+    doc.dereference(dests).ok()?.1.as_dict().ok()
+}
+
+fn dests_tree_root(doc: &Document) -> Option<Object> {
+    let names = doc.dereference(doc.catalog().ok()?.get(b"Names").ok()?).ok()?.1.as_dict().ok()?;
+    names.get(b"Dests").ok().cloned()
+}
+
+// the explicit destination a /Dests entry holds: the array itself, or a dictionary holding it under /D
+fn entry_destination(doc: &Document, found: &Object) -> Option<Object> {
+    let found = match doc.dereference(found) {
+        Ok((_, Object::Dictionary(dict))) => dict.get(b"D").ok()?.clone(),
+        Ok((_, object)) => object.clone(),
+        Err(_) => return None,
+    };
+    match doc.dereference(&found) {
+        Ok((_, object)) => Some(object.clone()),
+        Err(_) => None,
+    }
+}
+
+// `name` with the number of the document it came from appended, and a counter too if that is taken
+fn unused_name(taken: &BTreeMap<Vec<u8>, Object>, name: &[u8], index: usize) -> Vec<u8> {
+    let mut candidate = [name, format!("-{}", index + 1).as_bytes()].concat();
+    let mut count = 1;
+    while taken.contains_key(&candidate) {
+        count += 1;
+        candidate = [name, format!("-{}-{}", index + 1, count).as_bytes()].concat();
+    }
+    candidate
+}
+
+// writes a catalog dictionary entry back, into its own object when it is an indirect one
+fn set_catalog_entry(doc: &mut Document, key: &[u8], value: Dictionary, remove: bool) {
+    let catalog_id = match doc.trailer.get(b"Root").and_then(Object::as_reference) {
+        Ok(id) => id,
+        Err(_) => return,
+    };
+    let existing = doc.get_dictionary(catalog_id).ok()
+        .and_then(|catalog| catalog.get(key).and_then(Object::as_reference).ok());
+
+    match (existing, remove) {
+        (Some(id), false) => {
+            doc.objects.insert(id, Object::Dictionary(value));
+        }
+        _ => {
+            if let Ok(catalog) = doc.get_object_mut(catalog_id).and_then(Object::as_dict_mut) {
+                if remove {
+                    catalog.remove(key);
+                } else {
+                    catalog.set(key.to_vec(), value);
+                }
+            }
+        }
+    }
+}
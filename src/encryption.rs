@@ -0,0 +1,555 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::PathBuf
+};
+use aes::cipher::{block_padding::{NoPadding, Pkcs7}, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use md5::{Digest, Md5};
+use sha2::{Sha256, Sha384, Sha512};
+
+use crate::prompt::{ask_password, PasswordPrompt};
+use crate::report;
+use crate::utils::{save_changes, SaveOptions, SaveReport};
+
+/// Padding for passwords of the RC4 and AES-128 security handlers, PDF 32000-1:2008 7.6.3.3
+const PASSWORD_PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// Removes the encryption of a PDF, and with it any restrictions on printing, copying or editing,
+/// either inplace or in a new file
+///
+/// * `infile` - a PathBuf of the file to decrypt
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `password` - the user or owner password, None to only decrypt documents that open without one
+/// * `prompt` - where to ask for the password when none was given and the document needs one
+/// * `compress` - a boolean flag to compress the outfile before saving
+/// * `save` - how to write the file, see SaveOptions
+///
+pub fn decrypt(infile: PathBuf,
+               outfile: Option<PathBuf>,
+               password: Option<String>,
+               prompt: &mut dyn PasswordPrompt,
+               compress: bool,
+               save: &SaveOptions) -> SaveReport {
+    let data = match fs::read(&infile) {
+        Ok(data) => data,
+        Err(error) => panic!("Failed to read {}: {}", infile.display(), error),
+    };
+
+    let mut result = decrypt_pdf(&data, password.as_deref().unwrap_or("").as_bytes());
+    if matches!(result, Err(DecryptError::WrongPassword)) && password.is_none() {
+        match ask_password(prompt, &format!("Password for {}", infile.display())) {
+            Ok(Some(entered)) => result = decrypt_pdf(&data, entered.as_bytes()),
+            Ok(None) => {
+                panic!("{} needs a user password to open, only owner restrictions can be lifted without one", infile.display())
+            }
+            Err(error) => panic!("Failed to decrypt {}: {}", infile.display(), error),
+        }
+    }
+    let mut doc = match result {
+        Ok(doc) => doc,
+        Err(error) => panic!("Failed to decrypt {}: {}", infile.display(), error),
+    };
+    let before = SaveReport { pages_in: doc.get_pages().len(), bytes_in: data.len() as u64, ..SaveReport::default() };
+    report::summary("removed the encryption and its restrictions");
+
+    if compress { report::timed("compress", || doc.compress()) }
+
+    save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Why a document could not be decrypted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecryptError {
+    /// The file could not be parsed as a PDF
+    Load(String),
+    /// The document has no /Encrypt dictionary
+    NotEncrypted,
+    /// The document uses a security handler or algorithm this does not implement
+    Unsupported(String),
+    /// The password is neither the user nor the owner password
+    WrongPassword,
+}
+
+impl std::fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecryptError::Load(error) => write!(f, "could not read the PDF: {}", error),
+            DecryptError::NotEncrypted => write!(f, "the document is not encrypted"),
+            DecryptError::Unsupported(what) => write!(f, "unsupported encryption: {}", what),
+            DecryptError::WrongPassword => write!(f, "the password is not correct"),
+        }
+    }
+}
+
+/// True when the document has an /Encrypt dictionary
+pub fn is_encrypted(doc: &Document) -> bool {
+    doc.trailer.has(b"Encrypt")
+}
+
+/// Loads an encrypted PDF and decrypts every string and stream with `password`, which may be the
+/// user or the owner password. Documents with only owner restrictions have an empty user password.
+/// The returned document no longer has an /Encrypt dictionary.
+///
+/// Supports the standard security handler with RC4 (40 to 128 bit), AES-128 and AES-256 keys.
+pub fn decrypt_pdf(data: &[u8], password: &[u8]) -> Result<Document, DecryptError> {
+    let mut doc = Document::load_mem(data).map_err(|e| DecryptError::Load(e.to_string()))?;
+
+    let encrypt_ref = doc.trailer.get(b"Encrypt").map_err(|_| DecryptError::NotEncrypted)?.clone();
+    let (encrypt_id, encrypt) = match &encrypt_ref {
+        Object::Reference(id) => (Some(*id), doc.get_dictionary(*id).map_err(|_| DecryptError::NotEncrypted)?.clone()),
+        Object::Dictionary(dict) => (None, dict.clone()),
+        _ => return Err(DecryptError::NotEncrypted),
+    };
+    let id = doc.trailer.get(b"ID")
+        .and_then(Object::as_array)
+        .ok()
+        .and_then(|ids| ids.first())
+        .and_then(|id| id.as_str().ok())
+        .unwrap_or_default()
+        .to_vec();
+
+    let handler = SecurityHandler::new(&encrypt, id)?;
+    let key = handler.authenticate(password).ok_or(DecryptError::WrongPassword)?;
+
+    // lopdf drops object streams it cannot decompress, which is every encrypted one
+    let recovered = recover_object_streams(&mut doc, data, &handler, &key);
+
+    let ids: Vec<ObjectId> = doc.objects.keys().copied().collect();
+    for id in ids {
+        if Some(id) == encrypt_id || recovered.contains(&id) { continue; }
+        if let Some(object) = doc.objects.get_mut(&id) {
+            handler.decrypt_object(&key, id, object);
+        }
+    }
+
+    doc.trailer.remove(b"Encrypt");
+    if let Some(id) = encrypt_id {
+        doc.objects.remove(&id);
+    }
+    Ok(doc)
+}
+
+
+// ------- Helpers -------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    Identity,
+    Rc4,
+    Aes128,
+    Aes256,
+}
+
+struct SecurityHandler {
+    revision: i64,
+    key_length: usize,
+    owner: Vec<u8>,
+    user: Vec<u8>,
+    owner_key: Vec<u8>,
+    user_key: Vec<u8>,
+    permissions: i32,
+    id: Vec<u8>,
+    encrypt_metadata: bool,
+    streams: Method,
+    strings: Method,
+}
+
+impl SecurityHandler {
+    fn new(encrypt: &Dictionary, id: Vec<u8>) -> Result<SecurityHandler, DecryptError> {
+        let filter = encrypt.get(b"Filter").and_then(Object::as_name).unwrap_or_default();
+        if filter != b"Standard" {
+            return Err(DecryptError::Unsupported(format!("the {} security handler", String::from_utf8_lossy(filter))));
+        }
+        let version = encrypt.get(b"V").and_then(Object::as_i64).unwrap_or(0);
+        let revision = encrypt.get(b"R").and_then(Object::as_i64).unwrap_or(2);
+        let bytes = |key: &[u8]| encrypt.get(key).and_then(Object::as_str).map(<[u8]>::to_vec).unwrap_or_default();
+
+        let (streams, strings, key_length) = match version {
+            1 => (Method::Rc4, Method::Rc4, 5),
+            2 => {
+                let bits = encrypt.get(b"Length").and_then(Object::as_i64).unwrap_or(40);
+                (Method::Rc4, Method::Rc4, (bits / 8).clamp(5, 16) as usize)
+            }
+            4 | 5 => {
+                let method = |key: &[u8]| crypt_filter_method(encrypt, key);
+                let streams = method(b"StmF")?;
+                let strings = method(b"StrF")?;
+                let key_length = if version == 5 { 32 } else { 16 };
+                (streams, strings, key_length)
+            }
+            _ => return Err(DecryptError::Unsupported(format!("version {}", version))),
+        };
+
+        Ok(SecurityHandler {
+            revision,
+            key_length,
+            owner: bytes(b"O"),
+            user: bytes(b"U"),
+            owner_key: bytes(b"OE"),
+            user_key: bytes(b"UE"),
+            permissions: encrypt.get(b"P").and_then(Object::as_i64).unwrap_or(0) as i32,
+            id,
+            encrypt_metadata: encrypt.get(b"EncryptMetadata").and_then(Object::as_bool).unwrap_or(true),
+            streams,
+            strings,
+        })
+    }
+
+    // the file encryption key, if the password is the user or the owner password
+    fn authenticate(&self, password: &[u8]) -> Option<Vec<u8>> {
+        if self.revision >= 5 {
+            let password = &password[..password.len().min(127)];
+            if self.owner.len() >= 48 && self.user.len() >= 48 {
+                let udata = &self.user[..48];
+                if self.hash(password, &self.owner[32..40], udata) == self.owner[..32] {
+                    let intermediate = self.hash(password, &self.owner[40..48], udata);
+                    return aes256_unwrap(&intermediate, &self.owner_key);
+                }
+            }
+            if self.user.len() >= 48 && self.hash(password, &self.user[32..40], &[]) == self.user[..32] {
+                let intermediate = self.hash(password, &self.user[40..48], &[]);
+                return aes256_unwrap(&intermediate, &self.user_key);
+            }
+            return None;
+        }
+
+        self.authenticate_user(password)
+            .or_else(|| self.authenticate_user(&self.user_password_from_owner(password)))
+    }
+
+    fn authenticate_user(&self, password: &[u8]) -> Option<Vec<u8>> {
+        let key = self.rc4_file_key(password);
+        let matches = if self.revision == 2 {
+            rc4(&key, &PASSWORD_PADDING) == self.user
+        } else {
+            let mut hash = Md5::new();
+            hash.update(PASSWORD_PADDING);
+            hash.update(&self.id);
+            let mut check = hash.finalize().to_vec();
+            for i in 0..20u8 {
+                let round_key: Vec<u8> = key.iter().map(|b| b ^ i).collect();
+                check = rc4(&round_key, &check);
+            }
+            self.user.len() >= 16 && check[..16] == self.user[..16]
+        };
+        matches.then_some(key)
+    }
+
+    // the owner password decrypts /O to the (padded) user password, algorithm 7
+    fn user_password_from_owner(&self, password: &[u8]) -> Vec<u8> {
+        let mut digest = Md5::digest(pad_password(password)).to_vec();
+        if self.revision >= 3 {
+            for _ in 0..50 {
+                digest = Md5::digest(&digest[..self.key_length]).to_vec();
+            }
+        }
+        let key = &digest[..self.key_length];
+
+        if self.revision == 2 {
+            return rc4(key, &self.owner);
+        }
+        let mut user = self.owner.clone();
+        for i in (0..20u8).rev() {
+            let round_key: Vec<u8> = key.iter().map(|b| b ^ i).collect();
+            user = rc4(&round_key, &user);
+        }
+        user
+    }
+
+    // algorithm 2
+    fn rc4_file_key(&self, password: &[u8]) -> Vec<u8> {
+        let mut hash = Md5::new();
+        hash.update(pad_password(password));
+        hash.update(&self.owner[..self.owner.len().min(32)]);
+        hash.update(self.permissions.to_le_bytes());
+        hash.update(&self.id);
+        if self.revision >= 4 && !self.encrypt_metadata {
+            hash.update([0xFF; 4]);
+        }
+        let mut digest = hash.finalize().to_vec();
+        if self.revision >= 3 {
+            for _ in 0..50 {
+                digest = Md5::digest(&digest[..self.key_length]).to_vec();
+            }
+        }
+        digest.truncate(self.key_length);
+        digest
+    }
+
+    // algorithm 2.A's hash, plain SHA-256 for revision 5 and algorithm 2.B for revision 6
+    fn hash(&self, password: &[u8], salt: &[u8], udata: &[u8]) -> Vec<u8> {
+        let mut k = Sha256::new().chain_update(password).chain_update(salt).chain_update(udata).finalize().to_vec();
+        if self.revision == 5 { return k; }
+
+        let mut round = 0;
+        loop {
+            let mut block = Vec::with_capacity(64 * (password.len() + k.len() + udata.len()));
+            for _ in 0..64 {
+                block.extend_from_slice(password);
+                block.extend_from_slice(&k);
+                block.extend_from_slice(udata);
+            }
+            let length = block.len();
+            // the block is a multiple of 64 bytes, so it needs no padding
+            let e = cbc::Encryptor::<aes::Aes128>::new(k[..16].into(), k[16..32].into())
+                .encrypt_padded_mut::<NoPadding>(&mut block, length)
+                .map(<[u8]>::to_vec)
+                .unwrap_or_default();
+            let remainder = e[..16].iter().map(|b| *b as u32).sum::<u32>() % 3;
+            k = match remainder {
+                0 => Sha256::digest(&e).to_vec(),
+                1 => Sha384::digest(&e).to_vec(),
+                _ => Sha512::digest(&e).to_vec(),
+            };
+            round += 1;
+            if round >= 64 && u32::from(*e.last().unwrap_or(&0)) + 32 <= round {
+                break;
+            }
+        }
+        k.truncate(32);
+        k
+    }
+
+    // algorithm 1, every object has its own key except with AES-256
+    fn object_key(&self, key: &[u8], id: ObjectId, method: Method) -> Vec<u8> {
+        if method == Method::Aes256 { return key.to_vec(); }
+        let mut hash = Md5::new();
+        hash.update(key);
+        hash.update(&id.0.to_le_bytes()[..3]);
+        hash.update(&id.1.to_le_bytes()[..2]);
+        if method == Method::Aes128 {
+            hash.update(b"sAlT");
+        }
+        let mut digest = hash.finalize().to_vec();
+        digest.truncate((key.len() + 5).min(16));
+        digest
+    }
+
+    fn decrypt_bytes(&self, key: &[u8], id: ObjectId, method: Method, data: &[u8]) -> Vec<u8> {
+        let key = self.object_key(key, id, method);
+        match method {
+            Method::Identity => data.to_vec(),
+            Method::Rc4 => rc4(&key, data),
+            Method::Aes128 | Method::Aes256 => aes_cbc_decrypt(&key, data),
+        }
+    }
+
+    fn decrypt_object(&self, key: &[u8], id: ObjectId, object: &mut Object) {
+        match object {
+            Object::String(bytes, _) => *bytes = self.decrypt_bytes(key, id, self.strings, bytes),
+            Object::Array(items) => {
+                for item in items {
+                    self.decrypt_object(key, id, item);
+                }
+            }
+            Object::Dictionary(dict) => {
+                for (_, value) in dict.iter_mut() {
+                    self.decrypt_object(key, id, value);
+                }
+            }
+            Object::Stream(stream) => {
+                for (_, value) in stream.dict.iter_mut() {
+                    self.decrypt_object(key, id, value);
+                }
+                // cross-reference streams are never encrypted, metadata only if the document says so
+                let kind = stream.dict.get(b"Type").and_then(Object::as_name).unwrap_or_default();
+                if kind == b"XRef" || (kind == b"Metadata" && !self.encrypt_metadata) { return; }
+                let content = self.decrypt_bytes(key, id, self.streams, &stream.content);
+                stream.set_content(content);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn crypt_filter_method(encrypt: &Dictionary, key: &[u8]) -> Result<Method, DecryptError> {
+    let name = encrypt.get(key).and_then(Object::as_name).unwrap_or(b"Identity");
+    if name == b"Identity" { return Ok(Method::Identity); }
+
+    let filter = encrypt.get(b"CF")
+        .and_then(Object::as_dict)
+        .and_then(|filters| filters.get(name))
+        .and_then(Object::as_dict)
+        .map_err(|_| DecryptError::Unsupported(format!("the missing crypt filter {}", String::from_utf8_lossy(name))))?;
+    match filter.get(b"CFM").and_then(Object::as_name).unwrap_or(b"None") {
+        b"None" => Ok(Method::Identity),
+        b"V2" => Ok(Method::Rc4),
+        b"AESV2" => Ok(Method::Aes128),
+        b"AESV3" => Ok(Method::Aes256),
+        other => Err(DecryptError::Unsupported(format!("the {} crypt filter method", String::from_utf8_lossy(other)))),
+    }
+}
+
+fn pad_password(password: &[u8]) -> Vec<u8> {
+    let mut padded: Vec<u8> = password.iter().take(32).copied().collect();
+    padded.extend_from_slice(&PASSWORD_PADDING[..32 - padded.len()]);
+    padded
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut state: Vec<u8> = (0..=255).collect();
+    let mut j: u8 = 0;
+    for i in 0..256 {
+        j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+        state.swap(i, j as usize);
+    }
+
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|byte| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(state[i as usize]);
+            state.swap(i as usize, j as usize);
+            byte ^ state[state[i as usize].wrapping_add(state[j as usize]) as usize]
+        })
+        .collect()
+}
+
+// the data starts with a 16 byte initialisation vector and is padded as in PKCS#7
+fn aes_cbc_decrypt(key: &[u8], data: &[u8]) -> Vec<u8> {
+    if data.len() < 32 || !data.len().is_multiple_of(16) { return Vec::new(); }
+    let (iv, data) = data.split_at(16);
+    let mut buffer = data.to_vec();
+    let unpadded = match key.len() {
+        16 => cbc::Decryptor::<aes::Aes128>::new(key.into(), iv.into())
+            .decrypt_padded_mut::<Pkcs7>(&mut buffer)
+            .map(|plain| plain.len()),
+        32 => cbc::Decryptor::<aes::Aes256>::new(key.into(), iv.into())
+            .decrypt_padded_mut::<Pkcs7>(&mut buffer)
+            .map(|plain| plain.len()),
+        _ => return Vec::new(),
+    };
+    // some writers leave the padding out, the buffer is decrypted either way
+    if let Ok(length) = unpadded {
+        buffer.truncate(length);
+    }
+    buffer
+}
+
+// decrypts /OE or /UE with the intermediate key, giving the file key
+fn aes256_unwrap(key: &[u8], wrapped: &[u8]) -> Option<Vec<u8>> {
+    if wrapped.len() != 32 { return None; }
+    let mut buffer = wrapped.to_vec();
+    cbc::Decryptor::<aes::Aes256>::new(key.into(), &[0u8; 16].into())
+        .decrypt_padded_mut::<NoPadding>(&mut buffer)
+        .ok()
+        .map(<[u8]>::to_vec)
+}
+
+// decrypts every object stream and adds the objects inside. lopdf either drops encrypted object
+// streams or keeps them with their content decompressed to nothing, so they are read from the file
+// again. Returns the ids of the added objects, which are already decrypted.
+fn recover_object_streams(doc: &mut Document, data: &[u8], handler: &SecurityHandler, key: &[u8]) -> HashSet<ObjectId> {
+    let loaded: Vec<ObjectId> = doc.objects.iter()
+        .filter(|(_, object)| object.as_stream().is_ok_and(|stream| stream.dict.type_is(b"ObjStm")))
+        .map(|(id, _)| *id)
+        .collect();
+    for id in loaded {
+        doc.objects.remove(&id);
+    }
+    let streams: Vec<(ObjectId, Dictionary, Vec<u8>)> = find_all(data, b"/ObjStm")
+        .into_iter()
+        .filter_map(|position| raw_stream_at(doc, data, position))
+        .collect();
+
+    let mut recovered: Vec<(ObjectId, Object)> = Vec::new();
+    for (id, dict, content) in streams {
+        let content = handler.decrypt_bytes(key, id, handler.streams, &content);
+        let mut stream = Stream::new(dict, content);
+        if stream.dict.has(b"Filter") {
+            match stream.decompressed_content() {
+                Ok(content) => {
+                    stream.dict.remove(b"Filter");
+                    stream.dict.remove(b"DecodeParms");
+                    stream.set_content(content);
+                }
+                Err(_) => continue,
+            }
+        }
+        recovered.extend(object_stream_objects(&stream));
+    }
+
+    // objects lopdf already loaded are newer than anything in a stream it could not read
+    let mut added = HashSet::new();
+    for (id, object) in recovered {
+        if doc.objects.contains_key(&id) && !added.contains(&id) { continue; }
+        doc.objects.insert(id, object);
+        added.insert(id);
+    }
+    added
+}
+
+fn find_all(haystack: &[u8], needle: &[u8]) -> Vec<usize> {
+    haystack.windows(needle.len())
+        .enumerate()
+        .filter(|(_, window)| *window == needle)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// the id, dictionary and raw data of the stream object whose dictionary contains `position`
+fn raw_stream_at(doc: &Document, data: &[u8], position: usize) -> Option<(ObjectId, Dictionary, Vec<u8>)> {
+    let obj = data[..position].windows(3).rposition(|w| w == b"obj")?;
+    let header = String::from_utf8_lossy(&data[obj.saturating_sub(24)..obj]).into_owned();
+    let mut numbers = header.split_whitespace().rev();
+    let generation: u16 = numbers.next()?.parse().ok()?;
+    let number: u32 = numbers.next()?.trim_start_matches(|c: char| !c.is_ascii_digit()).parse().ok()?;
+
+    let stream_keyword = position + find_all(&data[position..], b"stream").first()?;
+    let dict = load_fragment(number, &data[obj + 3..stream_keyword])?
+        .get_dictionary((number, 0))
+        .ok()?
+        .clone();
+
+    let length = match dict.get(b"Length").ok()? {
+        Object::Reference(id) => doc.get_object(*id).and_then(Object::as_i64).ok()?,
+        length => length.as_i64().ok()?,
+    };
+    let mut start = stream_keyword + b"stream".len();
+    if data.get(start) == Some(&b'\r') { start += 1; }
+    if data.get(start) == Some(&b'\n') { start += 1; }
+    let content = data.get(start..start + usize::try_from(length).ok()?)?.to_vec();
+
+    Some(((number, generation), dict, content))
+}
+
+// the objects of a decrypted, uncompressed object stream
+fn object_stream_objects(stream: &Stream) -> Vec<(ObjectId, Object)> {
+    let first = match stream.dict.get(b"First").and_then(Object::as_i64) {
+        Ok(first) => first,
+        Err(_) => return Vec::new(),
+    };
+    let index = String::from_utf8_lossy(stream.content.get(..first as usize).unwrap_or_default()).into_owned();
+    let ids: Vec<u32> = index.split_whitespace().step_by(2).filter_map(|n| n.parse().ok()).collect();
+    let container = ids.iter().max().map_or(1, |max| max + 1);
+
+    // let lopdf parse the objects by handing it a file that holds nothing but this stream
+    let mut body = format!("<< /Type /ObjStm /N {} /First {} /Length {} >>\nstream\n",
+                           ids.len(), first, stream.content.len()).into_bytes();
+    body.extend_from_slice(&stream.content);
+    body.extend_from_slice(b"\nendstream");
+    let fragment = match load_fragment(container, &body) {
+        Some(fragment) => fragment,
+        None => return Vec::new(),
+    };
+    fragment.objects.into_iter().filter(|(id, _)| ids.contains(&id.0)).collect()
+}
+
+// a minimal PDF holding only `body` as object `id`, loaded with lopdf
+fn load_fragment(id: u32, body: &[u8]) -> Option<Document> {
+    let mut pdf = b"%PDF-1.5\n".to_vec();
+    let offset = pdf.len();
+    pdf.extend_from_slice(format!("{} 0 obj\n", id).as_bytes());
+    pdf.extend_from_slice(body);
+    pdf.extend_from_slice(b"\nendobj\n");
+    let xref = pdf.len();
+    pdf.extend_from_slice(format!(
+        "xref\n0 1\n0000000000 65535 f \n{} 1\n{:010} 00000 n \ntrailer\n<< /Size {} >>\nstartxref\n{}\n%%EOF\n",
+        id, offset, id + 1, xref
+    ).as_bytes());
+    Document::load_mem(&pdf).ok()
+}
@@ -0,0 +1,122 @@
+use std::{fmt, io, path::PathBuf};
+
+/// Why a command failed
+#[derive(Debug)]
+pub enum Error {
+    /// An input could not be read or parsed as a PDF
+    LoadFailed { path: PathBuf, source: LoadError },
+    /// An input pattern like reports/2023-*.pdf matches no files
+    NoMatch { pattern: String },
+    /// The output could not be written
+    SaveFailed { path: PathBuf, source: io::Error },
+    /// Every page was deleted, or none were selected
+    NoPagesRemaining,
+    /// The pages to work on cannot be selected, e.g. --every 0
+    InvalidSelection(String),
+    /// A line of a merge manifest is malformed, or the input it names cannot be used
+    Manifest { path: PathBuf, line: usize, reason: String },
+    /// A pattern for the names of output files is malformed, or gives two files the same name
+    OutputPattern(String),
+    /// The directory to write to does not exist and was not to be created
+    OutputDirMissing(PathBuf),
+    /// An output would overwrite the input of a command that only writes new files
+    OutputIsInput(PathBuf),
+    /// The document has no bookmarks to split it at
+    NoBookmarks(PathBuf),
+    /// Every input was skipped by merge --skip-errors
+    AllInputsFailed { count: usize },
+    /// None of the documents to merge has a page tree
+    PagesRootMissing,
+    /// None of the documents to merge has a catalog
+    CatalogMissing,
+    /// The document is signed and rewriting it would invalidate the signatures
+    Signed { path: PathBuf, fields: Vec<String> },
+    /// The changes could not be appended to the input as an incremental update
+    IncrementalUpdateFailed { path: PathBuf, reason: String },
+    /// An input given as a URL could not be fetched, or is not a PDF
+    DownloadFailed { url: String, reason: String },
+    /// The command has to rewrite the whole file and --incremental was given
+    RewriteRequired(String),
+    /// The areas to redact cannot be blacked out, or the content under them cannot be read
+    RedactFailed(String),
+}
+
+/// Why an input could not be loaded
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file could not be read
+    Io(io::Error),
+    /// The file is not a PDF lopdf can parse
+    Parse(lopdf::Error),
+    /// The file does not parse and --lenient could not recover it either
+    Unrecoverable { parse: lopdf::Error, recovery: String },
+    /// The zip archive the file was in could not be read
+    Archive(String),
+    /// The file is encrypted with a user password and none was given
+    PasswordNeeded,
+    /// The file is encrypted and the password given is neither its user nor its owner password
+    WrongPassword,
+    /// The file is encrypted in a way that cannot be decrypted
+    Undecryptable(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::LoadFailed { path, source } => write!(f, "Failed to load {}: {}", path.display(), source),
+            Error::NoMatch { pattern } => write!(f, "No files match {}", pattern),
+            Error::SaveFailed { path, source } => write!(f, "Failed to write out file {}: {}", path.display(), source),
+            Error::NoPagesRemaining => write!(f, "Resulting document would have no pages."),
+            Error::InvalidSelection(reason) => write!(f, "Invalid page selection: {}", reason),
+            Error::Manifest { path, line, reason } => write!(f, "{}:{}: {}", path.display(), line, reason),
+            Error::OutputPattern(reason) => write!(f, "Invalid output pattern: {}", reason),
+            Error::OutputDirMissing(dir) => write!(f, "{} does not exist, add --mkdir to create it", dir.display()),
+            Error::OutputIsInput(path) => write!(f, "{} is the input, write to another file", path.display()),
+            Error::NoBookmarks(path) => write!(f, "{} has no bookmarks to split at, split it into single pages or use --ranges instead", path.display()),
+            Error::AllInputsFailed { count } => write!(f, "Failed to merge: none of the {} inputs could be loaded", count),
+            Error::PagesRootMissing => write!(f, "Failed to merge: no page tree root found"),
+            Error::CatalogMissing => write!(f, "Failed to merge: no document catalog found"),
+            Error::Signed { path, fields } => write!(f, "{} is digitally signed ({}) and rewriting it would invalidate the signatures. \
+                                                        Use --incremental to keep them, or --break-signature to rewrite it anyway",
+                                                     path.display(), fields.join(", ")),
+            Error::IncrementalUpdateFailed { path, reason } => write!(f, "Failed to write an incremental update to {}: {}", path.display(), reason),
+            Error::DownloadFailed { url, reason } => write!(f, "Failed to download {}: {}", url, reason),
+            Error::RewriteRequired(reason) => write!(f, "{}, it cannot be saved as an incremental update", reason),
+            Error::RedactFailed(reason) => write!(f, "Failed to redact: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::LoadFailed { source, .. } => Some(source),
+            Error::SaveFailed { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => write!(f, "{}", error),
+            LoadError::Parse(error) => write!(f, "{}", error),
+            LoadError::Unrecoverable { parse, recovery } => write!(f, "{} (recovery failed: {})", parse, recovery),
+            LoadError::Archive(error) => write!(f, "{}", error),
+            LoadError::PasswordNeeded => write!(f, "the file is encrypted, supply its password with --password"),
+            LoadError::WrongPassword => write!(f, "the password is not correct"),
+            LoadError::Undecryptable(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(error) => Some(error),
+            LoadError::Parse(error) | LoadError::Unrecoverable { parse: error, .. } => Some(error),
+            LoadError::Archive(_) | LoadError::PasswordNeeded | LoadError::WrongPassword | LoadError::Undecryptable(_) => None,
+        }
+    }
+}
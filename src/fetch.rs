@@ -0,0 +1,343 @@
+use std::{fs, path::{Path, PathBuf}, time::Duration};
+
+use crate::error::Error;
+
+/// Largest body that is downloaded, anything bigger is refused
+pub const MAX_DOWNLOAD: u64 = 512 * 1024 * 1024;
+
+/// A downloaded input, saved to a temporary file that is removed when this is dropped
+#[derive(Debug)]
+pub struct Download {
+    path: PathBuf,
+}
+
+impl Download {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Download {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// True when an input names a URL rather than a file
+pub fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|text| {
+        let lower = text.to_ascii_lowercase();
+        lower.starts_with("http://") || lower.starts_with("https://")
+    })
+}
+
+/// Replaces the URLs among the inputs with local copies of what they point to, so that commands
+/// can read them like any other file. The copies are added to `downloads` and last as long as it.
+/// Fails if a URL cannot be fetched or does not return a PDF.
+///
+/// * `paths` - files, directories and URLs
+/// * `timeout` - how long to wait to connect, and for each read
+/// * `downloads` - keeps the temporary copies until the command is done with them
+///
+pub fn localize(paths: Vec<PathBuf>, timeout: Duration, downloads: &mut Vec<Download>) -> Result<Vec<PathBuf>, Error> {
+    paths.into_iter().map(|path| {
+        if !is_url(&path) { return Ok(path); }
+
+        let url = path.to_string_lossy().into_owned();
+        let failed = |reason: String| Error::DownloadFailed { url: url.clone(), reason };
+        let body = fetch(&url, timeout).map_err(failed)?;
+        if !looks_like_pdf(&body) {
+            return Err(failed("the response is not a PDF".to_string()));
+        }
+
+        let download = Download { path: temp_path(&url, downloads.len()) };
+        fs::write(download.path(), body)
+            .map_err(|error| failed(format!("cannot save it to {}: {}", download.path().display(), error)))?;
+        let local = download.path().to_path_buf();
+        downloads.push(download);
+        Ok(local)
+    }).collect()
+}
+
+/// Fetches the body of a URL with a GET, following redirects. Credentials in the URL are sent with
+/// basic authentication. Bodies larger than `MAX_DOWNLOAD` are refused.
+///
+/// HTTPS servers are verified against the Mozilla root certificates bundled by webpki-roots.
+#[cfg(feature = "http")]
+pub fn fetch(url: &str, timeout: Duration) -> Result<Vec<u8>, String> {
+    http::get(url, timeout)
+}
+
+/// Fetches the body of a URL. This build was made without the `http` feature and always fails.
+#[cfg(not(feature = "http"))]
+pub fn fetch(_url: &str, _timeout: Duration) -> Result<Vec<u8>, String> {
+    Err("URLs are only supported when built with the http feature".to_string())
+}
+
+// ------- Helpers -------
+
+// A PDF header within the first KiB, where readers look for it
+fn looks_like_pdf(body: &[u8]) -> bool {
+    body[..body.len().min(1024)].windows(5).any(|window| window == b"%PDF-")
+}
+
+fn temp_path(url: &str, index: usize) -> PathBuf {
+    let name = url.split(['?', '#']).next().unwrap_or("")
+        .rsplit('/').next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or("download.pdf");
+    let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' }).collect();
+    std::env::temp_dir().join(format!("tpdf-{}-{}-{}", std::process::id(), index, name))
+}
+
+#[cfg(feature = "http")]
+mod http {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpStream, ToSocketAddrs};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use rustls::pki_types::ServerName;
+    use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+    use super::MAX_DOWNLOAD;
+
+    const MAX_REDIRECTS: usize = 10;
+
+    struct Url {
+        tls: bool,
+        host: String,
+        port: u16,
+        path: String,
+        credentials: Option<String>,
+    }
+
+    pub fn get(url: &str, timeout: Duration) -> Result<Vec<u8>, String> {
+        let mut url = url.to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            let parsed = parse(&url)?;
+            match request(&parsed, timeout)? {
+                Response::Body(body) => return Ok(body),
+                Response::Redirect(location) => url = resolve(&url, &parsed, &location),
+            }
+        }
+        Err(format!("more than {} redirects", MAX_REDIRECTS))
+    }
+
+    enum Response {
+        Body(Vec<u8>),
+        Redirect(String),
+    }
+
+    // A plain TCP stream, or one wrapped in TLS
+    trait Connection: Read + Write {}
+    impl<T: Read + Write> Connection for T {}
+
+    fn parse(url: &str) -> Result<Url, String> {
+        let (scheme, rest) = url.split_once("://").ok_or("not a URL")?;
+        let (tls, default_port) = match scheme.to_ascii_lowercase().as_str() {
+            "http" => (false, 80),
+            "https" => (true, 443),
+            other => return Err(format!("unsupported scheme {}", other)),
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        let path = path.split('#').next().unwrap_or("/").to_string();
+        let (credentials, host_port) = match authority.rsplit_once('@') {
+            Some((credentials, host_port)) => (Some(percent_decode(credentials)), host_port),
+            None => (None, authority),
+        };
+        // an IPv6 address is bracketed, its colons are not the port separator
+        let port_separator = match host_port.rfind(']') {
+            Some(bracket) => host_port[bracket..].find(':').map(|colon| bracket + colon),
+            None => host_port.rfind(':'),
+        };
+        let (host, port) = match port_separator {
+            Some(colon) => {
+                let port = &host_port[colon + 1..];
+                (&host_port[..colon], port.parse().map_err(|_| format!("invalid port {}", port))?)
+            }
+            None => (host_port, default_port),
+        };
+        if host.is_empty() { return Err("the URL has no host".to_string()); }
+
+        Ok(Url { tls, host: host.to_string(), port, path, credentials })
+    }
+
+    // A Location header may be absolute, relative to the host, or relative to the current path
+    fn resolve(current: &str, parsed: &Url, location: &str) -> String {
+        if location.contains("://") {
+            location.to_string()
+        } else if location.starts_with('/') {
+            format!("{}://{}:{}{}", if parsed.tls { "https" } else { "http" }, parsed.host, parsed.port, location)
+        } else {
+            let base = current.rsplit_once('/').map_or(current, |(base, _)| base);
+            format!("{}/{}", base, location)
+        }
+    }
+
+    fn request(url: &Url, timeout: Duration) -> Result<Response, String> {
+        let stream = connect(url, timeout)?;
+        stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+        stream.set_write_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+        let mut stream: Box<dyn Connection> = match url.tls {
+            true => Box::new(tls(url, stream)?),
+            false => Box::new(stream),
+        };
+
+        let default_port = if url.tls { 443 } else { 80 };
+        let host = if url.port == default_port { url.host.clone() } else { format!("{}:{}", url.host, url.port) };
+        let mut head = format!("GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: tpdf/{}\r\nAccept: application/pdf, */*\r\nConnection: close\r\n",
+                               url.path, host, env!("CARGO_PKG_VERSION"));
+        if let Some(credentials) = &url.credentials {
+            head.push_str(&format!("Authorization: Basic {}\r\n", base64(credentials.as_bytes())));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes()).map_err(|e| io_error(e, timeout))?;
+
+        let mut reader = BufReader::new(stream);
+        let status_line = read_line(&mut reader, timeout)?;
+        let mut parts = status_line.splitn(3, ' ');
+        let status: u16 = parts.nth(1).and_then(|code| code.parse().ok())
+            .ok_or_else(|| format!("invalid response {:?}", status_line))?;
+        let reason = parts.next().unwrap_or("").trim().to_string();
+
+        let mut headers = Vec::new();
+        loop {
+            let line = read_line(&mut reader, timeout)?;
+            if line.is_empty() { break; }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+        let header = |name: &str| headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+
+        if matches!(status, 301 | 302 | 303 | 307 | 308) {
+            let location = header("location").ok_or_else(|| format!("HTTP {} without a Location", status))?;
+            return Ok(Response::Redirect(location.to_string()));
+        }
+        if !(200..300).contains(&status) {
+            return Err(format!("HTTP {} {}", status, reason).trim_end().to_string());
+        }
+
+        let body = if header("transfer-encoding").is_some_and(|value| value.eq_ignore_ascii_case("chunked")) {
+            read_chunked(&mut reader, timeout)?
+        } else if let Some(length) = header("content-length") {
+            let length: u64 = length.parse().map_err(|_| format!("invalid Content-Length {}", length))?;
+            if length > MAX_DOWNLOAD { return Err(too_large()); }
+            let mut body = vec![0; length as usize];
+            reader.read_exact(&mut body).map_err(|e| io_error(e, timeout))?;
+            body
+        } else {
+            read_capped(&mut reader, MAX_DOWNLOAD, timeout)?
+        };
+        Ok(Response::Body(body))
+    }
+
+    fn connect(url: &Url, timeout: Duration) -> Result<TcpStream, String> {
+        let host = url.host.trim_start_matches('[').trim_end_matches(']');
+        let addresses = (host, url.port).to_socket_addrs().map_err(|e| format!("cannot resolve {}: {}", url.host, e))?;
+
+        let mut last_error = format!("no address found for {}", url.host);
+        for address in addresses {
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(error) => last_error = format!("cannot connect to {}: {}", url.host, io_error(error, timeout)),
+            }
+        }
+        Err(last_error)
+    }
+
+    // The handshake happens on the first write, certificate errors surface there
+    fn tls(url: &Url, stream: TcpStream) -> Result<StreamOwned<ClientConnection, TcpStream>, String> {
+        let roots = RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+        let config = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| e.to_string())?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let host = url.host.trim_start_matches('[').trim_end_matches(']').to_string();
+        let name = ServerName::try_from(host).map_err(|_| format!("invalid host name {}", url.host))?;
+        let connection = ClientConnection::new(Arc::new(config), name).map_err(|e| e.to_string())?;
+        Ok(StreamOwned::new(connection, stream))
+    }
+
+    fn read_line(reader: &mut impl BufRead, timeout: Duration) -> Result<String, String> {
+        let mut line = String::new();
+        reader.read_line(&mut line).map_err(|e| io_error(e, timeout))?;
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    fn read_chunked(reader: &mut impl BufRead, timeout: Duration) -> Result<Vec<u8>, String> {
+        let mut body = Vec::new();
+        loop {
+            let line = read_line(reader, timeout)?;
+            let size = line.split(';').next().unwrap_or("").trim();
+            let size = u64::from_str_radix(size, 16).map_err(|_| format!("invalid chunk size {:?}", line))?;
+            if size == 0 { break; }
+            if body.len() as u64 + size > MAX_DOWNLOAD { return Err(too_large()); }
+
+            let start = body.len();
+            body.resize(start + size as usize, 0);
+            reader.read_exact(&mut body[start..]).map_err(|e| io_error(e, timeout))?;
+            read_line(reader, timeout)?;
+        }
+        Ok(body)
+    }
+
+    fn read_capped(reader: &mut impl Read, cap: u64, timeout: Duration) -> Result<Vec<u8>, String> {
+        let mut body = Vec::new();
+        match reader.take(cap + 1).read_to_end(&mut body) {
+            // many HTTPS servers close the connection without a TLS close_notify, the body is complete
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => {}
+            result => { result.map_err(|e| io_error(e, timeout))?; }
+        }
+        if body.len() as u64 > cap { return Err(too_large()); }
+        Ok(body)
+    }
+
+    fn too_large() -> String {
+        format!("the response is larger than {} MiB", MAX_DOWNLOAD / 1024 / 1024)
+    }
+
+    fn io_error(error: std::io::Error, timeout: Duration) -> String {
+        match error.kind() {
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => format!("timed out after {}s", timeout.as_secs_f64()),
+            _ => error.to_string(),
+        }
+    }
+
+    fn percent_decode(text: &str) -> String {
+        let bytes = text.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok()).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match (bytes[i], hex) {
+                (b'%', Some(byte)) => { decoded.push(byte); i += 3; }
+                (byte, _) => { decoded.push(byte); i += 1; }
+            }
+        }
+        String::from_utf8_lossy(&decoded).into_owned()
+    }
+
+    fn base64(data: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut encoded = String::new();
+        for chunk in data.chunks(3) {
+            let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+            let bits = (u32::from(bytes[0]) << 16) | (u32::from(bytes[1]) << 8) | u32::from(bytes[2]);
+            for position in 0..4 {
+                if position <= chunk.len() {
+                    encoded.push(ALPHABET[(bits >> (18 - 6 * position) & 63) as usize] as char);
+                } else {
+                    encoded.push('=');
+                }
+            }
+        }
+        encoded
+    }
+}
@@ -0,0 +1,864 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::PathBuf
+};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use serde::{Deserialize, Serialize};
+
+use crate::content::transform_box;
+use crate::links::{page_annotations, set_page_annotations};
+use crate::report;
+use crate::utils::{inherited_attribute, decode_text_string, encode_text_string, load_for_update, load_pdf, save_changes, SaveOptions, SaveReport};
+
+// field flags, PDF 32000-1:2008 tables 226 and 230
+const FLAG_RADIO: i64 = 1 << 15;
+const FLAG_PUSHBUTTON: i64 = 1 << 16;
+const FLAG_MULTI_SELECT: i64 = 1 << 21;
+
+/// File formats `export_fields` can write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// An object of fully qualified field names and values, the same shape `--fill` reads
+    Json,
+    /// Forms Data Format, which Acrobat can import
+    Fdf,
+}
+
+/// The value of a form field: text, the export value of a checkbox or radio button, or the
+/// selected option(s) of a choice field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FieldValue {
+    Text(String),
+    List(Vec<String>),
+}
+
+/// A terminal form field as reported by `forms --list`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldInfo {
+    /// Fully qualified name, the partial names of the field and its ancestors joined by dots
+    pub name: String,
+    /// One of text, checkbox, radio, pushbutton, choice, signature or unknown
+    pub kind: String,
+    /// The current value, if the field has one
+    pub value: Option<FieldValue>,
+    /// The value the field resets to, if the field has one
+    pub default: Option<FieldValue>,
+    /// The export values of a checkbox or radio button, or the options of a choice field
+    pub options: Vec<String>,
+}
+
+/// Prints every form field in a PDF with its current value
+///
+/// * `infile` - a PathBuf of the file to list fields from
+///
+pub fn print_fields(infile: PathBuf) {
+    let doc = load_pdf(&infile);
+
+    for field in list_fields(&doc) {
+        let value = match &field.value {
+            Some(FieldValue::Text(text)) => text.clone(),
+            Some(FieldValue::List(items)) => items.join(", "),
+            None => "-".to_string(),
+        };
+        println!("{} ({}): {}", field.name, field.kind, value);
+    }
+}
+
+/// Writes the value of every form field in a PDF to a JSON or FDF file. Fields without a value
+/// are null in JSON and have no /V in FDF; push buttons and signatures are left out.
+///
+/// * `infile` - a PathBuf of the file to export fields from
+/// * `datafile` - a PathBuf of the file to write the values to
+/// * `format` - whether to write JSON or FDF
+///
+pub fn export_fields(infile: PathBuf, datafile: PathBuf, format: ExportFormat) {
+    let doc = load_pdf(&infile);
+    let fields: Vec<FieldInfo> = list_fields(&doc)
+        .into_iter()
+        .filter(|field| has_value(&field.kind))
+        .collect();
+
+    let data = match format {
+        ExportFormat::Json => {
+            let values: BTreeMap<&str, &Option<FieldValue>> = fields.iter()
+                .map(|field| (field.name.as_str(), &field.value))
+                .collect();
+            let mut json = serde_json::to_string_pretty(&values).expect("failed to serialize fields");
+            json.push('\n');
+            json.into_bytes()
+        }
+        ExportFormat::Fdf => fdf(&fields),
+    };
+
+    if let Err(error) = fs::write(&datafile, data) {
+        panic!("Failed to write {}: {}", datafile.display(), error);
+    }
+    report::summary(format!("exported {} fields", fields.len()));
+}
+
+/// Fills the form fields of a PDF from a JSON file written by `export_fields`, either inplace or
+/// in a new file. A null value clears the field; fields not named in the file are left alone.
+///
+/// * `infile` - a PathBuf of the file to fill
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `datafile` - a PathBuf of the JSON file to read values from
+/// * `compress` - a boolean flag to compress the outfile before saving
+/// * `save` - how to write the file, see SaveOptions
+///
+pub fn fill_form(infile: PathBuf, outfile: Option<PathBuf>, datafile: PathBuf, compress: bool, save: &SaveOptions) -> SaveReport {
+    let (mut doc, before) = load_for_update(&infile);
+
+    let json = match fs::read_to_string(&datafile) {
+        Ok(json) => json,
+        Err(error) => panic!("Failed to read {}: {}", datafile.display(), error),
+    };
+    let values: BTreeMap<String, Option<FieldValue>> = match serde_json::from_str(&json) {
+        Ok(values) => values,
+        Err(error) => panic!("Failed to parse {}: {}", datafile.display(), error),
+    };
+
+    match fill_fields(&mut doc, &values) {
+        Ok(filled) => report::summary(format!("filled {} fields", filled)),
+        Err(error) => panic!("Failed to fill the form: {}", error),
+    }
+
+    if compress { report::timed("compress", || doc.compress()) }
+
+    save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Resets the form fields of a PDF to their defaults, either inplace or in a new file
+///
+/// * `infile` - a PathBuf of the file to reset
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `fields` - the fully qualified names of the fields to reset, or empty for every field
+/// * `compress` - a boolean flag to compress the outfile before saving
+/// * `save` - how to write the file, see SaveOptions
+///
+pub fn reset_form(infile: PathBuf, outfile: Option<PathBuf>, fields: Vec<String>, compress: bool, save: &SaveOptions) -> SaveReport {
+    let (mut doc, before) = load_for_update(&infile);
+
+    match reset_fields(&mut doc, &fields) {
+        Ok(reset) => report::summary(format!("reset {} fields", reset)),
+        Err(error) => panic!("Failed to reset the form: {}", error),
+    }
+
+    if compress { report::timed("compress", || doc.compress()) }
+
+    save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Removes the signature fields of a PDF and their widgets, either inplace or in a new file
+///
+/// * `infile` - a PathBuf of the file to change
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `flatten_first` - draw each signature's appearance onto its page before removing the widget
+/// * `compress` - a boolean flag to compress the outfile before saving
+/// * `save` - how to write the file, see SaveOptions
+///
+pub fn remove_signatures(infile: PathBuf, outfile: Option<PathBuf>, flatten_first: bool, compress: bool, save: &SaveOptions) -> SaveReport {
+    let (mut doc, before) = load_for_update(&infile);
+
+    let removed = remove_signature_fields(&mut doc, flatten_first);
+    report::summary(format!("removed {} signature fields", removed));
+
+    if compress { report::timed("compress", || doc.compress()) }
+
+    save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Lists the terminal fields of the document's interactive form, in the order of /Fields
+pub fn list_fields(doc: &Document) -> Vec<FieldInfo> {
+    collect_fields(doc)
+        .into_iter()
+        .map(|field| {
+            let dict = field.dict(doc);
+            FieldInfo {
+                value: field_value(doc, dict.get(b"V").ok().or(field.inherited.get(b"V").ok())),
+                default: field_value(doc, dict.get(b"DV").ok().or(field.inherited.get(b"DV").ok())),
+                options: field.options(doc),
+                kind: field.kind.name().to_string(),
+                name: field.name,
+            }
+        })
+        .collect()
+}
+
+/// Names the signature fields that have been signed, i.e. whose value is a signature dictionary
+/// with a /ByteRange
+pub fn signed_fields(doc: &Document) -> Vec<String> {
+    collect_fields(doc)
+        .into_iter()
+        .filter(|field| field.kind == FieldKind::Signature)
+        .filter(|field| {
+            let value = field.dict(doc).get(b"V").ok().or(field.inherited.get(b"V").ok());
+            value.and_then(|v| doc.dereference(v).ok())
+                .and_then(|(_, v)| v.as_dict().ok())
+                .is_some_and(|v| v.has(b"ByteRange"))
+        })
+        .map(|field| field.name)
+        .collect()
+}
+
+/// Deletes every signature field, signed or not, from the form and its widgets from their pages,
+/// along with the catalog's /Perms (which holds the DocMDP certification signature). The signature
+/// dictionaries and appearance streams are left unreferenced so they get pruned when the document
+/// is saved.
+///
+/// * `flatten_first` - draw the normal appearance of each widget onto its page first
+///
+/// Returns the number of fields removed.
+pub fn remove_signature_fields(doc: &mut Document, flatten_first: bool) -> usize {
+    let signatures: Vec<Field> = collect_fields(doc)
+        .into_iter()
+        .filter(|field| field.kind == FieldKind::Signature)
+        .collect();
+    let widgets: HashSet<ObjectId> = signatures.iter().flat_map(|field| field.widgets.iter().copied()).collect();
+
+    for (_, page_id) in doc.get_pages() {
+        let annots = page_annotations(doc, page_id);
+        let (on_page, kept): (Vec<Object>, Vec<Object>) = annots.iter()
+            .cloned()
+            .partition(|annot| annot.as_reference().is_ok_and(|id| widgets.contains(&id)));
+        if on_page.is_empty() { continue; }
+
+        if flatten_first {
+            for widget in on_page.iter().filter_map(|annot| annot.as_reference().ok()) {
+                flatten_widget(doc, page_id, widget);
+            }
+        }
+        set_page_annotations(doc, page_id, kept);
+    }
+
+    // fields are referenced from /Fields or the /Kids of their parent
+    let removed: HashSet<ObjectId> = signatures.iter().map(|field| field.id).collect();
+    let parents: Vec<ObjectId> = signatures.iter()
+        .filter_map(|field| field.dict(doc).get(b"Parent").and_then(Object::as_reference).ok())
+        .collect();
+    let not_removed = |item: &Object| !item.as_reference().is_ok_and(|id| removed.contains(&id));
+    for parent in parents {
+        if let Ok(Object::Array(kids)) = doc.get_object_mut(parent).and_then(Object::as_dict_mut).and_then(|p| p.get_mut(b"Kids")) {
+            kids.retain(not_removed);
+        }
+    }
+    let fields_id = acroform(doc).and_then(|acroform| acroform.get(b"Fields").and_then(Object::as_reference).ok());
+    if let Some(acroform) = acroform_mut(doc) {
+        if let Ok(Object::Array(fields)) = acroform.get_mut(b"Fields") {
+            fields.retain(not_removed);
+        }
+        acroform.remove(b"SigFlags");
+    }
+    if let Some(Ok(Object::Array(fields))) = fields_id.map(|id| doc.get_object_mut(id)) {
+        fields.retain(not_removed);
+    }
+
+    let catalog_id = doc.trailer.get(b"Root").and_then(Object::as_reference).ok();
+    if let Some(Ok(catalog)) = catalog_id.map(|id| doc.get_object_mut(id).and_then(Object::as_dict_mut)) {
+        catalog.remove(b"Perms");
+    }
+
+    signatures.len()
+}
+
+/// Sets the values of the named fields. Checkboxes and radio buttons take one of their export
+/// values (or Off) and have their widgets switched to match; text and choice fields are marked
+/// for viewers to regenerate their appearance.
+///
+/// Returns the number of fields filled, or an error naming a field that does not exist or a
+/// value the field cannot take.
+pub fn fill_fields(doc: &mut Document, values: &BTreeMap<String, Option<FieldValue>>) -> Result<usize, String> {
+    let fields = collect_fields(doc);
+    let by_name: BTreeMap<&str, &Field> = fields.iter().map(|field| (field.name.as_str(), field)).collect();
+
+    let mut needs_appearances = false;
+    for (name, value) in values {
+        let field = by_name.get(name.as_str()).ok_or_else(|| format!("there is no field named `{}`", name))?;
+        needs_appearances |= set_field(doc, field, value.as_ref())?;
+    }
+
+    if needs_appearances {
+        if let Some(acroform) = acroform_mut(doc) {
+            acroform.set("NeedAppearances", true);
+        }
+    }
+
+    Ok(values.len())
+}
+
+/// Resets fields to their default value (/DV), or clears them if they have none. Checkboxes and
+/// radio buttons are switched to the default state or Off. Only the named fields are reset, or
+/// every field when `names` is empty.
+///
+/// Returns the number of fields reset, or an error naming a field that does not exist.
+pub fn reset_fields(doc: &mut Document, names: &[String]) -> Result<usize, String> {
+    let fields = collect_fields(doc);
+    if let Some(missing) = names.iter().find(|name| !fields.iter().any(|field| &field.name == *name)) {
+        return Err(format!("there is no field named `{}`", missing));
+    }
+
+    let mut reset = 0;
+    let mut needs_appearances = false;
+    for field in &fields {
+        if !names.is_empty() && !names.contains(&field.name) { continue; }
+        if !has_value(field.kind.name()) { continue; }
+
+        let dict = field.dict(doc);
+        let mut default = field_value(doc, dict.get(b"DV").ok().or(field.inherited.get(b"DV").ok()));
+        if let (FieldKind::Checkbox | FieldKind::Radio, Some(FieldValue::Text(state))) = (field.kind, &default) {
+            // a default that is not one of the button's states would not show, fall back to Off
+            if !field.options(doc).contains(state) { default = None; }
+        }
+        // a default that the field cannot take is as good as none
+        needs_appearances |= match set_field(doc, field, default.as_ref()) {
+            Ok(needs_appearance) => needs_appearance,
+            Err(_) => set_field(doc, field, None)?,
+        };
+        reset += 1;
+    }
+
+    if needs_appearances {
+        if let Some(acroform) = acroform_mut(doc) {
+            acroform.set("NeedAppearances", true);
+        }
+    }
+
+    Ok(reset)
+}
+
+
+/// The interactive forms of several documents, gathered to go into the one they are merged into.
+/// The /Fields of every form are put one after the other, and a top level field whose name is
+/// already taken by an earlier document gets the number of its document appended (`name` becomes
+/// `name_2`) so viewers do not tie unrelated fields together. The /DR resources are combined, the
+/// first form's wins where two use the same resource name. XFA is dropped, it cannot be combined.
+#[derive(Debug, Default)]
+pub struct MergedForms {
+    found: bool,
+    fields: Vec<Object>,
+    names: HashSet<String>,
+    resources: Dictionary,
+    default_appearance: Option<Vec<u8>>,
+    need_appearances: bool,
+    sig_flags: i64,
+    calculation_order: Vec<Object>,
+    /// Number of top level fields that were renamed
+    pub renamed: usize,
+}
+
+impl MergedForms {
+    /// Adds the form of a document, renaming its fields where needed. A field that relied on a
+    /// default appearance (/DA) other than the merged form's gets its own.
+    ///
+    /// * `doc` - the document, with its objects numbered as they will be in the merged one
+    /// * `index` - the position of the document among those merged, starting at 0
+    ///
+    pub fn add(&mut self, doc: &mut Document, index: usize) {
+        let form = match acroform(doc) {
+            Some(form) => form.clone(),
+            None => return,
+        };
+        self.found = true;
+        let resolved = |key: &[u8]| form.get(key).ok()
+            .and_then(|value| doc.dereference(value).ok())
+            .map(|(_, value)| value.clone());
+
+        let roots: Vec<ObjectId> = resolved(b"Fields")
+            .and_then(|fields| fields.as_array().ok().cloned())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|field| field.as_reference().ok())
+            .collect();
+        if let Some(Object::Dictionary(resources)) = resolved(b"DR") {
+            for (category, entries) in resources.iter() {
+                let entries = match doc.dereference(entries) {
+                    Ok((_, Object::Dictionary(entries))) => entries.clone(),
+                    _ => continue,
+                };
+                let mut merged = self.resources.get(category).and_then(Object::as_dict).cloned().unwrap_or_default();
+                for (name, resource) in entries.iter() {
+                    if !merged.has(name) {
+                        merged.set(name.clone(), resource.clone());
+                    }
+                }
+                self.resources.set(category.clone(), merged);
+            }
+        }
+        let default_appearance = resolved(b"DA").and_then(|da| da.as_str().ok().map(<[u8]>::to_vec));
+        self.need_appearances |= resolved(b"NeedAppearances").and_then(|need| need.as_bool().ok()).unwrap_or(false);
+        self.sig_flags |= resolved(b"SigFlags").and_then(|flags| flags.as_i64().ok()).unwrap_or(0);
+        if let Some(Object::Array(order)) = resolved(b"CO") {
+            self.calculation_order.extend(order);
+        }
+
+        // /DA is inheritable, so a root field takes it from the form when it does not set its own
+        let own_appearance = match (&self.default_appearance, default_appearance) {
+            (None, da) => {
+                self.default_appearance = da;
+                None
+            }
+            (Some(merged), Some(da)) if *merged != da => Some(da),
+            _ => None,
+        };
+
+        for root in roots {
+            let field = match doc.get_object_mut(root).and_then(Object::as_dict_mut) {
+                Ok(field) => field,
+                Err(_) => continue,
+            };
+            if let (Some(da), false) = (&own_appearance, field.has(b"DA")) {
+                field.set("DA", Object::string_literal(da.clone()));
+            }
+            if let Ok(partial) = field.get(b"T").and_then(Object::as_str) {
+                let name = decode_text_string(partial);
+                let name = if self.names.contains(&name) {
+                    let renamed = unused_field_name(&self.names, &name, index);
+                    field.set("T", encode_text_string(&renamed));
+                    self.renamed += 1;
+                    renamed
+                } else {
+                    name
+                };
+                self.names.insert(name);
+            }
+            self.fields.push(root.into());
+        }
+    }
+
+    /// Writes the gathered form into the catalog of the merged document, replacing its /AcroForm.
+    /// Leaves the catalog alone if none of the documents had a form.
+    pub fn write(self, doc: &mut Document) {
+        if !self.found { return; }
+
+        let mut form = dictionary! { "Fields" => self.fields };
+        if !self.resources.is_empty() {
+            form.set("DR", self.resources);
+        }
+        if let Some(da) = self.default_appearance {
+            form.set("DA", Object::string_literal(da));
+        }
+        if self.need_appearances {
+            form.set("NeedAppearances", true);
+        }
+        if self.sig_flags != 0 {
+            form.set("SigFlags", self.sig_flags);
+        }
+        if !self.calculation_order.is_empty() {
+            form.set("CO", self.calculation_order);
+        }
+
+        let form_id = doc.add_object(form);
+        let catalog_id = match doc.trailer.get(b"Root").and_then(Object::as_reference) {
+            Ok(id) => id,
+            Err(_) => return,
+        };
+        if let Ok(catalog) = doc.get_object_mut(catalog_id).and_then(Object::as_dict_mut) {
+            catalog.set("AcroForm", form_id);
+        }
+    }
+}
+
+// ------- Helpers -------
+
+// `name` with the number of the document it came from appended, and a counter too if that is taken
+pub(crate) fn unused_field_name(taken: &HashSet<String>, name: &str, index: usize) -> String {
+    let mut candidate = format!("{}_{}", name, index + 1);
+    let mut count = 1;
+    while taken.contains(&candidate) {
+        count += 1;
+        candidate = format!("{}_{}_{}", name, index + 1, count);
+    }
+    candidate
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldKind {
+    Text,
+    Checkbox,
+    Radio,
+    PushButton,
+    Choice { multi_select: bool },
+    Signature,
+    Unknown,
+}
+
+impl FieldKind {
+    fn name(&self) -> &'static str {
+        match self {
+            FieldKind::Text => "text",
+            FieldKind::Checkbox => "checkbox",
+            FieldKind::Radio => "radio",
+            FieldKind::PushButton => "pushbutton",
+            FieldKind::Choice { .. } => "choice",
+            FieldKind::Signature => "signature",
+            FieldKind::Unknown => "unknown",
+        }
+    }
+}
+
+struct Field {
+    id: ObjectId,
+    name: String,
+    kind: FieldKind,
+    /// the inheritable entries of its ancestors, /FT, /Ff, /V, /DV and /Opt
+    inherited: Dictionary,
+    widgets: Vec<ObjectId>,
+}
+
+impl Field {
+    fn dict<'a>(&self, doc: &'a Document) -> &'a Dictionary {
+        // collect_fields only keeps fields that are dictionaries
+        doc.get_dictionary(self.id).unwrap()
+    }
+
+    fn options(&self, doc: &Document) -> Vec<String> {
+        match self.kind {
+            FieldKind::Checkbox | FieldKind::Radio => {
+                let mut states: Vec<String> = Vec::new();
+                for widget in &self.widgets {
+                    for state in widget_states(doc, *widget) {
+                        if state != "Off" && !states.contains(&state) { states.push(state); }
+                    }
+                }
+                states
+            }
+            FieldKind::Choice { .. } => {
+                let dict = self.dict(doc);
+                let options = dict.get(b"Opt").ok().or(self.inherited.get(b"Opt").ok());
+                let options = match options.map(|o| doc.dereference(o)) {
+                    Some(Ok((_, Object::Array(options)))) => options.clone(),
+                    _ => return Vec::new(),
+                };
+                // each option is its text, or a pair of export value and text
+                options.iter()
+                    .filter_map(|option| match option {
+                        Object::Array(pair) => pair.first().cloned(),
+                        other => Some(other.clone()),
+                    })
+                    .filter_map(|option| option.as_str().ok().map(decode_text_string))
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+// sets or clears the value of one field, returning whether viewers need to regenerate its appearance
+fn set_field(doc: &mut Document, field: &Field, value: Option<&FieldValue>) -> Result<bool, String> {
+    let name = &field.name;
+    match field.kind {
+        FieldKind::Checkbox | FieldKind::Radio => {
+            let state = match value {
+                Some(FieldValue::Text(state)) => state.as_str(),
+                Some(FieldValue::List(_)) => return Err(format!("`{}` takes a single export value", name)),
+                None => "Off",
+            };
+            if state != "Off" && !field.options(doc).iter().any(|option| option == state) {
+                return Err(format!("`{}` is not an export value of `{}`", state, name));
+            }
+            set_value(doc, field.id, value.map(|_| Object::Name(state.as_bytes().to_vec())));
+            for widget in &field.widgets {
+                let on = widget_states(doc, *widget).iter().any(|s| s == state);
+                if let Ok(widget) = doc.get_object_mut(*widget).and_then(Object::as_dict_mut) {
+                    widget.set("AS", Object::Name(if on { state.as_bytes().to_vec() } else { b"Off".to_vec() }));
+                }
+            }
+            Ok(false)
+        }
+        FieldKind::Text | FieldKind::Choice { .. } => {
+            let object = match value {
+                Some(FieldValue::Text(text)) => Some(encode_text_string(text)),
+                Some(FieldValue::List(items)) => match field.kind {
+                    FieldKind::Choice { multi_select: true } => Some(Object::Array(items.iter().map(|i| encode_text_string(i)).collect())),
+                    _ => return Err(format!("`{}` takes a single value", name)),
+                },
+                None => None,
+            };
+            set_value(doc, field.id, object);
+            Ok(true)
+        }
+        _ => Err(format!("`{}` is a {} and has no value to fill", name, field.kind.name())),
+    }
+}
+
+fn has_value(kind: &str) -> bool {
+    kind != "pushbutton" && kind != "signature"
+}
+
+fn acroform(doc: &Document) -> Option<&Dictionary> {
+    let acroform = doc.catalog().ok()?.get(b"AcroForm").ok()?;
+    doc.dereference(acroform).ok()?.1.as_dict().ok()
+}
+
+fn acroform_mut(doc: &mut Document) -> Option<&mut Dictionary> {
+    let catalog_id = doc.trailer.get(b"Root").and_then(Object::as_reference).ok()?;
+    let acroform_id = doc.get_dictionary(catalog_id).ok()?.get(b"AcroForm").and_then(Object::as_reference).ok();
+    match acroform_id {
+        Some(id) => doc.get_object_mut(id).and_then(Object::as_dict_mut).ok(),
+        None => doc.get_object_mut(catalog_id)
+            .and_then(Object::as_dict_mut)
+            .and_then(|catalog| catalog.get_mut(b"AcroForm"))
+            .and_then(Object::as_dict_mut)
+            .ok(),
+    }
+}
+
+fn collect_fields(doc: &Document) -> Vec<Field> {
+    let mut fields = Vec::new();
+    let roots = acroform(doc)
+        .and_then(|acroform| acroform.get(b"Fields").ok())
+        .and_then(|fields| doc.dereference(fields).ok())
+        .and_then(|(_, fields)| fields.as_array().ok().cloned())
+        .unwrap_or_default();
+
+    let mut visited = HashSet::new();
+    for root in roots {
+        if let Ok(id) = root.as_reference() {
+            walk_field(doc, id, "", &Dictionary::new(), &mut visited, &mut fields);
+        }
+    }
+    fields
+}
+
+fn walk_field(doc: &Document,
+              id: ObjectId,
+              parent_name: &str,
+              inherited: &Dictionary,
+              visited: &mut HashSet<ObjectId>,
+              fields: &mut Vec<Field>) {
+    if !visited.insert(id) { return; }
+    let dict = match doc.get_dictionary(id) {
+        Ok(dict) => dict,
+        Err(_) => return,
+    };
+
+    let name = match dict.get(b"T").and_then(Object::as_str) {
+        Ok(partial) if parent_name.is_empty() => decode_text_string(partial),
+        Ok(partial) => format!("{}.{}", parent_name, decode_text_string(partial)),
+        Err(_) => parent_name.to_string(),
+    };
+    let mut inherited = inherited.clone();
+    for key in [b"FT".as_slice(), b"Ff", b"V", b"DV", b"Opt"] {
+        if let Ok(value) = dict.get(key) {
+            inherited.set(key.to_vec(), value.clone());
+        }
+    }
+
+    // kids with a partial name are fields of their own, the rest are this field's widgets
+    let kids: Vec<ObjectId> = dict.get(b"Kids")
+        .and_then(Object::as_array)
+        .map(|kids| kids.iter().filter_map(|kid| kid.as_reference().ok()).collect())
+        .unwrap_or_default();
+    let (child_fields, widgets): (Vec<ObjectId>, Vec<ObjectId>) = kids.into_iter()
+        .partition(|kid| doc.get_dictionary(*kid).is_ok_and(|kid| kid.has(b"T")));
+
+    if !child_fields.is_empty() {
+        for child in child_fields {
+            walk_field(doc, child, &name, &inherited, visited, fields);
+        }
+        return;
+    }
+
+    let widgets = if widgets.is_empty() { vec![id] } else { widgets };
+    let flags = inherited.get(b"Ff").and_then(Object::as_i64).unwrap_or(0);
+    let kind = match inherited.get(b"FT").and_then(Object::as_name) {
+        Ok(b"Tx") => FieldKind::Text,
+        Ok(b"Btn") if flags & FLAG_PUSHBUTTON != 0 => FieldKind::PushButton,
+        Ok(b"Btn") if flags & FLAG_RADIO != 0 => FieldKind::Radio,
+        Ok(b"Btn") => FieldKind::Checkbox,
+        Ok(b"Ch") => FieldKind::Choice { multi_select: flags & FLAG_MULTI_SELECT != 0 },
+        Ok(b"Sig") => FieldKind::Signature,
+        _ => FieldKind::Unknown,
+    };
+
+    // inherited holds this field's own entries too, keep only what came from above
+    let mut from_ancestors = inherited;
+    for (key, _) in dict.iter() {
+        from_ancestors.remove(key);
+    }
+    fields.push(Field { id, name, kind, inherited: from_ancestors, widgets });
+}
+
+// draws the normal appearance of a widget onto its page, as a viewer would show it, PDF 32000-1:2008 12.5.5
+fn flatten_widget(doc: &mut Document, page_id: ObjectId, widget: ObjectId) {
+    let widget_dict = match doc.get_dictionary(widget) {
+        Ok(dict) => dict,
+        Err(_) => return,
+    };
+    let appearance = widget_dict.get(b"AP").ok()
+        .and_then(|ap| doc.dereference(ap).ok())
+        .and_then(|(_, ap)| ap.as_dict().ok())
+        .and_then(|ap| ap.get(b"N").and_then(Object::as_reference).ok());
+    let rect: Vec<f64> = widget_dict.get(b"Rect").and_then(Object::as_array)
+        .map(|rect| rect.iter().filter_map(|n| n.as_float().ok()).collect())
+        .unwrap_or_default();
+    let (appearance, [x0, y0, x1, y1]) = match (appearance, &rect[..]) {
+        (Some(appearance), &[x0, y0, x1, y1]) => (appearance, [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]),
+        _ => return,
+    };
+    let stream = match doc.get_object(appearance).and_then(Object::as_stream) {
+        Ok(stream) => stream,
+        Err(_) => return,
+    };
+    let numbers = |key: &[u8]| -> Vec<f64> {
+        stream.dict.get(key).and_then(Object::as_array)
+            .map(|values| values.iter().filter_map(|n| n.as_float().ok()).collect())
+            .unwrap_or_default()
+    };
+    let matrix: [f64; 6] = numbers(b"Matrix").try_into().unwrap_or([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+    let bbox = match numbers(b"BBox")[..] {
+        [bx0, by0, bx1, by1] => transform_box(&matrix, bx0, by0, bx1, by1),
+        _ => return,
+    };
+    if bbox[2] == bbox[0] || bbox[3] == bbox[1] { return; }
+
+    // maps the transformed bounding box onto the widget's rectangle
+    let sx = (x1 - x0) / (bbox[2] - bbox[0]);
+    let sy = (y1 - y0) / (bbox[3] - bbox[1]);
+    let name = format!("SigAp{}", appearance.0);
+
+    let mut resources = match inherited_attribute(doc, page_id, b"Resources") {
+        Some(Object::Dictionary(resources)) => resources,
+        _ => Dictionary::new(),
+    };
+    let mut xobjects = resources.get(b"XObject").ok()
+        .and_then(|x| doc.dereference(x).ok())
+        .and_then(|(_, x)| x.as_dict().ok())
+        .cloned()
+        .unwrap_or_default();
+    xobjects.set(name.as_bytes().to_vec(), appearance);
+    resources.set("XObject", xobjects);
+
+    // the existing content runs in its own graphics state so the appearance is drawn untransformed
+    let save_state = doc.add_object(Stream::new(dictionary! {}, b"q".to_vec()));
+    let draw = format!("Q q {} 0 0 {} {} {} cm /{} Do Q", sx, sy, x0 - bbox[0] * sx, y0 - bbox[1] * sy, name);
+    let draw = doc.add_object(Stream::new(dictionary! {}, draw.into_bytes()));
+    let mut contents: Vec<Object> = vec![save_state.into()];
+    match doc.get_dictionary(page_id).and_then(|page| page.get(b"Contents")) {
+        Ok(Object::Array(existing)) => contents.extend(existing.iter().cloned()),
+        Ok(existing) => contents.push(existing.clone()),
+        Err(_) => {}
+    }
+    contents.push(draw.into());
+
+    if let Ok(page) = doc.get_object_mut(page_id).and_then(Object::as_dict_mut) {
+        page.set("Resources", resources);
+        page.set("Contents", contents);
+    }
+}
+
+// the appearance states of a checkbox or radio button widget, e.g. ["Yes", "Off"]
+fn widget_states(doc: &Document, widget: ObjectId) -> Vec<String> {
+    let normal = doc.get_dictionary(widget).ok()
+        .and_then(|widget| widget.get(b"AP").ok())
+        .and_then(|ap| doc.dereference(ap).ok())
+        .and_then(|(_, ap)| ap.as_dict().ok())
+        .and_then(|ap| ap.get(b"N").ok())
+        .and_then(|n| doc.dereference(n).ok())
+        .and_then(|(_, n)| n.as_dict().ok());
+    match normal {
+        Some(normal) => normal.iter().map(|(state, _)| String::from_utf8_lossy(state).into_owned()).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn field_value(doc: &Document, value: Option<&Object>) -> Option<FieldValue> {
+    let (_, value) = doc.dereference(value?).ok()?;
+    match value {
+        Object::Name(name) => Some(FieldValue::Text(String::from_utf8_lossy(name).into_owned())),
+        Object::String(text, _) => Some(FieldValue::Text(decode_text_string(text))),
+        Object::Array(items) => Some(FieldValue::List(
+            items.iter().filter_map(|item| item.as_str().ok().map(decode_text_string)).collect()
+        )),
+        _ => None,
+    }
+}
+
+fn set_value(doc: &mut Document, id: ObjectId, value: Option<Object>) {
+    if let Ok(field) = doc.get_object_mut(id).and_then(Object::as_dict_mut) {
+        match value {
+            Some(value) => field.set("V", value),
+            None => { field.remove(b"V"); }
+        }
+    }
+}
+
+// a minimal FDF file with the fields nested by their partial names, as Acrobat expects
+fn fdf(fields: &[FieldInfo]) -> Vec<u8> {
+    #[derive(Default)]
+    struct Node {
+        value: Option<String>,
+        kids: BTreeMap<String, Node>,
+    }
+
+    let mut root = Node::default();
+    for field in fields {
+        let mut node = &mut root;
+        for part in field.name.split('.') {
+            node = node.kids.entry(part.to_string()).or_default();
+        }
+        node.value = field.value.as_ref().map(|value| match (value, field.kind.as_str()) {
+            (FieldValue::Text(state), "checkbox" | "radio") => fdf_name(state),
+            (FieldValue::Text(text), _) => fdf_string(text),
+            (FieldValue::List(items), _) => {
+                let items: Vec<String> = items.iter().map(|item| fdf_string(item)).collect();
+                format!("[{}]", items.join(" "))
+            }
+        });
+    }
+
+    fn write_kids(kids: &BTreeMap<String, Node>, out: &mut String) {
+        for (name, node) in kids {
+            out.push_str(&format!("<< /T {}", fdf_string(name)));
+            if let Some(value) = &node.value {
+                out.push_str(&format!(" /V {}", value));
+            }
+            if !node.kids.is_empty() {
+                out.push_str(" /Kids [\n");
+                write_kids(&node.kids, out);
+                out.push(']');
+            }
+            out.push_str(" >>\n");
+        }
+    }
+
+    let mut out = String::from("%FDF-1.2\n1 0 obj\n<< /FDF << /Fields [\n");
+    write_kids(&root.kids, &mut out);
+    out.push_str("] >> >>\nendobj\ntrailer\n<< /Root 1 0 R >>\n%%EOF\n");
+    out.into_bytes()
+}
+
+fn fdf_string(text: &str) -> String {
+    if text.is_ascii() {
+        let mut escaped = String::from("(");
+        for c in text.chars() {
+            match c {
+                '(' | ')' | '\\' => { escaped.push('\\'); escaped.push(c); }
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                _ => escaped.push(c),
+            }
+        }
+        escaped.push(')');
+        return escaped;
+    }
+    let mut hex = String::from("<FEFF");
+    for unit in text.encode_utf16() {
+        hex.push_str(&format!("{:04X}", unit));
+    }
+    hex.push('>');
+    hex
+}
+
+fn fdf_name(name: &str) -> String {
+    let mut escaped = String::from("/");
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.+*".contains(&byte) {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("#{:02X}", byte));
+        }
+    }
+    escaped
+}
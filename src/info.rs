@@ -0,0 +1,207 @@
+use std::{fmt, fs, path::PathBuf, time::Duration};
+
+use lopdf::{Document, Object};
+use serde::{Deserialize, Serialize};
+
+use crate::bookmarks::top_level_count;
+use crate::encryption::is_encrypted;
+use crate::fetch;
+use crate::error::LoadError;
+use crate::report::{self, human_size};
+use crate::utils::{decode_text_string, parse_document, try_load_pdf};
+
+/// What `info` tells about a PDF, in text or as JSON with these field names. The document
+/// information fields that are not set are None, null in JSON.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+    pub path: PathBuf,
+    pub pages: usize,
+    pub version: String,
+    pub encrypted: bool,
+    /// Size in bytes
+    pub file_size: u64,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub creator: Option<String>,
+    pub producer: Option<String>,
+    /// The CreationDate in RFC 3339, as written when it is not a valid PDF date
+    pub created: Option<String>,
+    /// The ModDate in RFC 3339, as written when it is not a valid PDF date
+    pub modified: Option<String>,
+    pub has_outline: bool,
+    /// The number of top level bookmarks, None without an outline
+    pub bookmarks: Option<usize>,
+    pub has_acroform: bool,
+}
+
+/// A summary, or why a file could not be summarized, for the JSON `info` prints
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Entry {
+    Summary(Box<Summary>),
+    Failed { path: PathBuf, error: String },
+}
+
+/// Prints a summary of each PDF, one after the other, as aligned key and value lines or as JSON.
+/// Files that do not load, and URLs that cannot be downloaded, are skipped with a warning, or
+/// listed with their error in JSON. A URL is summarized from a local copy but listed as the URL.
+///
+/// * `paths` - the PDFs to summarize, files or URLs
+/// * `json` - print a JSON object for one file, an array of them for more, see `Entry`
+/// * `timeout` - how long to wait to connect to a URL, and for each read
+///
+pub fn print_info(paths: &[PathBuf], json: bool, timeout: Duration) {
+    let mut downloads = Vec::new();
+    let entries: Vec<Entry> = paths.iter().map(|path| {
+        let summary = fetch::localize(vec![path.clone()], timeout, &mut downloads)
+            .map_err(|error| error.to_string())
+            .and_then(|local| summarize(&local[0]).map_err(|error| error.to_string()));
+        match summary {
+            Ok(summary) => Entry::Summary(Box::new(Summary { path: path.clone(), ..summary })),
+            Err(error) => Entry::Failed { path: path.clone(), error },
+        }
+    }).collect();
+
+    if json {
+        let printed = match entries.as_slice() {
+            [entry] => serde_json::to_string_pretty(entry),
+            entries => serde_json::to_string_pretty(entries),
+        };
+        println!("{}", printed.expect("failed to serialize the summaries"));
+        return;
+    }
+    let text: Vec<String> = entries.into_iter().filter_map(|entry| match entry {
+        Entry::Summary(summary) => Some(summary.to_string()),
+        Entry::Failed { path, error } => {
+            report::warning(format!("skipped {}: {}", path.display(), error));
+            None
+        }
+    }).collect();
+    print!("{}", text.join("\n"));
+}
+
+/// Reads the summary of a PDF. Encrypted documents are decrypted with --password, or the empty
+/// user password, for their document information; it is left out when that fails.
+pub fn summarize(path: &PathBuf) -> Result<Summary, LoadError> {
+    let file_size = fs::metadata(path).map_err(LoadError::Io)?.len();
+    // as stored, the page tree and catalog are readable without the password
+    let stored = parse_document(path)?;
+    let encrypted = is_encrypted(&stored);
+    let decrypted = match encrypted {
+        true => match try_load_pdf(path) {
+            Ok(doc) => Some(doc),
+            Err(error) => {
+                report::warning(format!("{} (its document information is left out)", error));
+                None
+            }
+        },
+        false => None,
+    };
+    let field = |key: &[u8]| match (encrypted, &decrypted) {
+        (false, _) => info_field(&stored, key),
+        (true, Some(doc)) => info_field(doc, key),
+        (true, None) => None,
+    };
+    let catalog = stored.catalog().ok();
+    let date = |key: &[u8]| field(key).map(|date| rfc3339(&date).unwrap_or(date));
+    let bookmarks = top_level_count(&stored);
+
+    Ok(Summary {
+        path: path.clone(),
+        pages: stored.get_pages().len(),
+        version: stored.version.clone(),
+        encrypted,
+        file_size,
+        title: field(b"Title"),
+        author: field(b"Author"),
+        subject: field(b"Subject"),
+        creator: field(b"Creator"),
+        producer: field(b"Producer"),
+        created: date(b"CreationDate"),
+        modified: date(b"ModDate"),
+        has_outline: bookmarks.is_some(),
+        bookmarks,
+        has_acroform: catalog.is_some_and(|catalog| catalog.has(b"AcroForm")),
+    })
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |value: bool| if value { "yes" } else { "no" }.to_string();
+        let bookmarks = match self.bookmarks {
+            Some(count) => format!("{} top level", count),
+            None => "none".to_string(),
+        };
+        let lines = [
+            ("Pages", Some(self.pages.to_string())),
+            ("Version", Some(self.version.clone())),
+            ("Encrypted", Some(yes_no(self.encrypted))),
+            ("Size", Some(format!("{} ({} bytes)", human_size(self.file_size), self.file_size))),
+            ("Title", self.title.clone()),
+            ("Author", self.author.clone()),
+            ("Subject", self.subject.clone()),
+            ("Creator", self.creator.clone()),
+            ("Producer", self.producer.clone()),
+            ("Created", self.created.clone()),
+            ("Modified", self.modified.clone()),
+            ("Bookmarks", Some(bookmarks)),
+            ("Form", Some(yes_no(self.has_acroform))),
+        ];
+
+        writeln!(f, "{}", self.path.display())?;
+        for (key, value) in lines {
+            if let Some(value) = value {
+                writeln!(f, "  {:<14}{}", format!("{}:", key), value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// ------- Helpers -------
+
+// A field of the document information dictionary, None when it is missing or empty
+fn info_field(doc: &Document, key: &[u8]) -> Option<String> {
+    let info = doc.trailer.get(b"Info").ok()
+        .and_then(|info| doc.dereference(info).ok())
+        .and_then(|(_, info)| info.as_dict().ok())?;
+    let value = info.get(key).ok().and_then(|value| doc.dereference(value).ok())?.1;
+    match value {
+        Object::String(bytes, _) => Some(decode_text_string(bytes)).filter(|text| !text.is_empty()),
+        _ => None,
+    }
+}
+
+// A PDF date, D:YYYYMMDDHHmmSSOHH'mm', in RFC 3339. Everything after the year is optional; a date
+// without its offset from UT gets -00:00, RFC 3339's unknown offset. None if it does not parse.
+fn rfc3339(date: &str) -> Option<String> {
+    let date = date.trim().trim_start_matches("D:");
+    let digits: String = date.chars().take_while(char::is_ascii_digit).collect();
+    if digits.len() < 4 || digits.len() > 14 || !digits.len().is_multiple_of(2) { return None; }
+    let field = |start: usize, default: u32| -> u32 {
+        digits.get(start..start + 2).and_then(|v| v.parse().ok()).unwrap_or(default)
+    };
+    let (month, day, hour, minute, second) = (field(4, 1), field(6, 1), field(8, 0), field(10, 0), field(12, 0));
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let rest = date[digits.len()..].trim_end_matches('\'');
+    let offset = match rest.chars().next() {
+        None => "-00:00".to_string(),
+        Some('Z') if rest.len() == 1 => "Z".to_string(),
+        Some(sign @ ('+' | '-')) => {
+            let mut numbers = rest[1..].split('\'').map(|v| v.parse::<u32>().ok());
+            let hours = numbers.next().flatten().filter(|hours| *hours <= 23)?;
+            let minutes = match numbers.next() {
+                Some(minutes) => minutes.filter(|minutes| *minutes <= 59)?,
+                None => 0,
+            };
+            if numbers.next().is_some() { return None; }
+            format!("{}{:02}:{:02}", sign, hours, minutes)
+        }
+        Some(_) => return None,
+    };
+    Some(format!("{}-{:02}-{:02}T{:02}:{:02}:{:02}{}", &digits[..4], month, day, hour, minute, second, offset))
+}
@@ -1,2 +1,32 @@
 #[macro_use]
-pub mod utils;
\ No newline at end of file
+pub mod utils;
+pub mod sanitize;
+pub mod links;
+pub mod dests;
+pub mod names;
+pub mod structure;
+pub mod redact;
+pub mod bookmarks;
+pub mod actions;
+pub mod forms;
+pub mod attachments;
+pub mod encryption;
+pub mod revisions;
+pub mod prompt;
+pub mod manpage;
+pub mod response;
+pub mod report;
+pub mod compat;
+pub mod recovery;
+pub mod query;
+pub mod info;
+pub mod normalize;
+pub mod fetch;
+pub mod zip;
+pub mod pages;
+pub mod error;
+pub mod ops;
+mod content;
+
+pub use error::Error;
+pub use pages::{pages, PageRef};
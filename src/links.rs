@@ -0,0 +1,427 @@
+use std::{collections::BTreeMap, path::PathBuf};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, StringFormat};
+use serde::Serialize;
+
+use crate::bookmarks::retargeted;
+use crate::dests::{explicit_destination, goto_destination, resolve_destination};
+use crate::report;
+use crate::utils::{decode_text_string, load_for_update, load_pdf, page_box, save_changes, SaveOptions, SaveReport};
+
+/// Where a link annotation takes the reader
+#[derive(Debug, Clone)]
+pub enum LinkTarget {
+    /// A URI action, usually a web address
+    Uri(String),
+    /// A destination inside the document, from either a /Dest entry or a GoTo action
+    Internal(Object),
+    /// Another file, opened by a GoToR or Launch action
+    File(String),
+    /// A JavaScript action
+    JavaScript,
+    /// Any other action type, or a link with no action at all
+    Unknown,
+}
+
+impl LinkTarget {
+    /// True for targets that leave the document
+    pub fn is_external(&self) -> bool {
+        matches!(self, LinkTarget::Uri(_) | LinkTarget::File(_))
+    }
+}
+
+/// What a link added by `add_link` points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NewLink {
+    /// A web address or other URI
+    Uri(String),
+    /// A page number in the same document
+    GoTo(u32),
+}
+
+/// A link annotation as reported by `links`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LinkInfo {
+    /// Page the link annotation is on
+    pub page: u32,
+    /// The clickable area as [llx lly urx ury], in default user space
+    pub rect: Vec<f64>,
+    /// One of uri, internal, file, javascript or unknown
+    pub kind: String,
+    /// The URI, file name or destination name, if the link has one
+    pub target: Option<String>,
+    /// For internal links, the page the destination resolves to
+    pub target_page: Option<u32>,
+    /// True for internal links whose destination does not resolve to a page
+    pub broken: bool,
+}
+
+/// Prints every link annotation in a PDF
+///
+/// * `infile` - a PathBuf of the file to list links from
+/// * `json` - print a JSON array instead of one line per link
+/// * `external_only` - only list links that leave the document
+/// * `broken_only` - only list internal links whose destination does not resolve
+///
+pub fn print_links(infile: PathBuf, json: bool, external_only: bool, broken_only: bool) {
+    let doc = load_pdf(&infile);
+
+    let links: Vec<LinkInfo> = list_links(&doc)
+        .into_iter()
+        .filter(|link| !external_only || link.kind == "uri" || link.kind == "file")
+        .filter(|link| !broken_only || link.broken)
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&links).expect("failed to serialize links"));
+        return;
+    }
+
+    for link in links {
+        let rect: Vec<String> = link.rect.iter().map(|n| format!("{:.2}", n)).collect();
+        let destination = match (link.target_page, link.broken) {
+            (Some(page), _) => format!("page {}", page),
+            (None, true) => "broken".to_string(),
+            (None, false) => String::new(),
+        };
+        let details: Vec<&str> = [destination.as_str(), link.target.as_deref().unwrap_or("")]
+            .into_iter()
+            .filter(|d| !d.is_empty())
+            .collect();
+        println!("page {:<4} [{}]  {:<10} {}", link.page, rect.join(" "), link.kind, details.join(" "));
+    }
+}
+
+/// Collects every link annotation in the document, in page order
+pub fn list_links(doc: &Document) -> Vec<LinkInfo> {
+    let page_numbers: BTreeMap<ObjectId, u32> = doc.get_pages()
+        .into_iter()
+        .map(|(num, id)| (id, num))
+        .collect();
+    let mut links = Vec::new();
+
+    for (page, page_id) in doc.get_pages() {
+        for annot in page_annotations(doc, page_id) {
+            let annot = match doc.dereference(&annot).and_then(|(_, a)| a.as_dict()) {
+                Ok(annot) => annot,
+                Err(_) => continue,
+            };
+            let target = match link_target(doc, annot) {
+                Some(target) => target,
+                None => continue,
+            };
+
+            let rect = annot.get(b"Rect")
+                .and_then(Object::as_array)
+                .map(|r| r.iter().filter_map(|n| n.as_float().ok()).collect())
+                .unwrap_or_default();
+
+            let info = match target {
+                LinkTarget::Uri(uri) => LinkInfo { page, rect, kind: "uri".to_string(), target: Some(uri), target_page: None, broken: false },
+                LinkTarget::File(file) => LinkInfo { page, rect, kind: "file".to_string(), target: Some(file), target_page: None, broken: false },
+                LinkTarget::JavaScript => LinkInfo { page, rect, kind: "javascript".to_string(), target: None, target_page: None, broken: false },
+                LinkTarget::Unknown => LinkInfo { page, rect, kind: "unknown".to_string(), target: None, target_page: None, broken: false },
+                LinkTarget::Internal(dest) => {
+                    let name = match &dest {
+                        Object::Name(name) | Object::String(name, _) => Some(decode_text_string(name)),
+                        _ => None,
+                    };
+                    let target_page = resolve_destination(doc, &dest)
+                        .and_then(|id| page_numbers.get(&id).copied());
+                    LinkInfo { page, rect, kind: "internal".to_string(), target: name, target_page, broken: target_page.is_none() }
+                }
+            };
+            links.push(info);
+        }
+    }
+
+    links
+}
+
+/// Adds a link annotation to a page of a PDF either inplace or in a new file
+///
+/// * `infile` - a PathBuf of the file to add the link to
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `page` - the page to put the link on
+/// * `rect` - the clickable area as [llx lly urx ury]
+/// * `target` - where the link goes
+/// * `save` - how to write the file, see SaveOptions
+///
+pub fn add_link_to_file(infile: PathBuf, outfile: Option<PathBuf>, page: u32, rect: [f64; 4], target: NewLink, save: &SaveOptions) -> SaveReport {
+    let (mut doc, before) = load_for_update(&infile);
+
+    if let Err(error) = add_link(&mut doc, page, rect, target) {
+        panic!("Failed to add link: {}", error);
+    }
+
+    save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Appends a link annotation to a page's /Annots, creating the array if the page has none.
+/// Internal links are written as explicit destinations that fit the target page in the window.
+///
+/// Returns an error if either page does not exist or the rectangle is not inside the page.
+pub fn add_link(doc: &mut Document, page: u32, rect: [f64; 4], target: NewLink) -> Result<ObjectId, String> {
+    let pages = doc.get_pages();
+    let page_id = *pages.get(&page)
+        .ok_or_else(|| format!("page {} does not exist, the document has {} pages", page, pages.len()))?;
+
+    let [x0, y0, x1, y1] = rect;
+    let rect = [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)];
+    if rect[0] == rect[2] || rect[1] == rect[3] {
+        return Err("the link rectangle has no area".to_string());
+    }
+    if let Some(bounds) = page_box(doc, page_id) {
+        if rect[0] < bounds[0] || rect[1] < bounds[1] || rect[2] > bounds[2] || rect[3] > bounds[3] {
+            return Err(format!("the link rectangle {:?} is outside the page box {:?}", rect, bounds));
+        }
+    }
+
+    let mut annot = dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Link",
+        "Rect" => rect.iter().map(|n| Object::Real(*n)).collect::<Vec<_>>(),
+        "Border" => vec![0.into(), 0.into(), 0.into()],
+        "F" => 4, // print
+    };
+    match target {
+        NewLink::Uri(uri) => {
+            annot.set("A", dictionary! {
+                "S" => "URI",
+                "URI" => Object::String(uri.into_bytes(), StringFormat::Literal),
+            });
+        }
+        NewLink::GoTo(target_page) => {
+            let target_id = *pages.get(&target_page)
+                .ok_or_else(|| format!("link target page {} does not exist, the document has {} pages", target_page, pages.len()))?;
+            annot.set("Dest", vec![target_id.into(), "Fit".into()]);
+        }
+    }
+    let annot_id = doc.add_object(annot);
+
+    let mut annots = page_annotations(doc, page_id);
+    annots.push(annot_id.into());
+    set_page_annotations(doc, page_id, annots);
+
+    Ok(annot_id)
+}
+
+/// Removes link annotations from a PDF either inplace or in a new file
+///
+/// * `infile` - a PathBuf of the file to strip links from
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `external_only` - only remove links that leave the document, keeping internal GoTo links
+/// * `domains` - only remove URI links whose host is one of these domains or a subdomain of them
+/// * `compress` - a boolean flag to compress the outfile before saving
+/// * `save` - how to write the file, see SaveOptions
+///
+pub fn strip_links(infile: PathBuf,
+                   outfile: Option<PathBuf>,
+                   external_only: bool,
+                   domains: Vec<String>,
+                   compress: bool,
+                   save: &SaveOptions) -> SaveReport {
+    let (mut doc, before) = load_for_update(&infile);
+
+    let removed = remove_links(&mut doc, external_only, &domains);
+    report::summary(format!("removed {} link annotations", removed));
+
+    if compress { report::timed("compress", || doc.compress()) }
+
+    save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Removes link annotations from every page and returns how many were removed. The annotation
+/// and action objects are left unreferenced so they get pruned when the document is saved.
+///
+/// * `external_only` - only remove links that leave the document
+/// * `domains` - if not empty, only remove URI links pointing at these domains
+///
+pub fn remove_links(doc: &mut Document, external_only: bool, domains: &[String]) -> usize {
+    let mut removed = 0;
+
+    for (_, page_id) in doc.get_pages() {
+        let annots = page_annotations(doc, page_id);
+        let kept: Vec<Object> = annots.iter()
+            .filter(|annot| {
+                let target = match doc.dereference(annot).and_then(|(_, a)| a.as_dict()) {
+                    Ok(dict) => link_target(doc, dict),
+                    Err(_) => None,
+                };
+                match target {
+                    Some(target) => !should_remove(&target, external_only, domains),
+                    None => true, // not a link
+                }
+            })
+            .cloned()
+            .collect();
+
+        if kept.len() != annots.len() {
+            removed += annots.len() - kept.len();
+            set_page_annotations(doc, page_id, kept);
+        }
+    }
+
+    removed
+}
+
+/// Readies the internal links of a document to be moved into another one. Destinations are
+/// rewritten as explicit /Dest arrays pointing at the page object, as the names and page indices
+/// they may use mean nothing in the other document. Links whose destination does not lead to a
+/// page are left as they are.
+///
+/// Returns the number of links rewritten.
+pub fn portable_links(doc: &mut Document) -> usize {
+    let mut rewritten = 0;
+
+    for page_id in doc.page_iter().collect::<Vec<_>>() {
+        let mut annots = page_annotations(doc, page_id);
+        let mut changed = false;
+
+        for annot in annots.iter_mut() {
+            let (annot_id, dict) = match doc.dereference(annot) {
+                Ok((id, Object::Dictionary(dict))) if dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Link") => (id, dict.clone()),
+                _ => continue,
+            };
+            let dest = match goto_destination(doc, &dict) {
+                Some(dest) => dest,
+                None => continue,
+            };
+            let (target, mut explicit) = match resolve_destination(doc, &dest).zip(explicit_destination(doc, &dest)) {
+                Some(found) => found,
+                None => continue,
+            };
+            if matches!(&dest, Object::Array(arr) if arr.first().and_then(|o| o.as_reference().ok()) == Some(target)) {
+                continue;
+            }
+
+            explicit[0] = Object::Reference(target);
+            let retargeted = retargeted(doc, &dict, explicit);
+            match annot_id {
+                Some(id) => { doc.objects.insert(id, Object::Dictionary(retargeted)); }
+                None => {
+                    *annot = Object::Dictionary(retargeted);
+                    changed = true;
+                }
+            }
+            rewritten += 1;
+        }
+
+        if changed {
+            set_page_annotations(doc, page_id, annots);
+        }
+    }
+
+    rewritten
+}
+
+/// Returns the target of a link annotation, or None if the annotation is not a link
+pub fn link_target(doc: &Document, annot: &Dictionary) -> Option<LinkTarget> {
+    if annot.get(b"Subtype").and_then(Object::as_name).ok() != Some(b"Link") {
+        return None;
+    }
+
+    if let Ok(dest) = annot.get(b"Dest") {
+        return Some(LinkTarget::Internal(dest.clone()));
+    }
+
+    let action = match annot.get(b"A").and_then(|a| doc.dereference(a)).and_then(|(_, a)| a.as_dict()) {
+        Ok(action) => action,
+        Err(_) => return Some(LinkTarget::Unknown),
+    };
+
+    let target = match action.get(b"S").and_then(Object::as_name).unwrap_or(b"") {
+        b"URI" => {
+            let uri = action.get(b"URI").and_then(Object::as_str).unwrap_or(b"");
+            LinkTarget::Uri(String::from_utf8_lossy(uri).into_owned())
+        }
+        b"GoTo" => match action.get(b"D") {
+            Ok(dest) => LinkTarget::Internal(dest.clone()),
+            Err(_) => LinkTarget::Unknown,
+        },
+        b"GoToR" | b"Launch" => LinkTarget::File(file_spec_name(doc, action.get(b"F").ok())),
+        b"JavaScript" => LinkTarget::JavaScript,
+        _ => LinkTarget::Unknown,
+    };
+
+    Some(target)
+}
+
+/// Returns the host portion of a URI, lowercased and without any user info or port
+pub fn uri_host(uri: &str) -> Option<String> {
+    let (_, rest) = uri.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = host.split(':').next()?;
+
+    if host.is_empty() { None } else { Some(host.to_ascii_lowercase()) }
+}
+
+
+// ------- Helpers -------
+
+/// The entries of a page's /Annots array, which may be stored directly on the page or as its own object
+pub(crate) fn page_annotations(doc: &Document, page_id: ObjectId) -> Vec<Object> {
+    doc.get_dictionary(page_id)
+        .and_then(|page| page.get(b"Annots"))
+        .and_then(|annots| doc.dereference(annots))
+        .and_then(|(_, annots)| annots.as_array())
+        .map(|annots| annots.to_vec())
+        .unwrap_or_default()
+}
+
+/// Replaces a page's /Annots array, removing the key when there is nothing left
+pub(crate) fn set_page_annotations(doc: &mut Document, page_id: ObjectId, annots: Vec<Object>) {
+    let annots_ref = doc.get_dictionary(page_id)
+        .and_then(|page| page.get(b"Annots"))
+        .and_then(Object::as_reference)
+        .ok();
+
+    if let (Some(id), false) = (annots_ref, annots.is_empty()) {
+        doc.objects.insert(id, Object::Array(annots));
+        return;
+    }
+
+    if let Ok(page) = doc.get_object_mut(page_id).and_then(Object::as_dict_mut) {
+        if annots.is_empty() {
+            page.remove(b"Annots");
+        } else {
+            page.set("Annots", annots);
+        }
+    }
+}
+
+fn should_remove(target: &LinkTarget, external_only: bool, domains: &[String]) -> bool {
+    if !domains.is_empty() {
+        return match target {
+            LinkTarget::Uri(uri) => uri_host(uri).is_some_and(|host| host_matches(&host, domains)),
+            _ => false,
+        };
+    }
+
+    !external_only || target.is_external()
+}
+
+fn host_matches(host: &str, domains: &[String]) -> bool {
+    domains.iter().any(|domain| {
+        let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+        host == domain || host.ends_with(&format!(".{}", domain))
+    })
+}
+
+// file specifications are either a plain string or a dictionary with /UF or /F entries
+fn file_spec_name(doc: &Document, spec: Option<&Object>) -> String {
+    let spec = match spec.map(|s| doc.dereference(s)) {
+        Some(Ok((_, spec))) => spec,
+        _ => return String::new(),
+    };
+
+    match spec {
+        Object::String(name, _) => decode_text_string(name),
+        Object::Dictionary(dict) => dict.get(b"UF")
+            .or_else(|_| dict.get(b"F"))
+            .and_then(Object::as_str)
+            .map(decode_text_string)
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
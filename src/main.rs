@@ -1,8 +1,11 @@
 extern crate lopdf;
 
-use clap::{Parser, Subcommand, ArgGroup};
+use std::io::IsTerminal;
 
-pub mod utils;
+use clap::{CommandFactory, ErrorKind, Parser, Subcommand, ArgGroup};
+
+use pdfh::{fetch, query, info, normalize, utils, sanitize, links, redact, bookmarks, dests, actions, forms, attachments, encryption, revisions, prompt, manpage, response, report, compat, zip};
+use pdfh::ops::{self, PageRange, PageSelection};
 
 
 const DEG_MULTIPLE: i32 = 90;
@@ -15,27 +18,146 @@ const DEG_MULTIPLE: i32 = 90;
 struct Cli {
     #[clap(subcommand)]
     command: Commands,
+    #[clap(long, global = true)]
+    /// Rewrite digitally signed documents even though their signatures will no longer validate
+    break_signature: bool,
+    #[clap(long, global = true)]
+    /// Append changes to the input as an incremental update instead of rewriting the file
+    incremental: bool,
+    #[clap(long, global = true)]
+    /// Keep the permissions and modification time of files changed inplace
+    preserve: bool,
+    #[clap(long, global = true)]
+    /// Never color errors and warnings. Also off when NO_COLOR is set or stderr is not a terminal
+    no_color: bool,
+    #[clap(long, global = true, value_enum)]
+    /// Accept the command line conventions of another tool
+    compat: Option<compat::Compat>,
+    #[clap(short, long, global = true)]
+    /// Don't print summaries of what was changed. Errors and warnings are still shown
+    quiet: bool,
+    #[clap(long, global = true)]
+    /// Print JSON instead of text: the links for links, the summaries for info, and the
+    /// end-of-run summary of commands that write a file. split --zip adds a manifest.json entry
+    json: bool,
+    #[clap(long, global = true, parse(from_os_str))]
+    /// Directory for the files commands write, relative output names are resolved against it.
+    /// Created if missing. Files changed inplace stay where they are
+    output_dir: Option<std::path::PathBuf>,
+    #[clap(long, global = true)]
+    /// Try to recover files with a damaged cross-reference table instead of failing, skipping
+    /// objects that can not be read
+    lenient: bool,
+    #[clap(long, global = true)]
+    /// The password of encrypted inputs, user or owner. The files written are not encrypted. Merge
+    /// also takes one per input as a.pdf::password, colons in it included. Arguments end up in
+    /// shell history
+    password: Option<String>,
+    #[clap(long, global = true)]
+    /// Show how long loading, changing, compressing and saving took
+    timings: bool,
+    #[clap(long, global = true, default_value = "30", value_parser)]
+    /// Seconds to wait for a server to connect or send data, when inputs are URLs
+    timeout: u64,
 }
 
 #[derive(Debug, Subcommand)]
 enum Commands {
-    #[clap(arg_required_else_help = false)]
+    #[clap(arg_required_else_help = false, visible_alias = "cat")]
     /// Merges PDFs into a single file
     Merge {
-        #[clap(required = true, parse(from_os_str))]
-        infiles: Vec<std::path::PathBuf>,
-        #[clap(required = true, parse(from_os_str))]
-        outfile: std::path::PathBuf,
+        #[clap(required = true, value_name = "PATHS", parse(from_os_str))]
+        /// PDFs, or directories and zip archives of PDFs, then the file to write to. Patterns like 'reports/2023-*.pdf' are expanded. @list.txt reads them from a file, one or more per line. A suffix picks pages of a file, in the order given: a.pdf:3,1-2 or a.pdf:even. With --manifest only the output is needed
+        paths: Vec<std::path::PathBuf>,
         #[clap(short, long)]
-        compress: bool
+        compress: bool,
+        #[clap(short, long)]
+        /// Also merge the PDFs in subdirectories of the given directories and zip archives, depth first
+        recursive: bool,
+        #[clap(long, value_enum, default_value_t)]
+        /// The order of the PDFs found in a directory. Files given directly keep their order
+        sort: utils::SortOrder,
+        #[clap(long)]
+        /// Skip the inputs that cannot be loaded with a warning, instead of stopping at the first
+        skip_errors: bool,
+        #[clap(long)]
+        /// Add a bookmark for each input, titled with its Title or file name, with its own bookmarks under it
+        bookmark_by_file: bool,
+        #[clap(long, parse(from_os_str))]
+        /// Also merge the PDFs listed in this file, one per line with the pages to take: `body.pdf 1-12`
+        manifest: Option<std::path::PathBuf>,
+        #[clap(long)]
+        /// Keep one copy of the fonts, images and other resources the inputs share, e.g. when they come from the same template
+        dedupe_resources: bool,
+        #[clap(long, value_parser)]
+        /// Title of the merged file. Without any of the --set options it keeps the first input's
+        set_title: Option<String>,
+        #[clap(long, value_parser)]
+        set_author: Option<String>,
+        #[clap(long, value_parser)]
+        set_subject: Option<String>,
+        #[clap(long, value_parser)]
+        set_keywords: Option<String>,
+        #[clap(long, parse(from_os_str))]
+        /// Read the title, author, subject and keywords from a JSON object, the --set options win
+        metadata_json: Option<std::path::PathBuf>,
     },
-    #[clap(arg_required_else_help = false)]
+    #[clap(arg_required_else_help = false, visible_alias = "burst", allow_negative_numbers = true)]
+    #[clap(group(
+        ArgGroup::new("split-pages")
+            .required(false)
+            .args(&["pages", "pages-file", "every"])
+            .conflicts_with_all(&["ranges", "by-bookmarks", "odd", "even"])
+        ))]
     /// Splits each page of a PDF into a separate file
     Split {
         #[clap(required = true, parse(from_os_str))]
         infile: std::path::PathBuf,
-        #[clap(required = true, parse(from_os_str))]
-        outfile: std::path::PathBuf,
+        #[clap(required = false, conflicts_with_all = &["pattern", "ranges", "by-bookmarks", "odd", "even"], parse(from_os_str))]
+        /// Names the files: out.pdf gives out-001.pdf, out-002.pdf, ... The input's name if neither this nor --pattern is given
+        outfile: Option<std::path::PathBuf>,
+        #[clap(long, parse(from_os_str))]
+        /// Directory to write the files to, instead of the global --output-dir or the current directory
+        out_dir: Option<std::path::PathBuf>,
+        #[clap(long)]
+        /// Names the files, e.g. '{stem}-{page:03}.pdf'. {stem} is the input's name without .pdf, {page} the page number and {total} the page count, :03 pads a number with zeros
+        pattern: Option<String>,
+        #[clap(long, conflicts_with_all = &["pattern", "by-bookmarks", "odd", "even"])]
+        /// Write page ranges to files of their own instead of single pages, e.g. '1-4:cover.pdf,5-30:body.pdf,31-:appendix.pdf'. Pages outside the ranges are left out
+        ranges: Option<String>,
+        #[clap(long, conflicts_with_all = &["pattern", "odd", "even"])]
+        /// Write one file per top level bookmark, named after its title, with the pages up to the next one
+        by_bookmarks: bool,
+        #[clap(long, conflicts_with = "pattern", parse(from_os_str))]
+        /// Write the odd numbered pages to this file, in order. With --even, for rescanning the backs of double sided sheets
+        odd: Option<std::path::PathBuf>,
+        #[clap(long, conflicts_with = "pattern", parse(from_os_str))]
+        /// Write the even numbered pages to this file, in order
+        even: Option<std::path::PathBuf>,
+        #[clap(group = "split-pages", short, long, multiple=true, value_parser = utils::parse_page_ranges)]
+        /// Only write these pages, e.g. 2,5,9 or 10-. The files keep the page numbers of the input
+        pages: Option<Vec<Vec<PageRange>>>,
+        #[clap(group = "split-pages", long, parse(from_os_str))]
+        /// Read the pages to write from a file instead, separated by commas, spaces or newlines. # starts a comment
+        pages_file: Option<std::path::PathBuf>,
+        #[clap(group = "split-pages", short, long, value_parser)]
+        /// Only write every ith page
+        every: Option<u32>,
+        #[clap(long)]
+        /// Fail if a listed page does not exist, instead of writing the others with a warning
+        strict: bool,
+        #[clap(long, conflicts_with_all = &["ranges", "by-bookmarks", "odd", "even"], parse(from_os_str))]
+        /// Write the pages into this zip archive instead of loose files, each entry named like its file would have been
+        zip: Option<std::path::PathBuf>,
+        #[clap(long, requires = "zip", value_enum)]
+        /// Store the pages in the archive as they are (the default), or deflate them
+        zip_compression: Option<zip::ZipCompression>,
+        #[clap(long)]
+        /// Create the --out-dir if it is missing
+        mkdir: bool,
+        #[clap(long)]
+        /// Leave out the title, author and other document information and the XMP metadata of the input
+        strip_metadata: bool,
         #[clap(short, long)]
         compress: bool
     },
@@ -46,17 +168,89 @@ enum Commands {
         infile: std::path::PathBuf,
         #[clap(required = true, parse(from_os_str))]
         outfile: std::path::PathBuf,
-        #[clap(required = true, short, long)]
-        // Number of times to duplicate
+        /// Number of copies in the output, at least 1
+        #[clap(required = true, short, long, value_parser = copies_at_least_one)]
         num: u16,
+        #[clap(long)]
+        /// Repeat each page --num times in a row (1,1,2,2) instead of whole copies (1,2,1,2), for stapling by hand
+        uncollated: bool,
         #[clap(short, long)]
         compress: bool
     },
     #[clap(arg_required_else_help = false)]
+    /// Merges two PDFs page by page, e.g. the fronts and backs of a double sided scan
+    Interleave {
+        #[clap(required = true, parse(from_os_str))]
+        /// Its pages come first in each pair, e.g. the fronts
+        first: std::path::PathBuf,
+        #[clap(required = true, parse(from_os_str))]
+        /// Its pages come second in each pair, e.g. the backs
+        second: std::path::PathBuf,
+        #[clap(required = true, parse(from_os_str))]
+        outfile: std::path::PathBuf,
+        #[clap(long)]
+        /// Take the pages of the second file last to first, for backs scanned in reverse order
+        reverse_second: bool,
+        #[clap(long)]
+        /// Fail if the files have different page counts, instead of putting the pages left over at the end
+        strict: bool,
+        #[clap(short, long)]
+        compress: bool
+    },
+    #[clap(arg_required_else_help = false)]
+    /// Put all the pages of one PDF into another at a given page
+    Insert {
+        #[clap(required = true, parse(from_os_str))]
+        /// The file to insert into
+        base: std::path::PathBuf,
+        #[clap(required = true, parse(from_os_str))]
+        /// The file whose pages are inserted
+        insert: std::path::PathBuf,
+        #[clap(required = true, parse(from_os_str))]
+        outfile: std::path::PathBuf,
+        #[clap(long, default_value = "end", value_parser = utils::parse_position)]
+        /// The page of the base file the inserted pages go before: 1 puts them first, end after the last
+        at: ops::Position,
+        #[clap(short, long)]
+        compress: bool
+    },
+    #[clap(arg_required_else_help = false)]
+    #[clap(group(
+        ArgGroup::new("places")
+            .required(true)
+            .multiple(true)
+            .args(&["after", "before"])
+        ))]
+    /// Add blank pages, e.g. separators between the parts of a print job
+    Blank {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(required = false, parse(from_os_str))]
+        /// Modified inplace if not provided
+        outfile: Option<std::path::PathBuf>,
+        #[clap(short, long, multiple = true, value_parser = utils::parse_page_spec)]
+        /// Put blank pages after these pages
+        after: Option<Vec<Vec<u32>>>,
+        #[clap(short, long, multiple = true, value_parser = utils::parse_page_spec)]
+        /// Put blank pages before these pages, 1 for blanks in front
+        before: Option<Vec<Vec<u32>>>,
+        #[clap(short = 'n', long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+        /// How many blank pages go in each place
+        count: u16,
+        #[clap(short, long, default_value = "match", value_parser = utils::parse_page_size)]
+        /// match for the size of the page before, or A4 or Letter
+        size: ops::PageSize,
+    },
+    #[clap(arg_required_else_help = false)]
     #[clap(group(
         ArgGroup::new("rot")
             .required(false)
-            .args(&["pages", "every"])
+            .args(&["pages", "pages-file", "every", "odd", "even"])
+        ))]
+    #[clap(group(
+        ArgGroup::new("turn")
+            .required(true)
+            .args(&["degrees", "spec"])
         ))]
     /// Rotate an entire document, or select pages
     Rotate {
@@ -65,39 +259,72 @@ enum Commands {
         /// Modified inplace if not provided
         #[clap(required = false, parse(from_os_str))]
         outfile: Option<std::path::PathBuf>,
-        #[clap(required=true, value_parser = degree_in_range, short, long)]
+        #[clap(value_parser = degree_in_range, short, long, allow_hyphen_values = true)]
         /// Positive values are CW, negative are CCW rotation. Multipules of 90.
-        degrees: i32,
-        #[clap(group = "rot", short, long, multiple=true, value_parser)]
-        /// List of space separated page numbers. All pages if not provided.
-        pages: Option<Vec<u32>>,
+        degrees: Option<i32>,
+        #[clap(long, conflicts_with = "rot")]
+        /// Rotate pages by different amounts in one go, e.g. '3:90,4-6:270,12:180'
+        spec: Option<String>,
+        #[clap(group = "rot", short, long, multiple=true, value_parser = utils::parse_page_spec)]
+        /// Pages and ranges separated by commas or spaces, e.g. 1-5,8,10-12. All pages if not provided.
+        pages: Option<Vec<Vec<u32>>>,
+        #[clap(group = "rot", long, parse(from_os_str))]
+        /// Read the pages and ranges from a file instead, separated by commas, spaces or newlines. # starts a comment
+        pages_file: Option<std::path::PathBuf>,
         #[clap(group = "rot", short, long, value_parser)]
-        every: Option<u32>
+        every: Option<u32>,
+        #[clap(group = "rot", long)]
+        /// Rotate pages 1, 3, 5...
+        odd: bool,
+        #[clap(group = "rot", long)]
+        /// Rotate pages 2, 4, 6..., e.g. the backs of a double sided scan
+        even: bool,
+        #[clap(long)]
+        /// Add the rotation to the one each page already has, instead of replacing it
+        relative: bool,
+        #[clap(long)]
+        /// Fail if a listed page does not exist, instead of rotating the others with a warning
+        strict: bool
     },
-    #[clap(arg_required_else_help = false)]
+    #[clap(arg_required_else_help = false, allow_negative_numbers = true)]
     #[clap(group(
         ArgGroup::new("dels")
             .required(false)
-            .args(&["pages", "every"])
+            .args(&["pages", "pages-file", "every", "blank"])
         ))]
     /// Delete pages from a PDF. 
-    /// A list of space separated pages or --every ith page
+    /// A list of pages and ranges or --every ith page
     Delete {
         #[clap(required = true, parse(from_os_str))]
         infile: std::path::PathBuf,
         #[clap(required = false, parse(from_os_str))]
         /// Modified inplace if not provided
         outfile: Option<std::path::PathBuf>,
-        #[clap(group = "dels", short, long,  multiple=true, value_parser)]
-        /// List of space separated page numbers
-        pages: Option<Vec<u32>>,
+        #[clap(group = "dels", short, long, multiple=true, value_parser = utils::parse_page_ranges)]
+        /// Pages and ranges separated by commas or spaces, e.g. 1-5,8,10-12. 20- runs to the last page, -5 from the first
+        pages: Option<Vec<Vec<PageRange>>>,
+        #[clap(group = "dels", long, parse(from_os_str))]
+        /// Read the pages and ranges from a file instead, separated by commas, spaces or newlines. # starts a comment
+        pages_file: Option<std::path::PathBuf>,
         #[clap(group = "dels", short, long, value_parser)]
         /// Delete every ith page
         every: Option<u32>,
+        #[clap(group = "dels", long)]
+        /// Delete the pages that draw nothing: no text, no images, and no lines or fills beyond --blank-threshold
+        blank: bool,
+        #[clap(long, requires = "blank", conflicts_with_all = &["pages", "pages-file", "every"], value_name = "BYTES")]
+        /// Content size up to which a page that only draws lines or fills still counts as blank [default: 64]
+        blank_threshold: Option<usize>,
         #[clap(required=false, long)]
         /// Negates the deletion operation, i.e. keep only the listed pages. 
         /// Used with --every, it will keep every ith page rather than delete it.
         negate: bool,
+        #[clap(long)]
+        /// Remove the structure tree of a tagged PDF instead of pruning it to the remaining pages
+        strip_structure: bool,
+        #[clap(long)]
+        /// Fail if a listed page does not exist, instead of deleting the others with a warning
+        strict: bool,
         #[clap(short, long)]
         compress: bool
 
@@ -111,11 +338,26 @@ enum Commands {
         /// Modified inplace if not provided
         outfile: Option<std::path::PathBuf>,
     },
-    #[clap(arg_required_else_help = false)]
+    #[clap(arg_required_else_help = false, allow_negative_numbers = true)]
+    /// Move a page or a run of pages to another place in a PDF
+    Move {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(required = false, parse(from_os_str))]
+        /// Modified inplace if not provided
+        outfile: Option<std::path::PathBuf>,
+        #[clap(short, long, value_parser = utils::parse_page_run)]
+        /// The pages to move, like 10-12. 10- runs to the last page and -3 starts at the first
+        pages: PageRange,
+        #[clap(long, value_parser = utils::parse_position)]
+        /// Where the first moved page ends up, numbered without the moved pages, or end
+        to: ops::Position,
+    },
+    #[clap(arg_required_else_help = false, allow_negative_numbers = true)]
     #[clap(group(
         ArgGroup::new("extract")
             .required(false)
-            .args(&["pages", "every"])
+            .args(&["pages", "pages-file", "every"])
         ))]
     /// Extract specific pages from a PDF
     Extract {
@@ -124,58 +366,697 @@ enum Commands {
         #[clap(required = true, parse(from_os_str))]
         /// Modified inplace if not provided
         outfile: std::path::PathBuf,
-        #[clap(group = "extract", short, long,  multiple=true, value_parser)]
-        /// List of space separated page numbers
-        pages: Option<Vec<u32>>,
+        #[clap(group = "extract", short, long, multiple=true, value_parser = utils::parse_page_ranges)]
+        /// Pages and ranges separated by commas or spaces, e.g. 1-5,8,10-12. 20- runs to the last page, -5 from the first. 
+        /// The pages come out in the order listed, a page listed twice is extracted twice
+        pages: Option<Vec<Vec<PageRange>>>,
+        #[clap(group = "extract", long, parse(from_os_str))]
+        /// Read the pages and ranges from a file instead, separated by commas, spaces or newlines. # starts a comment
+        pages_file: Option<std::path::PathBuf>,
         #[clap(group = "extract", short, long, value_parser)]
-        /// Delete every ith page
+        /// Extract every ith page
         every: Option<u32>,
+        #[clap(long)]
+        /// Extract every page except the listed ones. 
+        /// Used with --every, it will leave out every ith page rather than extract it.
+        negate: bool,
+        #[clap(long)]
+        /// Point links and bookmarks to pages that are not extracted at the nearest extracted page, instead of removing them
+        retarget_links: bool,
+        #[clap(long)]
+        /// Remove the bookmarks, instead of keeping those that lead to extracted pages
+        drop_bookmarks: bool,
+        #[clap(long)]
+        /// Remove the structure tree of a tagged PDF instead of pruning it to the extracted pages
+        strip_structure: bool,
+        #[clap(long)]
+        /// Leave out the title, author and other document information and the XMP metadata of the input
+        strip_metadata: bool,
+        #[clap(long)]
+        /// Fail if a listed page does not exist, instead of extracting the others with a warning
+        strict: bool,
+    },
+    #[clap(arg_required_else_help = false)]
+    #[clap(group(
+        ArgGroup::new("sanitize")
+            .required(true)
+            .multiple(true)
+            .args(&["remove", "profile"])
+        ))]
+    /// Remove private or unwanted data from a PDF
+    Sanitize {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(required = false, parse(from_os_str))]
+        /// Modified inplace if not provided
+        outfile: Option<std::path::PathBuf>,
+        #[clap(short, long, multiple = true, value_enum)]
+        /// List of space separated categories of data to remove
+        remove: Vec<sanitize::Removal>,
+        #[clap(long, value_enum)]
+        /// Remove a preset group of categories, in addition to any given with --remove
+        profile: Option<sanitize::Profile>,
+        #[clap(short, long)]
+        compress: bool
+    },
+    #[clap(arg_required_else_help = false)]
+    #[clap(group(
+        ArgGroup::new("annots")
+            .required(true)
+            .args(&["strip-links"])
+        ))]
+    /// Remove annotations from a PDF
+    Annots {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(required = false, parse(from_os_str))]
+        /// Modified inplace if not provided
+        outfile: Option<std::path::PathBuf>,
+        #[clap(group = "annots", long)]
+        /// Remove link annotations. All links are removed unless filtered further.
+        strip_links: bool,
+        #[clap(long, requires = "strip-links")]
+        /// Only remove links that leave the document, keeping internal links
+        external_only: bool,
+        #[clap(long, multiple = true, value_parser, requires = "strip-links")]
+        /// Only remove web links to these domains (subdomains included)
+        domain: Vec<String>,
+        #[clap(short, long)]
+        compress: bool
+    },
+    #[clap(arg_required_else_help = false)]
+    #[clap(group(
+        ArgGroup::new("filter")
+            .required(false)
+            .args(&["external-only", "broken-only"])
+        ))]
+    #[clap(group(
+        ArgGroup::new("link-target")
+            .required(false)
+            .args(&["uri", "goto"])
+        ))]
+    /// List the links in a PDF and where they lead, or add a new link
+    Links {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(required = false, requires = "add", parse(from_os_str))]
+        /// Only used with --add. Modified inplace if not provided
+        outfile: Option<std::path::PathBuf>,
+        #[clap(group = "filter", long)]
+        /// Only list links that leave the document
+        external_only: bool,
+        #[clap(group = "filter", long)]
+        /// Only list internal links whose destination does not resolve
+        broken_only: bool,
+        #[clap(long, requires_all = &["page", "rect", "link-target"], conflicts_with = "filter")]
+        /// Add a link annotation instead of listing links
+        add: bool,
+        #[clap(long, requires = "add", value_parser)]
+        /// Page to add the link to
+        page: Option<u32>,
+        #[clap(long, requires = "add", value_parser = parse_rect)]
+        /// Clickable area as "llx lly urx ury" in points, e.g. "72 700 300 720"
+        rect: Option<[f64; 4]>,
+        #[clap(group = "link-target", long, requires = "add", value_parser)]
+        /// Make the link open this URI
+        uri: Option<String>,
+        #[clap(group = "link-target", long, requires = "add", value_parser)]
+        /// Make the link go to this page of the document
+        goto: Option<u32>
+    },
+    #[clap(arg_required_else_help = false)]
+    #[clap(group(
+        ArgGroup::new("redaction")
+            .required(true)
+            .multiple(true)
+            .args(&["area", "text"])
+        ))]
+    /// Black out areas of a PDF and remove the text and images underneath (best-effort)
+    Redact {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(required = false, parse(from_os_str))]
+        /// Modified inplace if not provided
+        outfile: Option<std::path::PathBuf>,
+        #[clap(long, multiple = true, value_parser = parse_area)]
+        /// Area to black out as "page:llx,lly,urx,ury" in points, e.g. "3:100,500,300,540"
+        area: Vec<redact::Area>,
+        #[clap(long, multiple = true, value_parser)]
+        /// Black out every occurrence of this text
+        text: Vec<String>,
+        #[clap(short, long)]
+        compress: bool
+    },
+    #[clap(arg_required_else_help = false)]
+    #[clap(group(
+        ArgGroup::new("bookmarks")
+            .required(true)
+            .args(&["shift", "generate", "repair"])
+        ))]
+    /// Generate or fix up the bookmarks of a PDF
+    Bookmarks {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(required = false, parse(from_os_str))]
+        /// Modified inplace if not provided
+        outfile: Option<std::path::PathBuf>,
+        #[clap(group = "bookmarks", long, allow_hyphen_values = true, value_parser)]
+        /// Move every bookmark this many pages later, or earlier if negative
+        shift: Option<i64>,
+        #[clap(long, requires = "shift")]
+        /// Also move the destinations of internal links
+        links: bool,
+        #[clap(group = "bookmarks", long)]
+        /// Replace the bookmarks with ones guessed from the size of the text
+        generate: bool,
+        #[clap(long, requires = "generate", default_value_t = 14.0, value_parser)]
+        /// Smallest font size, in points, that counts as a heading
+        min_size: f64,
+        #[clap(long, requires = "generate", default_value_t = 2, value_parser = clap::value_parser!(u64).range(1..))]
+        /// Number of heading sizes to turn into levels of bookmarks
+        levels: u64,
+        #[clap(long, requires = "generate")]
+        /// Print the headings that would become bookmarks without changing the file
+        dry_run: bool,
+        #[clap(group = "bookmarks", long)]
+        /// Fix broken /Count values and sibling or parent links in the outline
+        repair: bool,
+        #[clap(short, long)]
+        compress: bool
+    },
+    #[clap(arg_required_else_help = false)]
+    #[clap(group(
+        ArgGroup::new("dests")
+            .required(true)
+            .args(&["list", "prune"])
+        ))]
+    /// List named destinations, or remove the ones that lead nowhere
+    Dests {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(required = false, requires = "prune", parse(from_os_str))]
+        /// Only used with --prune. Modified inplace if not provided
+        outfile: Option<std::path::PathBuf>,
+        #[clap(group = "dests", long)]
+        /// List each named destination and the page it leads to
+        list: bool,
+        #[clap(group = "dests", long)]
+        /// Remove named destinations that do not lead to a page
+        prune: bool,
+        #[clap(short, long, requires = "prune")]
+        compress: bool
+    },
+    #[clap(arg_required_else_help = false)]
+    #[clap(group(
+        ArgGroup::new("openaction")
+            .required(true)
+            .args(&["clear", "set"])
+        ))]
+    /// Remove or change where a PDF opens
+    Openaction {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(required = false, parse(from_os_str))]
+        /// Modified inplace if not provided
+        outfile: Option<std::path::PathBuf>,
+        #[clap(group = "openaction", long)]
+        /// Remove the open action, viewers then start at the first page
+        clear: bool,
+        #[clap(group = "openaction", long, value_parser = actions::OpenDestination::parse)]
+        /// Open at a page with a fit mode, e.g. "page=1,fit", "page=2,fith=700" or "page=3,xyz=::1.5"
+        set: Option<actions::OpenDestination>,
+        #[clap(long)]
+        /// Also remove the /AA actions of the document and its pages
+        strip_additional_actions: bool,
+        #[clap(short, long)]
+        compress: bool
+    },
+    #[clap(arg_required_else_help = false)]
+    #[clap(group(
+        ArgGroup::new("forms")
+            .required(true)
+            .args(&["list", "export", "fill", "reset", "remove-signatures"])
+        ))]
+    #[clap(group(
+        ArgGroup::new("form-changes")
+            .args(&["fill", "reset", "remove-signatures"])
+        ))]
+    /// List, export, fill or reset the form fields of a PDF, or remove its signature fields
+    Forms {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(required = false, requires = "form-changes", parse(from_os_str))]
+        /// Only used with --fill, --reset or --remove-signatures. Modified inplace if not provided
+        outfile: Option<std::path::PathBuf>,
+        #[clap(group = "forms", long)]
+        /// List each field with its current value
+        list: bool,
+        #[clap(group = "forms", long, parse(from_os_str))]
+        /// Write the value of every field to this file
+        export: Option<std::path::PathBuf>,
+        #[clap(long, requires = "export", value_enum, default_value_t = forms::ExportFormat::Json)]
+        format: forms::ExportFormat,
+        #[clap(group = "forms", long, parse(from_os_str))]
+        /// Set field values from a JSON file in the format written by --export
+        fill: Option<std::path::PathBuf>,
+        #[clap(group = "forms", long)]
+        /// Clear every field, or restore its default value if it has one
+        reset: bool,
+        #[clap(long, requires = "reset", use_value_delimiter = true, value_delimiter = ',')]
+        /// Only reset these comma separated fields
+        fields: Vec<String>,
+        #[clap(group = "forms", long)]
+        /// Delete the signature fields, their widgets and any certification (DocMDP) permissions
+        remove_signatures: bool,
+        #[clap(long, requires = "remove-signatures")]
+        /// Draw each signature's appearance onto the page before removing it
+        flatten_first: bool,
+        #[clap(short, long, requires = "form-changes")]
+        compress: bool
+    },
+    #[clap(arg_required_else_help = false)]
+    #[clap(group(
+        ArgGroup::new("attach")
+            .required(true)
+            .args(&["list", "extract-all"])
+        ))]
+    /// List or extract the files attached to a PDF
+    Attach {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(group = "attach", long)]
+        /// List each attached file with its size
+        list: bool,
+        #[clap(group = "attach", long)]
+        /// Write every attached file to --output-dir, or the current directory
+        extract_all: bool,
+        #[clap(long, requires = "extract-all")]
+        /// Drop folders from stored file names instead of folding them into the name
+        flatten_names: bool,
+    },
+    #[clap(arg_required_else_help = false)]
+    /// Remove the encryption of a PDF, lifting any restrictions on printing, copying or editing
+    ///
+    /// Takes the user or owner password from --password, or asks for it when one is needed and
+    /// stdin is a terminal.
+    Decrypt {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(required = false, parse(from_os_str))]
+        /// Modified inplace if not provided
+        outfile: Option<std::path::PathBuf>,
+        #[clap(long)]
+        /// Only decrypt a document that opens without a password, refusing if it needs one
+        restrictions_only: bool,
+        #[clap(short, long)]
+        compress: bool
+    },
+    #[clap(arg_required_else_help = false)]
+    /// Count the revisions saved in a PDF by incremental updates, or flatten them into one
+    Revisions {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(required = false, requires = "flatten", parse(from_os_str))]
+        /// Only used with --flatten. Modified inplace if not provided
+        outfile: Option<std::path::PathBuf>,
+        #[clap(long)]
+        /// Rewrite the file as a single revision, discarding earlier versions of changed objects
+        flatten: bool,
+        #[clap(short, long, requires = "flatten")]
+        compress: bool
+    },
+    #[clap(arg_required_else_help = false)]
+    /// Write the man page for this tool
+    Manpage {
+        #[clap(short, long, parse(from_os_str))]
+        /// Written to stdout if not provided
+        output: Option<std::path::PathBuf>,
+    },
+    #[clap(arg_required_else_help = true)]
+    /// Print the page count, version, document information and more of PDFs
+    Info {
+        #[clap(required = true, parse(from_os_str))]
+        /// PDFs to summarize, one after the other
+        infiles: Vec<std::path::PathBuf>,
+    },
+    #[clap(arg_required_else_help = true)]
+    /// List the PDFs in files or directories that match every given criterion
+    Query {
+        #[clap(required = true, parse(from_os_str))]
+        /// PDFs, or directories of PDFs to search
+        paths: Vec<std::path::PathBuf>,
+        #[clap(long, value_parser)]
+        min_pages: Option<usize>,
+        #[clap(long, value_parser)]
+        max_pages: Option<usize>,
+        #[clap(long)]
+        /// Only encrypted PDFs
+        encrypted: bool,
+        #[clap(long, value_parser = parse_size)]
+        /// Size in bytes, or with a K, M or G suffix, e.g. 10M
+        larger_than: Option<u64>,
+        #[clap(long, value_parser = parse_size)]
+        /// Size in bytes, or with a K, M or G suffix
+        smaller_than: Option<u64>,
+        #[clap(long, value_parser)]
+        /// Text that appears on some page. Slower, the text of every page is extracted
+        contains: Option<String>,
+        #[clap(long)]
+        /// Only PDFs with form fields
+        has_forms: bool,
+        #[clap(long)]
+        /// Only PDFs with attached files
+        has_attachments: bool,
+    },
+    #[clap(arg_required_else_help = true)]
+    /// Rewrite a PDF in a canonical form, so that documents with the same content have the same bytes
+    Normalize {
+        #[clap(required = true, parse(from_os_str))]
+        infile: std::path::PathBuf,
+        #[clap(required = false, parse(from_os_str))]
+        /// Modified inplace if not provided
+        outfile: Option<std::path::PathBuf>,
     }
 }
 
 fn main() {
-    let args = Cli::parse();
+    let raw_args = match response::expand_response_files(std::env::args_os()) {
+        Ok(raw_args) => raw_args,
+        Err(message) => Cli::command().error(ErrorKind::Io, message).exit(),
+    };
+    let raw_args = compat::strip_output_keyword(raw_args);
+    let args = Cli::parse_from(raw_args);
+    report::set_color(report::color_enabled(args.no_color, std::env::var_os("NO_COLOR").as_deref(), std::io::stderr().is_terminal()));
+    report::set_quiet(args.quiet);
+    report::enable_timings(args.timings);
+    utils::set_lenient(args.lenient);
+    utils::set_password(args.password.clone());
+    report::install_panic_hook();
+    let started = std::time::Instant::now();
+    let output_dir = args.output_dir.clone();
+    // Where a named output file goes, the files changed inplace (None) stay put
+    let out = |path: std::path::PathBuf| utils::output_path(&path, None, output_dir.as_deref());
+    let save = utils::SaveOptions { break_signatures: args.break_signature, incremental: args.incremental, preserve: args.preserve };
+    let timeout = std::time::Duration::from_secs(args.timeout);
+    // Local copies of inputs given as URLs, removed when main returns
+    let mut downloads = Vec::new();
+    // The commands that return their errors instead of panicking fail through this
+    let mut failed = false;
+    let mut check = |result: Result<utils::SaveReport, pdfh::Error>| match result {
+        Ok(saved) => Some(saved),
+        Err(error) => {
+            report::error(&error);
+            failed = true;
+            None
+        }
+    };
 
-    match args.command {
-        Commands::Merge { mut infiles, outfile, compress } => {
-            utils::merge(&infiles, outfile, compress)
+    let saved = match args.command {
+        Commands::Merge { paths, compress, recursive, sort, skip_errors, bookmark_by_file, manifest, dedupe_resources, set_title, set_author, set_subject, set_keywords, metadata_json } => {
+            // the last path is the output, clap cannot tell it from the inputs when --manifest makes them optional
+            let mut infiles = paths;
+            let outfile = infiles.pop().unwrap();
+            if infiles.is_empty() && manifest.is_none() {
+                let message = format!("At least one input is required, {} is the file to write to. Give the inputs before it or list them with --manifest", outfile.display());
+                Cli::command().error(ErrorKind::MissingRequiredArgument, message).exit()
+            }
+            let infiles = fetch::localize(infiles, timeout, &mut downloads);
+            let info = metadata_json.map(|f| utils::read_info_file(&f)).transpose().map(|from_file| {
+                let from_file = from_file.unwrap_or_default();
+                utils::DocumentInfo {
+                    title: set_title.or(from_file.title),
+                    author: set_author.or(from_file.author),
+                    subject: set_subject.or(from_file.subject),
+                    keywords: set_keywords.or(from_file.keywords),
+                }
+            });
+            check(info.and_then(|info| infiles.and_then(|infiles| utils::merge(&infiles, out(outfile), compress, recursive, sort, skip_errors, bookmark_by_file, manifest.as_deref(), dedupe_resources, &info))))
         }
-        Commands::Split { infile, outfile, compress} => {
-            // TODO
-            println!("Not Implemented");
+        Commands::Split { infile, outfile, out_dir, pattern, ranges, by_bookmarks, odd, even, pages, pages_file, every, strict, zip, zip_compression, mkdir, strip_metadata, compress } => {
+            // the global --output-dir is always created
+            let create_dir = mkdir || out_dir.is_none();
+            let dir = utils::output_dir(out_dir.as_deref(), output_dir.as_deref());
+            if let Some(ranges) = ranges {
+                check(utils::split_ranges(infile, &dir, &ranges, create_dir, strip_metadata, compress))
+            } else if odd.is_some() || even.is_some() {
+                let resolve = |outfile: std::path::PathBuf| utils::resolve_output_path(&outfile, out_dir.as_deref(), output_dir.as_deref());
+                check(utils::split_odd_even(infile, odd.map(resolve), even.map(resolve), create_dir, strip_metadata, compress))
+            } else if by_bookmarks {
+                check(utils::split_bookmarks(infile, &dir, create_dir, strip_metadata, compress))
+            } else {
+                let naming = match pattern {
+                    Some(pattern) => utils::SplitNaming::Pattern { dir, pattern },
+                    None => {
+                        let outfile = outfile.unwrap_or_else(|| infile.file_name().unwrap_or_default().into());
+                        utils::SplitNaming::Outfile(utils::resolve_output_path(&outfile, out_dir.as_deref(), output_dir.as_deref()))
+                    }
+                };
+                let zip = zip.map(|path| utils::SplitZip {
+                    path: utils::resolve_output_path(&path, out_dir.as_deref(), output_dir.as_deref()),
+                    compression: zip_compression.unwrap_or(zip::ZipCompression::Store),
+                    manifest: args.json,
+                });
+                check(page_ranges(pages, pages_file, every).and_then(|selection| utils::split(infile, &naming, selection, zip.as_ref(), create_dir, strip_metadata, compress, strict)))
+            }
         }
-        Commands::Dupe { infile, outfile, num, compress} => {
-            utils::dupe(infile, outfile, num, compress);
+        Commands::Dupe { infile, outfile, num, uncollated, compress } => {
+            check(utils::dupe(infile, out(outfile), num, !uncollated, compress))
+        }
+        Commands::Interleave { first, second, outfile, reverse_second, strict, compress } => {
+            check(utils::interleave(first, second, out(outfile), reverse_second, strict, compress))
+        }
+        Commands::Insert { base, insert, outfile, at, compress } => {
+            check(utils::insert(base, insert, out(outfile), at, compress))
+        }
+        Commands::Blank { infile, outfile, after, before, count, size } => {
+            let after = after.map(utils::join_page_specs).unwrap_or_default();
+            let before = before.map(utils::join_page_specs).unwrap_or_default();
+            check(utils::insert_blank_pages(infile, outfile.map(out), &after, &before, count, size, &save))
         }
         Commands::Rotate { infile,
                            outfile, 
                            degrees, 
+                           spec,
                            pages,
-                           every } => {
-            utils::rotate(infile, outfile, degrees, pages, every);
+                           pages_file,
+                           every,
+                           odd,
+                           even,
+                           relative,
+                           strict } => {
+            match (degrees, spec) {
+                (_, Some(spec)) => check(utils::rotate_spec(infile, outfile.map(out), &spec, relative, strict, &save)),
+                (degrees, None) => {
+                    let selection = match (pages, pages_file) {
+                        (Some(pages), _) => Ok(PageSelection::new(Some(utils::join_page_specs(pages)), every)),
+                        (None, Some(pages_file)) => utils::read_pages_file(&pages_file).map(PageSelection::Ranges),
+                        (None, None) => Ok(PageSelection::new(None, every)),
+                    };
+                    let selection = selection.map(|selection| match (odd, even) {
+                        (true, _) => PageSelection::Odd,
+                        (_, true) => PageSelection::Every(2),
+                        _ => selection,
+                    });
+                    // the turn group makes sure one of them is given
+                    check(selection.and_then(|selection| utils::rotate(infile, outfile.map(out), degrees.unwrap_or_default(), selection, relative, strict, &save)))
+                }
+            }
         },
         Commands::Delete { infile, 
                            outfile, 
                            pages, 
+                           pages_file,
                            every, 
+                           blank,
+                           blank_threshold,
                            negate,
+                           strip_structure,
+                           strict,
                            compress } => {
+            let selection = match blank {
+                true => Ok(PageSelection::Blank(blank_threshold.unwrap_or(ops::BLANK_THRESHOLD))),
+                false => page_ranges(pages, pages_file, every),
+            };
 
-            utils::delete(infile, outfile, pages, every, negate, compress);
+            check(selection.and_then(|selection| utils::delete(infile, outfile.map(out), selection, negate, strip_structure, compress, strict, &save)))
         },
         Commands::Reverse { infile, outfile } => {
-            utils::reverse(infile, outfile);
+            check(utils::reverse(infile, outfile.map(out), &save))
+        },
+        Commands::Move { infile, outfile, pages, to } => {
+            check(utils::move_pages(infile, outfile.map(out), pages, to, &save))
         },
         Commands::Extract { infile, 
                             outfile, 
                             pages, 
-                            every } => {
-            utils::extract(infile, outfile, pages, every);
+                            pages_file,
+                            every,
+                            negate,
+                            retarget_links,
+                            drop_bookmarks,
+                            strip_structure,
+                            strip_metadata,
+                            strict } => {
+            let selection = page_ranges(pages, pages_file, every);
+            let infile = fetch::localize(vec![infile], timeout, &mut downloads).map(|mut infiles| infiles.remove(0));
+            check(selection.and_then(|selection| utils::extract(infile?, out(outfile), selection, negate, retarget_links, drop_bookmarks, strip_structure, strip_metadata, strict, &save)))
+        },
+        Commands::Sanitize { infile, outfile, mut remove, profile, compress } => {
+            if let Some(profile) = profile {
+                remove.splice(0..0, profile.removals().iter().copied());
+            }
+            check(sanitize::sanitize(infile, outfile.map(out), remove, compress, &save))
+        },
+        Commands::Annots { infile,
+                           outfile,
+                           strip_links: _,
+                           external_only,
+                           domain,
+                           compress } => {
+            Some(links::strip_links(infile, outfile.map(out), external_only, domain, compress, &save))
+        },
+        Commands::Links { infile,
+                          outfile,
+                          external_only,
+                          broken_only,
+                          add,
+                          page,
+                          rect,
+                          uri,
+                          goto } => {
+            if add {
+                // clap makes sure page, rect and one of uri or goto are present with --add
+                let target = match (uri, goto) {
+                    (Some(u), _) => links::NewLink::Uri(u),
+                    (None, Some(g)) => links::NewLink::GoTo(g),
+                    (None, None) => unreachable!(),
+                };
+                Some(links::add_link_to_file(infile, outfile.map(out), page.unwrap(), rect.unwrap(), target, &save))
+            } else {
+                links::print_links(infile, args.json, external_only, broken_only);
+                None
+            }
+        },
+        Commands::Redact { infile, outfile, area, text, compress } => {
+            check(redact::redact(infile, outfile.map(out), area, text, compress, &save))
+        },
+        Commands::Bookmarks { infile,
+                              outfile,
+                              shift,
+                              links,
+                              generate: _,
+                              min_size,
+                              levels,
+                              dry_run,
+                              repair,
+                              compress } => {
+            match shift {
+                Some(offset) => Some(bookmarks::shift_bookmarks(infile, outfile.map(out), offset, links, compress, &save)),
+                None if repair => Some(bookmarks::repair_bookmarks(infile, outfile.map(out), compress, &save)),
+                None => bookmarks::generate_bookmarks(infile, outfile.map(out), min_size, levels as usize, dry_run, compress, &save),
+            }
+        },
+        Commands::Dests { infile, outfile, list: _, prune, compress } => {
+            if prune {
+                Some(dests::prune_dests(infile, outfile.map(out), compress, &save))
+            } else {
+                dests::print_dests(infile);
+                None
+            }
+        }
+        Commands::Openaction { infile, outfile, clear: _, set, strip_additional_actions, compress } => {
+            Some(actions::open_action(infile, outfile.map(out), set, strip_additional_actions, compress, &save))
+        }
+        Commands::Forms { infile,
+                          outfile,
+                          list: _,
+                          export,
+                          format,
+                          fill,
+                          reset,
+                          fields,
+                          remove_signatures,
+                          flatten_first,
+                          compress } => {
+            match (export, fill) {
+                (Some(datafile), _) => { forms::export_fields(infile, datafile, format); None }
+                (None, Some(datafile)) => Some(forms::fill_form(infile, outfile.map(out), datafile, compress, &save)),
+                (None, None) if reset => Some(forms::reset_form(infile, outfile.map(out), fields, compress, &save)),
+                (None, None) if remove_signatures => Some(forms::remove_signatures(infile, outfile.map(out), flatten_first, compress, &save)),
+                (None, None) => { forms::print_fields(infile); None }
+            }
         }
-    }    
+        Commands::Attach { infile, list: _, extract_all, flatten_names } => {
+            if extract_all {
+                attachments::extract_attachments(infile, utils::output_dir(None, output_dir.as_deref()), flatten_names);
+            } else {
+                attachments::print_attachments(infile);
+            }
+            None
+        }
+        Commands::Decrypt { infile, outfile, restrictions_only, compress } => {
+            let password = args.password;
+            if restrictions_only && password.is_some() {
+                Cli::command().error(ErrorKind::ArgumentConflict, "--restrictions-only cannot be used with --password").exit()
+            }
+            if restrictions_only {
+                Some(encryption::decrypt(infile, outfile.map(out), password, &mut prompt::NoPrompt, compress, &save))
+            } else {
+                Some(encryption::decrypt(infile, outfile.map(out), password, &mut prompt::Terminal, compress, &save))
+            }
+        }
+        Commands::Revisions { infile, outfile, flatten, compress } => {
+            revisions::revisions(infile, outfile.map(out), flatten, compress, &save).transpose().and_then(check)
+        }
+        Commands::Manpage { output } => {
+            let page = manpage::render(&Cli::command(), manpage::EXAMPLES);
+            match output.map(out) {
+                Some(path) => std::fs::write(&path, page)
+                    .map(|()| None)
+                    .map_err(|source| pdfh::Error::SaveFailed { path, source })
+                    .transpose()
+                    .and_then(check),
+                None => {
+                    print!("{}", page);
+                    None
+                }
+            }
+        }
+        Commands::Info { infiles } => {
+            info::print_info(&infiles, args.json, timeout);
+            None
+        }
+        Commands::Query { paths, min_pages, max_pages, encrypted, larger_than, smaller_than, contains, has_forms, has_attachments } => {
+            let criteria = query::Criteria { min_pages, max_pages, encrypted, larger_than, smaller_than, contains, has_forms, has_attachments };
+            query::query(&paths, &criteria, args.json);
+            None
+        }
+        Commands::Normalize { infile, outfile } => {
+            check(normalize::normalize(infile, outfile.map(out), &save))
+        }
+    };
+
+    if failed {
+        drop(downloads);
+        std::process::exit(1);
+    }
+    if let Some(saved) = saved {
+        let mut summary = report::RunSummary::new(saved, started.elapsed(), report::warning_count());
+        if args.timings { summary.timings = Some(report::timings()); }
+        report::run_summary(&summary, args.json);
+    }
 }
 
 
+// The selection of delete and extract: --pages with its open ranges, a --pages-file or --every
+fn page_ranges(pages: Option<Vec<Vec<PageRange>>>, pages_file: Option<std::path::PathBuf>, every: Option<u32>) -> Result<PageSelection, pdfh::Error> {
+    match (pages, pages_file) {
+        (Some(pages), _) => Ok(PageSelection::Ranges(pages.into_iter().flatten().collect())),
+        (None, Some(pages_file)) => utils::read_pages_file(&pages_file).map(PageSelection::Ranges),
+        (None, None) => Ok(PageSelection::new(None, every)),
+    }
+}
+
 fn degree_in_range(s: &str) -> Result<i32, String> {
     let degree: i32 = s
         .parse()
@@ -189,3 +1070,52 @@ fn degree_in_range(s: &str) -> Result<i32, String> {
         ))
     }
 }
+
+fn copies_at_least_one(s: &str) -> Result<u16, String> {
+    let num: u16 = s
+        .parse()
+        .map_err(|_| format!("`{}` is not a number between 1 and {}", s, u16::MAX))?;
+    if num == 0 {
+        return Err("the output needs at least one copy".to_string());
+    }
+    Ok(num)
+}
+
+fn parse_rect(s: &str) -> Result<[f64; 4], String> {
+    let values = s
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse::<f64>().map_err(|_| format!("`{}` is not a number", v)))
+        .collect::<Result<Vec<f64>, String>>()?;
+    match values[..] {
+        [llx, lly, urx, ury] => Ok([llx, lly, urx, ury]),
+        _ => Err(format!("expected 4 numbers \"llx lly urx ury\", found {}", values.len())),
+    }
+}
+
+fn parse_area(s: &str) -> Result<redact::Area, String> {
+    let (page, rect) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected \"page:llx,lly,urx,ury\", found `{}`", s))?;
+    let page: u32 = page
+        .trim()
+        .parse()
+        .map_err(|_| format!("`{}` is not a page number", page))?;
+    Ok(redact::Area { page, rect: parse_rect(rect)? })
+}
+
+fn parse_size(s: &str) -> Result<u64, String> {
+    let upper = s.trim().to_ascii_uppercase();
+    let digits = upper.trim_end_matches("IB").trim_end_matches('B');
+    let (number, multiplier) = match digits.chars().last() {
+        Some('K') => (&digits[..digits.len() - 1], 1024),
+        Some('M') => (&digits[..digits.len() - 1], 1024 * 1024),
+        Some('G') => (&digits[..digits.len() - 1], 1024 * 1024 * 1024),
+        _ => (digits, 1),
+    };
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("`{}` is not a size, e.g. 2048, 500K or 10M", s))?;
+    Ok((number * multiplier as f64) as u64)
+}
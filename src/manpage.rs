@@ -0,0 +1,197 @@
+use clap::{Arg, Command};
+use roff::{bold, italic, roman, Inline, Roff};
+
+/// An example invocation shown in a subcommand's section of the man page
+pub struct Example {
+    /// The subcommand the example belongs to
+    pub command: &'static str,
+    /// What the example does
+    pub description: &'static str,
+    /// The arguments after the program name
+    pub args: &'static str,
+}
+
+/// Examples for the man page, in the order they are shown within each subcommand
+pub const EXAMPLES: &[Example] = &[
+    Example { command: "merge", description: "Merge two files, and every PDF in a directory", args: "merge a.pdf b.pdf scans/ merged.pdf" },
+    Example { command: "merge", description: "The same merge, spelled the pdftk way", args: "cat a.pdf b.pdf scans/ output merged.pdf" },
+    Example { command: "merge", description: "Merge the chapters of a book and give it a title", args: "merge chapters/ book.pdf --set-title \"Field Guide\" --set-author \"A. Writer\"" },
+    Example { command: "merge", description: "Assemble a report from the files and pages listed in build.txt, like `body.pdf 1-12`", args: "merge --manifest build.txt report.pdf" },
+    Example { command: "merge", description: "Put the last page of a.pdf first and add pages 1-3 of b.pdf", args: "merge a.pdf:7,1-6 b.pdf:1-3 out.pdf" },
+    Example { command: "split", description: "Write each page to pages/report-001.pdf, pages/report-002.pdf, ...", args: "split report.pdf --out-dir pages --mkdir --pattern '{stem}-{page:03}.pdf'" },
+    Example { command: "split", description: "Write each chapter of a book to a file named after its bookmark", args: "split book.pdf --by-bookmarks --out-dir chapters --mkdir" },
+    Example { command: "split", description: "Write the fronts and backs of a double sided scan to files of their own", args: "split scan.pdf --odd fronts.pdf --even backs.pdf" },
+    Example { command: "split", description: "Write only pages 2, 5 and 9, as scan-002.pdf, scan-005.pdf and scan-009.pdf", args: "split scan.pdf --pages 2 5 9" },
+    Example { command: "dupe", description: "Print three copies of a form from one file", args: "dupe --num 3 form.pdf copies.pdf" },
+    Example { command: "interleave", description: "Put the backs of a double sided scan, scanned last to first, after their fronts", args: "interleave fronts.pdf backs.pdf scan.pdf --reverse-second" },
+    Example { command: "insert", description: "Put a signed addendum before page 5 of a contract", args: "insert contract.pdf addendum.pdf signed.pdf --at 5" },
+    Example { command: "blank", description: "Put a blank Letter page after pages 3 and 7 to separate the parts of a print job", args: "blank job.pdf separated.pdf --after 3 7 --size Letter" },
+    Example { command: "rotate", description: "Turn pages 2 and 5 a quarter turn clockwise", args: "rotate scan.pdf --degrees 90 --pages 2 5" },
+    Example { command: "rotate", description: "Straighten scanned pages that face different ways, in one pass", args: "rotate scan.pdf --spec '3:90,7:270,12:180'" },
+    Example { command: "delete", description: "Drop every other page into a new file", args: "delete book.pdf odd.pdf --every 2" },
+    Example { command: "delete", description: "Remove the blank pages a scanner left between the written ones", args: "delete scan.pdf clean.pdf --blank" },
+    Example { command: "move", description: "Put the appendix, pages 40-45, right after page 3", args: "move report.pdf --pages 40-45 --to 4" },
+    Example { command: "extract", description: "Keep only the first three pages", args: "extract report.pdf summary.pdf --pages 1-3" },
+    Example { command: "extract", description: "Copy out every page except the two blank separators", args: "extract report.pdf body.pdf --pages 3 7 --negate" },
+    Example { command: "sanitize", description: "Strip everything but the pages before sharing", args: "sanitize draft.pdf public.pdf --profile strict" },
+    Example { command: "sanitize", description: "Remove only application-private data, inplace", args: "sanitize figure.pdf --remove private-data" },
+    Example { command: "annots", description: "Remove links to one site", args: "annots paper.pdf --strip-links --domain example.com" },
+    Example { command: "links", description: "List internal links that lead nowhere", args: "links manual.pdf --broken-only" },
+    Example { command: "redact", description: "Black out an account number wherever it appears", args: "redact statement.pdf redacted.pdf --text 12345678" },
+    Example { command: "bookmarks", description: "Build bookmarks from the headings", args: "bookmarks thesis.pdf --generate --min-size 16" },
+    Example { command: "forms", description: "Save the values of a filled in form", args: "forms application.pdf --export values.json" },
+    Example { command: "forms", description: "Remove the signature fields of a contract", args: "forms contract.pdf template.pdf --remove-signatures --break-signature" },
+    Example { command: "attach", description: "Save every attached file", args: "attach invoice.pdf --extract-all --output-dir invoice-files" },
+    Example { command: "decrypt", description: "Lift printing and copying restrictions", args: "decrypt locked.pdf unlocked.pdf --restrictions-only" },
+    Example { command: "revisions", description: "Discard earlier versions kept by incremental saves", args: "revisions edited.pdf clean.pdf --flatten" },
+    Example { command: "normalize", description: "Rewrite an export so it can be compared with cmp or diff", args: "normalize export.pdf export-normal.pdf" },
+    Example { command: "info", description: "Check the page counts and titles of the chapters before merging them", args: "info chapters/*.pdf" },
+    Example { command: "manpage", description: "Install the man page", args: "manpage --output /usr/local/share/man/man1/tpdf.1" },
+];
+
+/// Renders a man page in roff for a command and its subcommands: a synopsis, the top level
+/// options, then a section per subcommand with its long help, arguments and any examples. The
+/// layout is our own, as clap_mangen needs clap 4 and writes a page per command without room for
+/// examples; the roff crate it is built on does the escaping.
+pub fn render(cmd: &Command, examples: &[Example]) -> String {
+    let name = cmd.get_name();
+    let mut page = Roff::new();
+
+    // the empty argument is the date, the footer shows the name and version instead
+    let source = format!("{} {}", name, cmd.get_version().unwrap_or(""));
+    page.control("TH", [name.to_uppercase().as_str(), "1", "\"\"", source.trim_end()]);
+    page.control("SH", ["NAME"]);
+    page.text([roman(format!("{} - {}", name, cmd.get_about().unwrap_or("")))]);
+    page.control("SH", ["SYNOPSIS"]);
+    page.text([bold(name), roman(" ["), italic("OPTIONS"), roman("] "), italic("COMMAND")]);
+
+    let options: Vec<&Arg> = cmd.get_arguments().filter(|arg| is_documented(arg)).collect();
+    if !options.is_empty() {
+        page.control("SH", ["OPTIONS"]);
+        for arg in options {
+            render_arg(&mut page, arg);
+        }
+    }
+
+    page.control("SH", ["COMMANDS"]);
+    for sub in cmd.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        let full_name = format!("{} {}", name, sub.get_name());
+        page.control("SS", [full_name.as_str()]);
+        paragraphs(&mut page, sub.get_long_about().or(sub.get_about()).unwrap_or(""));
+        let aliases: Vec<&str> = sub.get_visible_aliases().collect();
+        if !aliases.is_empty() {
+            page.control("PP", []);
+            page.text([roman(format!("Aliases: {}", aliases.join(", ")))]);
+        }
+        page.control("PP", []);
+        page.text(synopsis(&full_name, sub));
+
+        for arg in sub.get_arguments().filter(|arg| is_documented(arg)) {
+            render_arg(&mut page, arg);
+        }
+
+        let sub_examples: Vec<&Example> = examples.iter().filter(|e| e.command == sub.get_name()).collect();
+        if !sub_examples.is_empty() {
+            page.control("PP", []);
+            page.text([roman("Examples:")]);
+            for example in sub_examples {
+                page.control("PP", []);
+                page.text([roman(format!("{}:", example.description))]);
+                page.control("RS", []).control("nf", []);
+                page.text([roman(format!("{} {}", name, example.args))]);
+                page.control("fi", []).control("RE", []);
+            }
+        }
+    }
+
+    page.render()
+}
+
+
+// ------- Helpers -------
+
+// --help and --version are added to every command, there is no need to list them each time
+fn is_documented(arg: &Arg) -> bool {
+    !arg.is_hide_set() && arg.get_id() != "help" && arg.get_id() != "version"
+}
+
+fn render_arg(page: &mut Roff, arg: &Arg) {
+    page.control("TP", []);
+    page.text(arg_label(arg));
+
+    if let Some(help) = arg.get_long_help().or(arg.get_help()) {
+        paragraphs(page, help);
+    }
+
+    // value enums list their values on the argument, other parsers may know them too
+    let values: Vec<String> = match arg.get_possible_values() {
+        Some(values) => values.iter().filter(|v| !v.is_hide_set()).map(|v| v.get_name().to_string()).collect(),
+        None => arg.get_value_parser()
+            .possible_values()
+            .map(|values| values.filter(|v| !v.is_hide_set()).map(|v| v.get_name().to_string()).collect())
+            .unwrap_or_default(),
+    };
+    if !values.is_empty() {
+        page.control("br", []);
+        page.text([roman(format!("Possible values: {}", values.join(", ")))]);
+    }
+    let defaults: Vec<String> = arg.get_default_values().iter().map(|v| v.to_string_lossy().into_owned()).collect();
+    if !defaults.is_empty() {
+        page.control("br", []);
+        page.text([roman(format!("Default: {}", defaults.join(", ")))]);
+    }
+}
+
+// e.g. -p, --pages <PAGES>... with the flags in bold and the value in italics
+fn arg_label(arg: &Arg) -> Vec<Inline> {
+    if arg.is_positional() {
+        return vec![italic(value_name(arg))];
+    }
+
+    let mut label = Vec::new();
+    if let Some(short) = arg.get_short() {
+        label.push(bold(format!("-{}", short)));
+    }
+    if let Some(long) = arg.get_long() {
+        if !label.is_empty() { label.push(roman(", ")); }
+        label.push(bold(format!("--{}", long)));
+    }
+    if arg.is_takes_value_set() {
+        label.push(roman(" "));
+        label.push(italic(value_name(arg)));
+    }
+    label
+}
+
+fn value_name(arg: &Arg) -> String {
+    let name = match arg.get_value_names() {
+        Some(names) => names.join(" "),
+        None => arg.get_id().to_uppercase().replace('-', "_"),
+    };
+    if arg.is_multiple_values_set() || arg.is_multiple_occurrences_set() {
+        format!("<{}>...", name)
+    } else {
+        format!("<{}>", name)
+    }
+}
+
+// the subcommand followed by its positional arguments, optional ones in brackets
+fn synopsis(full_name: &str, cmd: &Command) -> Vec<Inline> {
+    let mut line = vec![bold(full_name), roman(" ["), italic("OPTIONS"), roman("]")];
+    for arg in cmd.get_positionals() {
+        if arg.is_required_set() {
+            line.extend([roman(" "), italic(value_name(arg))]);
+        } else {
+            line.extend([roman(" ["), italic(value_name(arg)), roman("]")]);
+        }
+    }
+    line
+}
+
+// blank lines in help text separate paragraphs
+fn paragraphs(page: &mut Roff, text: &str) {
+    for (i, paragraph) in text.split("\n\n").enumerate() {
+        if i > 0 { page.control("sp", []); }
+        page.text([roman(paragraph.trim())]);
+    }
+}
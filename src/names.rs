@@ -0,0 +1,146 @@
+use std::collections::{BTreeMap, HashSet};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, StringFormat};
+
+/// Most entries (or kids) written into a single name tree node
+const NODE_SIZE: usize = 64;
+
+/// Finds the value stored under `key` in a name tree
+pub fn name_tree_lookup(doc: &Document, root: &Object, key: &[u8]) -> Option<Object> {
+    name_tree_entries(doc, root).remove(key)
+}
+
+/// Flattens a name tree into a map of keys to (undereferenced) values. Cycles in /Kids are ignored.
+pub fn name_tree_entries(doc: &Document, root: &Object) -> BTreeMap<Vec<u8>, Object> {
+    let mut entries = BTreeMap::new();
+    let mut visited = HashSet::new();
+    collect_name_tree(doc, root, &mut entries, &mut visited);
+    entries
+}
+
+/// Writes `entries` out as a new name tree and returns the id of its root. Small trees are a single
+/// root node; larger ones are split into leaves of up to 64 entries with /Limits, under as many
+/// levels of intermediate nodes as needed.
+pub fn build_name_tree(doc: &mut Document, entries: BTreeMap<Vec<u8>, Object>) -> ObjectId {
+    let entries = entries.into_iter()
+        .map(|(key, value)| (Object::String(key, StringFormat::Literal), value))
+        .collect();
+    build_tree(doc, b"Names", entries)
+}
+
+/// Flattens a number tree (such as a structure tree's /ParentTree) into a map of keys to
+/// (undereferenced) values. Cycles in /Kids are ignored.
+pub fn number_tree_entries(doc: &Document, root: &Object) -> BTreeMap<i64, Object> {
+    let mut entries = BTreeMap::new();
+    let mut visited = HashSet::new();
+    collect_number_tree(doc, root, &mut entries, &mut visited);
+    entries
+}
+
+/// Writes `entries` out as a new number tree and returns the id of its root, splitting it the same
+/// way as `build_name_tree`
+pub fn build_number_tree(doc: &mut Document, entries: BTreeMap<i64, Object>) -> ObjectId {
+    let entries = entries.into_iter()
+        .map(|(key, value)| (Object::Integer(key), value))
+        .collect();
+    build_tree(doc, b"Nums", entries)
+}
+
+// ------- Helpers -------
+
+fn collect_name_tree(doc: &Document,
+                     node: &Object,
+                     entries: &mut BTreeMap<Vec<u8>, Object>,
+                     visited: &mut HashSet<ObjectId>) {
+    if let Object::Reference(id) = node {
+        if !visited.insert(*id) { return; }
+    }
+
+    let node = match doc.dereference(node).and_then(|(_, n)| n.as_dict()) {
+        Ok(node) => node,
+        Err(_) => return,
+    };
+
+    if let Ok(names) = node.get(b"Names").and_then(|n| doc.dereference(n)).and_then(|(_, n)| n.as_array()) {
+        for pair in names.chunks_exact(2) {
+            if let Ok(key) = pair[0].as_str() {
+                entries.insert(key.to_vec(), pair[1].clone());
+            }
+        }
+    }
+
+    if let Ok(kids) = node.get(b"Kids").and_then(|k| doc.dereference(k)).and_then(|(_, k)| k.as_array()) {
+        for kid in kids {
+            collect_name_tree(doc, kid, entries, visited);
+        }
+    }
+}
+
+fn collect_number_tree(doc: &Document,
+                       node: &Object,
+                       entries: &mut BTreeMap<i64, Object>,
+                       visited: &mut HashSet<ObjectId>) {
+    if let Object::Reference(id) = node {
+        if !visited.insert(*id) { return; }
+    }
+
+    let node = match doc.dereference(node).and_then(|(_, n)| n.as_dict()) {
+        Ok(node) => node,
+        Err(_) => return,
+    };
+
+    if let Ok(nums) = node.get(b"Nums").and_then(|n| doc.dereference(n)).and_then(|(_, n)| n.as_array()) {
+        for pair in nums.chunks_exact(2) {
+            if let Ok(key) = pair[0].as_i64() {
+                entries.insert(key, pair[1].clone());
+            }
+        }
+    }
+
+    if let Ok(kids) = node.get(b"Kids").and_then(|k| doc.dereference(k)).and_then(|(_, k)| k.as_array()) {
+        for kid in kids {
+            collect_number_tree(doc, kid, entries, visited);
+        }
+    }
+}
+
+// `entries` must be sorted by key, `leaf_key` is /Names or /Nums
+fn build_tree(doc: &mut Document, leaf_key: &[u8], entries: Vec<(Object, Object)>) -> ObjectId {
+    let flatten = |entries: &[(Object, Object)]| -> Vec<Object> {
+        entries.iter().flat_map(|(key, value)| [key.clone(), value.clone()]).collect()
+    };
+
+    if entries.len() <= NODE_SIZE {
+        let mut root = Dictionary::new();
+        root.set(leaf_key.to_vec(), flatten(&entries));
+        return doc.add_object(root);
+    }
+
+    let mut nodes: Vec<(ObjectId, Object, Object)> = entries
+        .chunks(NODE_SIZE)
+        .map(|chunk| {
+            let (first, last) = (chunk[0].0.clone(), chunk[chunk.len() - 1].0.clone());
+            let mut leaf = Dictionary::new();
+            leaf.set(leaf_key.to_vec(), flatten(chunk));
+            leaf.set("Limits", vec![first.clone(), last.clone()]);
+            (doc.add_object(leaf), first, last)
+        })
+        .collect();
+
+    while nodes.len() > NODE_SIZE {
+        nodes = nodes
+            .chunks(NODE_SIZE)
+            .map(|chunk| {
+                let (first, last) = (chunk[0].1.clone(), chunk[chunk.len() - 1].2.clone());
+                let kids: Vec<Object> = chunk.iter().map(|(id, _, _)| Object::Reference(*id)).collect();
+                let id = doc.add_object(dictionary! {
+                    "Kids" => kids,
+                    "Limits" => vec![first.clone(), last.clone()],
+                });
+                (id, first, last)
+            })
+            .collect();
+    }
+
+    let kids: Vec<Object> = nodes.iter().map(|(id, _, _)| Object::Reference(*id)).collect();
+    doc.add_object(dictionary! { "Kids" => kids })
+}
@@ -0,0 +1,166 @@
+use std::{collections::{HashMap, HashSet}, path::PathBuf};
+
+use lopdf::{Dictionary, Document, Object, ObjectId, StringFormat};
+
+use crate::encryption::is_encrypted;
+use crate::error::Error;
+use crate::report;
+use crate::utils::{try_load_for_update, try_save_changes, SaveOptions, SaveReport};
+
+// What the dates of the document information dictionary are set to
+const ZERO_DATE: &str = "D:19700101000000Z";
+
+/// Rewrites a PDF in a canonical form, either inplace or in a new file, so that two documents with
+/// the same content can be compared byte for byte. See `canonicalize` for what is changed.
+///
+/// * `infile` - a PathBuf of the file to normalize
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `save` - how to write the file, the canonical form cannot be an incremental update
+///
+pub fn normalize(infile: PathBuf, outfile: Option<PathBuf>, save: &SaveOptions) -> Result<SaveReport, Error> {
+    if save.incremental {
+        return Err(Error::RewriteRequired("Normalizing rewrites the whole file".to_string()));
+    }
+
+    let (mut doc, before) = try_load_for_update(&infile)?;
+    if is_encrypted(&doc) {
+        panic!("{} is encrypted, decrypt it before normalizing", infile.display());
+    }
+    report::timed("normalize", || canonicalize(&mut doc));
+
+    try_save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Puts a document in a canonical form:
+///
+/// * streams are decompressed where the filter allows it, images are left as they are
+/// * objects are renumbered from 1 in the order they are reached from the trailer, following the
+///   keys of each dictionary in sorted order; objects that cannot be reached are dropped
+/// * dictionary keys are sorted, and stream lengths are direct
+/// * the creation and modification dates of the document information are zeroed
+/// * the trailer only keeps /Root and /Info, with a fixed /ID
+///
+/// Saving the result writes a single revision, so documents with the same content give the same
+/// bytes however they were written. The XMP metadata stream is left untouched.
+pub fn canonicalize(doc: &mut Document) {
+    doc.decompress();
+    // saving drops these, doing it first means saving leaves the canonical form alone
+    doc.delete_zero_length_streams();
+    zero_dates(doc);
+
+    let mut trailer = Dictionary::new();
+    for key in [b"Info".as_slice(), b"Root"] {
+        if let Ok(value) = doc.trailer.get(key) {
+            trailer.set(key.to_vec(), value.clone());
+        }
+    }
+    let fixed_id = Object::String(vec![0; 16], StringFormat::Hexadecimal);
+    trailer.set("ID", vec![fixed_id.clone(), fixed_id]);
+
+    let order = reachable_objects(doc, &trailer);
+    let numbers: HashMap<ObjectId, ObjectId> = order.iter()
+        .enumerate()
+        .map(|(index, id)| (*id, (index as u32 + 1, 0)))
+        .collect();
+
+    doc.objects = order.iter()
+        .map(|id| (numbers[id], canonical_object(&doc.objects[id], &numbers)))
+        .collect();
+    doc.max_id = order.len() as u32;
+    doc.trailer = sorted(&trailer, &numbers);
+    doc.trailer.set("Size", i64::from(doc.max_id + 1));
+}
+
+/// The bytes of the canonical form of a document, as `normalize` would write them. Documents with
+/// the same content give the same bytes.
+pub fn canonical_bytes(doc: &mut Document) -> Vec<u8> {
+    canonicalize(doc);
+
+    let mut bytes = Vec::new();
+    if let Err(error) = doc.save_to(&mut bytes) {
+        panic!("Failed to write the canonical form: {}", error);
+    }
+    bytes
+}
+
+// ------- Helpers -------
+
+fn zero_dates(doc: &mut Document) {
+    let info_id = match doc.trailer.get(b"Info").and_then(Object::as_reference) {
+        Ok(id) => id,
+        Err(_) => return,
+    };
+    if let Ok(Object::Dictionary(info)) = doc.get_object_mut(info_id) {
+        for key in [b"CreationDate".as_slice(), b"ModDate"] {
+            if info.has(key) {
+                info.set(key.to_vec(), Object::string_literal(ZERO_DATE));
+            }
+        }
+    }
+}
+
+// Objects in the order a depth-first walk from the trailer first reaches them
+fn reachable_objects(doc: &Document, trailer: &Dictionary) -> Vec<ObjectId> {
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+
+    let mut pending = Vec::new();
+    references(&Object::Dictionary(trailer.clone()), &mut pending);
+    pending.reverse();
+
+    while let Some(id) = pending.pop() {
+        if !doc.objects.contains_key(&id) || !seen.insert(id) { continue; }
+        order.push(id);
+
+        let mut found = Vec::new();
+        references(&doc.objects[&id], &mut found);
+        pending.extend(found.into_iter().rev());
+    }
+
+    order
+}
+
+// References in an object, in sorted key order for dictionaries
+fn references(object: &Object, found: &mut Vec<ObjectId>) {
+    match object {
+        Object::Reference(id) => found.push(*id),
+        Object::Array(array) => array.iter().for_each(|item| references(item, found)),
+        Object::Dictionary(dict) => sorted_entries(dict).into_iter().for_each(|(_, value)| references(value, found)),
+        // the length is made direct, an indirect one is not kept
+        Object::Stream(stream) => sorted_entries(&stream.dict).into_iter()
+            .filter(|(key, _)| key.as_slice() != b"Length")
+            .for_each(|(_, value)| references(value, found)),
+        _ => {}
+    }
+}
+
+fn sorted_entries(dict: &Dictionary) -> Vec<(&Vec<u8>, &Object)> {
+    let mut entries: Vec<_> = dict.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+// A copy of the object with its references renumbered and its dictionary keys sorted. References
+// to objects that do not exist become null, which is what a reader takes them for.
+fn canonical_object(object: &Object, numbers: &HashMap<ObjectId, ObjectId>) -> Object {
+    match object {
+        Object::Reference(id) => numbers.get(id).map_or(Object::Null, |&id| Object::Reference(id)),
+        Object::Array(array) => Object::Array(array.iter().map(|item| canonical_object(item, numbers)).collect()),
+        Object::Dictionary(dict) => Object::Dictionary(sorted(dict, numbers)),
+        Object::Stream(stream) => {
+            let mut stream = stream.clone();
+            stream.dict.set("Length", stream.content.len() as i64);
+            stream.dict = sorted(&stream.dict, numbers);
+            Object::Stream(stream)
+        }
+        other => other.clone(),
+    }
+}
+
+fn sorted(dict: &Dictionary, numbers: &HashMap<ObjectId, ObjectId>) -> Dictionary {
+    let mut canonical = Dictionary::new();
+    for (key, value) in sorted_entries(dict) {
+        canonical.set(key.clone(), canonical_object(value, numbers));
+    }
+    canonical
+}
@@ -0,0 +1,978 @@
+//! Page operations on documents already in memory, for composing several without writing files
+//! in between. The path-based commands in `utils` load, call these and save.
+//!
+//! ```
+//! use pdfh::ops::{self, PageSelection};
+//!
+//! let mut first = lopdf::Document::load("test-data/single-pages-object-multi-page.pdf").unwrap();
+//! ops::rotate_pages(&mut first, 90, &PageSelection::Pages(vec![1])).unwrap();
+//! ops::delete_pages(&mut first, &PageSelection::Every(2)).unwrap();
+//!
+//! let second = lopdf::Document::load("test-data/two-pages.pdf").unwrap();
+//! let mut merged = ops::merge(vec![first, second]).unwrap();
+//! assert_eq!(merged.get_pages().len(), 4);
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::bookmarks::{self, RemapReport};
+use crate::content::is_blank_page;
+use crate::dests::MergedDestinations;
+use crate::error::Error;
+use crate::forms::{unused_field_name, MergedForms};
+use crate::links;
+use crate::utils::{encode_text_string, info_object, inherited_attribute, inherited_entry, inherited_rect};
+
+const VERSION: &str = "1.5";
+/// Bytes of content a page that only paints paths may have and still be blank, see `PageSelection::Blank`
+pub const BLANK_THRESHOLD: usize = 64;
+// dictionaries that may be shared by anything using an identical one, besides page resources
+const SHAREABLE_TYPES: [&[u8]; 4] = [b"Font", b"FontDescriptor", b"Encoding", b"ExtGState"];
+// the page attributes a page takes from the page tree when it does not set them itself
+const INHERITABLE: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Which pages of a document an operation applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageSelection {
+    All,
+    /// Page numbers starting at 1, the ones past the last page are ignored
+    Pages(Vec<u32>),
+    /// Every nth page: 2 selects pages 2, 4, 6...
+    Every(u32),
+    /// Pages 1, 3, 5... the ones `Every(2)` leaves out
+    Odd,
+    /// Ranges that may run to the last page, like `5-`
+    Ranges(Vec<PageRange>),
+    /// Pages that draw nothing, or only paths in at most this many bytes of content, like a white
+    /// background or a speck on a scan
+    Blank(usize),
+}
+
+/// Pages `first` to `last`, both included. A range without a `last` runs to the last page of the
+/// document it is used on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageRange {
+    pub first: u32,
+    pub last: Option<u32>,
+}
+
+/// Where pages go, see `move_pages` and `insert`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    /// Before this page of the ones already there, so the first page put there gets its number
+    Page(u32),
+    /// After the last page
+    End,
+}
+
+/// The size of the pages `insert_blank_pages` adds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageSize {
+    /// The MediaBox of the page before, or of the first page for blanks put in front
+    Match,
+    /// 210 x 297 mm
+    A4,
+    /// 8.5 x 11 in
+    Letter,
+}
+
+impl PageSize {
+    // The MediaBox for a page next to `page_id`, A4 when matching a page without a valid one
+    fn media_box(self, doc: &Document, page_id: Option<ObjectId>) -> [f64; 4] {
+        match self {
+            PageSize::Match => page_id.and_then(|id| inherited_rect(doc, id, b"MediaBox")).unwrap_or_else(|| PageSize::A4.media_box(doc, None)),
+            PageSize::A4 => [0.0, 0.0, 595.276, 841.89],
+            PageSize::Letter => [0.0, 0.0, 612.0, 792.0],
+        }
+    }
+}
+
+impl PageRange {
+    /// Whether the range has `page` in a document of `count` pages
+    pub fn contains(&self, page: u32, count: u32) -> bool {
+        self.first <= page && page <= self.last.unwrap_or(count)
+    }
+}
+
+impl PageSelection {
+    /// The selection made by --pages and --every, all pages when neither is given
+    pub fn new(pages: Option<Vec<u32>>, every: Option<u32>) -> PageSelection {
+        match (pages, every) {
+            (Some(pages), _) => PageSelection::Pages(pages),
+            (None, Some(every)) => PageSelection::Every(every),
+            (None, None) => PageSelection::All,
+        }
+    }
+
+    /// The numbers of the selected pages of `doc`, in page order. Fails for `Every(0)`.
+    pub fn page_numbers(&self, doc: &Document) -> Result<Vec<u32>, Error> {
+        let count = doc.get_pages().len() as u32;
+        match self {
+            PageSelection::All => Ok((1..=count).collect()),
+            PageSelection::Pages(pages) => {
+                let listed: HashSet<u32> = pages.iter().copied().collect();
+                Ok((1..=count).filter(|page| listed.contains(page)).collect())
+            }
+            PageSelection::Every(0) => Err(Error::InvalidSelection("--every must be at least 1".to_string())),
+            PageSelection::Every(every) => Ok((1..=count).filter(|page| page % every == 0).collect()),
+            PageSelection::Odd => Ok((1..=count).step_by(2).collect()),
+            PageSelection::Ranges(ranges) => Ok((1..=count).filter(|page| ranges.iter().any(|range| range.contains(*page, count))).collect()),
+            PageSelection::Blank(threshold) => Ok(doc.get_pages().into_iter()
+                .filter(|(_, page_id)| is_blank_page(doc, *page_id, *threshold))
+                .map(|(page, _)| page)
+                .collect()),
+        }
+    }
+
+    /// The listed page numbers `doc` does not have, sorted and without repeats. Empty unless
+    /// pages are listed. A range open at the end is missing its first page if that is past the
+    /// last one.
+    pub fn missing_pages(&self, doc: &Document) -> Vec<u32> {
+        let count = doc.get_pages().len() as u32;
+        let missing: BTreeSet<u32> = match self {
+            PageSelection::Pages(pages) => pages.iter().copied().filter(|page| *page == 0 || *page > count).collect(),
+            PageSelection::Ranges(ranges) => ranges.iter()
+                .flat_map(|range| match range.last {
+                    Some(last) => (range.first..=last).filter(|page| *page == 0 || *page > count).collect(),
+                    None if range.first == 0 || range.first > count => vec![range.first],
+                    None => Vec::new(),
+                })
+                .collect(),
+            _ => BTreeSet::new(),
+        };
+        missing.into_iter().collect()
+    }
+
+    /// The selected pages in the order they are listed, with repeats, leaving out those `doc` does
+    /// not have. Selections that do not list pages give `page_numbers`.
+    pub fn listed_pages(&self, doc: &Document) -> Result<Vec<u32>, Error> {
+        let count = doc.get_pages().len() as u32;
+        match self {
+            PageSelection::Pages(pages) => Ok(pages.iter().copied().filter(|page| (1..=count).contains(page)).collect()),
+            PageSelection::Ranges(ranges) => Ok(ranges.iter()
+                .flat_map(|range| range.first.max(1)..=range.last.unwrap_or(count).min(count))
+                .collect()),
+            _ => self.page_numbers(doc),
+        }
+    }
+
+    /// The pages of `doc` this does not select, what --negate turns a selection into
+    pub fn inverted(&self, doc: &Document) -> Result<PageSelection, Error> {
+        let selected: HashSet<u32> = self.page_numbers(doc)?.into_iter().collect();
+        let count = doc.get_pages().len() as u32;
+        Ok(PageSelection::Pages((1..=count).filter(|page| !selected.contains(page)).collect()))
+    }
+}
+
+/// Sets the rotation of the selected pages
+///
+/// * `degrees` - clockwise, a multiple of 90
+/// * `selection` - the pages to rotate
+///
+/// Returns the listed pages the document does not have, which are skipped.
+pub fn rotate_pages(doc: &mut Document, degrees: i32, selection: &PageSelection) -> Result<Vec<u32>, Error> {
+    let pages: BTreeMap<u32, ObjectId> = doc.get_pages();
+
+    for number in selection.page_numbers(doc)? {
+        set_rotation(doc, pages[&number], i64::from(degrees));
+    }
+    Ok(selection.missing_pages(doc))
+}
+
+/// Turns the selected pages further from where they are: the rotation a page has, its own or
+/// taken from the page tree, plus `degrees`. The result is set on the page as 0, 90, 180 or 270.
+///
+/// * `degrees` - clockwise, a multiple of 90, negative for counterclockwise
+/// * `selection` - the pages to rotate
+///
+/// Returns the listed pages the document does not have, which are skipped.
+pub fn rotate_pages_by(doc: &mut Document, degrees: i32, selection: &PageSelection) -> Result<Vec<u32>, Error> {
+    let pages: BTreeMap<u32, ObjectId> = doc.get_pages();
+
+    for number in selection.page_numbers(doc)? {
+        let page_id = pages[&number];
+        set_rotation(doc, page_id, page_rotation(doc, page_id) + i64::from(degrees));
+    }
+    Ok(selection.missing_pages(doc))
+}
+
+/// Rotates each listed page by its own amount, with the same pass over the document for all
+///
+/// * `rotations` - page numbers and their rotation, clockwise in multiples of 90
+/// * `relative` - add to the rotation each page already has, see `rotate_pages_by`
+///
+/// Returns the listed pages the document does not have, which are skipped.
+pub fn rotate_each_page(doc: &mut Document, rotations: &[(u32, i32)], relative: bool) -> Vec<u32> {
+    let pages: BTreeMap<u32, ObjectId> = doc.get_pages();
+
+    let mut missing = BTreeSet::new();
+    for (number, degrees) in rotations {
+        let Some(&page_id) = pages.get(number) else {
+            missing.insert(*number);
+            continue;
+        };
+        let current = if relative { page_rotation(doc, page_id) } else { 0 };
+        set_rotation(doc, page_id, current + i64::from(*degrees));
+    }
+    missing.into_iter().collect()
+}
+
+/// A rotation as one of 0, 90, 180 or 270, the values /Rotate should have: -90 is 270 and 450 is
+/// 90. Anything between multiples of 90 is rounded down.
+pub fn normalize_rotation(degrees: i64) -> i64 {
+    degrees.rem_euclid(360) / 90 * 90
+}
+
+// The rotation a page has, its own or taken from the page tree
+fn page_rotation(doc: &Document, page_id: ObjectId) -> i64 {
+    inherited_attribute(doc, page_id, b"Rotate").and_then(|rotate| rotate.as_i64().ok()).unwrap_or(0)
+}
+
+// Sets the normalized rotation on a page. No rotation takes the key out, unless the page would
+// then take a rotation from the page tree
+fn set_rotation(doc: &mut Document, page_id: ObjectId, degrees: i64) {
+    let rotation = normalize_rotation(degrees);
+    let inherited = doc.get_dictionary(page_id).ok()
+        .and_then(|page| page.get(b"Parent").and_then(Object::as_reference).ok())
+        .and_then(|parent_id| inherited_attribute(doc, parent_id, b"Rotate"))
+        .and_then(|rotate| rotate.as_i64().ok())
+        .map_or(0, normalize_rotation);
+    if let Ok(Object::Dictionary(page)) = doc.get_object_mut(page_id) {
+        if rotation == 0 && inherited == 0 {
+            page.remove(b"Rotate");
+        } else {
+            page.set("Rotate", rotation);
+        }
+    }
+}
+
+/// Deletes the selected pages. The structure tree of a tagged PDF still refers to them, see
+/// `structure::prune_structure_tree`.
+pub fn delete_pages(doc: &mut Document, selection: &PageSelection) -> Result<(), Error> {
+    let page_numbers = selection.page_numbers(doc)?;
+    doc.delete_pages(&page_numbers);
+    Ok(())
+}
+
+/// Deletes every page that is not selected. Links and bookmarks to the deleted pages are removed,
+/// or with `retarget_links` pointed at the nearest page that is kept. The pages that are kept
+/// end up in the order they are listed in, see `arrange_pages`. Links and bookmarks to a page
+/// listed more than once lead to where it first appears.
+pub fn extract_pages(doc: &mut Document, selection: &PageSelection, retarget_links: bool) -> Result<RemapReport, Error> {
+    let page_numbers = selection.page_numbers(doc)?;
+    let listed = selection.listed_pages(doc)?;
+
+    // links have to be dealt with first, deleting a page also strips it out of every destination
+    let selected: HashSet<u32> = page_numbers.into_iter().collect();
+    let (kept, removed): (Vec<_>, Vec<_>) = doc.get_pages().into_iter().partition(|(num, _)| selected.contains(num));
+    let kept: HashSet<ObjectId> = kept.into_iter().map(|(_, id)| id).collect();
+    let report = bookmarks::remap_to_pages(doc, &kept, retarget_links);
+
+    let removed: Vec<u32> = removed.into_iter().map(|(num, _)| num).collect();
+    doc.delete_pages(&removed);
+
+    // in document order already, nothing to move
+    if listed.windows(2).all(|pair| pair[0] < pair[1]) { return Ok(report); }
+    let renumbered: HashMap<u32, u32> = distinct_pages(&listed).into_iter().zip(1..).collect();
+    let order: Vec<u32> = listed.iter().map(|page| renumbered[page]).collect();
+    arrange_pages(doc, &order)?;
+    Ok(report)
+}
+
+/// Puts the pages in the given order, copying those that appear more than once: the first time a
+/// page appears it is the page itself, each later time a copy of it, made as by `repeat_pages`.
+/// The page tree becomes a single node, see `reorder_pages`.
+///
+/// * `order` - every current page number at least once, in the order the pages should be in
+///
+pub fn arrange_pages(doc: &mut Document, order: &[u32]) -> Result<(), Error> {
+    let count = doc.get_pages().len() as u32;
+    if distinct_pages(order) != (1..=count).collect::<Vec<u32>>() {
+        return Err(Error::InvalidSelection(format!("{:?} does not have each of the {} pages", order, count)));
+    }
+    reorder_pages(doc, &(1..=count).collect::<Vec<u32>>())?;
+    let pages: Vec<ObjectId> = doc.page_iter().collect();
+
+    let mut taken: HashSet<String> = field_names(doc);
+    let mut fields: Vec<Object> = Vec::new();
+    // the copies of each page, the page itself first
+    let mut copies: Vec<Vec<ObjectId>> = pages.iter().map(|page_id| vec![*page_id]).collect();
+    let mut times = vec![0; pages.len()];
+    for page in order {
+        times[*page as usize - 1] += 1;
+    }
+    let mut copy = 1;
+    loop {
+        let repeated: Vec<usize> = (0..pages.len()).filter(|index| times[*index] > copy).collect();
+        if repeated.is_empty() { break; }
+        let originals: Vec<ObjectId> = repeated.iter().map(|index| pages[*index]).collect();
+        let copied = duplicate_pages(doc, &originals, copy, &mut taken, &mut fields);
+        for (index, page_id) in repeated.into_iter().zip(copied) {
+            copies[index].push(page_id);
+        }
+        copy += 1;
+    }
+
+    let mut used = vec![0; pages.len()];
+    let kids: Vec<Object> = order.iter().map(|page| {
+        let index = *page as usize - 1;
+        used[index] += 1;
+        copies[index][used[index] - 1].into()
+    }).collect();
+    set_page_tree(doc, kids, fields)
+}
+
+/// Reverses the page order by reversing the kids of every page tree node
+pub fn reverse(doc: &mut Document) {
+    for object in doc.objects.values_mut() {
+        if object.type_name().unwrap_or("") != "Pages" { continue; }
+        if let Object::Dictionary(dict) = object {
+            if let Ok(Object::Array(kids)) = dict.get_mut(b"Kids") {
+                kids.reverse();
+            }
+        }
+    }
+}
+
+/// Puts the pages in a new order. The page tree becomes a single node with every page under it,
+/// each page taking the attributes it inherited from the nodes it leaves.
+///
+/// * `order` - every current page number once, in the order the pages should be in
+///
+pub fn reorder_pages(doc: &mut Document, order: &[u32]) -> Result<(), Error> {
+    let pages = doc.get_pages();
+    let mut sorted = order.to_vec();
+    sorted.sort_unstable();
+    if !sorted.iter().copied().eq(1..=pages.len() as u32) {
+        return Err(Error::InvalidSelection(format!("{:?} is not an order of the {} pages", order, pages.len())));
+    }
+    let root_id = doc.catalog().ok()
+        .and_then(|catalog| catalog.get(b"Pages").and_then(Object::as_reference).ok())
+        .ok_or(Error::PagesRootMissing)?;
+
+    let flattened: Vec<(ObjectId, Option<Object>)> = pages.values().map(|id| (*id, flattened_page(doc, *id))).collect();
+    for (page_id, page) in flattened {
+        if let Some(Object::Dictionary(mut page)) = page {
+            page.set("Parent", root_id);
+            doc.objects.insert(page_id, Object::Dictionary(page));
+        }
+    }
+    // the nodes in between are left unreferenced, saving prunes them
+    let kids: Vec<Object> = order.iter().map(|number| pages[number].into()).collect();
+    if let Ok(root) = doc.get_object_mut(root_id).and_then(Object::as_dict_mut) {
+        root.set("Count", kids.len() as i64);
+        root.set("Kids", kids);
+    }
+    Ok(())
+}
+
+/// Moves a run of pages to another place in the document, keeping their order. The page tree
+/// becomes a single node, see `reorder_pages`.
+///
+/// * `range` - the pages to move, a range without a `last` runs to the last page
+/// * `to` - where the first moved page ends up once the moved pages are taken out, `Page(n)`
+///   puts them right before the nth of the remaining pages
+///
+pub fn move_pages(doc: &mut Document, range: PageRange, to: Position) -> Result<(), Error> {
+    let count = doc.get_pages().len() as u32;
+    let last = range.last.unwrap_or(count);
+    if range.first > count || last > count {
+        return Err(Error::InvalidSelection(format!("the document has {} pages, there is no page {}", count, last.max(range.first))));
+    }
+    let mut order: Vec<u32> = (1..=count).filter(|page| !range.contains(*page, count)).collect();
+    let index = match to {
+        Position::End => order.len(),
+        Position::Page(page) if page >= 1 && page as usize <= order.len() + 1 => page as usize - 1,
+        Position::Page(page) => return Err(Error::InvalidSelection(format!(
+            "cannot move pages to {}, {} pages are left once they are taken out", page, order.len()))),
+    };
+    order.splice(index..index, range.first..=last);
+    reorder_pages(doc, &order)
+}
+
+/// Repeats all the pages `copies` times, one copy of the document after the other, or each page
+/// `copies` times in a row when not collated. Only the
+/// pages, their annotations and the form fields of those are duplicated, the copies share their
+/// contents, resources and everything else. The fields of the later copies get the number of
+/// their copy in their name (`name_2`), as when merging. The page tree becomes a single node, see
+/// `reorder_pages`.
+///
+/// * `copies` - how many times the pages appear, at least 1
+/// * `collate` - 1,2,3,1,2,3 instead of 1,1,2,2,3,3
+///
+pub fn repeat_pages(doc: &mut Document, copies: u16, collate: bool) -> Result<(), Error> {
+    if copies == 0 {
+        return Err(Error::InvalidSelection("the output needs at least one copy".to_string()));
+    }
+    let count = doc.get_pages().len() as u32;
+    reorder_pages(doc, &(1..=count).collect::<Vec<u32>>())?;
+    let pages: Vec<ObjectId> = doc.page_iter().collect();
+
+    let mut taken: HashSet<String> = field_names(doc);
+    // the pages of each copy, in order
+    let mut copy_pages: Vec<Vec<ObjectId>> = vec![pages.clone()];
+    let mut fields: Vec<Object> = Vec::new();
+
+    for copy in 1..copies as usize {
+        copy_pages.push(duplicate_pages(doc, &pages, copy, &mut taken, &mut fields));
+    }
+
+    let kids: Vec<Object> = if collate {
+        copy_pages.concat().into_iter().map(Object::from).collect()
+    } else {
+        (0..pages.len()).flat_map(|index| copy_pages.iter().map(move |copy| copy[index].into())).collect()
+    };
+    set_page_tree(doc, kids, fields)
+}
+
+// Copies the pages with their annotations and the form fields of those, for `repeat_pages`.
+// Returns the copies in the order of `pages`. The top level fields copied get the number of the
+// copy in their name, unless it is taken, and are added to `fields`.
+fn duplicate_pages(doc: &mut Document, pages: &[ObjectId], copy: usize, taken: &mut HashSet<String>, fields: &mut Vec<Object>) -> Vec<ObjectId> {
+    let mut copied: HashMap<ObjectId, ObjectId> = HashMap::new();
+    for page_id in pages {
+        copy_object(doc, *page_id, &mut copied);
+        if let Ok(array_id) = doc.get_dictionary(*page_id).and_then(|page| page.get(b"Annots")).and_then(Object::as_reference) {
+            copy_object(doc, array_id, &mut copied);
+        }
+        for annot_id in page_annotations(doc, *page_id) {
+            // a widget takes the fields it belongs to along, a popup its markup annotation
+            let mut next = Some(annot_id);
+            while let Some(id) = next.filter(|id| !copied.contains_key(id)) {
+                copy_object(doc, id, &mut copied);
+                next = doc.get_dictionary(id).and_then(|dict| dict.get(b"Parent")).and_then(Object::as_reference).ok();
+            }
+        }
+    }
+
+    // ids are handed out in the order the objects were copied
+    let mut new_ids: Vec<ObjectId> = copied.values().copied().collect();
+    new_ids.sort_unstable();
+
+    // the copies refer to each other, and to what they share with the original
+    for new_id in &new_ids {
+        if let Some(object) = doc.objects.get_mut(new_id) {
+            remap_references(object, &copied);
+        }
+    }
+    for new_id in new_ids {
+        let Some(Object::Dictionary(field)) = doc.objects.get_mut(&new_id) else { continue };
+        // markup annotations have a /T too, the author
+        let is_field = field.has(b"FT") || field.has(b"Kids") || field.get(b"Subtype").and_then(Object::as_name).is_ok_and(|subtype| subtype == b"Widget");
+        if field.has(b"Parent") || !is_field { continue; }
+        let Ok(name) = field.get(b"T").and_then(Object::as_str).map(|name| String::from_utf8_lossy(name).into_owned()) else { continue };
+        let renamed = unused_field_name(taken, &name, copy);
+        field.set("T", encode_text_string(&renamed));
+        taken.insert(renamed);
+        fields.push(new_id.into());
+    }
+    pages.iter().map(|old_id| copied[old_id]).collect()
+}
+
+// Makes `kids` the pages of a page tree that is a single node, and adds the fields of the copied
+// pages to the form
+fn set_page_tree(doc: &mut Document, kids: Vec<Object>, fields: Vec<Object>) -> Result<(), Error> {
+    let root_id = doc.catalog().ok()
+        .and_then(|catalog| catalog.get(b"Pages").and_then(Object::as_reference).ok())
+        .ok_or(Error::PagesRootMissing)?;
+    if let Ok(root) = doc.get_object_mut(root_id).and_then(Object::as_dict_mut) {
+        root.set("Count", kids.len() as i64);
+        root.set("Kids", kids);
+    }
+    let form_id = doc.catalog().ok().and_then(|catalog| catalog.get(b"AcroForm").and_then(Object::as_reference).ok());
+    if let Some(form) = form_id.and_then(|id| doc.get_object_mut(id).and_then(Object::as_dict_mut).ok()) {
+        if let Ok(Object::Array(existing)) = form.get_mut(b"Fields") {
+            existing.extend(fields);
+        }
+    }
+    Ok(())
+}
+
+// The page numbers sorted and without repeats
+fn distinct_pages(pages: &[u32]) -> Vec<u32> {
+    pages.iter().copied().collect::<BTreeSet<u32>>().into_iter().collect()
+}
+
+/// Puts the pages of the documents one after the other in a new document. The merged document
+/// keeps the catalog and document information of the first one, and the bookmarks of each one
+/// follow those of the documents before it. Bookmarks that do not lead to a page are dropped.
+/// Internal links keep working, and the named destinations of every document are kept, those
+/// whose name is already taken renamed with the number of their document (`intro-2`). The
+/// fields of every form are combined into one, see `MergedForms`.
+/// Fails if none of the documents has a catalog or a page tree.
+pub fn merge(documents: Vec<Document>) -> Result<Document, Error> {
+    let mut merged = Document::with_version(VERSION);
+    merge_documents(documents, None, &mut merged)?;
+    Ok(merged)
+}
+
+/// Merges two documents with their pages taking turns: the first page of each, then the second of
+/// each and so on. The pages left over when one has more are put at the end in order.
+///
+/// * `first` - the document whose pages come first in each pair, e.g. the fronts of a scan
+/// * `second` - the other document, e.g. the backs
+/// * `reverse_second` - take the pages of `second` last to first, for backs scanned in reverse
+///
+pub fn interleave(first: Document, second: Document, reverse_second: bool) -> Result<Document, Error> {
+    let first_count = first.get_pages().len() as u32;
+    let second_count = second.get_pages().len() as u32;
+    let mut merged = merge(vec![first, second])?;
+
+    let mut backs: Vec<u32> = (first_count + 1..=first_count + second_count).collect();
+    if reverse_second { backs.reverse(); }
+    let mut order = Vec::with_capacity(backs.len() + first_count as usize);
+    for i in 0..first_count.max(second_count) {
+        if i < first_count { order.push(i + 1); }
+        if let Some(back) = backs.get(i as usize) { order.push(*back); }
+    }
+    reorder_pages(&mut merged, &order)?;
+    Ok(merged)
+}
+
+/// Merges one document into another at a page of it, like `merge` otherwise.
+///
+/// * `base` - the document whose catalog and document information are kept
+/// * `insert` - the document whose pages are put into it, all of them in order
+/// * `at` - the page of `base` they go before, `Page(1)` puts them first
+///
+pub fn insert(base: Document, insert: Document, at: Position) -> Result<Document, Error> {
+    let base_count = base.get_pages().len() as u32;
+    let insert_count = insert.get_pages().len() as u32;
+    let index = match at {
+        Position::End => base_count,
+        Position::Page(page) if page >= 1 && page <= base_count + 1 => page - 1,
+        Position::Page(page) => return Err(Error::InvalidSelection(format!(
+            "cannot insert at page {}, the document has {} pages, use 1 to {} or end", page, base_count, base_count + 1))),
+    };
+    let mut merged = merge(vec![base, insert])?;
+
+    let order: Vec<u32> = (1..=index)
+        .chain(base_count + 1..=base_count + insert_count)
+        .chain(index + 1..=base_count)
+        .collect();
+    reorder_pages(&mut merged, &order)?;
+    Ok(merged)
+}
+
+/// Adds blank pages after and before the given pages, `count` at each place. The blank pages
+/// draw nothing and share one empty content stream. The page tree becomes a single node, see
+/// `reorder_pages`.
+///
+/// * `after` - the pages to put blanks after, a page listed twice gets them once
+/// * `before` - the pages to put blanks before, e.g. 1 for a leading blank
+/// * `count` - how many blank pages go in each place
+/// * `size` - the size of the blank pages
+///
+pub fn insert_blank_pages(doc: &mut Document, after: &[u32], before: &[u32], count: u16, size: PageSize) -> Result<(), Error> {
+    let page_count = doc.get_pages().len() as u32;
+    if let Some(page) = after.iter().chain(before).find(|page| **page == 0 || **page > page_count) {
+        return Err(Error::InvalidSelection(format!("the document has {} pages, there is no page {}", page_count, page)));
+    }
+    // blanks before a page are the ones after the page before it, 0 standing for the front
+    let places = distinct_pages(&after.iter().copied().chain(before.iter().map(|page| page - 1)).collect::<Vec<_>>());
+
+    reorder_pages(doc, &(1..=page_count).collect::<Vec<_>>())?;
+    let root_id = doc.catalog().ok()
+        .and_then(|catalog| catalog.get(b"Pages").and_then(Object::as_reference).ok())
+        .ok_or(Error::PagesRootMissing)?;
+    let pages: Vec<ObjectId> = doc.page_iter().collect();
+    let content_id = doc.add_object(Stream::new(Dictionary::new(), Vec::new()));
+
+    let mut kids: Vec<Object> = Vec::with_capacity(pages.len() + places.len() * count as usize);
+    let mut places = places.into_iter().peekable();
+    for index in 0..=pages.len() {
+        if places.next_if_eq(&(index as u32)).is_some() {
+            // the page before, or the first page for blanks in front
+            let next_to = pages.get(index.saturating_sub(1)).copied();
+            let media_box: Vec<Object> = size.media_box(doc, next_to).iter().map(|v| Object::Real(*v)).collect();
+            for _ in 0..count {
+                let mut blank = Dictionary::new();
+                blank.set("Type", "Page");
+                blank.set("Parent", root_id);
+                blank.set("MediaBox", media_box.clone());
+                blank.set("Resources", Dictionary::new());
+                blank.set("Contents", content_id);
+                kids.push(doc.add_object(blank).into());
+            }
+        }
+        if let Some(page) = pages.get(index) {
+            kids.push((*page).into());
+        }
+    }
+    set_page_tree(doc, kids, Vec::new())
+}
+
+/// Like `merge`, with a top level bookmark for each document that shows its first page. The
+/// bookmarks of a document go under its own.
+///
+/// * `documents` - the documents to merge, in order
+/// * `titles` - the title of each document's bookmark, in the same order
+///
+pub fn merge_with_bookmarks(documents: Vec<Document>, titles: &[String]) -> Result<Document, Error> {
+    let mut merged = Document::with_version(VERSION);
+    merge_documents(documents, Some(titles), &mut merged)?;
+    Ok(merged)
+}
+
+/// Keeps one copy of identical fonts, images and other resources, as merging copies of documents
+/// made from the same template repeats them once per copy. Streams are the same when their
+/// dictionaries (but for /Length) and their content as stored are, so two images that differ only
+/// in /Decode or /SMask are kept apart. Fonts, font descriptors, encodings, graphics states and
+/// the /Resources dictionaries of pages are compared entry by entry. Passes repeat until nothing
+/// changes, since sharing a font file makes the descriptors that use it identical.
+///
+/// Returns the number of objects removed.
+pub fn dedupe_resources(doc: &mut Document) -> usize {
+    let mut removed = 0;
+
+    loop {
+        let resources: HashSet<ObjectId> = doc.page_iter()
+            .filter_map(|page_id| doc.get_dictionary(page_id).ok()?.get(b"Resources").ok()?.as_reference().ok())
+            .collect();
+
+        let mut canonical: HashMap<Vec<u8>, ObjectId> = HashMap::new();
+        let mut duplicates: HashMap<ObjectId, ObjectId> = HashMap::new();
+        for (id, object) in &doc.objects {
+            let shareable = match object {
+                Object::Stream(_) => true,
+                Object::Dictionary(dict) => resources.contains(id) || dict.get(b"Type")
+                    .and_then(Object::as_name)
+                    .is_ok_and(|kind| SHAREABLE_TYPES.contains(&kind)),
+                _ => false,
+            };
+            if !shareable { continue; }
+
+            let mut key = Vec::new();
+            object_key(object, &mut key);
+            match canonical.get(&key) {
+                Some(first) => { duplicates.insert(*id, *first); }
+                None => { canonical.insert(key, *id); }
+            }
+        }
+        if duplicates.is_empty() { break; }
+
+        removed += duplicates.len();
+        doc.objects.retain(|id, _| !duplicates.contains_key(id));
+        for object in doc.objects.values_mut() {
+            replace_references(object, &duplicates);
+        }
+        for (_, value) in doc.trailer.iter_mut() {
+            replace_references(value, &duplicates);
+        }
+    }
+
+    removed
+}
+
+// ------- Helpers -------
+
+// this is almost unmodified from the examples in the lopdf README https://github.com/J-F-Liu/lopdf
+// TODO: consider refactoring
+fn merge_documents(documents: Vec<Document>, titles: Option<&[String]>, outdoc: &mut Document) -> Result<(), Error> {
+    // Define a starting max_id (will be used as start index for object_ids)
+    let mut max_id = 1;
+    // let mut pagenum = 1;
+    // Collect all Documents Objects grouped by a map
+    // in page order, which the object numbers do not follow once a document has been reordered
+    let mut documents_pages = Vec::new();
+    let mut documents_objects = BTreeMap::new();
+    // The merged document keeps the information dictionary of the first input
+    let mut info_id = None;
+    // the top level bookmarks of every document, one after the other
+    let mut outline_items = Vec::new();
+    // only the first catalog is kept, so the named destinations of all of them are gathered
+    let mut destinations = MergedDestinations::default();
+    let mut forms = MergedForms::default();
+
+    for (index, mut doc) in documents.into_iter().enumerate() {
+        // let mut first = false;
+
+        // renumber the current doc starting with the current max_id
+        doc.renumber_objects_with(max_id);
+        if index == 0 {
+            info_id = info_object(&mut doc);
+        }
+        // links and bookmarks stop using names, which may be taken by another document
+        links::portable_links(&mut doc);
+        destinations.add(&doc, index);
+        forms.add(&mut doc, index);
+        let items = bookmarks::portable_outline(&mut doc);
+        match titles.and_then(|titles| titles.get(index)) {
+            Some(title) => outline_items.extend(document_bookmark(&mut doc, title, &items)),
+            None => outline_items.extend(items),
+        }
+        // sets the new max_id to the id of the last page of the current doc + 1 so that the next doc starts in the correct location
+        max_id = doc.max_id + 1; 
+
+        // extend the documents_pages with the ObjectId and Object of each page, Object being an enum of Object types
+        // An object can be:
+        /*
+            pub enum Object {
+                Null,
+                Boolean(bool),
+                Integer(i64),
+                Real(f64),
+                Name(Vec<u8>),
+                String(Vec<u8>, StringFormat),
+                Array(Vec<Object>),
+                Dictionary(Dictionary),
+                Stream(Stream),
+                Reference(ObjectId),
+            }
+        */
+        // the page trees are replaced by a single Pages node, so each page takes what it inherited
+        documents_pages.extend(
+            doc
+                    .get_pages()
+                    .into_values()
+                    .filter_map(|object_id| Some((object_id, flattened_page(&doc, object_id)?))),
+        );
+
+        // add all the objects from each document to a collection
+        documents_objects.extend(doc.objects);
+    }
+
+    // Catalog and Pages are mandatory 
+    let mut catalog_object: Option<(ObjectId, Object)> = None;
+    let mut pages_object: Option<(ObjectId, Object)> = None;
+
+    // Process all objects except "Page" type
+    for (object_id, object) in documents_objects.iter() {
+        // We have to ignore "Page" (as are processed later) and "Outlines" objects, the outline is joined up later
+        // All other objects should be collected and inserted into the main Document
+        match object.type_name().unwrap_or("") {
+            "Catalog" => {
+                // Collect the first "Catalog" object, the first document's, and use it for the future "Pages"
+                if catalog_object.is_none() {
+                    catalog_object = Some((*object_id, object.clone()));
+                }
+            }
+            "Pages" => {
+                // Collect a first "Pages" object and use it for the future "Catalog". The others,
+                // intermediate nodes included, are dropped as their pages now carry what they inherited
+                if pages_object.is_none() {
+                    pages_object = Some((*object_id, object.clone()));
+                }
+            }
+            "Page" => {}     // Ignored, processed later and separately
+            "Outlines" => {} // Ignored, every document's items go under a new root
+            _ => {
+                outdoc.objects.insert(*object_id, object.clone());
+            }
+        }
+    }
+
+    // If no "Pages" found abort
+    if pages_object.is_none() {
+        return Err(Error::PagesRootMissing);
+    }
+
+    // Iter over all "Page" and collect with the parent "Pages" created before
+    for (object_id, object) in documents_pages.iter() {
+        if let Ok(dictionary) = object.as_dict() {
+            let mut dictionary = dictionary.clone();
+            dictionary.set("Parent", pages_object.as_ref().unwrap().0);
+
+            outdoc
+                    .objects
+                    .insert(*object_id, Object::Dictionary(dictionary));
+        }
+    }
+
+    // If no "Catalog" found abort
+    if catalog_object.is_none() {
+        return Err(Error::CatalogMissing);
+    }
+
+    let catalog_object = catalog_object.unwrap();
+    let pages_object = pages_object.unwrap();
+
+    // Build a new "Pages" with updated fields
+    if pages_object.1.as_dict().is_ok() {
+        // nothing is left for the pages to inherit, and a Parent would make the root a child
+        let mut dictionary = Dictionary::new();
+        dictionary.set("Type", "Pages");
+
+        // Set new pages count
+        dictionary.set("Count", documents_pages.len() as u32);
+
+        // Set new "Kids" list (collected from documents pages) for "Pages"
+        dictionary.set(
+            "Kids",
+            documents_pages
+                    .into_iter()
+                    .map(|(object_id, _)| Object::Reference(object_id))
+                    .collect::<Vec<_>>(),
+        );
+
+        outdoc
+                .objects
+                .insert(pages_object.0, Object::Dictionary(dictionary));
+    }
+
+    // Build a new "Catalog" with updated fields
+    if let Ok(dictionary) = catalog_object.1.as_dict() {
+        let mut dictionary = dictionary.clone();
+        dictionary.set("Pages", pages_object.0);
+        dictionary.remove(b"Outlines"); // replaced below by the joined outline
+
+        outdoc
+                .objects
+                .insert(catalog_object.0, Object::Dictionary(dictionary));
+    }
+
+    outdoc.trailer.set("Root", catalog_object.0);
+    if let Some(info_id) = info_id.filter(|id| outdoc.objects.contains_key(id)) {
+        outdoc.trailer.set("Info", info_id);
+    }
+
+    // Update the max internal ID as wasn't updated before due to direct objects insertion
+    outdoc.max_id = outdoc.objects.keys().map(|id| id.0).max().unwrap_or(0);
+
+    bookmarks::join_outlines(outdoc, &outline_items);
+    destinations.write(outdoc);
+    forms.write(outdoc);
+
+    // Reorder all new Document objects
+    outdoc.renumber_objects();
+
+     //Set any Bookmarks to the First child if they are not set to a page
+    outdoc.adjust_zero_pages();
+
+    //Set all bookmarks to the PDF Object tree then set the Outlines to the Bookmark content map.
+    if let Some(n) = outdoc.build_outline() {
+        if let Ok(Object::Dictionary(ref mut dict)) = outdoc.get_object_mut(catalog_object.0) {
+            dict.set("Outlines", Object::Reference(n));
+        }
+    }
+    Ok(())
+}
+
+// Adds a copy of an object under a new id, unless it was copied already
+fn copy_object(doc: &mut Document, id: ObjectId, copied: &mut HashMap<ObjectId, ObjectId>) {
+    if copied.contains_key(&id) { return; }
+    if let Some(object) = doc.objects.get(&id).cloned() {
+        copied.insert(id, doc.add_object(object));
+    }
+}
+
+// Points the references to copied objects at the copies, without following references
+fn remap_references(object: &mut Object, copied: &HashMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => if let Some(new_id) = copied.get(id) { *id = *new_id },
+        Object::Array(items) => items.iter_mut().for_each(|item| remap_references(item, copied)),
+        Object::Dictionary(dict) => dict.iter_mut().for_each(|(_, value)| remap_references(value, copied)),
+        Object::Stream(stream) => stream.dict.iter_mut().for_each(|(_, value)| remap_references(value, copied)),
+        _ => {}
+    }
+}
+
+// The annotations of a page that are objects of their own, the only ones that can be shared
+fn page_annotations(doc: &Document, page_id: ObjectId) -> Vec<ObjectId> {
+    let annots = doc.get_dictionary(page_id).and_then(|page| page.get(b"Annots")).and_then(|annots| doc.dereference(annots));
+    match annots {
+        Ok((_, Object::Array(annots))) => annots.iter().filter_map(|annot| annot.as_reference().ok()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+// The names of the top level fields of the form
+fn field_names(doc: &Document) -> HashSet<String> {
+    let fields = doc.catalog().and_then(|catalog| catalog.get(b"AcroForm")).and_then(|form| doc.dereference(form))
+        .and_then(|(_, form)| form.as_dict().and_then(|form| form.get(b"Fields")).and_then(|fields| doc.dereference(fields)));
+    let Ok((_, Object::Array(fields))) = fields else { return HashSet::new() };
+    fields.iter()
+        .filter_map(|field| doc.dereference(field).ok()?.1.as_dict().ok()?.get(b"T").ok()?.as_str().ok())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect()
+}
+
+// A copy of the page dictionary with the attributes it inherits from the page tree set on it
+fn flattened_page(doc: &Document, page_id: ObjectId) -> Option<Object> {
+    let mut page = doc.get_dictionary(page_id).ok()?.clone();
+    for key in INHERITABLE {
+        if page.has(key) { continue; }
+        if let Some(value) = inherited_entry(doc, page_id, key) {
+            page.set(key.to_vec(), value.clone());
+        }
+    }
+    Some(Object::Dictionary(page))
+}
+
+// Adds a bookmark showing the first page of the document, with `children` (the top level items of
+// its outline) under it. None for a document without pages.
+fn document_bookmark(doc: &mut Document, title: &str, children: &[ObjectId]) -> Option<ObjectId> {
+    let first_page = doc.page_iter().next()?;
+    let mut item = Dictionary::new();
+    item.set("Title", encode_text_string(title));
+    item.set("Dest", vec![first_page.into(), "Fit".into()]);
+    if let Some(first) = children.first() {
+        item.set("First", *first);
+    }
+    let item_id = doc.add_object(item);
+
+    // the sibling chain is already linked, the outline is repaired once everything is joined
+    for child in children {
+        if let Ok(child) = doc.get_object_mut(*child).and_then(Object::as_dict_mut) {
+            child.set("Parent", item_id);
+        }
+    }
+    Some(item_id)
+}
+
+// Bytes that are the same for two objects exactly when the objects are, dictionaries in key order.
+// The /Length of a stream is left out, it follows from the content.
+fn object_key(object: &Object, key: &mut Vec<u8>) {
+    let dict_key = |dict: &Dictionary, key: &mut Vec<u8>| {
+        let mut entries: Vec<_> = dict.iter().filter(|(name, _)| name.as_slice() != b"Length").collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        key.extend_from_slice(format!("<<{}", entries.len()).as_bytes());
+        for (name, value) in entries {
+            key.extend_from_slice(format!("/{}:", name.len()).as_bytes());
+            key.extend_from_slice(name);
+            object_key(value, key);
+        }
+    };
+    match object {
+        Object::Null => key.push(b'n'),
+        Object::Boolean(value) => key.extend_from_slice(if *value { b"t" } else { b"f" }),
+        Object::Integer(value) => key.extend_from_slice(format!("i{};", value).as_bytes()),
+        Object::Real(value) => key.extend_from_slice(format!("r{};", value.to_bits()).as_bytes()),
+        Object::Name(name) => {
+            key.extend_from_slice(format!("/{}:", name.len()).as_bytes());
+            key.extend_from_slice(name);
+        }
+        Object::String(text, _) => {
+            key.extend_from_slice(format!("({}:", text.len()).as_bytes());
+            key.extend_from_slice(text);
+        }
+        Object::Array(array) => {
+            key.extend_from_slice(format!("[{}", array.len()).as_bytes());
+            array.iter().for_each(|item| object_key(item, key));
+        }
+        Object::Dictionary(dict) => dict_key(dict, key),
+        Object::Stream(stream) => {
+            key.push(b's');
+            dict_key(&stream.dict, key);
+            key.extend_from_slice(format!("{}:", stream.content.len()).as_bytes());
+            key.extend_from_slice(&stream.content);
+        }
+        Object::Reference(id) => key.extend_from_slice(format!("R{},{};", id.0, id.1).as_bytes()),
+    }
+}
+
+fn replace_references(object: &mut Object, replacements: &HashMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => {
+            if let Some(canonical) = replacements.get(id) {
+                *id = *canonical;
+            }
+        }
+        Object::Array(array) => array.iter_mut().for_each(|item| replace_references(item, replacements)),
+        Object::Dictionary(dict) => dict.iter_mut().for_each(|(_, value)| replace_references(value, replacements)),
+        Object::Stream(stream) => stream.dict.iter_mut().for_each(|(_, value)| replace_references(value, replacements)),
+        _ => {}
+    }
+}
@@ -0,0 +1,97 @@
+use lopdf::{Dictionary, Document, ObjectId};
+
+use crate::content::page_layout;
+use crate::utils::{inherited_attribute, inherited_entry, inherited_rect};
+
+/// A page with the attributes it inherits through the page tree resolved, so that callers do not
+/// have to walk up the /Parent chain themselves
+#[derive(Debug, Clone)]
+pub struct PageRef<'a> {
+    doc: &'a Document,
+    /// Page number, starting at 1
+    pub number: u32,
+    pub id: ObjectId,
+    /// The MediaBox as [llx lly urx ury], None if neither the page nor its ancestors have a valid one
+    pub media_box: Option<[f64; 4]>,
+    /// The CropBox as [llx lly urx ury], the media box when the page does not set one
+    pub crop_box: Option<[f64; 4]>,
+    /// Clockwise rotation in degrees, 0, 90, 180 or 270
+    pub rotation: i64,
+    /// The object holding the page's resource dictionary, None if the dictionary is direct or missing
+    pub resources_id: Option<ObjectId>,
+    /// The content streams of the page, in drawing order
+    pub content_ids: Vec<ObjectId>,
+}
+
+/// The pages of a document in order, with their inherited attributes resolved
+///
+/// ```
+/// let doc = lopdf::Document::load("test-data/two-pages.pdf").unwrap();
+/// for page in pdfh::pages(&doc) {
+///     let [_, _, width, height] = page.crop_box.unwrap();
+///     println!("page {} is {}x{} points, turned {} degrees", page.number, width, height, page.rotation);
+/// }
+/// ```
+pub fn pages(doc: &Document) -> impl Iterator<Item = PageRef<'_>> {
+    doc.get_pages().into_iter().map(move |(number, id)| PageRef::new(doc, number, id))
+}
+
+impl<'a> PageRef<'a> {
+    fn new(doc: &'a Document, number: u32, id: ObjectId) -> PageRef<'a> {
+        let media_box = inherited_rect(doc, id, b"MediaBox");
+        let crop_box = inherited_rect(doc, id, b"CropBox").or(media_box);
+        let rotation = inherited_attribute(doc, id, b"Rotate")
+            .and_then(|rotate| rotate.as_i64().ok())
+            .map_or(0, |degrees| degrees.rem_euclid(360));
+        let resources_id = inherited_entry(doc, id, b"Resources").and_then(|resources| resources.as_reference().ok());
+        let content_ids = doc.get_page_contents(id);
+
+        PageRef { doc, number, id, media_box, crop_box, rotation, resources_id, content_ids }
+    }
+
+    /// The resource dictionary the page draws with, wherever in the page tree it is
+    pub fn resources(&self) -> Option<&'a Dictionary> {
+        let resources = inherited_entry(self.doc, self.id, b"Resources")?;
+        self.doc.dereference(resources).ok()?.1.as_dict().ok()
+    }
+
+    /// The text drawn on the page, in drawing order, with a line break wherever the text moves to
+    /// another line. Returns an error if the content cannot be parsed.
+    ///
+    /// ```
+    /// let doc = lopdf::Document::load("test-data/two-pages.pdf").unwrap();
+    /// let first = pdfh::pages(&doc).next().unwrap();
+    /// assert!(!first.extract_text().unwrap().is_empty());
+    /// ```
+    pub fn extract_text(&self) -> Result<String, String> {
+        let (_, layout) = page_layout(self.doc, self.id)?;
+
+        let mut text = String::new();
+        let mut baseline: Option<f64> = None;
+        for run in &layout.runs {
+            let first = match run.glyphs.first() {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            if baseline.is_some_and(|y| (first.bbox[1] - y).abs() > run.size / 2.0) {
+                text.push('\n');
+            }
+            baseline = Some(first.bbox[1]);
+            text.extend(run.glyphs.iter().map(|glyph| glyph.unicode.as_str()));
+        }
+        Ok(text)
+    }
+
+    /// The annotation dictionaries of the page, links and form widgets included
+    pub fn annotations(&self) -> Vec<&'a Dictionary> {
+        let annots = self.doc.get_dictionary(self.id)
+            .and_then(|page| page.get(b"Annots"))
+            .and_then(|annots| self.doc.dereference(annots))
+            .and_then(|(_, annots)| annots.as_array());
+
+        match annots {
+            Ok(annots) => annots.iter().filter_map(|annot| self.doc.dereference(annot).ok()?.1.as_dict().ok()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::io::{self, IsTerminal};
+
+/// Somewhere to ask the user for a password. The terminal in normal use, tests supply their own
+/// answers.
+pub trait PasswordPrompt {
+    /// True when there is someone to answer, e.g. stdin is a terminal
+    fn is_interactive(&self) -> bool;
+    /// Shows the prompt and reads one line without echoing it
+    fn read_password(&mut self, prompt: &str) -> io::Result<String>;
+}
+
+/// Prompts on the controlling terminal with echo turned off
+pub struct Terminal;
+
+impl PasswordPrompt for Terminal {
+    fn is_interactive(&self) -> bool {
+        io::stdin().is_terminal()
+    }
+
+    fn read_password(&mut self, prompt: &str) -> io::Result<String> {
+        rpassword::prompt_password(prompt)
+    }
+}
+
+/// Never prompts, so a missing password is an error as if stdin were not a terminal
+pub struct NoPrompt;
+
+impl PasswordPrompt for NoPrompt {
+    fn is_interactive(&self) -> bool {
+        false
+    }
+
+    fn read_password(&mut self, _prompt: &str) -> io::Result<String> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "not an interactive session"))
+    }
+}
+
+/// Why no password was read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptError {
+    /// Reading from the terminal failed
+    Io(String),
+}
+
+impl std::fmt::Display for PromptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PromptError::Io(error) => write!(f, "could not read the password: {}", error),
+        }
+    }
+}
+
+/// Asks for an existing password. Returns None without asking when the prompt is not interactive.
+pub fn ask_password(prompt: &mut dyn PasswordPrompt, label: &str) -> Result<Option<String>, PromptError> {
+    if !prompt.is_interactive() {
+        return Ok(None);
+    }
+    let password = prompt.read_password(&format!("{}: ", label)).map_err(|e| PromptError::Io(e.to_string()))?;
+    Ok(Some(password))
+}
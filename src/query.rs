@@ -0,0 +1,109 @@
+use std::{fs, path::PathBuf};
+
+use serde::Serialize;
+
+use crate::attachments::list_attachments;
+use crate::encryption::is_encrypted;
+use crate::forms::list_fields;
+use crate::redact::find_text;
+use crate::report;
+use crate::utils::{expand_dirs_if_necessary, parse_document, SortOrder};
+
+/// What a PDF must have to be listed by `query`. Every criterion that is set must hold.
+#[derive(Debug, Clone, Default)]
+pub struct Criteria {
+    pub min_pages: Option<usize>,
+    pub max_pages: Option<usize>,
+    /// Only encrypted documents
+    pub encrypted: bool,
+    /// Size in bytes
+    pub larger_than: Option<u64>,
+    /// Size in bytes
+    pub smaller_than: Option<u64>,
+    /// Text that must appear on some page
+    pub contains: Option<String>,
+    pub has_forms: bool,
+    pub has_attachments: bool,
+}
+
+/// A PDF that met the criteria, with the attributes that were looked at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Match {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub pages: usize,
+    pub encrypted: bool,
+    pub has_forms: bool,
+    pub has_attachments: bool,
+}
+
+/// Prints the PDFs among the given files and directories that match the criteria, one path per
+/// line or as JSON
+///
+/// * `paths` - files, and directories whose PDFs are searched
+/// * `criteria` - what a PDF must have to be listed
+/// * `json` - print a JSON array of the matches and their attributes instead
+///
+pub fn query(paths: &Vec<PathBuf>, criteria: &Criteria, json: bool) {
+    let matches = find_matches(paths, criteria);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&matches).expect("failed to serialize the matches"));
+        return;
+    }
+    for found in matches {
+        println!("{}", found.path.display());
+    }
+}
+
+/// The PDFs among the given files and directories that match the criteria, sorted by path. The
+/// size is checked before a file is parsed, and the text is only extracted for `contains`. Files
+/// that do not load are skipped with a warning.
+pub fn find_matches(paths: &Vec<PathBuf>, criteria: &Criteria) -> Vec<Match> {
+    let mut files = expand_dirs_if_necessary(paths, false, SortOrder::Name).unwrap_or_else(|error| panic!("{}", error));
+    files.sort();
+
+    files.into_iter().filter_map(|path| check(path, criteria)).collect()
+}
+
+// ------- Helpers -------
+
+fn check(path: PathBuf, criteria: &Criteria) -> Option<Match> {
+    let bytes = match fs::metadata(&path) {
+        Ok(metadata) => metadata.len(),
+        Err(error) => {
+            report::warning(format!("skipped {}: {}", path.display(), error));
+            return None;
+        }
+    };
+    if criteria.larger_than.is_some_and(|size| bytes <= size) { return None; }
+    if criteria.smaller_than.is_some_and(|size| bytes >= size) { return None; }
+
+    // as stored, so that encrypted documents are listed without their password
+    let doc = match parse_document(&path) {
+        Ok(doc) => doc,
+        Err(error) => {
+            report::warning(format!("skipped {}: {}", path.display(), error));
+            return None;
+        }
+    };
+
+    let pages = doc.get_pages().len();
+    if criteria.min_pages.is_some_and(|min| pages < min) { return None; }
+    if criteria.max_pages.is_some_and(|max| pages > max) { return None; }
+
+    let encrypted = is_encrypted(&doc);
+    if criteria.encrypted && !encrypted { return None; }
+
+    let has_forms = !list_fields(&doc).is_empty();
+    if criteria.has_forms && !has_forms { return None; }
+
+    let has_attachments = !list_attachments(&doc).is_empty();
+    if criteria.has_attachments && !has_attachments { return None; }
+
+    if let Some(text) = &criteria.contains {
+        if encrypted || find_text(&doc, text).is_empty() { return None; }
+    }
+
+    Some(Match { path, bytes, pages, encrypted, has_forms, has_attachments })
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+
+use lopdf::{Document, ObjectId};
+
+/// What recovery had to work around
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Recovery {
+    /// Objects found in the file that could not be parsed and were left out
+    pub skipped: Vec<ObjectId>,
+}
+
+/// Loads a PDF whose cross-reference table is damaged by scanning the file for objects instead.
+/// Every `N G obj` header found is indexed, later copies of an object winning as they would in an
+/// incremental update, and the catalog is found by its /Type. Objects that do not parse are left
+/// out. Fails if no page tree can be put together from what is left.
+///
+/// # Arguments
+///
+/// * `data` - the bytes of the damaged file
+///
+pub fn recover(data: &[u8]) -> Result<(Document, Recovery), String> {
+    let offsets = scan_objects(data);
+    if offsets.is_empty() { return Err("no objects found".to_string()); }
+
+    let mut rebuilt = data.to_vec();
+    append_xref(&mut rebuilt, &offsets, find_info(data));
+    let mut doc = Document::load_mem(&rebuilt).map_err(|error| error.to_string())?;
+
+    let root = doc.objects.iter()
+        .rev()
+        .find(|(_, object)| object.as_dict().is_ok_and(|dict| dict.type_is(b"Catalog")))
+        .map(|(id, _)| *id)
+        .ok_or("no document catalog found")?;
+    doc.trailer.set("Root", root);
+
+    if doc.get_pages().is_empty() { return Err("no pages found".to_string()); }
+
+    let skipped = offsets.keys().filter(|id| !doc.objects.contains_key(id)).copied().collect();
+    Ok((doc, Recovery { skipped }))
+}
+
+// ------- Helpers -------
+
+// Object id -> offset of its `N G obj` header
+fn scan_objects(data: &[u8]) -> BTreeMap<ObjectId, usize> {
+    let mut offsets = BTreeMap::new();
+
+    let mut at = 0;
+    while let Some(found) = find(&data[at..], b"obj") {
+        let keyword = at + found;
+        at = keyword + 3;
+
+        // `obj` must end the token, which rules out `endobj` and `objstm`-like names too
+        if data.get(keyword + 3).is_some_and(|c| c.is_ascii_alphanumeric()) { continue; }
+        if let Some((id, start)) = object_header(data, keyword) {
+            offsets.insert(id, start);
+        }
+    }
+
+    offsets
+}
+
+// Reads `N G ` backwards from the `obj` keyword at `keyword`
+fn object_header(data: &[u8], keyword: usize) -> Option<(ObjectId, usize)> {
+    let (generation, before_generation) = number_before(data, skip_spaces_back(data, keyword)?)?;
+    let (number, start) = number_before(data, skip_spaces_back(data, before_generation)?)?;
+    if start > 0 && !data[start - 1].is_ascii_whitespace() { return None; }
+
+    Some(((u32::try_from(number).ok()?, u16::try_from(generation).ok()?), start))
+}
+
+// Position just after the last non-space before `end`, requiring at least one space
+fn skip_spaces_back(data: &[u8], end: usize) -> Option<usize> {
+    let mut at = end;
+    while at > 0 && data[at - 1].is_ascii_whitespace() { at -= 1; }
+    (at < end).then_some(at)
+}
+
+fn number_before(data: &[u8], end: usize) -> Option<(u64, usize)> {
+    let mut start = end;
+    while start > 0 && data[start - 1].is_ascii_digit() { start -= 1; }
+    if start == end || end - start > 10 { return None; }
+    let number = std::str::from_utf8(&data[start..end]).ok()?.parse().ok()?;
+    Some((number, start))
+}
+
+// The /Info reference of the last trailer, if it can still be read
+fn find_info(data: &[u8]) -> Option<ObjectId> {
+    let trailer = rfind(data, b"trailer")?;
+    let info = trailer + find(&data[trailer..], b"/Info")? + 5;
+    let text = String::from_utf8_lossy(&data[info..(info + 32).min(data.len())]).into_owned();
+    let mut parts = text.split_whitespace();
+    let number = parts.next()?.parse().ok()?;
+    let generation = parts.next()?.parse().ok()?;
+    (parts.next()?.starts_with('R')).then_some((number, generation))
+}
+
+fn append_xref(data: &mut Vec<u8>, offsets: &BTreeMap<ObjectId, usize>, info: Option<ObjectId>) {
+    let size = offsets.keys().map(|(number, _)| number + 1).max().unwrap_or(1);
+    let by_number: BTreeMap<u32, (u16, usize)> = offsets.iter().map(|(&(number, generation), &offset)| (number, (generation, offset))).collect();
+
+    data.extend_from_slice(b"\n");
+    let xref_start = data.len();
+    data.extend_from_slice(format!("xref\n0 {}\n", size).as_bytes());
+    for number in 0..size {
+        match by_number.get(&number) {
+            Some((generation, offset)) => data.extend_from_slice(format!("{:010} {:05} n\r\n", offset, generation).as_bytes()),
+            None => data.extend_from_slice(b"0000000000 65535 f\r\n"),
+        }
+    }
+
+    let mut trailer = format!("trailer\n<< /Size {}", size);
+    if let Some((number, generation)) = info.filter(|id| offsets.contains_key(id)) {
+        trailer.push_str(&format!(" /Info {} {} R", number, generation));
+    }
+    trailer.push_str(&format!(" >>\nstartxref\n{}\n%%EOF\n", xref_start));
+    data.extend_from_slice(trailer.as_bytes());
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|window| window == needle)
+}
+
+fn rfind(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).rposition(|window| window == needle)
+}
@@ -0,0 +1,241 @@
+use std::{collections::BTreeMap, path::PathBuf};
+use lopdf::{content::Operation, dictionary, Document, Object, Stream};
+
+use crate::content::{encode_content, page_layout, TextRun};
+use crate::error::Error;
+use crate::report;
+use crate::utils::{try_load_for_update, try_save_changes, SaveOptions, SaveReport};
+
+/// A rectangle on one page to black out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Area {
+    pub page: u32,
+    /// [llx lly urx ury] in default user space
+    pub rect: [f64; 4],
+}
+
+/// Summary of what a redaction removed
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RedactReport {
+    /// Number of areas blacked out
+    pub areas: usize,
+    /// Number of glyphs whose centre was inside an area
+    pub glyphs: usize,
+    /// Number of images that were entirely inside an area
+    pub images: usize,
+}
+
+/// Blacks out areas of a PDF and removes the text and images underneath, either inplace or in a
+/// new file. This is best-effort: text drawn inside form XObjects, vector graphics and images that
+/// only partly overlap an area are covered but not removed.
+///
+/// * `infile` - a PathBuf of the file to redact
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `areas` - the rectangles to black out
+/// * `text` - text to search for, every match is blacked out as well
+/// * `compress` - a boolean flag to compress the outfile before saving
+/// * `save` - how to write the file, an incremental update would leave the removed content in the
+///   earlier revision and is refused
+///
+pub fn redact(infile: PathBuf, outfile: Option<PathBuf>, mut areas: Vec<Area>, text: Vec<String>, compress: bool, save: &SaveOptions) -> Result<SaveReport, Error> {
+    if save.incremental {
+        return Err(Error::RewriteRequired("Redacting rewrites the whole file so the removed content is not left in an earlier revision".to_string()));
+    }
+    let (mut doc, before) = try_load_for_update(&infile)?;
+
+    for pattern in &text {
+        let found = find_text(&doc, pattern);
+        report::summary(format!("found {} matches for \"{}\"", found.len(), pattern));
+        areas.extend(found);
+    }
+
+    let report = redact_areas(&mut doc, &areas).map_err(Error::RedactFailed)?;
+    report::summary(format!("blacked out {} areas, removed {} glyphs and {} images",
+                            report.areas, report.glyphs, report.images));
+
+    if compress { report::timed("compress", || doc.compress()) }
+
+    try_save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Finds every occurrence of `pattern` in the text of the document and returns the area it covers.
+/// A match that spans several text showing operators (or lines) produces one area per operator.
+pub fn find_text(doc: &Document, pattern: &str) -> Vec<Area> {
+    let mut areas = Vec::new();
+    if pattern.is_empty() { return areas; }
+
+    for (page, page_id) in doc.get_pages() {
+        let page_layout = match page_layout(doc, page_id) {
+            Ok((_, page_layout)) => page_layout,
+            Err(_) => continue,
+        };
+
+        // the page's text in drawing order, with the (run, glyph) each character came from
+        let mut text = String::new();
+        let mut origins: Vec<(usize, usize)> = Vec::new();
+        for (r, run) in page_layout.runs.iter().enumerate() {
+            for (g, glyph) in run.glyphs.iter().enumerate() {
+                for c in glyph.unicode.chars() {
+                    text.push(c);
+                    origins.extend(std::iter::repeat_n((r, g), c.len_utf8()));
+                }
+            }
+        }
+
+        for (start, found) in text.match_indices(pattern) {
+            let mut boxes: BTreeMap<usize, [f64; 4]> = BTreeMap::new();
+            for (r, g) in &origins[start..start + found.len()] {
+                let bbox = page_layout.runs[*r].glyphs[*g].bbox;
+                let merged = boxes.entry(*r).or_insert(bbox);
+                *merged = [merged[0].min(bbox[0]), merged[1].min(bbox[1]), merged[2].max(bbox[2]), merged[3].max(bbox[3])];
+            }
+            areas.extend(boxes.into_values().map(|rect| Area { page, rect }));
+        }
+    }
+
+    areas
+}
+
+/// Removes the glyphs and images under each area and paints the area black. Every redacted page
+/// gets a new content stream, so pages that shared content with it are left untouched.
+///
+/// Returns an error if an area is on a page that does not exist, has no size, or a page's content
+/// could not be parsed.
+pub fn redact_areas(doc: &mut Document, areas: &[Area]) -> Result<RedactReport, String> {
+    let pages = doc.get_pages();
+    let mut by_page: BTreeMap<u32, Vec<[f64; 4]>> = BTreeMap::new();
+    for area in areas {
+        if !pages.contains_key(&area.page) {
+            return Err(format!("page {} does not exist, the document has {} pages", area.page, pages.len()));
+        }
+        let [x0, y0, x1, y1] = area.rect;
+        let rect = [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)];
+        if rect[0] == rect[2] || rect[1] == rect[3] {
+            return Err(format!("the area {:?} on page {} has no size", area.rect, area.page));
+        }
+        by_page.entry(area.page).or_default().push(rect);
+    }
+
+    let mut report = RedactReport { areas: areas.len(), ..RedactReport::default() };
+
+    for (page, rects) in by_page {
+        let page_id = pages[&page];
+        let (operations, page_layout) = page_layout(doc, page_id)
+            .map_err(|e| format!("could not read the content of page {}: {}", page, e))?;
+
+        let mut replacements: BTreeMap<usize, Vec<Operation>> = BTreeMap::new();
+        for run in &page_layout.runs {
+            let keep: Vec<bool> = run.glyphs.iter()
+                .map(|glyph| !rects.iter().any(|r| contains_point(r, centre(&glyph.bbox))))
+                .collect();
+            let removed = keep.iter().filter(|k| !**k).count();
+            if removed > 0 {
+                report.glyphs += removed;
+                replacements.insert(run.op_index, rewrite_run(&operations[run.op_index], run, &keep));
+            }
+        }
+        for image in &page_layout.images {
+            if rects.iter().any(|r| contains_box(r, &image.bbox)) {
+                report.images += 1;
+                replacements.insert(image.op_index, Vec::new());
+            }
+        }
+
+        // the original content runs in its own graphics state so the boxes are drawn untransformed
+        let mut redacted = vec![Operation::new("q", vec![])];
+        for (i, operation) in operations.into_iter().enumerate() {
+            match replacements.remove(&i) {
+                Some(replacement) => redacted.extend(replacement),
+                None => redacted.push(operation),
+            }
+        }
+        redacted.push(Operation::new("Q", vec![]));
+        redacted.push(Operation::new("q", vec![]));
+        redacted.push(Operation::new("g", vec![0.into()]));
+        for rect in &rects {
+            redacted.push(Operation::new("re", vec![
+                rect[0].into(), rect[1].into(), (rect[2] - rect[0]).into(), (rect[3] - rect[1]).into(),
+            ]));
+        }
+        redacted.push(Operation::new("f", vec![]));
+        redacted.push(Operation::new("Q", vec![]));
+
+        let content_id = doc.add_object(Stream::new(dictionary! {}, encode_content(&redacted)));
+        doc.get_object_mut(page_id)
+            .and_then(Object::as_dict_mut)
+            .map_err(|e| format!("page {} is not a dictionary: {}", page, e))?
+            .set("Contents", content_id);
+    }
+
+    Ok(report)
+}
+
+
+// ------- Helpers -------
+
+// rewrites a text showing operation as a TJ that skips the removed glyphs, moving by their width
+// instead so the text that is kept does not shift
+fn rewrite_run(operation: &Operation, run: &TextRun, keep: &[bool]) -> Vec<Operation> {
+    let mut replacement = Vec::new();
+    let strings: Vec<Object> = match operation.operator.as_str() {
+        "'" => {
+            replacement.push(Operation::new("T*", vec![]));
+            operation.operands.clone()
+        }
+        "\"" if operation.operands.len() == 3 => {
+            replacement.push(Operation::new("Tw", vec![operation.operands[0].clone()]));
+            replacement.push(Operation::new("Tc", vec![operation.operands[1].clone()]));
+            replacement.push(Operation::new("T*", vec![]));
+            vec![operation.operands[2].clone()]
+        }
+        "TJ" => operation.operands.first().and_then(|a| a.as_array().ok()).cloned().unwrap_or_default(),
+        _ => operation.operands.clone(),
+    };
+
+    let scale = run.font_size * run.horizontal_scale;
+    let mut glyphs = run.glyphs.iter().zip(keep).peekable();
+    let mut items = Vec::new();
+
+    for (i, item) in strings.into_iter().enumerate() {
+        let format = match item {
+            Object::String(_, format) => format,
+            number => {
+                items.push(number);
+                continue;
+            }
+        };
+        let mut kept = Vec::new();
+        while let Some((glyph, keep)) = glyphs.next_if(|(g, _)| g.item == i) {
+            if *keep {
+                kept.extend_from_slice(&glyph.bytes);
+                continue;
+            }
+            if !kept.is_empty() {
+                items.push(Object::String(std::mem::take(&mut kept), format.clone()));
+            }
+            let adjustment = if scale == 0.0 { 0.0 } else { -glyph.advance * 1000.0 / scale };
+            items.push(Object::Real(adjustment));
+        }
+        if !kept.is_empty() {
+            items.push(Object::String(kept, format));
+        }
+    }
+
+    replacement.push(Operation::new("TJ", vec![Object::Array(items)]));
+    replacement
+}
+
+fn centre(bbox: &[f64; 4]) -> (f64, f64) {
+    ((bbox[0] + bbox[2]) / 2.0, (bbox[1] + bbox[3]) / 2.0)
+}
+
+fn contains_point(rect: &[f64; 4], (x, y): (f64, f64)) -> bool {
+    x >= rect[0] && x <= rect[2] && y >= rect[1] && y <= rect[3]
+}
+
+fn contains_box(rect: &[f64; 4], bbox: &[f64; 4]) -> bool {
+    // allow for rounding in the CTM
+    const TOLERANCE: f64 = 0.01;
+    bbox[0] >= rect[0] - TOLERANCE && bbox[1] >= rect[1] - TOLERANCE
+        && bbox[2] <= rect[2] + TOLERANCE && bbox[3] <= rect[3] + TOLERANCE
+}
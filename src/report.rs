@@ -0,0 +1,225 @@
+use std::ffi::OsStr;
+use std::fmt::{self, Display};
+use std::panic;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::utils::SaveReport;
+
+// Off unless main turns it on, so library callers and tests get plain text
+static COLOR: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+static TIMINGS_ENABLED: AtomicBool = AtomicBool::new(false);
+static TIMINGS: Mutex<Vec<Timing>> = Mutex::new(Vec::new());
+
+/// How a diagnostic is shown when color is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Red
+    Error,
+    /// Yellow
+    Warning,
+    /// Bold
+    Summary,
+}
+
+/// Decides whether diagnostics are colored: only when stderr is a terminal, --no-color was not
+/// passed and NO_COLOR is unset or empty (https://no-color.org)
+///
+/// # Arguments
+///
+/// * `no_color` - the --no-color flag
+/// * `no_color_env` - the value of the NO_COLOR environment variable, if set
+/// * `terminal` - whether stderr is a terminal
+///
+pub fn color_enabled(no_color: bool, no_color_env: Option<&OsStr>, terminal: bool) -> bool {
+    terminal && !no_color && no_color_env.is_none_or(|value| value.is_empty())
+}
+
+/// Turns colored diagnostics on or off for the rest of the run
+pub fn set_color(enabled: bool) {
+    COLOR.store(enabled, Ordering::Relaxed);
+}
+
+/// Silences summaries, including the end-of-run summary. Errors and warnings are still shown
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Number of warnings printed so far
+pub fn warning_count() -> usize {
+    WARNINGS.load(Ordering::Relaxed)
+}
+
+/// Starts recording how long each phase of the run takes, see timed
+pub fn enable_timings(enabled: bool) {
+    TIMINGS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Runs one phase of a command (loading a file, compressing, saving, ...) and records its
+/// wall-clock time if timings are enabled. When they are not this is just a call to `f`.
+///
+/// # Arguments
+///
+/// * `phase` - the name shown in the timings table, e.g. "load a.pdf"
+/// * `f` - the work to time
+///
+pub fn timed<T>(phase: impl Into<String>, f: impl FnOnce() -> T) -> T {
+    if !TIMINGS_ENABLED.load(Ordering::Relaxed) { return f(); }
+
+    let started = Instant::now();
+    let result = f();
+    let seconds = started.elapsed().as_secs_f64();
+    TIMINGS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(Timing { phase: phase.into(), seconds });
+    result
+}
+
+/// The phases recorded so far, in the order they finished
+pub fn timings() -> Vec<Timing> {
+    TIMINGS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
+
+/// Wraps text in the escape codes for a style, or returns it unchanged when color is off
+pub fn paint(style: Style, text: &str, color: bool) -> String {
+    if !color { return text.to_string(); }
+
+    let code = match style {
+        Style::Error => "31",
+        Style::Warning => "33",
+        Style::Summary => "1",
+    };
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+/// Prints an error to stderr
+pub fn error(message: impl Display) {
+    eprintln!("{}", paint(Style::Error, &format!("error: {}", message), COLOR.load(Ordering::Relaxed)));
+}
+
+/// Prints a warning to stderr
+pub fn warning(message: impl Display) {
+    WARNINGS.fetch_add(1, Ordering::Relaxed);
+    eprintln!("{}", paint(Style::Warning, &format!("warning: {}", message), COLOR.load(Ordering::Relaxed)));
+}
+
+/// Prints what a command did to stderr, keeping stdout for the command's actual output
+pub fn summary(message: impl Display) {
+    if QUIET.load(Ordering::Relaxed) { return; }
+    eprintln!("{}", paint(Style::Summary, &message.to_string(), COLOR.load(Ordering::Relaxed)));
+}
+
+/// What a command did to the document, printed once it has been saved
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunSummary {
+    #[serde(flatten)]
+    pub saved: SaveReport,
+    /// Wall-clock time of the whole run
+    pub seconds: f64,
+    pub warnings: usize,
+    /// Time per phase, only with --timings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<Vec<Timing>>,
+}
+
+/// How long one phase of the run took
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Timing {
+    pub phase: String,
+    pub seconds: f64,
+}
+
+impl RunSummary {
+    pub fn new(saved: SaveReport, elapsed: Duration, warnings: usize) -> RunSummary {
+        RunSummary { saved, seconds: elapsed.as_secs_f64(), warnings, timings: None }
+    }
+
+    /// The timings table printed by --timings: one row per phase, then whatever was not part of
+    /// a timed phase (mostly the command's own work) and the total
+    pub fn timings_table(&self) -> Option<String> {
+        let timings = self.timings.as_ref()?;
+        let timed: f64 = timings.iter().map(|timing| timing.seconds).sum();
+        let other = Timing { phase: "other".to_string(), seconds: (self.seconds - timed).max(0.0) };
+        let total = Timing { phase: "total".to_string(), seconds: self.seconds };
+
+        let rows: Vec<&Timing> = timings.iter().chain([&other, &total]).collect();
+        let width = rows.iter().map(|timing| timing.phase.chars().count()).max().unwrap_or(0);
+        let mut table = String::new();
+        for timing in rows {
+            table.push_str(&format!("{:<width$}  {:>9.3}s\n", timing.phase, timing.seconds, width = width));
+        }
+        Some(table)
+    }
+}
+
+impl Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let saved = &self.saved;
+        write!(f, "pages {} -> {}", saved.pages_in, saved.pages_out)?;
+        match saved.stats.removed() {
+            0 => {}
+            1 => write!(f, ", pruned 1 object")?,
+            n => write!(f, ", pruned {} objects", n)?,
+        }
+        write!(f, ", size {} -> {}", human_size(saved.bytes_in), human_size(saved.bytes_out))?;
+        if saved.bytes_in > 0 {
+            let change = (saved.bytes_out as f64 - saved.bytes_in as f64) / saved.bytes_in as f64 * 100.0;
+            write!(f, " ({:+.1}%)", change)?;
+        }
+        write!(f, ", {:.2}s", self.seconds)?;
+        match self.warnings {
+            1 => write!(f, ", 1 warning"),
+            n => write!(f, ", {} warnings", n),
+        }
+    }
+}
+
+/// Prints the end-of-run summary, as one line on stderr or as a JSON object on stdout
+pub fn run_summary(summary: &RunSummary, json: bool) {
+    if json {
+        println!("{}", serde_json::to_string(summary).expect("failed to serialize the summary"));
+        return;
+    }
+    self::summary(summary);
+    // Asked for explicitly, so shown even with --quiet
+    if let Some(table) = summary.timings_table() {
+        eprint!("{}", table);
+    }
+}
+
+/// Reports panics, which is how commands fail, as a plain error line instead of Rust's panic
+/// message. RUST_BACKTRACE still gets the full default report.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if std::env::var_os("RUST_BACKTRACE").is_some_and(|value| value != "0") {
+            return default_hook(info);
+        }
+        let payload = info.payload();
+        match payload.downcast_ref::<&str>() {
+            Some(message) => error(message),
+            None => match payload.downcast_ref::<String>() {
+                Some(message) => error(message),
+                None => error("unexpected failure"),
+            },
+        }
+    }));
+}
+
+// ------- Helpers -------
+
+pub(crate) fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 { return format!("{} B", bytes); }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
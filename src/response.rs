@@ -0,0 +1,105 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::PathBuf;
+
+/// Replaces every `@file` argument with the arguments listed in that file, so argument lists too
+/// long for the command line (e.g. merging thousands of PDFs on Windows) can be passed in.
+///
+/// Arguments in the file are separated by whitespace or newlines. Single or double quotes group a
+/// path containing spaces, e.g. `"My Scans/page 1.pdf"`. Response files may not name other
+/// response files. The first argument, the program name, is never expanded. On unix, paths that
+/// are not valid UTF-8 are kept byte for byte, both on the command line and in the file.
+///
+/// # Arguments
+///
+/// * `args` - the raw command line, program name included
+///
+pub fn expand_response_files<I>(args: I) -> Result<Vec<OsString>, String>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let mut expanded = Vec::new();
+
+    for (index, arg) in args.into_iter().enumerate() {
+        let path = match response_file_path(&arg) {
+            Some(path) if index > 0 => path,
+            _ => {
+                expanded.push(arg);
+                continue;
+            }
+        };
+
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(error) => return Err(format!("Failed to read response file {}: {}", path.display(), error)),
+        };
+
+        let tokens = split_arguments(&data).map_err(|error| format!("{} in response file {}", error, path.display()))?;
+        for token in tokens {
+            if token.starts_with(b"@") {
+                return Err(format!("Response file {} names another response file ({}), nested response files are not supported",
+                                   path.display(), String::from_utf8_lossy(&token)));
+            }
+            expanded.push(os_string(token).map_err(|error| format!("{} in response file {}", error, path.display()))?);
+        }
+    }
+
+    Ok(expanded)
+}
+
+// ------- Helpers -------
+
+// `@file` -> `file`, or None for an ordinary argument
+fn response_file_path(arg: &OsStr) -> Option<PathBuf> {
+    #[cfg(unix)]
+    let path = {
+        use std::os::unix::ffi::OsStrExt;
+        OsStr::from_bytes(arg.as_bytes().strip_prefix(b"@")?)
+    };
+    #[cfg(not(unix))]
+    let path = OsStr::new(arg.to_str()?.strip_prefix('@')?);
+
+    if path.is_empty() { return None; }
+    Some(PathBuf::from(path))
+}
+
+#[cfg(unix)]
+fn os_string(bytes: Vec<u8>) -> Result<OsString, String> {
+    use std::os::unix::ffi::OsStringExt;
+    Ok(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn os_string(bytes: Vec<u8>) -> Result<OsString, String> {
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok(OsString::from(text)),
+        Err(error) => Err(format!("`{}` is not valid UTF-8", String::from_utf8_lossy(error.as_bytes()))),
+    }
+}
+
+// Quotes and separators are all ASCII, so splitting the raw bytes never cuts a character in two
+fn split_arguments(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let mut arguments = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    let mut quote: Option<u8> = None;
+
+    for &c in data {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.get_or_insert_with(Vec::new).push(c),
+            None if c == b'"' || c == b'\'' => {
+                quote = Some(c);
+                current.get_or_insert_with(Vec::new);
+            }
+            None if c.is_ascii_whitespace() => {
+                if let Some(argument) = current.take() { arguments.push(argument); }
+            }
+            None => current.get_or_insert_with(Vec::new).push(c),
+        }
+    }
+
+    if let Some(q) = quote { return Err(format!("Unterminated {} quote", q as char)); }
+    if let Some(argument) = current { arguments.push(argument); }
+
+    Ok(arguments)
+}
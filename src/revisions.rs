@@ -0,0 +1,180 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+use lopdf::{Document, Object, ObjectId};
+
+use crate::error::{Error, LoadError};
+use crate::report;
+use crate::utils::{try_load_for_update, try_save_changes, SaveOptions, SaveReport};
+
+/// Reports how many revisions a PDF holds, and optionally rewrites it as a single revision either
+/// inplace or in a new file. Every incremental update appends the objects it changed and leaves the
+/// earlier versions in the file, where they can still be read.
+///
+/// * `infile` - a PathBuf of the file to check
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `flatten` - rewrite the document with only the latest version of each object
+/// * `compress` - a boolean flag to compress the outfile before saving
+/// * `save` - how to write the file, flattening cannot be done with an incremental update
+///
+pub fn revisions(infile: PathBuf, outfile: Option<PathBuf>, flatten: bool, compress: bool, save: &SaveOptions) -> Result<Option<SaveReport>, Error> {
+    if flatten && save.incremental {
+        return Err(Error::RewriteRequired("Flattening rewrites the whole file".to_string()));
+    }
+    let data = fs::read(&infile).map_err(|source| Error::LoadFailed { path: infile.clone(), source: LoadError::Io(source) })?;
+    let count = count_revisions(&data);
+    match count {
+        1 => println!("1 revision present"),
+        n => println!("{} revisions present", n),
+    }
+
+    if !flatten { return Ok(None); }
+    if count > 1 {
+        report::warning("flattening discards the earlier revisions, any digital signature will no longer validate");
+    }
+
+    // lopdf reads the latest version of every object, and writes a single revision
+    let (mut doc, before) = try_load_for_update(&infile)?;
+    if compress { report::timed("compress", || doc.compress()) }
+
+    try_save_changes(&mut doc, &infile, outfile, save, before).map(Some)
+}
+
+/// Counts the revisions in a PDF file: the original plus one per incremental update. Each revision
+/// ends with its own `startxref` and `%%EOF`; a linearized file has an extra one for its first page
+/// that is not a revision of its own.
+pub fn count_revisions(data: &[u8]) -> usize {
+    let mut count = 0;
+    let mut position = 0;
+    while let Some(found) = find(&data[position..], b"startxref") {
+        position += found + b"startxref".len();
+        if ends_revision(&data[position..]) {
+            count += 1;
+        }
+    }
+
+    let linearized = find(&data[..data.len().min(1024)], b"/Linearized").is_some();
+    if linearized && count > 1 {
+        count -= 1;
+    }
+    count.max(1)
+}
+
+/// Appends the objects of `doc` that differ from the document in `data` to it as an incremental
+/// update, leaving the bytes of every earlier revision untouched. Objects the changes left
+/// unreferenced are kept, as are any that were removed.
+///
+/// Returns an error if `data` cannot be parsed, or is encrypted while `doc` was decrypted.
+pub fn append_update(data: &mut Vec<u8>, doc: &Document) -> Result<(), String> {
+    let original = Document::load_mem(data).map_err(|e| e.to_string())?;
+    if original.trailer.has(b"Encrypt") && !doc.trailer.has(b"Encrypt") {
+        return Err("the input is encrypted and the update would not be".to_string());
+    }
+    let prev = last_xref_offset(data).ok_or("the file has no startxref")?;
+
+    let changed: BTreeMap<ObjectId, Object> = doc.objects.iter()
+        .filter(|(id, object)| !original.objects.get(id).is_some_and(|o| same_object(o, object)))
+        .map(|(id, object)| (*id, object.clone()))
+        .collect();
+
+    let mut trailer = doc.trailer.clone();
+    // a cross-reference stream leaves its own keys in the trailer, the update is a plain table
+    for key in [b"Prev".as_slice(), b"XRefStm", b"Type", b"W", b"Index", b"Filter", b"DecodeParms", b"Length"] {
+        trailer.remove(key);
+    }
+    trailer.set("Prev", prev as i64);
+
+    // lopdf doesn't expose its object writer, so write a scratch document holding only the changed
+    // objects and lift its body and trailer
+    let mut scratch = Document::with_version(original.version.as_str());
+    scratch.objects = changed;
+    scratch.max_id = doc.max_id.max(original.max_id);
+    scratch.trailer = trailer;
+    let mut written: Vec<u8> = Vec::new();
+    // writing to memory cannot fail
+    scratch.save_to(&mut written).ok();
+
+    let body_start = find(&written, b"\n").map_or(0, |newline| newline + 1);
+    let xref_start = last_xref_offset(&written).ok_or("the update has no startxref")?;
+    let entries_start = xref_start + find(&written[xref_start..], b"\n").unwrap_or(0) + 1;
+    let entries_start = entries_start + find(&written[entries_start..], b"\n").unwrap_or(0) + 1;
+    let trailer_start = xref_start + find(&written[xref_start..], b"trailer").ok_or("the update has no trailer")?;
+    let trailer_end = trailer_start + find(&written[trailer_start..], b"\nstartxref").unwrap_or(0);
+
+    if !data.ends_with(b"\n") { data.push(b'\n'); }
+    let base = data.len();
+    let xref_offset = base + xref_start - body_start;
+
+    // each entry of the scratch table is 20 bytes, starting with the 10 digit offset
+    let mut sections: Vec<(ObjectId, usize)> = Vec::new();
+    for id in scratch.objects.keys() {
+        let entry = entries_start + id.0 as usize * 20;
+        let offset: usize = String::from_utf8_lossy(&written[entry..entry + 10]).parse().map_err(|_| "the update has a malformed cross-reference table")?;
+        sections.push((*id, base + offset - body_start));
+    }
+
+    data.extend_from_slice(&written[body_start..xref_start]);
+    data.extend_from_slice(b"xref\n");
+    let mut start = 0;
+    while start < sections.len() {
+        let mut end = start + 1;
+        while end < sections.len() && sections[end].0.0 == sections[end - 1].0.0 + 1 { end += 1; }
+        data.extend_from_slice(format!("{} {}\n", sections[start].0.0, end - start).as_bytes());
+        for ((_, generation), offset) in &sections[start..end] {
+            data.extend_from_slice(format!("{:010} {:05} n \n", offset, generation).as_bytes());
+        }
+        start = end;
+    }
+    data.extend_from_slice(&written[trailer_start..trailer_end]);
+    data.extend_from_slice(format!("\nstartxref\n{}\n%%EOF\n", xref_offset).as_bytes());
+
+    Ok(())
+}
+
+
+// ------- Helpers -------
+
+// the offset after the last startxref in the file, where the newest cross-reference section starts
+fn last_xref_offset(data: &[u8]) -> Option<usize> {
+    let position = data.windows(b"startxref".len()).rposition(|window| window == b"startxref")?;
+    let rest = trim_start(&data[position + b"startxref".len()..]);
+    let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+    String::from_utf8_lossy(&rest[..digits]).parse().ok()
+}
+
+// lopdf objects don't implement PartialEq
+fn same_object(a: &Object, b: &Object) -> bool {
+    match (a, b) {
+        (Object::Null, Object::Null) => true,
+        (Object::Boolean(x), Object::Boolean(y)) => x == y,
+        (Object::Integer(x), Object::Integer(y)) => x == y,
+        (Object::Real(x), Object::Real(y)) => x == y,
+        (Object::Name(x), Object::Name(y)) => x == y,
+        (Object::String(x, _), Object::String(y, _)) => x == y,
+        (Object::Reference(x), Object::Reference(y)) => x == y,
+        (Object::Array(x), Object::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(x, y)| same_object(x, y))
+        }
+        (Object::Dictionary(x), Object::Dictionary(y)) => same_dictionary(x, y),
+        (Object::Stream(x), Object::Stream(y)) => x.content == y.content && same_dictionary(&x.dict, &y.dict),
+        _ => false,
+    }
+}
+
+fn same_dictionary(a: &lopdf::Dictionary, b: &lopdf::Dictionary) -> bool {
+    a.len() == b.len() && a.iter().all(|(key, value)| b.get(key).is_ok_and(|other| same_object(value, other)))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+// whitespace, the offset of the cross-reference section, whitespace and %%EOF
+fn ends_revision(rest: &[u8]) -> bool {
+    let rest = trim_start(rest);
+    let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+    digits > 0 && trim_start(&rest[digits..]).starts_with(b"%%EOF")
+}
+
+fn trim_start(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(bytes.len());
+    &bytes[start..]
+}
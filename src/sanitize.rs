@@ -0,0 +1,457 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Write},
+    path::PathBuf
+};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::error::{Error, LoadError};
+use crate::links::{link_target, page_annotations, set_page_annotations};
+use crate::report;
+use crate::revisions::count_revisions;
+use crate::utils::{try_load_for_update, try_save_changes, SaveOptions, SaveReport};
+
+/// Keys written by authoring applications for their own use. None of them affect how a page is displayed.
+const PRIVATE_KEYS: [&[u8]; 3] = [b"PieceInfo", b"LastModified", b"SpiderInfo"];
+/// pdfTeX writes its own bookkeeping as PTEX.* keys on pages, XObjects and the Info dictionary
+const PRIVATE_KEY_PREFIXES: [&[u8]; 1] = [b"PTEX."];
+
+/// Categories of data that `sanitize` can remove
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum Removal {
+    /// The document information dictionary: title, author, producer, dates and so on
+    Metadata,
+    /// XMP metadata streams of the document, its pages and images
+    Xmp,
+    /// Document level scripts and JavaScript actions
+    #[clap(name = "javascript")]
+    JavaScript,
+    /// The action run when the document is opened, e.g. jumping to a page and zoom level
+    #[clap(name = "openaction")]
+    OpenAction,
+    /// /AA trigger actions on the catalog and pages, run on events like closing or printing
+    AdditionalActions,
+    /// Attached files, both document level and file attachment annotations
+    EmbeddedFiles,
+    /// Link annotations that leave the document
+    ExternalLinks,
+    /// /PieceInfo, /SpiderInfo and other application-private data
+    PrivateData,
+    /// Earlier revisions kept by incremental updates. Sanitize always rewrites the file as a single
+    /// revision, this only reports how many were flattened.
+    Revisions,
+}
+
+impl Removal {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Removal::Metadata => "metadata",
+            Removal::Xmp => "xmp",
+            Removal::JavaScript => "javascript",
+            Removal::OpenAction => "openaction",
+            Removal::AdditionalActions => "additional-actions",
+            Removal::EmbeddedFiles => "embedded-files",
+            Removal::ExternalLinks => "external-links",
+            Removal::PrivateData => "private-data",
+            Removal::Revisions => "revisions",
+        }
+    }
+}
+
+/// Everything `sanitize` knows how to remove
+const STRICT: [Removal; 9] = [
+    Removal::Metadata,
+    Removal::Xmp,
+    Removal::JavaScript,
+    Removal::OpenAction,
+    Removal::AdditionalActions,
+    Removal::EmbeddedFiles,
+    Removal::ExternalLinks,
+    Removal::PrivateData,
+    Removal::Revisions,
+];
+/// Hidden and active content, leaving what a reader would expect to keep
+const STANDARD: [Removal; 7] = [
+    Removal::Metadata,
+    Removal::Xmp,
+    Removal::JavaScript,
+    Removal::OpenAction,
+    Removal::AdditionalActions,
+    Removal::PrivateData,
+    Removal::Revisions,
+];
+
+/// Named sets of categories for `sanitize --profile`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Profile {
+    /// Remove every category
+    Strict,
+    /// Remove every category except external links and embedded files
+    Standard,
+}
+
+impl Profile {
+    /// The categories removed by this profile, in the order they are run
+    pub fn removals(&self) -> &'static [Removal] {
+        match self {
+            Profile::Strict => &STRICT,
+            Profile::Standard => &STANDARD,
+        }
+    }
+}
+
+/// Summary of what a single cleanup pass removed from a document
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PassReport {
+    /// Number of dictionary entries removed
+    pub entries: usize,
+    /// Number of indirect objects that were only reachable through the removed entries
+    pub objects: usize,
+    /// Serialized size of everything that was removed
+    pub bytes: u64,
+}
+
+/// Removes the selected categories of data from a PDF either inplace or in a new file
+///
+/// * `infile` - a PathBuf of the file to sanitize
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `remove` - the categories of data to remove, repeated categories only run once
+/// * `compress` - a boolean flag to compress the outfile before saving
+/// * `save` - how to write the file, an incremental update would leave the removed data in the
+///   earlier revision and is refused
+///
+pub fn sanitize(infile: PathBuf, outfile: Option<PathBuf>, remove: Vec<Removal>, compress: bool, save: &SaveOptions) -> Result<SaveReport, Error> {
+    if save.incremental {
+        return Err(Error::RewriteRequired("Sanitizing rewrites the whole file so the removed data is not left in an earlier revision".to_string()));
+    }
+    let data = fs::read(&infile).map_err(|source| Error::LoadFailed { path: infile.clone(), source: LoadError::Io(source) })?;
+    let (mut doc, before) = try_load_for_update(&infile)?;
+
+    let mut seen = HashSet::new();
+    for category in remove.into_iter().filter(|category| seen.insert(*category)) {
+        if category == Removal::Revisions {
+            match count_revisions(&data) {
+                0 | 1 => report::summary("revisions: no earlier revisions, nothing to flatten"),
+                n => {
+                    report::summary(format!("revisions: flattened {} revisions into one", n));
+                    report::warning("flattening discards the earlier revisions, any digital signature will no longer validate");
+                }
+            }
+            continue;
+        }
+        let report = remove_category(&mut doc, category);
+        report::summary(format!("{}: removed {} entries, pruned {} objects, reclaimed {} bytes",
+                 category.name(), report.entries, report.objects, report.bytes));
+    }
+
+    if compress { report::timed("compress", || doc.compress()) }
+
+    try_save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Runs the cleanup pass for a single category. Revisions has nothing to remove from a loaded
+/// document and always reports an empty pass.
+pub fn remove_category(doc: &mut Document, category: Removal) -> PassReport {
+    match category {
+        Removal::Metadata => remove_metadata(doc),
+        Removal::Xmp => remove_xmp(doc),
+        Removal::JavaScript => remove_javascript(doc),
+        Removal::OpenAction => remove_open_action(doc),
+        Removal::AdditionalActions => remove_additional_actions(doc),
+        Removal::EmbeddedFiles => remove_embedded_files(doc),
+        Removal::ExternalLinks => remove_external_links(doc),
+        Removal::PrivateData => remove_private_data(doc),
+        Removal::Revisions => PassReport::default(),
+    }
+}
+
+/// Removes the trailer's /Info dictionary
+pub fn remove_metadata(doc: &mut Document) -> PassReport {
+    let unreferenced_before = unreferenced_objects(doc);
+    let mut removed: Vec<Object> = Vec::new();
+
+    remove_key(&mut doc.trailer, b"Info", &mut removed);
+
+    finish_pass(doc, unreferenced_before, removed)
+}
+
+/// Strips every /Metadata entry, which is where the catalog, pages, images and fonts keep their XMP
+pub fn remove_xmp(doc: &mut Document) -> PassReport {
+    let unreferenced_before = unreferenced_objects(doc);
+    let mut removed: Vec<Object> = Vec::new();
+
+    for object in doc.objects.values_mut() {
+        strip_keys(object, &|key| key == b"Metadata", &mut removed);
+    }
+
+    finish_pass(doc, unreferenced_before, removed)
+}
+
+/// Removes the /JavaScript name tree and every entry whose value is a JavaScript action, such as
+/// an /OpenAction, the /A of an annotation or a trigger in an /AA dictionary
+pub fn remove_javascript(doc: &mut Document) -> PassReport {
+    let unreferenced_before = unreferenced_objects(doc);
+    let mut removed: Vec<Object> = Vec::new();
+
+    if let Some(names) = names_mut(doc) {
+        remove_key(names, b"JavaScript", &mut removed);
+    }
+
+    let script_ids: HashSet<ObjectId> = doc.objects.iter()
+        .filter(|(_, object)| is_javascript_action(object))
+        .map(|(id, _)| *id)
+        .collect();
+    let is_script = |value: &Object| match value {
+        Object::Reference(id) => script_ids.contains(id),
+        _ => is_javascript_action(value),
+    };
+    for object in doc.objects.values_mut() {
+        strip_values(object, &is_script, &mut removed);
+    }
+
+    finish_pass(doc, unreferenced_before, removed)
+}
+
+/// Removes the /EmbeddedFiles name tree and every file attachment annotation
+pub fn remove_embedded_files(doc: &mut Document) -> PassReport {
+    let unreferenced_before = unreferenced_objects(doc);
+    let mut removed: Vec<Object> = Vec::new();
+
+    if let Some(names) = names_mut(doc) {
+        remove_key(names, b"EmbeddedFiles", &mut removed);
+    }
+    remove_annotations(doc, &|_, annot| {
+        annot.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"FileAttachment")
+    }, &mut removed);
+
+    finish_pass(doc, unreferenced_before, removed)
+}
+
+/// Removes link annotations to web addresses and other files, keeping links within the document
+pub fn remove_external_links(doc: &mut Document) -> PassReport {
+    let unreferenced_before = unreferenced_objects(doc);
+    let mut removed: Vec<Object> = Vec::new();
+
+    remove_annotations(doc, &|doc, annot| {
+        link_target(doc, annot).is_some_and(|target| target.is_external())
+    }, &mut removed);
+
+    finish_pass(doc, unreferenced_before, removed)
+}
+
+/// Strips /PieceInfo, /LastModified, /SpiderInfo and PTEX.* entries from every dictionary in the
+/// document, along with any objects that were only referenced from them
+pub fn remove_private_data(doc: &mut Document) -> PassReport {
+    let unreferenced_before = unreferenced_objects(doc);
+    let mut removed: Vec<Object> = Vec::new();
+
+    for object in doc.objects.values_mut() {
+        strip_keys(object, &is_private_key, &mut removed);
+    }
+
+    finish_pass(doc, unreferenced_before, removed)
+}
+
+/// Removes the catalog's /OpenAction, so viewers open the document at the first page with their
+/// own default zoom
+pub fn remove_open_action(doc: &mut Document) -> PassReport {
+    let unreferenced_before = unreferenced_objects(doc);
+    let mut removed: Vec<Object> = Vec::new();
+
+    if let Some(catalog) = catalog_mut(doc) {
+        remove_key(catalog, b"OpenAction", &mut removed);
+    }
+
+    finish_pass(doc, unreferenced_before, removed)
+}
+
+/// Removes the /AA additional actions dictionaries of the catalog and every page
+pub fn remove_additional_actions(doc: &mut Document) -> PassReport {
+    let unreferenced_before = unreferenced_objects(doc);
+    let mut removed: Vec<Object> = Vec::new();
+
+    if let Some(catalog) = catalog_mut(doc) {
+        remove_key(catalog, b"AA", &mut removed);
+    }
+    let page_ids: Vec<ObjectId> = doc.page_iter().collect();
+    for page_id in page_ids {
+        if let Ok(page) = doc.get_object_mut(page_id).and_then(Object::as_dict_mut) {
+            remove_key(page, b"AA", &mut removed);
+        }
+    }
+
+    finish_pass(doc, unreferenced_before, removed)
+}
+
+
+// ------- Helpers -------
+
+// takes out the objects only the removed entries referenced and measures everything that went
+fn finish_pass(doc: &mut Document, unreferenced_before: HashSet<ObjectId>, removed: Vec<Object>) -> PassReport {
+    let mut report = PassReport { entries: removed.len(), ..PassReport::default() };
+
+    // only take out what this pass orphaned, anything that was already unreferenced is left for save_pdf
+    let mut orphans: Vec<(ObjectId, Object)> = Vec::new();
+    for id in unreferenced_objects(doc).difference(&unreferenced_before) {
+        if let Some(object) = doc.objects.remove(id) {
+            orphans.push((*id, object));
+        }
+    }
+    report.objects = orphans.len();
+
+    if !removed.is_empty() {
+        orphans.push(((doc.max_id + 1, 0), Object::Array(removed)));
+    }
+    if !orphans.is_empty() {
+        report.bytes = serialized_size(orphans);
+    }
+
+    report
+}
+
+fn catalog_mut(doc: &mut Document) -> Option<&mut Dictionary> {
+    let catalog_id = doc.trailer.get(b"Root").and_then(Object::as_reference).ok()?;
+    doc.get_object_mut(catalog_id).and_then(Object::as_dict_mut).ok()
+}
+
+// the catalog's /Names dictionary, which may be stored directly in the catalog or as its own object
+fn names_mut(doc: &mut Document) -> Option<&mut Dictionary> {
+    let names_id = match doc.catalog().ok()?.get(b"Names").ok()? {
+        Object::Reference(id) => *id,
+        _ => return catalog_mut(doc)?.get_mut(b"Names").and_then(Object::as_dict_mut).ok(),
+    };
+    doc.get_object_mut(names_id).and_then(Object::as_dict_mut).ok()
+}
+
+fn is_javascript_action(object: &Object) -> bool {
+    let dict = match object {
+        Object::Dictionary(dict) => dict,
+        _ => return false,
+    };
+    dict.get(b"S").and_then(Object::as_name).ok() == Some(b"JavaScript")
+}
+
+// takes the matching annotations off every page, recording each removed annotation
+fn remove_annotations(doc: &mut Document, matches: &dyn Fn(&Document, &Dictionary) -> bool, removed: &mut Vec<Object>) {
+    for (_, page_id) in doc.get_pages() {
+        let annots = page_annotations(doc, page_id);
+        let (dropped, kept): (Vec<Object>, Vec<Object>) = annots.into_iter()
+            .partition(|annot| match doc.dereference(annot).and_then(|(_, a)| a.as_dict()) {
+                Ok(dict) => matches(doc, dict),
+                Err(_) => false,
+            });
+
+        if !dropped.is_empty() {
+            removed.extend(dropped);
+            set_page_annotations(doc, page_id, kept);
+        }
+    }
+}
+
+fn remove_key(dict: &mut Dictionary, key: &[u8], removed: &mut Vec<Object>) {
+    if let Some(value) = dict.remove(key) {
+        removed.push(Object::Name(key.to_vec()));
+        removed.push(value);
+    }
+}
+
+fn is_private_key(key: &[u8]) -> bool {
+    PRIVATE_KEYS.contains(&key) || PRIVATE_KEY_PREFIXES.iter().any(|prefix| key.starts_with(prefix))
+}
+
+// removes matching keys from a dictionary (or stream dictionary) and any direct objects nested inside it
+fn strip_keys(object: &mut Object, matches: &dyn Fn(&[u8]) -> bool, removed: &mut Vec<Object>) {
+    let dict = match object {
+        Object::Dictionary(dict) => dict,
+        Object::Stream(stream) => &mut stream.dict,
+        Object::Array(arr) => {
+            for item in arr.iter_mut() {
+                strip_keys(item, matches, removed);
+            }
+            return;
+        }
+        _ => return,
+    };
+
+    let keys: Vec<Vec<u8>> = dict.iter()
+        .map(|(key, _)| key.clone())
+        .filter(|key| matches(key))
+        .collect();
+    for key in keys {
+        if let Some(value) = dict.remove(&key) {
+            removed.push(Object::Name(key));
+            removed.push(value);
+        }
+    }
+
+    for (_, value) in dict.iter_mut() {
+        strip_keys(value, matches, removed);
+    }
+}
+
+// like strip_keys, but removes the entries whose value matches
+fn strip_values(object: &mut Object, matches: &dyn Fn(&Object) -> bool, removed: &mut Vec<Object>) {
+    let dict = match object {
+        Object::Dictionary(dict) => dict,
+        Object::Stream(stream) => &mut stream.dict,
+        Object::Array(arr) => {
+            for item in arr.iter_mut() {
+                strip_values(item, matches, removed);
+            }
+            return;
+        }
+        _ => return,
+    };
+
+    let keys: Vec<Vec<u8>> = dict.iter()
+        .filter(|(_, value)| matches(value))
+        .map(|(key, _)| key.clone())
+        .collect();
+    for key in keys {
+        remove_key(dict, &key, removed);
+    }
+
+    for (_, value) in dict.iter_mut() {
+        strip_values(value, matches, removed);
+    }
+}
+
+fn unreferenced_objects(doc: &mut Document) -> HashSet<ObjectId> {
+    let refs: HashSet<ObjectId> = doc.traverse_objects(|_| {}).into_iter().collect();
+    doc.objects.keys()
+        .filter(|id| !refs.contains(id))
+        .copied()
+        .collect()
+}
+
+struct CountingWriter {
+    count: u64,
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.count += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// lopdf doesn't expose its object writer, so measure by saving a scratch document with and without the objects
+fn serialized_size(objects: Vec<(ObjectId, Object)>) -> u64 {
+    let mut empty = Document::with_version("1.5");
+    let mut scratch = empty.clone();
+    scratch.objects.extend(objects);
+    scratch.max_id = empty.max_id;
+
+    let mut with = CountingWriter { count: 0 };
+    let mut without = CountingWriter { count: 0 };
+    // writing to the counter cannot fail
+    scratch.save_to(&mut with).ok();
+    empty.save_to(&mut without).ok();
+
+    with.count.saturating_sub(without.count)
+}
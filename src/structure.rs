@@ -0,0 +1,229 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::links::page_annotations;
+use crate::names::{build_name_tree, build_number_tree, name_tree_entries, number_tree_entries};
+
+/// Removes the structure elements of a tagged PDF whose content is on pages that are no longer in
+/// the document, along with their /ParentTree and /IDTree entries. Marked content needs a page to
+/// live on: an element keeps a marked content id only while its /Pg (or its nearest ancestor's)
+/// is a page of the document, and an object reference only while the object still exists. An
+/// element with nothing left in /K is removed, and its parent's /K is rewritten without it.
+///
+/// Returns the number of structure elements removed.
+pub fn prune_structure_tree(doc: &mut Document) -> usize {
+    let (root_id, root) = match structure_root(doc) {
+        Some(root) => root,
+        None => return 0,
+    };
+
+    let mut pruner = Pruner {
+        pages: doc.page_iter().collect(),
+        removed: HashSet::new(),
+        updated: HashMap::new(),
+        visited: HashSet::new(),
+    };
+    let kids = kids_of(&root);
+    let kept = pruner.prune_kids(doc, kids, None);
+
+    let mut root = root;
+    set_kids(&mut root, kept);
+
+    let Pruner { removed, updated, .. } = pruner;
+    for (id, element) in updated {
+        doc.objects.insert(id, Object::Dictionary(element));
+    }
+    for id in &removed {
+        doc.objects.remove(id);
+    }
+
+    if let Some(parent_tree) = root.get(b"ParentTree").ok().cloned() {
+        let entries: BTreeMap<i64, Object> = number_tree_entries(doc, &parent_tree)
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, without_removed(doc, value, &removed)?)))
+            .collect();
+        let tree_id = build_number_tree(doc, entries);
+        root.set("ParentTree", tree_id);
+    }
+
+    if let Some(id_tree) = root.get(b"IDTree").ok().cloned() {
+        let entries: BTreeMap<Vec<u8>, Object> = name_tree_entries(doc, &id_tree)
+            .into_iter()
+            .filter(|(_, element)| !matches!(element, Object::Reference(id) if removed.contains(id)))
+            .collect();
+        if entries.is_empty() {
+            root.remove(b"IDTree");
+        } else {
+            let tree_id = build_name_tree(doc, entries);
+            root.set("IDTree", tree_id);
+        }
+    }
+
+    doc.objects.insert(root_id, Object::Dictionary(root));
+    removed.len()
+}
+
+/// Removes the structure tree altogether, along with the /StructParents and /StructParent keys
+/// that pointed into it. The document is no longer tagged afterwards.
+pub fn strip_structure_tree(doc: &mut Document) {
+    if let Ok(catalog_id) = doc.trailer.get(b"Root").and_then(Object::as_reference) {
+        if let Ok(catalog) = doc.get_object_mut(catalog_id).and_then(Object::as_dict_mut) {
+            catalog.remove(b"StructTreeRoot");
+            catalog.remove(b"MarkInfo");
+        }
+    }
+
+    let page_ids: Vec<ObjectId> = doc.page_iter().collect();
+    for page_id in page_ids {
+        for annot in page_annotations(doc, page_id) {
+            match annot {
+                Object::Reference(id) => {
+                    if let Ok(annot) = doc.get_object_mut(id).and_then(Object::as_dict_mut) {
+                        annot.remove(b"StructParent");
+                    }
+                }
+                // direct annotations are rare enough to leave, StructParent is harmless without a tree
+                _ => continue,
+            }
+        }
+        if let Ok(page) = doc.get_object_mut(page_id).and_then(Object::as_dict_mut) {
+            page.remove(b"StructParents");
+        }
+    }
+}
+
+
+// ------- Helpers -------
+
+struct Pruner {
+    pages: HashSet<ObjectId>,
+    removed: HashSet<ObjectId>,
+    updated: HashMap<ObjectId, Dictionary>,
+    visited: HashSet<ObjectId>,
+}
+
+impl Pruner {
+    // `page` is the page marked content ids in these kids belong to, if it still exists
+    fn prune_kids(&mut self, doc: &Document, kids: Vec<Object>, page: Option<ObjectId>) -> Vec<Object> {
+        let mut kept = Vec::new();
+
+        for kid in kids {
+            match &kid {
+                Object::Integer(_) => {
+                    if page.is_some() { kept.push(kid); }
+                }
+                Object::Reference(id) => {
+                    let dict = match doc.get_dictionary(*id) {
+                        Ok(dict) => dict,
+                        Err(_) => continue,
+                    };
+                    if is_content_item(dict) {
+                        if self.content_item_exists(doc, dict, page) { kept.push(kid); }
+                        continue;
+                    }
+                    if !self.visited.insert(*id) { continue; }
+                    match self.prune_element(doc, dict, page) {
+                        Some(element) => {
+                            self.updated.insert(*id, element);
+                            kept.push(kid);
+                        }
+                        None => {
+                            self.removed.insert(*id);
+                        }
+                    }
+                }
+                Object::Dictionary(dict) => {
+                    if is_content_item(dict) {
+                        if self.content_item_exists(doc, dict, page) { kept.push(kid); }
+                    } else if let Some(element) = self.prune_element(doc, dict, page) {
+                        kept.push(Object::Dictionary(element));
+                    }
+                }
+                _ => kept.push(kid),
+            }
+        }
+
+        kept
+    }
+
+    // the pruned element, or None when none of its content is left
+    fn prune_element(&mut self, doc: &Document, element: &Dictionary, inherited: Option<ObjectId>) -> Option<Dictionary> {
+        let page = match element.get(b"Pg").and_then(Object::as_reference) {
+            Ok(page) => self.pages.contains(&page).then_some(page),
+            Err(_) => inherited,
+        };
+
+        let kids = kids_of(element);
+        if kids.is_empty() {
+            // an element without content only stays if it does not claim a page that is gone
+            let claims_missing_page = element.has(b"Pg") && page.is_none();
+            return (!claims_missing_page).then(|| element.clone());
+        }
+
+        let kept = self.prune_kids(doc, kids, page);
+        if kept.is_empty() { return None; }
+
+        let mut element = element.clone();
+        set_kids(&mut element, kept);
+        Some(element)
+    }
+
+    // marked content references (MCR) and object references (OBJR) may name their own page
+    fn content_item_exists(&self, doc: &Document, item: &Dictionary, page: Option<ObjectId>) -> bool {
+        let page = match item.get(b"Pg").and_then(Object::as_reference) {
+            Ok(own) => self.pages.contains(&own).then_some(own),
+            Err(_) => page,
+        };
+        if page.is_none() { return false; }
+
+        match item.get(b"Obj").and_then(Object::as_reference) {
+            Ok(obj) => doc.get_object(obj).is_ok(),
+            Err(_) => true,
+        }
+    }
+}
+
+fn structure_root(doc: &Document) -> Option<(ObjectId, Dictionary)> {
+    let root_id = doc.catalog().ok()?.get(b"StructTreeRoot").and_then(Object::as_reference).ok()?;
+    let root = doc.get_dictionary(root_id).ok()?.clone();
+    Some((root_id, root))
+}
+
+fn is_content_item(dict: &Dictionary) -> bool {
+    matches!(dict.get(b"Type").and_then(Object::as_name), Ok(b"MCR") | Ok(b"OBJR"))
+}
+
+fn kids_of(element: &Dictionary) -> Vec<Object> {
+    match element.get(b"K") {
+        Ok(Object::Array(kids)) => kids.clone(),
+        Ok(kid) => vec![kid.clone()],
+        Err(_) => Vec::new(),
+    }
+}
+
+fn set_kids(element: &mut Dictionary, mut kids: Vec<Object>) {
+    match kids.len() {
+        0 => { element.remove(b"K"); }
+        1 => element.set("K", kids.remove(0)),
+        _ => element.set("K", kids),
+    }
+}
+
+// /ParentTree values are either one element or an array of them indexed by marked content id.
+// Removed elements become null so the ids of the rest stay put; entries with nothing left go.
+fn without_removed(doc: &Document, value: Object, removed: &HashSet<ObjectId>) -> Option<Object> {
+    let is_live = |object: &Object| match object {
+        Object::Reference(id) => !removed.contains(id) && doc.get_object(*id).is_ok(),
+        _ => false,
+    };
+
+    match doc.dereference(&value) {
+        Ok((_, Object::Array(elements))) => {
+            let elements: Vec<Object> = elements.iter()
+                .map(|element| if is_live(element) { element.clone() } else { Object::Null })
+                .collect();
+            elements.iter().any(|element| !matches!(element, Object::Null)).then_some(Object::Array(elements))
+        }
+        _ => is_live(&value).then_some(value),
+    }
+}
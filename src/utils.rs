@@ -1,560 +1,1984 @@
 use std::{
-    collections::{HashSet, BTreeMap},
+    cmp,
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::{OsStr, OsString},
     fs,
-    path::{PathBuf}
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Mutex},
+    thread,
 };
 use lopdf::{Document, Object, ObjectId};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::bookmarks;
+use crate::encryption::{decrypt_pdf, is_encrypted, DecryptError};
+use crate::error::{Error, LoadError};
+use crate::ops::{self, PageRange, PageSelection, PageSize, Position};
+use crate::forms::signed_fields;
+use crate::recovery::recover;
+use crate::report;
+use crate::revisions::append_update;
+use crate::structure;
+use crate::zip::{ZipArchive, ZipCompression, ZipWriter};
+
+static LENIENT: AtomicBool = AtomicBool::new(false);
+static PASSWORD: Mutex<Option<String>> = Mutex::new(None);
+
+/// How commands that change an existing PDF write it back out
+#[derive(Debug, Clone, Default)]
+pub struct SaveOptions {
+    /// Rewrite a digitally signed document even though its signatures will no longer validate
+    pub break_signatures: bool,
+    /// Append the changes to the input as an incremental update instead of rewriting it, which
+    /// leaves the signed revision intact
+    pub incremental: bool,
+    /// Keep the permissions and modification time of a file changed inplace
+    pub preserve: bool,
+}
+
+/// Page counts and file sizes before and after a command, for the end-of-run summary
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SaveReport {
+    /// Pages in the input, or all inputs together
+    pub pages_in: usize,
+    /// Pages in the written file
+    pub pages_out: usize,
+    /// Size of the input, or all inputs together, in bytes
+    pub bytes_in: u64,
+    /// Size of the written file in bytes
+    pub bytes_out: u64,
+    /// What was cleaned out of the written files
+    #[serde(flatten)]
+    pub stats: SaveStats,
+}
+
+/// The objects dropped when a document is written, added up over the files written. Pages that
+/// were deleted but whose images or fonts are still shared with other pages free nothing here.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SaveStats {
+    /// Objects in the documents before they were pruned
+    pub objects_before: usize,
+    /// Objects no longer referenced from the trailer, removed by pruning
+    pub objects_pruned: usize,
+    /// Streams with no data, removed after pruning
+    pub empty_streams: usize,
+    /// Objects written
+    pub objects_after: usize,
+}
+
+impl SaveStats {
+    /// Objects removed in all, pruned or empty
+    pub fn removed(&self) -> usize {
+        self.objects_pruned + self.empty_streams
+    }
+}
+
+impl std::ops::AddAssign for SaveStats {
+    fn add_assign(&mut self, other: SaveStats) {
+        self.objects_before += other.objects_before;
+        self.objects_pruned += other.objects_pruned;
+        self.empty_streams += other.empty_streams;
+        self.objects_after += other.objects_after;
+    }
+}
 
-const VERSION: &str = "1.5";
+/// The order the PDFs found in a directory are taken in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    /// By file name, character by character
+    #[default]
+    Name,
+    /// By file name, with numbers compared by value: page2.pdf before page10.pdf
+    Natural,
+    /// Oldest modification time first
+    Mtime,
+}
 
-/// Creates a silgle PDF containing all passed infiles, or all PDFs in passed directories
+/// Fields of the document information dictionary to write, the ones left out keep their value
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DocumentInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+}
+
+/// Creates a silgle PDF containing all passed infiles, or all PDFs in passed directories and zip
+/// archives. The PDFs at the top of an archive are merged in natural order (2.pdf before 10.pdf)
+/// straight from memory. The merged file gets the document information of the first input, with
+/// the fields given in `info` replaced. Inputs are parsed in parallel and merged in order.
 /// 
 /// # Arguments
 /// 
-/// * `infiles` - a vector of PathBuf which could include directories or files
+/// * `infiles` - a vector of PathBuf which could include directories, zip archives or files. A
+///   file can have a pdftk style page selection appended, see `split_page_suffix`
 /// * `outfile` - a PathBuf representing the location to save the merged file to
 /// * `compress` - a boolean flag to compress the outfile file before saving
+/// * `recursive` - also merge the PDFs in subdirectories of the directories, depth first
+/// * `sort` - the order of the PDFs found in a directory, files given directly keep their order
+/// * `skip_errors` - warn about inputs that cannot be loaded and merge the rest, failing only if
+///   none can be
+/// * `bookmark_by_file` - add a top level bookmark for each input, titled with its Title or
+///   else its file name, with the input's own bookmarks under it
+/// * `manifest` - a file listing more inputs after those in `infiles`, see `read_manifest`
+/// * `dedupe` - keep one copy of the fonts, images and other resources the inputs have in common
+/// * `info` - title, author, subject and keywords to set on the merged file
 /// 
-pub fn merge(infiles: &Vec<PathBuf>, outfile: PathBuf, compress: bool) {
+#[allow(clippy::too_many_arguments)]
+pub fn merge(infiles: &Vec<PathBuf>,
+             outfile: PathBuf,
+             compress: bool,
+             recursive: bool,
+             sort: SortOrder,
+             skip_errors: bool,
+             bookmark_by_file: bool,
+             manifest: Option<&Path>,
+             dedupe: bool,
+             info: &DocumentInfo) -> Result<SaveReport, Error> {
     // make vector of Document data structures
     let mut documents: Vec<Document> = Vec::new();
-    let mut doc: Document;
-    let mut document = Document::with_version(VERSION);
+    let mut titles: Vec<String> = Vec::new();
+    let mut report = SaveReport::default();
+    let mut skipped = 0;
+
+    // Takes a loaded input with its size and where it came from, or deals with the error it failed with
+    let mut add = |loaded: Result<(Document, u64, PathBuf), Error>| match loaded {
+        Ok((doc, bytes, path)) => {
+            report.pages_in += doc.get_pages().len();
+            report.bytes_in += bytes;
+            titles.push(bookmark_title(&doc, &path));
+            documents.push(doc);
+            Ok(())
+        }
+        Err(error) if skip_errors => {
+            report::warning(format!("{}, skipped", error));
+            skipped += 1;
+            Ok(())
+        }
+        Err(error) => Err(error),
+    };
+
+    // Work out every input first, then parse them in parallel and add them in order
+    let mut inputs: Vec<MergeInput> = Vec::new();
+    for given in infiles {
+        let (path, suffix) = match split_page_suffix(given) {
+            Some((path, suffix)) => (path, Some(suffix)),
+            None => (given.clone(), None),
+        };
+        let (path, password) = match split_password(&path) {
+            Some((path, password)) => (path, Some(password)),
+            None => (path, None),
+        };
+        if suffix.is_some() || password.is_some() {
+            let first = !inputs.iter().any(|input| matches!(input, MergeInput::Selected { path: earlier, .. } if *earlier == path));
+            inputs.push(MergeInput::Selected { path, suffix, password, first });
+            continue;
+        }
+
+        for fname in expand_dirs_if_necessary(&vec![given.clone()], recursive, sort)? {
+            if !is_zip(&fname) {
+                inputs.push(MergeInput::File(fname));
+                continue;
+            }
+            match pdfs_in_zip(&fname, recursive) {
+                Ok(entries) => inputs.extend(entries.into_iter().map(|(name, data)| {
+                    MergeInput::ZipEntry { label: format!("{}:{}", fname.display(), name), name, data }
+                })),
+                Err(error) => inputs.push(MergeInput::Failed(error)),
+            }
+        }
+    }
+    inputs.extend(manifest.map(read_manifest).transpose()?.unwrap_or_default().into_iter().map(MergeInput::Manifest));
+
+    // the first failure in order is all that is reported without skip_errors, so loading stops there
+    let loaded = in_parallel(&inputs, load_merge_input, |result| !skip_errors && matches!(result, Some(Err(_))));
 
-    let files = expand_dirs_if_necessary(infiles);
+    // an input given with several page selections is kept until its last occurrence has a copy
+    let mut occurrences: HashMap<PathBuf, usize> = HashMap::new();
+    for input in &inputs {
+        if let MergeInput::Selected { path, .. } = input { *occurrences.entry(path.clone()).or_default() += 1; }
+    }
+    let mut loaded_once: HashMap<PathBuf, Document> = HashMap::new();
+
+    for (input, result) in inputs.into_iter().zip(loaded) {
+        // inputs are started in order, so those never started come after the failure returned below
+        let Some(result) = result else { break };
+        let loaded = match input {
+            MergeInput::Failed(error) => Err(error),
+            MergeInput::Selected { path, suffix, password, .. } => {
+                let left = occurrences.get_mut(&path).unwrap();
+                *left -= 1;
+                let doc = match result {
+                    Some(result) => result.inspect(|(doc, _)| if *left > 0 { loaded_once.insert(path.clone(), doc.clone()); }),
+                    None => {
+                        let kept = if *left == 0 { loaded_once.remove(&path) } else { loaded_once.get(&path).cloned() };
+                        // without a copy the first occurrence failed, and this one fails the same way
+                        kept.map_or_else(|| try_load_pdf_with_password(&path, password.as_deref()), Ok).map(|doc| (doc, 0))
+                    }
+                };
+                doc.and_then(|(doc, bytes)| Ok((select_pages(doc, &path, suffix.as_ref())?, bytes, path)))
+            }
+            input => result.expect("only repeated page selections share a load").map(|(doc, bytes)| (doc, bytes, input.title_path().to_path_buf())),
+        };
+        add(loaded)?;
+    }
 
-    for fname in files {
-        // TODO: should not panic on I/O error as this is common. Handle better for user.
-        doc = Document::load(&fname).expect("failed to open PDF");
-        documents.push(doc);
+    if skip_errors {
+        let total = documents.len() + skipped;
+        if documents.is_empty() && skipped > 0 { return Err(Error::AllInputsFailed { count: total }); }
+        report::summary(format!("merged {} of {} files ({} skipped)", documents.len(), total, skipped));
     }
 
-    merge_documents(documents, &mut document);
+    let mut document = report::timed("transform", || {
+        let mut document = if bookmark_by_file {
+            ops::merge_with_bookmarks(documents, &titles)?
+        } else {
+            ops::merge(documents)?
+        };
+        set_info(&mut document, info);
+        Ok(document)
+    })?;
+
+    if dedupe {
+        let removed = report::timed("dedupe", || ops::dedupe_resources(&mut document));
+        report::summary(format!("removed {} duplicate objects", removed));
+    }
 
-    if compress { document.compress(); }
+    if compress { report::timed("compress", || document.compress()); }
 
     // Save the merged PDF
-    save_pdf(&mut document, outfile);
+    let (bytes, stats) = save_pdf(&mut document, outfile)?;
+    report.bytes_out = bytes;
+    report.stats = stats;
+    report.pages_out = document.get_pages().len();
+    Ok(report)
 }
 
-/// Creates a single PDF containing num copies of the input PDF
+/// Creates a single PDF containing num copies of the input PDF. The copies share everything but
+/// their pages, see `ops::repeat_pages`. A `num` of 0 is an `Error::InvalidSelection`.
 /// 
 /// # Arguments
 /// 
 /// * `infile` - a PathBuf of a single file
 /// * `outfile` - a PathBuf representing the location to save the output file to
 /// * `num` - a u16 integer representing the number of times to duplicate the infile
+/// * `collate` - put whole copies one after the other (1,2,1,2), else each page `num` times in a
+///   row (1,1,2,2)
 /// * `compress` - a boolean flag to compress the outfile before saving
 /// 
-pub fn dupe(infile: PathBuf, outfile: PathBuf, num: u16, compress: bool) {
-    let doc: Document = Document::load(infile).unwrap();
-    let mut documents: Vec<Document> = Vec::new();
-    let mut outdoc = Document::with_version(VERSION);
-
-    for _ in 0..num {
-        documents.push(doc.clone());
+pub fn dupe(infile: PathBuf, outfile: PathBuf, num: u16, collate: bool, compress: bool) -> Result<SaveReport, Error> {
+    if num == 0 {
+        return Err(Error::InvalidSelection("the output needs at least one copy".to_string()));
     }
+    let (mut outdoc, mut report) = try_load_for_update(&infile)?;
 
-    merge_documents(documents, &mut outdoc);
+    report::timed("transform", || ops::repeat_pages(&mut outdoc, num, collate))?;
 
-    if compress { outdoc.compress(); }
+    if compress { report::timed("compress", || outdoc.compress()); }
     
     // Save the merged PDF
-    save_pdf(&mut outdoc, outfile)
-    // call merge but refactor merge to call a helper that operates on Document 
-    // data types, rather than accepting a list of PathBuf
+    let (bytes, stats) = save_pdf(&mut outdoc, outfile)?;
+    report.bytes_out = bytes;
+    report.stats = stats;
+    report.pages_out = outdoc.get_pages().len();
+    Ok(report)
+}
+
+/// Merges two PDFs page by page, e.g. the fronts and backs of a double sided scan: first page of
+/// `first`, first page of `second`, second page of `first` and so on. See `ops::interleave`.
+///
+/// * `first` - a PathBuf of the file whose pages come first in each pair
+/// * `second` - a PathBuf of the other file
+/// * `outfile` - a PathBuf representing the location to save the output file to
+/// * `reverse_second` - take the pages of `second` last to first, for backs scanned in reverse
+/// * `strict` - fail if the files have different page counts, instead of putting the pages left
+///   over at the end with a warning
+/// * `compress` - a boolean flag to compress the outfile before saving
+///
+pub fn interleave(first: PathBuf, second: PathBuf, outfile: PathBuf, reverse_second: bool, strict: bool, compress: bool) -> Result<SaveReport, Error> {
+    let (first_doc, first_report) = try_load_for_update(&first)?;
+    let (second_doc, second_report) = try_load_for_update(&second)?;
+    let mut report = SaveReport {
+        pages_in: first_report.pages_in + second_report.pages_in,
+        bytes_in: first_report.bytes_in + second_report.bytes_in,
+        ..SaveReport::default()
+    };
+
+    if first_report.pages_in != second_report.pages_in {
+        let (longer, extra) = match first_report.pages_in > second_report.pages_in {
+            true => (&first, first_report.pages_in - second_report.pages_in),
+            false => (&second, second_report.pages_in - first_report.pages_in),
+        };
+        let counts = format!("{} has {} pages and {} has {}", first.display(), first_report.pages_in, second.display(), second_report.pages_in);
+        if strict { return Err(Error::InvalidSelection(format!("{}, --strict needs the same number", counts))); }
+        report::warning(format!("{}, the last {} of {} are put at the end", counts, extra, longer.display()));
+    }
+
+    let mut outdoc = report::timed("transform", || ops::interleave(first_doc, second_doc, reverse_second))?;
+
+    if compress { report::timed("compress", || outdoc.compress()); }
+
+    let (bytes, stats) = save_pdf(&mut outdoc, outfile)?;
+    report.bytes_out = bytes;
+    report.stats = stats;
+    report.pages_out = outdoc.get_pages().len();
+    Ok(report)
+}
+
+/// Puts all the pages of one PDF into another at a given page, see `ops::insert`
+///
+/// * `base` - a PathBuf of the file to insert into
+/// * `insert` - a PathBuf of the file whose pages are inserted
+/// * `outfile` - a PathBuf representing the location to save the output file to
+/// * `at` - the page of `base` the inserted pages go before, or the end
+/// * `compress` - a boolean flag to compress the outfile before saving
+///
+pub fn insert(base: PathBuf, insert: PathBuf, outfile: PathBuf, at: Position, compress: bool) -> Result<SaveReport, Error> {
+    let (base_doc, base_report) = try_load_for_update(&base)?;
+    let (insert_doc, insert_report) = try_load_for_update(&insert)?;
+    let mut report = SaveReport {
+        pages_in: base_report.pages_in + insert_report.pages_in,
+        bytes_in: base_report.bytes_in + insert_report.bytes_in,
+        ..SaveReport::default()
+    };
+
+    let mut outdoc = report::timed("transform", || ops::insert(base_doc, insert_doc, at))?;
 
-    // this adds a large memory overhead as we keep many copies of the same file 
-    // rather than reusing a single in-memory copy of the file
-    // this would have at least double the memory usage of a rused copy in the final merge
+    if compress { report::timed("compress", || outdoc.compress()); }
 
+    let (bytes, stats) = save_pdf(&mut outdoc, outfile)?;
+    report.bytes_out = bytes;
+    report.stats = stats;
+    report.pages_out = outdoc.get_pages().len();
+    Ok(report)
 }
 
 /// Deletes the pages listed in --pages, or deletes every --every page in a PDF
 /// 
 /// * `infile` - a PathBuf of a single file
 /// * `outfile` - a PathBuf representing the location to save the output file to
-/// * `pages` - a list of page numbers to delete
-/// * `every` - an integer 
+/// * `selection` - the pages to delete, listed, every nth page or the blank ones
 /// * `negate` - negates/inverts the --page or --every selection, instead keeping only those pages listed
+/// * `strip_structure` - remove the structure tree instead of pruning it to the remaining pages
 /// * `compress` - a boolean flag to compress the outfile before saving
+/// * `strict` - fail if a listed page does not exist, instead of going on with a warning
+/// * `save` - how to write the file, see SaveOptions
 /// 
+#[allow(clippy::too_many_arguments)]
 pub fn delete(infile: PathBuf, 
     outfile: Option<PathBuf>, 
-    pages: Option<Vec<u32>>,
-    every: Option<u32>,
+    selection: PageSelection,
     negate: bool,
-    compress: bool) {
-
-    let mut doc: Document = load_pdf(&infile);
-
-    delete_pages(&mut doc, pages, every, negate);
-
-    if compress { doc.compress() }
-    
-    match outfile {
-        Some(f) => {
-            save_pdf(&mut doc, f);
+    strip_structure: bool,
+    compress: bool,
+    strict: bool,
+    save: &SaveOptions) -> Result<SaveReport, Error> {
+
+    let (mut doc, before) = try_load_for_update(&infile)?;
+
+    let mut selection = required_selection(selection)?;
+    check_listed_pages(&doc, &selection, strict)?;
+    if let PageSelection::Blank(_) = selection {
+        // found once, the content of every page is parsed to tell
+        let blank = report::timed("find blank pages", || selection.page_numbers(&doc))?;
+        let count = doc.get_pages().len();
+        if !negate && blank.len() == count {
+            return Err(Error::InvalidSelection("every page is blank, deleting them would leave none".to_string()));
         }
-        None => {
-            save_pdf(&mut doc, infile);
+        if negate && blank.is_empty() {
+            return Err(Error::InvalidSelection("no page is blank, keeping only the blank ones would leave none".to_string()));
         }
+        report::summary(format!("{} of {} pages are blank", blank.len(), count));
+        selection = PageSelection::Pages(blank);
     }
+    let selection = if negate { selection.inverted(&doc)? } else { selection };
+    ops::delete_pages(&mut doc, &selection)?;
+    fix_structure_tree(&mut doc, strip_structure);
+
+    if compress { report::timed("compress", || doc.compress()) }
+    
+    try_save_changes(&mut doc, &infile, outfile, save, before)
 }
 
 /// Extracts the pages listed in --pages, or every --every page in a PDF
 /// 
 /// * `infile` - a PathBuf of a single file
 /// * `outfile` - a PathBuf representing the location to save the output file to
-/// * `pages` - a list of page numbers to delete
-/// * `every` - an integer 
-/// * `negate` - negates/inverts the --page or --every selection, instead keeping only those pages listed
+/// * `selection` - the pages to extract, listed or every nth page
+/// * `negate` - extract every page but the selected ones
+/// * `retarget_links` - point links and bookmarks to pages that were not extracted at the nearest extracted page instead of removing them
+/// * `drop_bookmarks` - remove the outline instead of keeping the items that lead to extracted pages
+/// * `strip_structure` - remove the structure tree instead of pruning it to the extracted pages
+/// * `strip_metadata` - leave out the document information and XMP metadata of the input
+/// * `strict` - fail if a listed page does not exist, instead of going on with a warning
+/// * `save` - how to write the file, see SaveOptions
 /// 
-pub fn extract(infile: PathBuf, outfile: PathBuf, pages: Option<Vec<u32>>, every: Option<u32>) {
-    let mut doc = Document::load(&infile).expect("failed to open PDF");
+#[allow(clippy::too_many_arguments)]
+pub fn extract(infile: PathBuf,
+               outfile: PathBuf,
+               selection: PageSelection,
+               negate: bool,
+               retarget_links: bool,
+               drop_bookmarks: bool,
+               strip_structure: bool,
+               strip_metadata: bool,
+               strict: bool,
+               save: &SaveOptions) -> Result<SaveReport, Error> {
+    let (mut doc, before) = try_load_for_update(&infile)?;
+    if strip_metadata { remove_document_metadata(&mut doc); }
+
+    let selection = required_selection(selection)?;
+    check_listed_pages(&doc, &selection, strict)?;
+    let selection = if negate { selection.inverted(&doc)? } else { selection };
+    let dropped = if drop_bookmarks { bookmarks::remove_outline(&mut doc) } else { 0 };
+    let mut report = ops::extract_pages(&mut doc, &selection, retarget_links)?;
+    report.bookmarks_dropped += dropped;
+    // an outline none of whose items lead to an extracted page is left out altogether
+    if bookmarks::outline_items(&doc).is_empty() { bookmarks::remove_outline(&mut doc); }
+    fix_structure_tree(&mut doc, strip_structure);
+    report::summary(format!("links: {} dropped, {} retargeted; bookmarks: {} dropped, {} retargeted",
+             report.links_dropped, report.links_retargeted, report.bookmarks_dropped, report.bookmarks_retargeted));
+
+    try_save_changes(&mut doc, &infile, Some(outfile), save, before)
+}
+
+
+
+/// How split names the files it writes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitNaming {
+    /// After an outfile with the page number appended, see `split_path`
+    Outfile(PathBuf),
+    /// From a pattern like `{stem}-{page:03}.pdf`, see `pattern_name`, in `dir`
+    Pattern { dir: PathBuf, pattern: String },
+}
+
+/// A zip archive split writes the pages into instead of loose files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitZip {
+    /// The archive to write
+    pub path: PathBuf,
+    /// Whether the entries are stored or deflated
+    pub compression: ZipCompression,
+    /// Add a manifest.json entry listing the page of each entry
+    pub manifest: bool,
+}
+
+/// Writes each page of a PDF to a file of its own, named after the outfile with the page number
+/// appended: out.pdf becomes out-001.pdf, out-002.pdf and so on. The numbers are padded to the
+/// same width so that the files sort in page order. A pattern names them instead, and fails
+/// before anything is written if it gives two pages the same name. With a selection only the
+/// selected pages are written, still named after their page number in the input. With an archive
+/// each page goes straight into an entry of it, named like the file it would have been written to
+/// relative to the directory, and nothing else is written.
+/// 
+/// * `infile` - a PathBuf of the file to split
+/// * `naming` - where the written files go and what they are called
+/// * `selection` - the pages to write, listed or every nth page, or all of them
+/// * `zip` - write the pages into this archive instead (Optional)
+/// * `create_dir` - create the directory the files (or the archive) go in if it is missing, instead of failing
+/// * `strip_metadata` - leave out the document information and XMP metadata of the input
+/// * `compress` - a boolean flag to compress each file before saving
+/// * `strict` - fail if a listed page does not exist, instead of going on with a warning
+/// 
+#[allow(clippy::too_many_arguments)]
+pub fn split(infile: PathBuf,
+             naming: &SplitNaming,
+             selection: PageSelection,
+             zip: Option<&SplitZip>,
+             create_dir: bool,
+             strip_metadata: bool,
+             compress: bool,
+             strict: bool) -> Result<SaveReport, Error> {
+    let dir = match naming {
+        SplitNaming::Outfile(outfile) => outfile.parent().unwrap_or(Path::new("")),
+        SplitNaming::Pattern { dir, .. } => dir,
+    };
+    match zip {
+        Some(zip) => prepare_output_dir(zip.path.parent().unwrap_or(Path::new("")), create_dir)?,
+        None => prepare_output_dir(dir, create_dir)?,
+    }
+    let (mut doc, mut report) = try_load_for_update(&infile)?;
+    if strip_metadata { remove_document_metadata(&mut doc); }
+    let count = doc.get_pages().len() as u32;
+
+    check_listed_pages(&doc, &selection, strict)?;
+    let selected = selection.page_numbers(&doc)?;
+    if selected.is_empty() {
+        return Err(Error::InvalidSelection("no page is selected, there is nothing to write".to_string()));
+    }
+
+    let names: Vec<PathBuf> = match naming {
+        SplitNaming::Outfile(outfile) => selected.iter().map(|page| split_path(outfile, *page, count)).collect(),
+        SplitNaming::Pattern { dir, pattern, .. } => {
+            let stem = infile.file_stem().unwrap_or(infile.as_os_str());
+            let mut names: Vec<PathBuf> = Vec::new();
+            for page in &selected {
+                let name = dir.join(pattern_name(pattern, stem, *page, count).map_err(Error::OutputPattern)?);
+                if let Some(earlier) = names.iter().position(|taken| *taken == name) {
+                    return Err(Error::OutputPattern(format!("pages {} and {} would both be written to {}", selected[earlier], page, name.display())));
+                }
+                names.push(name);
+            }
+            names
+        }
+    };
+
+    let mut archive = match zip {
+        Some(zip) => {
+            let file = fs::File::create(&zip.path).map_err(|source| Error::SaveFailed { path: zip.path.clone(), source })?;
+            Some(ZipWriter::new(std::io::BufWriter::new(file), zip.compression))
+        }
+        None => None,
+    };
+    let mut manifest = Vec::new();
+
+    let pages = doc.get_pages();
+    for (page, name) in selected.iter().zip(names) {
+        let (page, page_id) = (*page, pages[page]);
+        let mut page_doc = report::timed(format!("transform page {}", page), || {
+            let mut page_doc = doc.clone();
+            let kept = HashSet::from([page_id]);
+            bookmarks::remap_to_pages(&mut page_doc, &kept, false);
+            keep_only_page(&mut page_doc, page_id);
+            fix_structure_tree(&mut page_doc, false);
+            page_doc
+        });
+        if compress { report::timed("compress", || page_doc.compress()); }
+
+        match (&mut archive, zip) {
+            (Some(archive), Some(zip)) => {
+                let entry = entry_name(name.strip_prefix(dir).unwrap_or(&name));
+                let stats = prepare_for_save(&mut page_doc)?;
+                let mut data = Vec::new();
+                let saved = report::timed("save", || page_doc.save_to(&mut data))
+                    .and_then(|_| archive.add(&entry, &data));
+                saved.map_err(|source| Error::SaveFailed { path: zip.path.clone(), source })?;
+                report.stats += stats;
+                manifest.push(serde_json::json!({ "name": entry, "page": page }));
+            }
+            _ => {
+                let (bytes, stats) = save_pdf(&mut page_doc, name)?;
+                report.bytes_out += bytes;
+                report.stats += stats;
+            }
+        }
+        report.pages_out += page_doc.get_pages().len();
+    }
+
+    if let (Some(mut archive), Some(zip)) = (archive, zip) {
+        let failed = |source| Error::SaveFailed { path: zip.path.clone(), source };
+        if zip.manifest {
+            let json = serde_json::to_vec_pretty(&manifest).expect("failed to serialize the manifest");
+            archive.add("manifest.json", &json).map_err(failed)?;
+        }
+        archive.finish().map_err(failed)?;
+        report.bytes_out = file_size(&zip.path);
+        report::summary(format!("wrote {} pages to {}", selected.len(), zip.path.display()));
+        return Ok(report);
+    }
+
+    report::summary(format!("wrote {} files", selected.len()));
+    Ok(report)
+}
+
+// The name of a zip entry for a path relative to the output directory, / between directories
+fn entry_name(path: &Path) -> String {
+    path.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+/// Writes the odd numbered pages of a PDF to one file and the even numbered ones to another, each
+/// in page order, to rescan the back of double sided sheets. Either file may be left out. A one
+/// page document has no even pages, its even file is not written.
+///
+/// * `infile` - a PathBuf of the file to split
+/// * `odd` - where the odd pages go, pages 1, 3, 5 and so on
+/// * `even` - where the even pages go
+/// * `create_dir` - create the directories the files go in if they are missing, instead of failing
+/// * `strip_metadata` - leave out the document information and XMP metadata of the input
+/// * `compress` - a boolean flag to compress each file before saving
+///
+pub fn split_odd_even(infile: PathBuf, odd: Option<PathBuf>, even: Option<PathBuf>, create_dir: bool, strip_metadata: bool, compress: bool) -> Result<SaveReport, Error> {
+    if odd.is_none() && even.is_none() {
+        return Err(Error::InvalidSelection("either --odd or --even is needed".to_string()));
+    }
+    if let Some(outfile) = odd.iter().chain(&even).find(|outfile| same_file(outfile, &infile)) {
+        return Err(Error::OutputIsInput(outfile.clone()));
+    }
+    if let (Some(odd), Some(even)) = (&odd, &even) {
+        if odd == even || same_file(odd, even) {
+            return Err(Error::OutputPattern(format!("the odd and even pages would both be written to {}", odd.display())));
+        }
+    }
+    for outfile in odd.iter().chain(&even) {
+        prepare_output_dir(outfile.parent().unwrap_or(Path::new("")), create_dir)?;
+    }
+    let (mut doc, mut report) = try_load_for_update(&infile)?;
+    if strip_metadata { remove_document_metadata(&mut doc); }
+
+    let mut written = 0;
+    for (outfile, even_pages) in [(odd, false), (even, true)] {
+        let Some(outfile) = outfile else { continue };
+        let selection = if even_pages { PageSelection::Every(2) } else { PageSelection::Odd };
+        if selection.page_numbers(&doc)?.is_empty() {
+            report::warning(format!("did not write {}, the input has no even pages", outfile.display()));
+            continue;
+        }
+
+        let label = if even_pages { "even pages" } else { "odd pages" };
+        let mut part = report::timed(format!("transform {}", label), || {
+            let mut part = doc.clone();
+            ops::extract_pages(&mut part, &selection, false)?;
+            fix_structure_tree(&mut part, false);
+            Ok::<Document, Error>(part)
+        })?;
+        if compress { report::timed("compress", || part.compress()); }
+
+        let (bytes, stats) = save_pdf(&mut part, outfile)?;
+        report.bytes_out += bytes;
+        report.stats += stats;
+        report.pages_out += part.get_pages().len();
+        written += 1;
+    }
+
+    report::summary(format!("wrote {} files", written));
+    Ok(report)
+}
+
+/// The name split gives the file for one page: the outfile with the zero padded page number
+/// appended to its stem. A stem or extension that is not valid UTF-8 is kept as it is.
+pub fn split_path(outfile: &Path, page: u32, count: u32) -> PathBuf {
+    let width = count.to_string().len().max(3);
+    let mut name = outfile.file_stem().unwrap_or(outfile.as_os_str()).to_os_string();
+    name.push(format!("-{:0width$}.", page, width = width));
+    name.push(outfile.extension().unwrap_or(OsStr::new("pdf")));
+    outfile.with_file_name(name)
+}
+
+/// Writes page ranges of a PDF to files of their own, in one pass: `1-4:cover.pdf,5-:body.pdf`.
+/// The ranges may leave pages out but not overlap. Nothing is written if a range is malformed
+/// or lies past the end of the document.
+///
+/// * `infile` - a PathBuf of the file to split
+/// * `dir` - where the files go
+/// * `spec` - the ranges and their files, see `parse_split_ranges`
+/// * `create_dir` - create `dir` if it is missing, instead of failing
+/// * `strip_metadata` - leave out the document information and XMP metadata of the input
+/// * `compress` - a boolean flag to compress each file before saving
+///
+pub fn split_ranges(infile: PathBuf, dir: &Path, spec: &str, create_dir: bool, strip_metadata: bool, compress: bool) -> Result<SaveReport, Error> {
+    let ranges = parse_split_ranges(spec).map_err(Error::InvalidSelection)?;
+    prepare_output_dir(dir, create_dir)?;
+    let (mut doc, mut report) = try_load_for_update(&infile)?;
+    if strip_metadata { remove_document_metadata(&mut doc); }
+    let count = doc.get_pages().len() as u32;
+
+    let mut spans: Vec<(&SplitRange, u32, u32)> = Vec::new();
+    for range in &ranges {
+        let last = range.last.unwrap_or(count);
+        if range.first > count || last > count {
+            return Err(Error::InvalidSelection(format!("`{}` goes past the last page, {}", range.token, count)));
+        }
+        if let Some((earlier, _, _)) = spans.iter().find(|(_, first, end)| range.first <= *end && *first <= last) {
+            return Err(Error::InvalidSelection(format!("`{}` overlaps `{}`", range.token, earlier.token)));
+        }
+        spans.push((range, range.first, last));
+    }
+
+    for (range, first, last) in spans {
+        let mut part = report::timed(format!("transform {}", range.token), || {
+            let mut part = doc.clone();
+            ops::extract_pages(&mut part, &PageSelection::Pages((first..=last).collect()), false)?;
+            fix_structure_tree(&mut part, false);
+            Ok::<Document, Error>(part)
+        })?;
+        if compress { report::timed("compress", || part.compress()); }
+
+        let (bytes, stats) = save_pdf(&mut part, dir.join(&range.name))?;
+        report.bytes_out += bytes;
+        report.stats += stats;
+        report.pages_out += part.get_pages().len();
+    }
+
+    report::summary(format!("wrote {} files", ranges.len()));
+    Ok(report)
+}
+
+/// A page range and the file split writes it to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitRange {
+    pub first: u32,
+    /// None for a range that runs to the last page
+    pub last: Option<u32>,
+    pub name: PathBuf,
+    /// What the range was parsed from, for errors
+    pub token: String,
+}
+
+/// Parses the ranges split writes to separate files: a page, an inclusive range or one that is
+/// open at the end, then `:` and the file name, separated by commas. Every range needs a name of
+/// its own.
+///
+/// * `spec` - e.g. `1-4:cover.pdf,5-30:body.pdf,31-:appendix.pdf`
+///
+pub fn parse_split_ranges(spec: &str) -> Result<Vec<SplitRange>, String> {
+    let mut ranges: Vec<SplitRange> = Vec::new();
+    for token in spec.split(',').map(str::trim).filter(|token| !token.is_empty()) {
+        let (span, name) = token.split_once(':')
+            .filter(|(_, name)| !name.is_empty())
+            .ok_or_else(|| format!("`{}` needs a file name, like 1-4:cover.pdf", token))?;
+        let (first, last) = page_span(span, token)?;
+        if let Some(earlier) = ranges.iter().find(|range| range.name == Path::new(name)) {
+            return Err(format!("`{}` writes to {} as well as `{}`", token, name, earlier.token));
+        }
+        ranges.push(SplitRange { first, last, name: PathBuf::from(name), token: token.to_string() });
+    }
+    if ranges.is_empty() { return Err("no ranges given".to_string()); }
+    Ok(ranges)
+}
+
+/// Parses the rotations rotate --spec gives single pages and ranges: the pages, then `:` and
+/// the degrees clockwise, separated by commas. A page may be listed more than once if it is
+/// turned the same way each time.
+///
+/// * `spec` - e.g. `3:90,4-6:270,12:-90`
+///
+/// Returns each page with its rotation, by page number.
+pub fn parse_rotation_spec(spec: &str) -> Result<Vec<(u32, i32)>, String> {
+    let mut rotations: BTreeMap<u32, (i32, &str)> = BTreeMap::new();
+    for token in spec.split(',').map(str::trim).filter(|token| !token.is_empty()) {
+        let (span, degrees) = token.split_once(':')
+            .filter(|(_, degrees)| !degrees.is_empty())
+            .ok_or_else(|| format!("`{}` needs the degrees to rotate by, like 3:90", token))?;
+        let (first, last) = match page_span(span, token)? {
+            (_, None) => return Err(format!("`{}` needs the last page of the range", token)),
+            (first, Some(last)) => (first, last),
+        };
+        let degrees: i32 = degrees.parse()
+            .ok().filter(|degrees| degrees % 90 == 0)
+            .ok_or_else(|| format!("`{}` does not rotate by a multiple of 90", token))?;
+
+        for page in first..=last {
+            match rotations.get(&page) {
+                Some((earlier, earlier_token)) if ops::normalize_rotation(i64::from(*earlier)) != ops::normalize_rotation(i64::from(degrees)) => {
+                    return Err(format!("`{}` and `{}` rotate page {} differently", earlier_token, token, page));
+                }
+                Some(_) => {}
+                None => { rotations.insert(page, (degrees, token)); }
+            }
+        }
+    }
+    if rotations.is_empty() { return Err("no rotations given".to_string()); }
+    Ok(rotations.into_iter().map(|(page, (degrees, _))| (page, degrees)).collect())
+}
 
-    extract_pages(&mut doc, pages, every);
+/// Writes each chapter of a PDF to a file of its own, a chapter being the pages from a top level
+/// bookmark up to the next one. Files are named after the bookmark titles, see
+/// `bookmark_file_names`. Pages before the first bookmark are left out, with a warning.
+///
+/// * `infile` - a PathBuf of the file to split
+/// * `dir` - where the files go
+/// * `create_dir` - create `dir` if it is missing, instead of failing
+/// * `strip_metadata` - leave out the document information and XMP metadata of the input
+/// * `compress` - a boolean flag to compress each file before saving
+///
+pub fn split_bookmarks(infile: PathBuf, dir: &Path, create_dir: bool, strip_metadata: bool, compress: bool) -> Result<SaveReport, Error> {
+    prepare_output_dir(dir, create_dir)?;
+    let (mut doc, mut report) = try_load_for_update(&infile)?;
+    if strip_metadata { remove_document_metadata(&mut doc); }
+    let count = doc.get_pages().len() as u32;
+
+    // sorted by page, bookmarks on the same page keep their outline order
+    let mut chapters = bookmarks::top_level_bookmarks(&doc);
+    if chapters.is_empty() { return Err(Error::NoBookmarks(infile)); }
+    chapters.sort_by_key(|(_, page)| *page);
+    let mut starts: Vec<(String, u32)> = Vec::new();
+    for (title, page) in chapters {
+        match starts.last() {
+            Some((earlier, start)) if *start == page => {
+                report::warning(format!("skipped the bookmark \"{}\", it starts on the same page as \"{}\"", title, earlier));
+            }
+            _ => starts.push((title, page)),
+        }
+    }
+    if starts[0].1 > 1 {
+        report::warning(format!("left out pages 1-{}, they come before the first bookmark", starts[0].1 - 1));
+    }
 
-    save_pdf(&mut doc, outfile);
+    let titles: Vec<String> = starts.iter().map(|(title, _)| title.clone()).collect();
+    let names = bookmark_file_names(&titles);
+    for (i, ((title, first), name)) in starts.iter().zip(&names).enumerate() {
+        let last = starts.get(i + 1).map_or(count, |(_, next)| next - 1);
+        let mut part = report::timed(format!("transform \"{}\"", title), || {
+            let mut part = doc.clone();
+            ops::extract_pages(&mut part, &PageSelection::Pages((*first..=last).collect()), false)?;
+            fix_structure_tree(&mut part, false);
+            Ok::<Document, Error>(part)
+        })?;
+        if compress { report::timed("compress", || part.compress()); }
+
+        let (bytes, stats) = save_pdf(&mut part, dir.join(name))?;
+        report.bytes_out += bytes;
+        report.stats += stats;
+        report.pages_out += part.get_pages().len();
+    }
+
+    report::summary(format!("wrote {} files", names.len()));
+    Ok(report)
 }
 
+/// File names for bookmark titles, in the same order. Characters that cannot be in a file name on
+/// some system become `_`, an empty title becomes `untitled`, and `.pdf` is added. Names that
+/// would clash, ignoring case, get `-2`, `-3` and so on in the order the titles are given.
+pub fn bookmark_file_names(titles: &[String]) -> Vec<String> {
+    let mut taken = HashSet::new();
+    titles.iter()
+        .map(|title| {
+            let cleaned: String = title.chars()
+                .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+                .take(120)
+                .collect();
+            // no hidden files, and Windows drops trailing dots and spaces
+            let stem = match cleaned.trim().trim_matches('.') {
+                "" => "untitled",
+                stem => stem,
+            };
+            let mut name = format!("{}.pdf", stem);
+            let mut copy = 2;
+            while !taken.insert(name.to_lowercase()) {
+                name = format!("{}-{}.pdf", stem, copy);
+                copy += 1;
+            }
+            name
+        })
+        .collect()
+}
 
+/// The name a pattern gives the file of one page. `{stem}` is the input's file name without its
+/// extension, `{page}` the page number and `{total}` the number of pages; a width zero pads a
+/// number, `{page:03}` gives 007. `{{` and `}}` are literal braces.
+///
+/// * `pattern` - e.g. `{stem}-{page:03}.pdf`
+/// * `stem` - the input's file name without its extension, kept as it is if not valid UTF-8
+/// * `page` - the page number, from 1
+/// * `total` - the number of pages
+///
+pub fn pattern_name(pattern: &str, stem: &OsStr, page: u32, total: u32) -> Result<OsString, String> {
+    let mut name = OsString::new();
+    let mut rest = pattern;
+    while let Some(at) = rest.find(['{', '}']) {
+        name.push(&rest[..at]);
+        let brace = &rest[at..];
+        if brace.starts_with("{{") || brace.starts_with("}}") {
+            name.push(&brace[..1]);
+            rest = &brace[2..];
+            continue;
+        }
+        let end = brace.find('}').filter(|_| brace.starts_with('{')).ok_or_else(|| format!("`{}` has an unmatched brace", pattern))?;
+        let (field, width) = match brace[1..end].split_once(':') {
+            Some((field, width)) => (field, Some(width)),
+            None => (&brace[1..end], None),
+        };
+        let width = match width {
+            Some(width) => width.parse::<usize>().map_err(|_| format!("`{}` in `{}` is not a width", width, pattern))?,
+            None => 0,
+        };
+        match field {
+            "stem" if width == 0 => name.push(stem),
+            "page" => name.push(format!("{:0width$}", page, width = width)),
+            "total" => name.push(format!("{:0width$}", total, width = width)),
+            _ => return Err(format!("`{}` is not one of {{stem}}, {{page}} or {{total}}", &brace[..=end])),
+        }
+        rest = &brace[end + 1..];
+    }
+    name.push(rest);
+    Ok(name)
+}
 
 /// Reverses the page order of a document either inplace or in a new file
 /// 
 /// * `infile` - a PathBuf of the file to reverse
 /// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `save` - how to write the file, see SaveOptions
 /// 
-pub fn reverse(infile: PathBuf, outfile: Option<PathBuf>) {
-    let mut doc = load_pdf(&infile);
+pub fn reverse(infile: PathBuf, outfile: Option<PathBuf>, save: &SaveOptions) -> Result<SaveReport, Error> {
+    let (mut doc, before) = try_load_for_update(&infile)?;
 
-    match outfile {
-        Some(of) => {
-            reverse_doc(&mut doc.clone(), of);
-        }
-        None => {
-            reverse_doc(&mut doc, infile);
-        }
-    }
+    ops::reverse(&mut doc);
+
+    try_save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Moves a run of pages to another place, either inplace or in a new file. Links and bookmarks
+/// keep pointing at the pages they did.
+///
+/// * `infile` - a PathBuf of the file to change
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `range` - the pages to move, see `parse_page_run`
+/// * `to` - where they go, see `ops::move_pages`
+/// * `save` - how to write the file, see SaveOptions
+///
+pub fn move_pages(infile: PathBuf, outfile: Option<PathBuf>, range: PageRange, to: Position, save: &SaveOptions) -> Result<SaveReport, Error> {
+    let (mut doc, before) = try_load_for_update(&infile)?;
+
+    ops::move_pages(&mut doc, range, to)?;
+
+    try_save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Adds blank pages, e.g. separators for a print shop, either inplace or in a new file. See
+/// `ops::insert_blank_pages`.
+///
+/// * `infile` - a PathBuf of the file to add pages to
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `after` - the pages to put blanks after
+/// * `before` - the pages to put blanks before
+/// * `count` - how many blank pages go in each place
+/// * `size` - the size of the blank pages, see `parse_page_size`
+/// * `save` - how to write the file, see SaveOptions
+///
+#[allow(clippy::too_many_arguments)]
+pub fn insert_blank_pages(infile: PathBuf,
+                          outfile: Option<PathBuf>,
+                          after: &[u32],
+                          before: &[u32],
+                          count: u16,
+                          size: PageSize,
+                          save: &SaveOptions) -> Result<SaveReport, Error> {
+    let (mut doc, before_report) = try_load_for_update(&infile)?;
+
+    ops::insert_blank_pages(&mut doc, after, before, count, size)?;
+
+    try_save_changes(&mut doc, &infile, outfile, save, before_report)
 }
 
 /// Rotates all pages by the input degree amount. 
 /// 
 /// * `infile` - a PathBuf of the file to reverse
 /// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `selection` - the pages to rotate, e.g. from --pages, --every or --odd
+/// * `relative` - add `degrees` to the rotation the pages already have instead of replacing it
+/// * `strict` - fail if a listed page does not exist, instead of rotating the others with a warning
+/// * `save` - how to write the file, see SaveOptions
 /// 
 pub fn rotate(infile: PathBuf, 
               outfile: Option<PathBuf>, 
               degrees: i32, 
-              pages: Option<Vec<u32>>, 
-              every: Option<u32>) {
-    let mut doc = load_pdf(&infile);
+              selection: PageSelection,
+              relative: bool,
+              strict: bool,
+              save: &SaveOptions) -> Result<SaveReport, Error> {
+    let (mut doc, before) = try_load_for_update(&infile)?;
+
+    let missing = if relative {
+        ops::rotate_pages_by(&mut doc, degrees, &selection)?
+    } else {
+        ops::rotate_pages(&mut doc, degrees, &selection)?
+    };
+    missing_pages(&doc, &missing, strict)?;
 
-    match outfile {
-        Some(of) => {
-            rotate_doc(&mut doc.clone(), of, degrees, pages, every);
-        }
-        None => {
-            rotate_doc(&mut doc, infile, degrees, pages, every);
+    try_save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Rotates pages by different amounts in one pass, e.g. `3:90,7:270,12:180`
+///
+/// * `infile` - a PathBuf of the file to rotate
+/// * `outfile` - a PathBuf representing the location to save the output file to (Optional)
+/// * `spec` - the pages and their rotations, see `parse_rotation_spec`
+/// * `relative` - add to the rotation the pages already have instead of replacing it
+/// * `strict` - fail if a listed page does not exist, instead of rotating the others with a warning
+/// * `save` - how to write the file, see SaveOptions
+///
+pub fn rotate_spec(infile: PathBuf, outfile: Option<PathBuf>, spec: &str, relative: bool, strict: bool, save: &SaveOptions) -> Result<SaveReport, Error> {
+    let rotations = parse_rotation_spec(spec).map_err(Error::InvalidSelection)?;
+    let (mut doc, before) = try_load_for_update(&infile)?;
+
+    let missing = ops::rotate_each_page(&mut doc, &rotations, relative);
+    missing_pages(&doc, &missing, strict)?;
+
+    try_save_changes(&mut doc, &infile, outfile, save, before)
+}
+
+/// Makes loading fall back to recovery::recover when a file's cross-reference table is damaged,
+/// instead of failing. Off by default.
+pub fn set_lenient(lenient: bool) {
+    LENIENT.store(lenient, Ordering::Relaxed);
+}
+
+/// The password encrypted inputs are opened with, the user or the owner password. Without one only
+/// documents that open with an empty user password, those with just restrictions, can be loaded.
+/// Off by default.
+pub fn set_password(password: Option<String>) {
+    *PASSWORD.lock().unwrap() = password;
+}
+
+/// Resolves where a command writes a file it was given a name for. An absolute path is used as
+/// is, a relative one goes in the command's own output directory if it has one, else the global
+/// --output-dir, else the current directory.
+///
+/// * `outfile` - the name given on the command line
+/// * `command_dir` - the command's own output directory option, if any
+/// * `global_dir` - the global --output-dir, if given
+///
+pub fn resolve_output_path(outfile: &Path, command_dir: Option<&Path>, global_dir: Option<&Path>) -> PathBuf {
+    if outfile.is_absolute() { return outfile.to_path_buf(); }
+
+    match command_dir.or(global_dir) {
+        Some(dir) => dir.join(outfile),
+        None => outfile.to_path_buf(),
+    }
+}
+
+/// Resolves an output path like resolve_output_path, and creates the output directory if it is
+/// missing. Panics if it can not be created.
+pub fn output_path(outfile: &Path, command_dir: Option<&Path>, global_dir: Option<&Path>) -> PathBuf {
+    let path = resolve_output_path(outfile, command_dir, global_dir);
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        if let Err(error) = fs::create_dir_all(parent) {
+            panic!("Failed to create {}: {}", parent.display(), error);
         }
     }
+    path
 }
 
+/// The directory a command writes several files to: its own output directory option if given,
+/// else the global --output-dir, else the current directory
+pub fn output_dir(command_dir: Option<&Path>, global_dir: Option<&Path>) -> PathBuf {
+    command_dir.or(global_dir).unwrap_or(Path::new(".")).to_path_buf()
+}
 
-// ------- Helpers -------
+/// Reads a page selection from a file: pages and ranges as --pages takes them, see
+/// `parse_page_ranges`, over any number of lines, with anything after a # ignored. The ranges are
+/// returned in the order they are listed, repeats included. Fails if the file cannot be read,
+/// holds something that is not a page or range, or selects no pages.
+/// 
+/// * `filepath` - a PathBuf of the file to read
+/// 
+pub fn read_pages_file(filepath: &PathBuf) -> Result<Vec<PageRange>, Error> {
+    let text = fs::read_to_string(filepath)
+        .map_err(|error| Error::LoadFailed { path: filepath.clone(), source: LoadError::Io(error) })?;
+
+    let mut ranges = Vec::new();
+    for line in text.lines() {
+        let selection = line.split('#').next().unwrap_or("");
+        let line_ranges = parse_page_ranges(selection)
+            .map_err(|error| Error::InvalidSelection(format!("{}: {}", filepath.display(), error)))?;
+        ranges.extend(line_ranges);
+    }
 
-fn load_pdf(filepath: &PathBuf) -> Document {
-    let doc = Document::load(filepath);
-    let doc = match doc {
-        Ok(d) => d,
-        Err(error) => panic!("Failed to load document: \n {}", error)
-    };
+    if ranges.is_empty() {
+        return Err(Error::InvalidSelection(format!("{} does not list any pages", filepath.display())));
+    }
+    Ok(ranges)
+}
 
-    doc    
+/// The pages of an input picked by a pdftk style suffix, `a.pdf:1-3`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageSuffix {
+    /// Pages and ranges as `parse_page_spec` takes them, in the order they are to be merged in,
+    /// repeats included
+    Pages(Vec<u32>),
+    Even,
+    Odd,
 }
 
-fn save_pdf(doc: &mut Document, filepath: PathBuf) {
-    doc.prune_objects();
-    doc.adjust_zero_pages();
-    doc.build_outline();
-    doc.delete_zero_length_streams();
+/// Splits a pdftk style page selection off an input: `a.pdf:1-3`, `a.pdf:7,2` or `a.pdf:even`.
+/// Returns None when there is no selection, which includes paths that exist as they are and
+/// paths whose last `:` is followed by something other than a selection, like `C:\a.pdf`.
+pub fn split_page_suffix(given: &Path) -> Option<(PathBuf, PageSuffix)> {
+    if given.exists() { return None; }
+    let (path, suffix) = given.to_str()?.rsplit_once(':')?;
+    if path.is_empty() { return None; }
+
+    let suffix = match suffix {
+        "even" => PageSuffix::Even,
+        "odd" => PageSuffix::Odd,
+        spec => {
+            // closed ranges only, the suffix is read before the file is and the last page is not known yet
+            let ranges = parse_page_ranges(spec).ok()?;
+            let pages: Vec<u32> = ranges.iter()
+                .map(|range| range.last.map(|last| range.first..=last))
+                .collect::<Option<Vec<_>>>()?
+                .into_iter()
+                .flatten()
+                .collect();
+            PageSuffix::Pages(Some(pages).filter(|pages| !pages.is_empty())?)
+        }
+    };
+    Some((PathBuf::from(path), suffix))
+}
 
-    if doc.get_pages().len() == 0 { panic!("Resulting document would have no pages."); }
+/// Splits the password off an input given as `a.pdf::secret`, or `a.pdf::secret:1-3` once the page
+/// selection is split off. The password is everything after the first `::`, so it may hold colons
+/// itself: `a.pdf::se:cret`. Only done when the path before the `::` is a file and the whole is
+/// not. A password that ends in `:` and something that reads as a page selection needs one after
+/// it.
+pub fn split_password(given: &Path) -> Option<(PathBuf, String)> {
+    if given.exists() { return None; }
+    let (path, password) = given.to_str()?.split_once("::")?;
+    Path::new(path).is_file().then(|| (PathBuf::from(path), password.to_string()))
+}
 
-    let result = doc.save(filepath);
-    match result {
-        Ok(_) => {}// do nothing
-        Err(error) => {panic!("Failed to write out file: {}", error)}
+/// An input listed in a merge manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub path: PathBuf,
+    /// The pages to take, in the order listed, or None for all of them
+    pub pages: Option<Vec<u32>>,
+    /// The manifest the entry is from and its line there, starting at 1
+    pub manifest: PathBuf,
+    pub line: usize,
+}
+
+/// Reads a merge manifest: one PDF per line, optionally followed by the pages to take from it as
+/// `parse_page_spec` takes them (`body.pdf 1-12`, `appendix.pdf 3,5,7`). A path with spaces in it
+/// goes in double quotes. Relative paths are relative to the manifest's directory. Blank lines
+/// and lines starting with # are ignored.
+///
+/// * `filepath` - the manifest to read
+///
+pub fn read_manifest(filepath: &Path) -> Result<Vec<ManifestEntry>, Error> {
+    let text = fs::read_to_string(filepath).map_err(|source| Error::LoadFailed { path: filepath.to_path_buf(), source: LoadError::Io(source) })?;
+    let base = filepath.parent().unwrap_or(Path::new(""));
+
+    let mut entries = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let failed = |reason: String| Error::Manifest { path: filepath.to_path_buf(), line: index + 1, reason };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+
+        let (path, selection) = match line.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').ok_or_else(|| failed("the closing quote is missing".to_string()))?,
+            None => line.split_once(char::is_whitespace).unwrap_or((line, "")),
+        };
+        let pages = match selection.trim() {
+            "" => None,
+            selection => Some(parse_page_spec(selection).map_err(failed)?),
+        };
+        entries.push(ManifestEntry { path: base.join(path), pages, manifest: filepath.to_path_buf(), line: index + 1 });
     }
+    Ok(entries)
 }
 
-fn delete_pages(doc: &mut Document, pages: Option<Vec<u32>>, every: Option<u32>, negate: bool) {
-    match pages {
-        Some(p) => {
-            let page_numbers: &[u32] = &make_pages_page_numbers(p, doc, negate);
-            doc.delete_pages(page_numbers); // silently fails for pages outside the actual page range    
+/// Parses a page selection: pages and inclusive ranges like `3-9`, separated by commas or
+/// spaces. Pages are listed once each, in the order they are first selected.
+/// 
+/// * `spec` - the selection, e.g. `1-5,8,10-12`
+/// 
+pub fn parse_page_spec(spec: &str) -> Result<Vec<u32>, String> {
+    let mut pages = Vec::new();
+    for token in spec.split(|c: char| c == ',' || c.is_whitespace()).filter(|token| !token.is_empty()) {
+        let (first, last) = match token.split_once('-') {
+            Some((first, last)) => (page_number(first, token)?, page_number(last, token)?),
+            None => { let page = page_number(token, token)?; (page, page) }
+        };
+        if first > last {
+            return Err(format!("`{}` is a reversed range, did you mean {}-{}?", token, last, first));
         }
-        None => {
-            // --every must have been used
-            // TODO: see if there is a way to not check match again b/c clap will make sure that if pages is None, then every is something.
-            match every {
-                Some(e) => {
-                    let page_numbers: &[u32] = &make_every_page_numbers(e, doc, negate);
-                    doc.delete_pages(page_numbers);
-                }
-                None => {
-                    panic!("--every is not a valid integer")
-                }
-            }
+        pages.extend(first..=last);
+    }
+    Ok(join_page_specs(vec![pages]))
+}
+
+/// Parses a page selection like `parse_page_spec` where a range may be open at either end: `5-`
+/// runs to the last page and `-5` starts at the first. The last page is only known once the
+/// document is loaded, see `PageSelection::Ranges`.
+///
+/// * `spec` - the selection, e.g. `1-3,8,20-`
+///
+pub fn parse_page_ranges(spec: &str) -> Result<Vec<PageRange>, String> {
+    let mut ranges = Vec::new();
+    for token in spec.split(|c: char| c == ',' || c.is_whitespace()).filter(|token| !token.is_empty()) {
+        let (first, last) = match token.split_once('-') {
+            Some(("", "")) => return Err(format!("`{}` is not a page number", token)),
+            Some(("", last)) => (1, Some(page_number(last, token)?)),
+            Some((first, "")) => (page_number(first, token)?, None),
+            Some((first, last)) => (page_number(first, token)?, Some(page_number(last, token)?)),
+            None => { let page = page_number(token, token)?; (page, Some(page)) }
+        };
+        if let Some(last) = last.filter(|last| first > *last) {
+            return Err(format!("`{}` is a reversed range, did you mean {}-{}?", token, last, first));
         }
+        ranges.push(PageRange { first, last });
+    }
+    Ok(ranges)
+}
+
+/// Parses a single page or one run of pages, open at either end like in `parse_page_ranges`
+///
+/// * `spec` - the pages, e.g. `10-12`, `7` or `20-`
+///
+pub fn parse_page_run(spec: &str) -> Result<PageRange, String> {
+    match parse_page_ranges(spec)?.as_slice() {
+        [range] => Ok(*range),
+        [] => Err("no pages are given".to_string()),
+        _ => Err(format!("`{}` is more than one run of pages, move them one run at a time", spec)),
+    }
+}
+
+/// Parses where to put pages for move and insert: a page number from 1, or `end`
+pub fn parse_position(s: &str) -> Result<Position, String> {
+    if s.eq_ignore_ascii_case("end") {
+        return Ok(Position::End);
+    }
+    page_number(s, s).map(Position::Page)
+}
+
+/// Parses the size of blank pages: `match` for the size of the page before, `A4` or `Letter`
+pub fn parse_page_size(s: &str) -> Result<PageSize, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "match" => Ok(PageSize::Match),
+        "a4" => Ok(PageSize::A4),
+        "letter" => Ok(PageSize::Letter),
+        _ => Err(format!("`{}` is not a page size, use match, A4 or Letter", s)),
+    }
+}
+
+/// Joins the selections given to a repeated --pages, dropping pages selected more than once
+pub fn join_page_specs(specs: Vec<Vec<u32>>) -> Vec<u32> {
+    let mut seen = HashSet::new();
+    specs.into_iter().flatten().filter(|page| seen.insert(*page)).collect()
+}
+
+/// Reads the document information to set from a JSON object with any of the keys title, author,
+/// subject and keywords. Fails if the file cannot be read or holds anything else.
+/// 
+/// * `filepath` - a PathBuf of the file to read
+/// 
+pub fn read_info_file(filepath: &PathBuf) -> Result<DocumentInfo, Error> {
+    let load_failed = |error| Error::LoadFailed { path: filepath.clone(), source: LoadError::Io(error) };
+    let text = fs::read_to_string(filepath).map_err(load_failed)?;
+    serde_json::from_str(&text).map_err(|error| load_failed(std::io::Error::from(error)))
+}
+
+// ------- Helpers -------
+
+// Makes sure the directory files are written to exists, `dir` is empty for the current directory
+fn prepare_output_dir(dir: &Path, create_dir: bool) -> Result<(), Error> {
+    if dir.as_os_str().is_empty() || dir.is_dir() { return Ok(()); }
+    if !create_dir { return Err(Error::OutputDirMissing(dir.to_path_buf())); }
+    fs::create_dir_all(dir).map_err(|source| Error::SaveFailed { path: dir.to_path_buf(), source })
+}
+
+// whether two paths lead to the same file, false when either does not exist
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// Fails for a listed page 0, pages are numbered from 1, then checks the listed pages are in the
+// document like missing_pages
+fn check_listed_pages(doc: &Document, selection: &PageSelection, strict: bool) -> Result<(), Error> {
+    let zero = match selection {
+        PageSelection::Pages(pages) => pages.contains(&0),
+        PageSelection::Ranges(ranges) => ranges.iter().any(|range| range.first == 0),
+        _ => false,
+    };
+    if zero {
+        return Err(Error::InvalidSelection("page 0 is listed, pages are numbered from 1".to_string()));
     }
+    missing_pages(doc, &selection.missing_pages(doc), strict)
 }
 
-fn extract_pages(doc: &mut Document, pages: Option<Vec<u32>>, every: Option<u32>) {
-    match pages {
-        Some(p) => {
-            let page_numbers = &make_pages_page_numbers(p, doc, true);
-            doc.delete_pages(page_numbers);
+// Warns about listed pages the document does not have, or with `strict` fails
+fn missing_pages(doc: &Document, missing: &[u32], strict: bool) -> Result<(), Error> {
+    if missing.is_empty() { return Ok(()); }
+    // runs of pages as ranges, 13-100 rather than every page of it
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for page in missing {
+        match runs.last_mut() {
+            Some((_, last)) if *last + 1 == *page => *last = *page,
+            _ => runs.push((*page, *page)),
         }
+    }
+    let listed: Vec<String> = runs.iter()
+        .map(|(first, last)| if first == last { first.to_string() } else { format!("{}-{}", first, last) })
+        .collect();
+    let reason = format!("the document has {} pages, there is no page {}", doc.get_pages().len(), listed.join(", "));
+    if strict { return Err(Error::InvalidSelection(reason)); }
+    report::warning(format!("{}, it was skipped", reason));
+    Ok(())
+}
+
+// A page, an inclusive range, or a range open at the end (None for the last page)
+fn page_span(span: &str, token: &str) -> Result<(u32, Option<u32>), String> {
+    let (first, last) = match span.split_once('-') {
+        Some((first, "")) => (page_number(first, token)?, None),
+        Some((first, last)) => (page_number(first, token)?, Some(page_number(last, token)?)),
+        None => { let page = page_number(span, token)?; (page, Some(page)) }
+    };
+    if let Some(last) = last.filter(|last| first > *last) {
+        return Err(format!("`{}` is a reversed range, did you mean {}-{}?", token, last, first));
+    }
+    Ok((first, last))
+}
+
+fn page_number(text: &str, token: &str) -> Result<u32, String> {
+    match text.parse::<u32>() {
+        Ok(0) => Err(format!("`{}` selects page 0, pages are numbered from 1", token)),
+        Ok(page) => Ok(page),
+        Err(_) => Err(format!("`{}` is not a page number", token)),
+    }
+}
+
+// Writes the given fields into the document information dictionary, creating it if needed
+pub(crate) fn set_info(doc: &mut Document, info: &DocumentInfo) {
+    let fields = [(b"Title".as_slice(), &info.title), (b"Author", &info.author), (b"Subject", &info.subject), (b"Keywords", &info.keywords)];
+    if fields.iter().all(|(_, value)| value.is_none()) { return; }
+
+    let info_id = match info_object(doc) {
+        Some(id) => id,
         None => {
-            match every {
-                Some(e) => {
-                    let page_numbers = &make_every_page_numbers(e, doc, true);
-                    doc.delete_pages(page_numbers);
-                }
-                None => {
-                    panic!("--every is not a valid integer");
-                }
+            let id = doc.add_object(lopdf::Dictionary::new());
+            doc.trailer.set("Info", id);
+            id
+        }
+    };
+    if let Ok(Object::Dictionary(dict)) = doc.get_object_mut(info_id) {
+        for (key, value) in fields {
+            if let Some(value) = value {
+                dict.set(key.to_vec(), encode_text_string(value));
             }
         }
     }
 }
 
-fn make_pages_page_numbers(pages: Vec<u32>, doc: &mut Document, negate: bool) -> Vec<u32> {
-    if negate {
-        let mut pages_set: HashSet<u32> = HashSet::new();
-        // problematic only if usize is 64bits and len() is above u32::MAX
-        pages_set.extend(1..=doc.get_pages().len() as u32); 
-        for p in pages.iter() {
-            pages_set.remove(p);
+// The document information dictionary as an object of its own, which it is made into if the
+// trailer holds it directly. None if there is none.
+pub(crate) fn info_object(doc: &mut Document) -> Option<ObjectId> {
+    match doc.trailer.get(b"Info") {
+        Ok(Object::Reference(id)) => doc.get_dictionary(*id).is_ok().then_some(*id),
+        Ok(Object::Dictionary(info)) => {
+            let id = doc.add_object(info.clone());
+            doc.trailer.set("Info", id);
+            Some(id)
         }
-        pages_set.into_iter().collect::<Vec<_>>()
-    } else {
-        pages
+        _ => None,
+    }
+}
+
+// Removes the document information dictionary and the XMP metadata of the catalog, saving prunes
+// what they referred to
+fn remove_document_metadata(doc: &mut Document) {
+    doc.trailer.remove(b"Info");
+    let root_id = doc.trailer.get(b"Root").and_then(Object::as_reference);
+    if let Ok(catalog) = root_id.and_then(|id| doc.get_object_mut(id)).and_then(Object::as_dict_mut) {
+        catalog.remove(b"Metadata");
     }
 }
 
-fn make_every_page_numbers(every: u32, doc: &mut Document, negate: bool) -> Vec<u32> {
-    let mut pages: Vec<u32> = Vec::new();
-    if negate {
-        for (page_num, _) in doc.get_pages() {
-            if page_num % every != 0 { pages.push(page_num); }
+// The Title from the document information dictionary, or the name of the file without its extension
+fn bookmark_title(doc: &Document, path: &Path) -> String {
+    let title = doc.trailer.get(b"Info").ok()
+        .and_then(|info| doc.dereference(info).ok())
+        .and_then(|(_, info)| info.as_dict().ok())
+        .and_then(|info| info.get(b"Title").ok())
+        .and_then(|title| doc.dereference(title).ok())
+        .and_then(|(_, title)| title.as_str().ok())
+        .map(|title| decode_text_string(title).trim().to_string())
+        .filter(|title| !title.is_empty());
+
+    title.unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().into_owned())
+}
+
+// Where a merge input comes from, worked out before any of them is loaded
+enum MergeInput {
+    File(PathBuf),
+    /// Given with a page selection or a password, possibly several times
+    Selected { path: PathBuf, suffix: Option<PageSuffix>, password: Option<String>, first: bool },
+    ZipEntry { label: String, name: String, data: Vec<u8> },
+    Manifest(ManifestEntry),
+    /// A zip archive that could not be read
+    Failed(Error),
+}
+
+impl MergeInput {
+    // what the bookmark title falls back to
+    fn title_path(&self) -> &Path {
+        match self {
+            MergeInput::File(path) | MergeInput::Selected { path, .. } | MergeInput::Manifest(ManifestEntry { path, .. }) => path,
+            MergeInput::ZipEntry { name, .. } => Path::new(name),
+            MergeInput::Failed(_) => Path::new(""),
         }
-        } else {
-            for (page_num, _) in doc.get_pages() {
-                if page_num % every == 0 { pages.push(page_num); }
-            } 
-    }
-    pages
-}
-
-fn reverse_doc(doc: &mut Document, filepath: PathBuf) {
-    // Try getting the Kids reference table from Pages and reversing the vector of references
-    // do this for all Pages objects as there may be more than one in the 
-
-    // inefficient to scan every object in the document when we are only looking for Pages
-    for (object_id, object) in doc.clone().objects.iter() { // TODO: fix wasteful clone
-        match object.type_name().unwrap_or("") {
-            "Pages" => {
-                if let Ok(dict) = object.as_dict() {
-                    let mut dict = dict.clone();
-                    let kids_refs = dict.get(b"Kids");
-                    match kids_refs {
-                        Ok(ref_arr) => {
-                            // get the Pages object, pull the Kids and reverse the array of page references
-                            // then replace the entire Pages object using the original object_id
-                            let mut arr = ref_arr.as_array().unwrap().clone();
-                            println!("{:?}", arr);
-                            arr.reverse();
-                            println!("{:?}", arr);
-                            dict.set("Kids", Object::Array(arr));
-
-                            doc.objects.insert(*object_id, Object::Dictionary(dict));
-                        }
-                        Err(error) => { println!("{}", error); } // TODO: temp, will leak impl details
+    }
+}
+
+// Loads an input and gives its size in bytes. None for a selection whose file an earlier
+// occurrence loads. Runs on a worker thread.
+fn load_merge_input(input: &MergeInput) -> Option<Result<(Document, u64), Error>> {
+    let loaded = match input {
+        MergeInput::File(path) => try_load_pdf(path).map(|doc| (doc, file_size(path))),
+        MergeInput::Selected { first: false, .. } | MergeInput::Failed(_) => return None,
+        MergeInput::Selected { path, password, .. } => try_load_pdf_with_password(path, password.as_deref()).map(|doc| (doc, file_size(path))),
+        MergeInput::ZipEntry { label, data, .. } => report::timed(format!("load {}", label), || load_document_mem(label, data))
+            .map(|doc| (doc, data.len() as u64))
+            .map_err(|source| Error::LoadFailed { path: PathBuf::from(label), source }),
+        MergeInput::Manifest(entry) => {
+            let failed = |reason: String| Error::Manifest { path: entry.manifest.clone(), line: entry.line, reason };
+            try_load_pdf(&entry.path).map_err(|error| failed(error.to_string())).and_then(|mut doc| {
+                if let Some(pages) = &entry.pages {
+                    let count = doc.get_pages().len();
+                    if let Some(past) = pages.iter().find(|page| **page as usize > count) {
+                        return Err(failed(format!("page {} is past the end of {}, which has {} pages", past, entry.path.display(), count)));
                     }
+                    // in the order listed, like a page suffix
+                    ops::extract_pages(&mut doc, &PageSelection::Pages(pages.clone()), false)?;
                 }
+                Ok((doc, file_size(&entry.path)))
+            })
+        }
+    };
+    Some(loaded)
+}
+
+// An input with only the pages of the suffix, in its order, or all of them without one
+fn select_pages(mut doc: Document, path: &Path, suffix: Option<&PageSuffix>) -> Result<Document, Error> {
+    let count = doc.get_pages().len();
+
+    let selection = match suffix {
+        None => return Ok(doc),
+        Some(PageSuffix::Even) => PageSelection::Every(2),
+        Some(PageSuffix::Odd) => PageSelection::Odd,
+        Some(PageSuffix::Pages(pages)) => {
+            if let Some(past) = pages.iter().find(|page| **page as usize > count) {
+                return Err(Error::InvalidSelection(format!("page {} is past the end of {}, which has {} pages", past, path.display(), count)));
             }
-            _ => {} // do nothing for all other object types
+            PageSelection::Pages(pages.clone())
         }
-    }
+    };
+    // in the order of the suffix, see ops::extract_pages
+    ops::extract_pages(&mut doc, &selection, false)?;
+    Ok(doc)
+}
 
-    save_pdf(doc, filepath);
+// try_load_pdf for the commands that fail by panicking
+pub(crate) fn load_pdf(filepath: &PathBuf) -> Document {
+    try_load_pdf(filepath).unwrap_or_else(|error| panic!("{}", error))
 }
 
-fn rotate_doc(doc: &mut Document, filepath: PathBuf, degrees: i32, pages: Option<Vec<u32>>, every: Option<u32>) {
-    match pages {
-        Some(p) => {
-            let page_numbers = &make_pages_page_numbers(p, doc, false);
-            rotate_select_pages(doc, page_numbers, degrees);
-        }
-        None => {
-            match every {
-                Some(e) => {
-                    let page_numbers = &make_every_page_numbers(e, doc, false);
-                    rotate_select_pages(doc, page_numbers, degrees);
-                }
-                None => {
-                    rotate_all_pages(doc, degrees);
-                }
+pub(crate) fn try_load_pdf(filepath: &PathBuf) -> Result<Document, Error> {
+    try_load_pdf_with_password(filepath, None)
+}
+
+// try_load_pdf with a password for this file that takes the place of --password
+pub(crate) fn try_load_pdf_with_password(filepath: &PathBuf, password: Option<&str>) -> Result<Document, Error> {
+    report::timed(format!("load {}", filepath.display()), || {
+        let data = fs::read(filepath).map_err(LoadError::Io)?;
+        open_document_mem(&filepath.display().to_string(), &data, password)
+    }).map_err(|source| Error::LoadFailed { path: filepath.clone(), source })
+}
+
+// try_load_pdf for a PDF already in memory, `name` says where it came from in warnings
+pub(crate) fn load_document_mem(name: &str, data: &[u8]) -> Result<Document, LoadError> {
+    open_document_mem(name, data, None)
+}
+
+// Loading without decrypting, for looking at documents the way they are stored
+pub(crate) fn parse_document(filepath: &PathBuf) -> Result<Document, LoadError> {
+    let data = fs::read(filepath).map_err(LoadError::Io)?;
+    parse_document_mem(&filepath.display().to_string(), &data)
+}
+
+// parse_document_mem, then encrypted documents are decrypted with `password` or else --password
+fn open_document_mem(name: &str, data: &[u8], password: Option<&str>) -> Result<Document, LoadError> {
+    let doc = parse_document_mem(name, data)?;
+    if !is_encrypted(&doc) { return Ok(doc); }
+
+    let global = PASSWORD.lock().unwrap().clone();
+    let password = password.map(str::to_string).or(global);
+    match decrypt_pdf(data, password.as_deref().unwrap_or("").as_bytes()) {
+        Ok(doc) => Ok(doc),
+        Err(DecryptError::WrongPassword) if password.is_none() => Err(LoadError::PasswordNeeded),
+        Err(DecryptError::WrongPassword) => Err(LoadError::WrongPassword),
+        Err(error) => Err(LoadError::Undecryptable(error.to_string())),
+    }
+}
+
+// Document::load, and with --lenient a second try that rebuilds a damaged cross-reference table
+fn parse_document_mem(name: &str, data: &[u8]) -> Result<Document, LoadError> {
+    let error = match Document::load_mem(data) {
+        Ok(doc) => return Ok(doc),
+        Err(error) => error,
+    };
+    if !LENIENT.load(Ordering::Relaxed) { return Err(LoadError::Parse(error)); }
+
+    match recover(data) {
+        Ok((doc, recovery)) => {
+            report::warning(format!("{} is damaged ({}), recovered {} objects by scanning the file",
+                                    name, error, doc.objects.len()));
+            if !recovery.skipped.is_empty() {
+                let skipped: Vec<String> = recovery.skipped.iter().map(|(number, generation)| format!("{} {} R", number, generation)).collect();
+                report::warning(format!("skipped {} unreadable objects: {}", skipped.len(), skipped.join(", ")));
             }
+            Ok(doc)
         }
+        Err(recovery) => Err(LoadError::Unrecoverable { parse: error, recovery }),
     }
+}
 
-    save_pdf(doc, filepath);
+// try_load_for_update for the commands that fail by panicking
+pub(crate) fn load_for_update(filepath: &PathBuf) -> (Document, SaveReport) {
+    try_load_for_update(filepath).unwrap_or_else(|error| panic!("{}", error))
 }
 
-fn rotate_select_pages(doc: &mut Document, page_numbers: &Vec<u32>, degrees: i32) {
-    let pages: BTreeMap<u32, ObjectId> = doc.get_pages();
+// Loads a document that is about to be changed, with its page count and size as the first half of
+// the report save_changes returns
+pub(crate) fn try_load_for_update(filepath: &PathBuf) -> Result<(Document, SaveReport), Error> {
+    let doc = try_load_pdf(filepath)?;
+    let before = SaveReport { pages_in: doc.get_pages().len(), bytes_in: file_size(filepath), ..SaveReport::default() };
+    Ok((doc, before))
+}
 
-    for p in page_numbers {
-        let object_id = pages.get(p);
-        match object_id {
-            Some(id) => {
-                if let Ok(page) = doc.get_object(*id) {
-                    if let Ok(dict) = page.as_dict() {
-                        let mut dict = dict.clone();
-                        
-                        dict.set("Rotate", degrees);
-                        doc.objects.insert(*id, Object::Dictionary(dict));
-                    }
-                }
-            }
-            None => {} // do nothing, but TODO: consider accumulating missed pages to output to user
+// Runs `f` on each item on as many threads as there are cores, returning the results in the order
+// of the items. Items are started in order, and once `stop` holds for a result no more are
+// started: those are left None.
+pub(crate) fn in_parallel<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync, stop: impl Fn(&R) -> bool + Sync) -> Vec<Option<R>> {
+    let next = AtomicUsize::new(0);
+    let stopped = AtomicBool::new(false);
+    let results: Vec<Mutex<Option<R>>> = items.iter().map(|_| Mutex::new(None)).collect();
+    let threads = thread::available_parallelism().map_or(1, usize::from).min(items.len());
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| while !stopped.load(Ordering::SeqCst) {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(item) = items.get(index) else { break };
+                let result = f(item);
+                if stop(&result) { stopped.store(true, Ordering::SeqCst); }
+                *results[index].lock().unwrap() = Some(result);
+            });
         }
-    }
+    });
+    results.into_iter().map(|result| result.into_inner().unwrap()).collect()
+}
+
+fn file_size(filepath: &PathBuf) -> u64 {
+    fs::metadata(filepath).map(|metadata| metadata.len()).unwrap_or(0)
+}
+
+// try_save_changes for the commands that fail by panicking
+pub(crate) fn save_changes(doc: &mut Document, infile: &PathBuf, outfile: Option<PathBuf>, options: &SaveOptions,
+                           before: SaveReport) -> SaveReport {
+    try_save_changes(doc, infile, outfile, options, before).unwrap_or_else(|error| panic!("{}", error))
 }
 
-fn rotate_all_pages(doc: &mut Document, degrees: i32) {
-    for (_, object_id) in doc.get_pages() {
-        if let Ok(page) = doc.get_object(object_id) {
-            if let Ok(dict) = page.as_dict() {
-                let mut dict = dict.clone();
-                
-                dict.set("Rotate", degrees);
-                doc.objects.insert(object_id, Object::Dictionary(dict));
+// Saves a document loaded from infile, inplace or to outfile. Signed documents are only rewritten
+// with break_signatures; an incremental update keeps the signed bytes and so is always allowed.
+pub(crate) fn try_save_changes(doc: &mut Document, infile: &PathBuf, outfile: Option<PathBuf>, options: &SaveOptions,
+                               before: SaveReport) -> Result<SaveReport, Error> {
+    // Read before anything is written, writing changes the modification time
+    let original = match outfile {
+        None if options.preserve => fs::metadata(infile).ok(),
+        _ => None,
+    };
+    let filepath = outfile.unwrap_or_else(|| infile.clone());
+
+    let (bytes_out, stats) = if options.incremental {
+        if doc.get_pages().is_empty() { return Err(Error::NoPagesRemaining); }
+        let mut data = fs::read(infile).map_err(|source| Error::LoadFailed { path: infile.clone(), source: LoadError::Io(source) })?;
+        append_update(&mut data, doc).map_err(|reason| Error::IncrementalUpdateFailed { path: infile.clone(), reason })?;
+        let bytes_out = data.len() as u64;
+        report::timed("save", || fs::write(&filepath, data)).map_err(|source| Error::SaveFailed { path: filepath.clone(), source })?;
+        (bytes_out, SaveStats::default())
+    } else {
+        let signed = signed_fields(doc);
+        if !signed.is_empty() {
+            if !options.break_signatures {
+                return Err(Error::Signed { path: infile.clone(), fields: signed });
             }
+            report::warning(format!("the signatures of {} will no longer validate", signed.join(", ")));
+        }
+        save_pdf(doc, filepath.clone())?
+    };
+
+    if let Some(metadata) = original {
+        if let Err(error) = restore_metadata(&filepath, &metadata) {
+            report::warning(format!("could not restore the permissions and modification time of {}: {}",
+                                    filepath.display(), error));
         }
     }
+
+    Ok(SaveReport { pages_out: doc.get_pages().len(), bytes_out, stats, ..before })
 }
 
-// check if any of the entries are directories, if they are, expand the vector to include
-// all PDFs in the directory (do not search subdirs)
-fn expand_dirs_if_necessary(infiles: &Vec<PathBuf>) -> Vec<PathBuf> {
-    let mut dir_pdf_files: Vec<PathBuf>;
-    let mut expanded = Vec::with_capacity(infiles.len());
+// Gives a rewritten file back the modification time and permissions it had before. The time goes
+// first, the file can not be opened to set it once it is read-only again.
+fn restore_metadata(filepath: &PathBuf, metadata: &fs::Metadata) -> std::io::Result<()> {
+    let file = fs::File::options().write(true).open(filepath)?;
+    file.set_modified(metadata.modified()?)?;
+    fs::set_permissions(filepath, metadata.permissions())
+}
 
-    for path in infiles {
-        if path.is_dir() {
-            dir_pdf_files = get_files_from_dir(path).expect("failed to get files from directory");
-            expanded.append(&mut dir_pdf_files);
-        } else  {
-            expanded.push(path.to_path_buf());
-        }
+// Returns the size of the written file and what was dropped from it
+pub(crate) fn save_pdf(doc: &mut Document, filepath: PathBuf) -> Result<(u64, SaveStats), Error> {
+    let stats = prepare_for_save(doc)?;
+
+    match report::timed("save", || doc.save(&filepath)) {
+        Ok(_) => Ok((file_size(&filepath), stats)),
+        Err(source) => Err(Error::SaveFailed { path: filepath, source }),
     }
+}
+
+// Prunes what a document no longer uses before it is written. Fails if it has no pages left.
+fn prepare_for_save(doc: &mut Document) -> Result<SaveStats, Error> {
+    let objects_before = doc.objects.len();
+    let objects_pruned = doc.prune_objects().len();
+    doc.adjust_zero_pages();
+    doc.build_outline();
+    let empty_streams = doc.delete_zero_length_streams().len();
+    let stats = SaveStats { objects_before, objects_pruned, empty_streams, objects_after: doc.objects.len() };
 
-    return expanded;
+    if doc.get_pages().is_empty() { return Err(Error::NoPagesRemaining); }
+    Ok(stats)
 }
 
-// https://stackoverflow.com/questions/58062887/filtering-files-or-directories-discovered-with-fsread-dir
-fn get_files_from_dir(dir: &PathBuf) -> Result<Vec<PathBuf>, std::io::Error> {
-    Ok(fs::read_dir(dir)?
-        .into_iter()
-        .filter(|result| result.is_ok()) // filter to non Err results
-        .map(|result| result.unwrap().path()) // turn valid DirEntries into PathBufs
-        .filter(|result| 
-            result.is_file() && 
-            result.extension() != None && 
-            (result.extension().unwrap() == "pdf" || result.extension().unwrap() == "PDF")) // filter to only files
-        .collect()
-    )
-}
-
-// this is almost unmodified from the examples in the lopdf README https://github.com/J-F-Liu/lopdf
-// TODO: consider refactoring
-// FIXME: this is broken for files with multiple Pages objects (I think)
-fn merge_documents(documents: Vec<Document>, outdoc: &mut Document) {
-    // Define a starting max_id (will be used as start index for object_ids)
-    let mut max_id = 1;
-    // let mut pagenum = 1;
-    // Collect all Documents Objects grouped by a map
-    let mut documents_pages = BTreeMap::new();
-    let mut documents_objects = BTreeMap::new();
-
-    for mut doc in documents {
-        // let mut first = false;
-
-        // renumber the current doc starting with the current max_id
-        doc.renumber_objects_with(max_id);
-        // sets the new max_id to the id of the last page of the current doc + 1 so that the next doc starts in the correct location
-        max_id = doc.max_id + 1; 
-
-        // extend the documents_pages with a BTreeMap of ObjectId and Object which is a enum of Object types
-        // An object can be:
-        /*
-            pub enum Object {
-                Null,
-                Boolean(bool),
-                Integer(i64),
-                Real(f64),
-                Name(Vec<u8>),
-                String(Vec<u8>, StringFormat),
-                Array(Vec<Object>),
-                Dictionary(Dictionary),
-                Stream(Stream),
-                Reference(ObjectId),
-            }
-        */
-        documents_pages.extend(
-            doc
-                    .get_pages()
-                    .into_iter()
-                    .map(|(_, object_id)| (object_id, doc.get_object(object_id).unwrap().to_owned(),))
-                    .collect::<BTreeMap<ObjectId, Object>>(),
-        );
-
-        // add all the objects from each document to a collection
-        documents_objects.extend(doc.objects);
-    }
-
-    // Catalog and Pages are mandatory 
-    let mut catalog_object: Option<(ObjectId, Object)> = None;
-    let mut pages_object: Option<(ObjectId, Object)> = None;
-
-    // Process all objects except "Page" type
-    for (object_id, object) in documents_objects.iter() {
-        // We have to ignore "Page" (as are processed later), "Outlines" and "Outline" objects
-        // All other objects should be collected and inserted into the main Document
-        match object.type_name().unwrap_or("") {
-            "Catalog" => {
-                // Collect a first "Catalog" object and use it for the future "Pages"
-                catalog_object = Some((
-                    if let Some((id, _)) = catalog_object {
-                        id
-                    } else {
-                        *object_id
-                    },
-                    object.clone(),
-                ));
-            }
-            "Pages" => {
-                // Collect and update a first "Pages" object and use it for the future "Catalog"
-                // We have also to merge all dictionaries of the old and the new "Pages" object
-                if let Ok(dictionary) = object.as_dict() {
-                    let mut dictionary = dictionary.clone();
-                    if let Some((_, ref object)) = pages_object {
-                        if let Ok(old_dictionary) = object.as_dict() {
-                            dictionary.extend(old_dictionary);
-                        }
-                    }
+// Looks up a page attribute, following /Parent links for the inheritable ones (Resources, MediaBox,
+// CropBox and Rotate)
+pub(crate) fn inherited_attribute(doc: &Document, page_id: ObjectId, key: &[u8]) -> Option<Object> {
+    let value = inherited_entry(doc, page_id, key)?;
+    doc.dereference(value).ok().map(|(_, v)| v.clone())
+}
 
-                    pages_object = Some((
-                        if let Some((id, _)) = pages_object {
-                            id
-                        } else {
-                            *object_id
-                        },
-                        Object::Dictionary(dictionary),
-                    ));
-                }
-            }
-            "Page" => {}     // Ignored, processed later and separately
-            "Outlines" => {} // Ignored, not supported yet
-            "Outline" => {}  // Ignored, not supported yet
-            _ => {
-                outdoc.objects.insert(*object_id, object.clone());
-            }
+// inherited_attribute without following the reference the value may be
+pub(crate) fn inherited_entry<'a>(doc: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut node_id = page_id;
+    let mut visited = HashSet::new();
+
+    while visited.insert(node_id) {
+        let node = doc.get_dictionary(node_id).ok()?;
+        if let Ok(value) = node.get(key) {
+            return Some(value);
         }
+        node_id = node.get(b"Parent").and_then(Object::as_reference).ok()?;
     }
 
-    // If no "Pages" found abort
-    if pages_object.is_none() {
-        println!("Pages root not found.");
+    None
+}
+
+// The visible area of a page as [llx lly urx ury]: the CropBox if present, otherwise the MediaBox
+pub(crate) fn page_box(doc: &Document, page_id: ObjectId) -> Option<[f64; 4]> {
+    inherited_rect(doc, page_id, b"CropBox").or_else(|| inherited_rect(doc, page_id, b"MediaBox"))
+}
 
-        return;
+// A rectangle attribute as [llx lly urx ury], whichever corners the file gives
+pub(crate) fn inherited_rect(doc: &Document, page_id: ObjectId, key: &[u8]) -> Option<[f64; 4]> {
+    let rect = inherited_attribute(doc, page_id, key)?;
+    let values: Vec<f64> = rect.as_array().ok()?
+        .iter()
+        .filter_map(|n| doc.dereference(n).ok()?.1.as_float().ok())
+        .collect();
+
+    match values[..] {
+        [x0, y0, x1, y1] => Some([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]),
+        _ => None,
     }
+}
 
-    // Iter over all "Page" and collect with the parent "Pages" created before
-    for (object_id, object) in documents_pages.iter() {
-        if let Ok(dictionary) = object.as_dict() {
-            let mut dictionary = dictionary.clone();
-            dictionary.set("Parent", pages_object.as_ref().unwrap().0);
+// PDF text strings are either UTF-16BE with a byte order mark or PDFDocEncoding, which is close enough
+// to Latin-1 for display purposes
+pub(crate) fn decode_text_string(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = bytes[2..]
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        bytes.iter().map(|b| *b as char).collect()
+    }
+}
 
-            outdoc
-                    .objects
-                    .insert(*object_id, Object::Dictionary(dictionary));
+// The reverse of decode_text_string: plain ASCII stays as it is, anything else is written as UTF-16BE
+pub(crate) fn encode_text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        return Object::string_literal(text);
+    }
+    let mut bytes = vec![0xFE, 0xFF];
+    for unit in text.encode_utf16() {
+        bytes.extend_from_slice(&unit.to_be_bytes());
+    }
+    Object::String(bytes, lopdf::StringFormat::Hexadecimal)
+}
+
+// delete and extract need --pages or --every, they do not default to every page like rotate
+fn required_selection(selection: PageSelection) -> Result<PageSelection, Error> {
+    if selection == PageSelection::All {
+        return Err(Error::InvalidSelection("either --pages or --every is needed".to_string()));
+    }
+    Ok(selection)
+}
+
+// Makes a page the only kid of the root page tree node, with the attributes it inherited copied
+// onto it. Much faster than deleting every other page, the rest is pruned when saving.
+fn keep_only_page(doc: &mut Document, page_id: ObjectId) {
+    let root_id = match doc.catalog().and_then(|catalog| catalog.get(b"Pages")).and_then(Object::as_reference) {
+        Ok(id) => id,
+        Err(_) => return,
+    };
+    let inherited: Vec<(&[u8], Object)> = [b"MediaBox".as_slice(), b"CropBox", b"Resources", b"Rotate"].into_iter()
+        .filter_map(|key| inherited_entry(doc, page_id, key).map(|value| (key, value.clone())))
+        .collect();
+
+    if let Ok(Object::Dictionary(page)) = doc.get_object_mut(page_id) {
+        for (key, value) in inherited {
+            page.set(key.to_vec(), value);
         }
+        page.set("Parent", root_id);
+    }
+    if let Ok(Object::Dictionary(root)) = doc.get_object_mut(root_id) {
+        root.set("Kids", vec![Object::Reference(page_id)]);
+        root.set("Count", 1);
     }
+}
 
-    // If no "Catalog" found abort
-    if catalog_object.is_none() {
-        println!("Catalog root not found.");
+// tagged PDFs keep structure elements for deleted pages around unless they are pruned
+fn fix_structure_tree(doc: &mut Document, strip: bool) {
+    if strip {
+        structure::strip_structure_tree(doc);
+    } else {
+        structure::prune_structure_tree(doc);
+    }
+}
+
+// check if any of the entries are directories, if they are, expand the vector to include
+// all PDFs in the directory in the given order, and with recursive the PDFs in its subdirectories too.
+// An entry that does not exist but has glob characters is replaced by the paths it matches, sorted.
+pub(crate) fn expand_dirs_if_necessary(infiles: &Vec<PathBuf>, recursive: bool, sort: SortOrder) -> Result<Vec<PathBuf>, Error> {
+    let mut dir_pdf_files: Vec<PathBuf>;
+    let mut expanded = Vec::with_capacity(infiles.len());
 
-        return;
+    for given in infiles {
+        let paths = if !given.exists() && is_glob(&given.to_string_lossy()) {
+            let matches = expand_glob(given);
+            if matches.is_empty() { return Err(Error::NoMatch { pattern: given.display().to_string() }); }
+            matches
+        } else {
+            vec![given.clone()]
+        };
+
+        for path in paths {
+            if path.is_dir() {
+                dir_pdf_files = get_files_from_dir(&path, recursive, sort)
+                    .map_err(|source| Error::LoadFailed { path: path.clone(), source: LoadError::Io(source) })?;
+                expanded.append(&mut dir_pdf_files);
+            } else  {
+                expanded.push(path);
+            }
+        }
     }
 
-    let catalog_object = catalog_object.unwrap();
-    let pages_object = pages_object.unwrap();
+    Ok(expanded)
+}
 
-    // Build a new "Pages" with updated fields
-    if let Ok(dictionary) = pages_object.1.as_dict() {
-        let mut dictionary = dictionary.clone();
+fn is_glob(text: &str) -> bool {
+    text.contains(['*', '?', '['])
+}
 
-        // Set new pages count
-        dictionary.set("Count", documents_pages.len() as u32);
+// The existing paths a pattern with * ? and [...] in any of its components matches, sorted.
+// Hidden files only match a component that starts with a dot as well, as in a shell.
+fn expand_glob(pattern: &Path) -> Vec<PathBuf> {
+    let mut matches = vec![PathBuf::new()];
 
-        // Set new "Kids" list (collected from documents pages) for "Pages"
-        dictionary.set(
-            "Kids",
-            documents_pages
-                    .into_iter()
-                    .map(|(object_id, _)| Object::Reference(object_id))
-                    .collect::<Vec<_>>(),
-        );
+    for component in pattern.components() {
+        let text = component.as_os_str().to_string_lossy();
+        if !is_glob(&text) {
+            matches.iter_mut().for_each(|path| path.push(component));
+            continue;
+        }
+        matches = matches.into_iter().flat_map(|dir| {
+            let listing = if dir.as_os_str().is_empty() { PathBuf::from(".") } else { dir.clone() };
+            // names are matched in their lossy form but joined as they are, non-UTF-8 ones included
+            let names: Vec<OsString> = fs::read_dir(listing).into_iter()
+                .flatten()
+                .filter_map(|entry| entry.ok().map(|entry| entry.file_name()))
+                .filter(|name| {
+                    let name = name.to_string_lossy();
+                    glob_match(&text, &name) && (!name.starts_with('.') || text.starts_with('.'))
+                })
+                .collect();
+            names.into_iter().map(move |name| dir.join(name))
+        }).collect();
+    }
+
+    matches.retain(|path| path.exists());
+    matches.sort();
+    matches
+}
 
-        outdoc
-                .objects
-                .insert(pages_object.0, Object::Dictionary(dictionary));
+// Whether a file name matches a pattern component: * is any run of characters, ? any one, and
+// [abc], [a-z] or [!a-z] one of a set. A [ that is not closed is taken literally.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    glob_match_chars(&pattern, &name)
+}
+
+// Matches left to right, going back only to the last * seen and letting it take one more
+// character, which is enough since an earlier * can never need to take more than it did
+fn glob_match_chars(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // the position of the last * in the pattern and of the name right after what it takes
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if pattern.get(p) == Some(&'*') {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some(len) = token_matches(&pattern[p..], name[n]) {
+            p += len;
+            n += 1;
+        } else if let Some((star_p, star_n)) = star {
+            star = Some((star_p, star_n + 1));
+            p = star_p + 1;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
     }
+    pattern[p..].iter().all(|&c| c == '*')
+}
 
-    // Build a new "Catalog" with updated fields
-    if let Ok(dictionary) = catalog_object.1.as_dict() {
-        let mut dictionary = dictionary.clone();
-        dictionary.set("Pages", pages_object.0);
-        dictionary.remove(b"Outlines"); // Outlines not supported in merged PDFs
+// How much of the pattern a ?, set or literal at its start takes if it matches `c`
+fn token_matches(pattern: &[char], c: char) -> Option<usize> {
+    match *pattern.first()? {
+        '?' => Some(1),
+        '[' => match class_end(pattern) {
+            Some(end) => class_matches(&pattern[1..end], c).then_some(end + 1),
+            None => (c == '[').then_some(1),
+        },
+        literal => (literal == c).then_some(1),
+    }
+}
 
-        outdoc
-                .objects
-                .insert(catalog_object.0, Object::Dictionary(dictionary));
+// The index of the ] closing the set the pattern starts with, a ] right after [ or [! is a member
+fn class_end(pattern: &[char]) -> Option<usize> {
+    let mut start = 1;
+    if matches!(pattern.get(start), Some('!') | Some('^')) { start += 1; }
+    if pattern.get(start) == Some(&']') { start += 1; }
+    pattern.iter().skip(start).position(|&c| c == ']').map(|at| at + start)
+}
+
+fn class_matches(set: &[char], c: char) -> bool {
+    let (negated, set) = match set.first() {
+        Some('!') | Some('^') => (true, &set[1..]),
+        _ => (false, set),
+    };
+    let mut found = false;
+    let mut at = 0;
+    while at < set.len() {
+        if at + 2 < set.len() && set[at + 1] == '-' {
+            found |= (set[at]..=set[at + 2]).contains(&c);
+            at += 3;
+        } else {
+            found |= set[at] == c;
+            at += 1;
+        }
     }
+    found != negated
+}
+
+fn is_zip(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+// The PDFs in a zip archive in natural order, with their names in the archive; only the top level unless recursive
+fn pdfs_in_zip(path: &PathBuf, recursive: bool) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    let load_failed = |source| Error::LoadFailed { path: path.clone(), source };
+    let data = fs::read(path).map_err(|error| load_failed(LoadError::Io(error)))?;
+    let archive = ZipArchive::new(data).map_err(|error| load_failed(LoadError::Archive(error)))?;
+
+    let mut entries: Vec<_> = archive.entries().iter()
+        .filter(|entry| !entry.is_dir() && Path::new(&entry.name).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("pdf")))
+        .collect();
+    if !recursive {
+        let nested = entries.iter().filter(|entry| entry.name.contains('/')).count();
+        if nested > 0 {
+            report::warning(format!("{} PDFs in subdirectories of {} are not merged, add --recursive to include them", nested, path.display()));
+        }
+        entries.retain(|entry| !entry.name.contains('/'));
+    }
+    // directory by directory, so a subdirectory's PDFs come where its name sorts, depth first
+    entries.sort_by(|a, b| {
+        let (mut a, mut b) = (a.name.split('/'), b.name.split('/'));
+        loop {
+            match (a.next(), b.next()) {
+                (Some(a), Some(b)) => match natural_cmp(a, b) {
+                    cmp::Ordering::Equal => continue,
+                    ordering => return ordering,
+                },
+                (a, b) => return a.is_some().cmp(&b.is_some()),
+            }
+        }
+    });
 
-    outdoc.trailer.set("Root", catalog_object.0);
+    entries.into_iter()
+        .map(|entry| archive.read(entry).map(|data| (entry.name.clone(), data)).map_err(|error| load_failed(LoadError::Archive(error))))
+        .collect()
+}
 
-    // Update the max internal ID as wasn't updated before due to direct objects insertion
-    outdoc.max_id = outdoc.objects.len() as u32;
+// Orders names the way people count, comparing runs of digits by their value: 2.pdf before 10.pdf
+pub(crate) fn natural_cmp(a: &str, b: &str) -> cmp::Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return cmp::Ordering::Equal,
+            (None, Some(_)) => return cmp::Ordering::Less,
+            (Some(_), None) => return cmp::Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_digits = a.iter().take_while(|c| c.is_ascii_digit()).count();
+                let b_digits = b.iter().take_while(|c| c.is_ascii_digit()).count();
+                let (a_number, b_number) = (&a[..a_digits], &b[..b_digits]);
+                let a_value = trim_zeros(a_number);
+                let b_value = trim_zeros(b_number);
+                let order = a_value.len().cmp(&b_value.len())
+                    .then_with(|| a_value.cmp(b_value))
+                    .then_with(|| a_digits.cmp(&b_digits));
+                if order != cmp::Ordering::Equal { return order; }
+                a = &a[a_digits..];
+                b = &b[b_digits..];
+            }
+            (Some(x), Some(y)) => {
+                let order = x.to_ascii_lowercase().cmp(&y.to_ascii_lowercase()).then_with(|| x.cmp(y));
+                if order != cmp::Ordering::Equal { return order; }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
 
-    // Reorder all new Document objects
-    outdoc.renumber_objects();
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&c| c == b'0').count();
+    &digits[zeros..]
+}
 
-     //Set any Bookmarks to the First child if they are not set to a page
-    outdoc.adjust_zero_pages();
+// The PDFs in a directory in sort order. With recursive, the PDFs of a subdirectory take its
+// place in that order, depth first. Symbolic links to directories are not followed, they could
+// loop.
+fn get_files_from_dir(dir: &Path, recursive: bool, sort: SortOrder) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+    match sort {
+        SortOrder::Name => {}
+        SortOrder::Natural => entries.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy())),
+        // stable, files modified at the same time stay in name order
+        SortOrder::Mtime => entries.sort_by_key(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok()),
+    }
 
-    //Set all bookmarks to the PDF Object tree then set the Outlines to the Bookmark content map.
-    if let Some(n) = outdoc.build_outline() {
-        if let Ok(x) = outdoc.get_object_mut(catalog_object.0) {
-            if let Object::Dictionary(ref mut dict) = x {
-                dict.set("Outlines", Object::Reference(n));
+    let mut files = Vec::new();
+    for path in entries {
+        if path.is_dir() {
+            if recursive && !path.is_symlink() {
+                files.append(&mut get_files_from_dir(&path, true, sort)?);
             }
+        } else if path.is_file() && path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("pdf")) {
+            files.push(path);
         }
     }
+    Ok(files)
 }
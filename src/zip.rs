@@ -0,0 +1,268 @@
+use std::io::{self, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+/// How the entries of a zip archive are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ZipCompression {
+    /// As they are, PDFs are usually compressed already
+    Store,
+    Deflate,
+}
+
+/// Writes a zip archive entry by entry, so that files produced one at a time go straight into the
+/// archive without being written to disk first. Call `finish` to write the central directory,
+/// without it the archive cannot be read.
+///
+/// Archives are limited to 65535 entries and 4 GiB, there is no Zip64 support.
+pub struct ZipWriter<W: Write> {
+    inner: W,
+    compression: ZipCompression,
+    written: u64,
+    entries: Vec<Entry>,
+    modified: (u16, u16),
+}
+
+struct Entry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed: u32,
+    size: u32,
+    offset: u32,
+}
+
+impl<W: Write> ZipWriter<W> {
+    /// * `inner` - where the archive is written, a file or a buffer
+    /// * `compression` - whether the entries are stored or deflated
+    ///
+    pub fn new(inner: W, compression: ZipCompression) -> ZipWriter<W> {
+        ZipWriter { inner, compression, written: 0, entries: Vec::new(), modified: dos_time(SystemTime::now()) }
+    }
+
+    /// Adds a file to the archive
+    ///
+    /// * `name` - the path of the entry in the archive, with `/` between directories
+    /// * `data` - the contents of the file
+    ///
+    pub fn add(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        if self.entries.len() == u16::MAX as usize {
+            return Err(io::Error::other("a zip archive holds at most 65535 entries"));
+        }
+
+        let (method, stored) = match self.compression {
+            ZipCompression::Store => (0, data.to_vec()),
+            ZipCompression::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                (8, encoder.finish()?)
+            }
+        };
+        let entry = Entry {
+            name: name.to_string(),
+            method,
+            crc: crc32fast::hash(data),
+            compressed: limit(stored.len() as u64)?,
+            size: limit(data.len() as u64)?,
+            offset: limit(self.written)?,
+        };
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        self.common_fields(&mut header, &entry);
+        header.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        header.extend_from_slice(name.as_bytes());
+
+        self.write(&header)?;
+        self.write(&stored)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Writes the central directory that lists the entries, and returns the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        let start = limit(self.written)?;
+
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes()); // made by, MS-DOS attributes
+            self.common_fields(&mut directory, entry);
+            directory.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+            directory.extend_from_slice(&0u16.to_le_bytes()); // comment length
+            directory.extend_from_slice(&0u16.to_le_bytes()); // disk number
+            directory.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+            directory.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+            directory.extend_from_slice(&entry.offset.to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+        let size = limit(directory.len() as u64)?;
+        let count = self.entries.len() as u16;
+
+        directory.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes()); // this disk
+        directory.extend_from_slice(&0u16.to_le_bytes()); // disk with the directory
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&size.to_le_bytes());
+        directory.extend_from_slice(&start.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes()); // comment length
+
+        self.write(&directory)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    // The fields from "version needed" to "file name length", shared by both headers
+    fn common_fields(&self, out: &mut Vec<u8>, entry: &Entry) {
+        out.extend_from_slice(&20u16.to_le_bytes()); // version needed, 2.0 for deflate
+        out.extend_from_slice(&(1u16 << 11).to_le_bytes()); // names are UTF-8
+        out.extend_from_slice(&entry.method.to_le_bytes());
+        out.extend_from_slice(&self.modified.1.to_le_bytes());
+        out.extend_from_slice(&self.modified.0.to_le_bytes());
+        out.extend_from_slice(&entry.crc.to_le_bytes());
+        out.extend_from_slice(&entry.compressed.to_le_bytes());
+        out.extend_from_slice(&entry.size.to_le_bytes());
+        out.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.inner.write_all(data)?;
+        self.written += data.len() as u64;
+        Ok(())
+    }
+}
+
+/// A zip archive read into memory. Entries are listed from the central directory and only
+/// decompressed when read.
+///
+/// Supports stored and deflated entries, without encryption or Zip64.
+pub struct ZipArchive {
+    data: Vec<u8>,
+    entries: Vec<ZipEntry>,
+}
+
+/// An entry listed in the central directory of an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipEntry {
+    /// The path of the entry in the archive, directories end with `/`
+    pub name: String,
+    /// Size once decompressed, in bytes
+    pub size: u64,
+    method: u16,
+    flags: u16,
+    crc: u32,
+    compressed: u64,
+    offset: u64,
+}
+
+impl ZipEntry {
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+}
+
+impl ZipArchive {
+    /// Lists the entries of an archive. Fails if `data` has no readable central directory.
+    pub fn new(data: Vec<u8>) -> Result<ZipArchive, String> {
+        let end = find_end_of_directory(&data).ok_or("not a zip archive")?;
+        let count = read_u16(&data, end + 10)? as usize;
+        let mut at = read_u32(&data, end + 16)? as usize;
+        if count == 0xFFFF || at == 0xFFFF_FFFF { return Err("Zip64 archives are not supported".to_string()); }
+
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            if read_u32(&data, at)? != 0x02014b50 { return Err("damaged central directory".to_string()); }
+            let name_length = read_u16(&data, at + 28)? as usize;
+            let skip = read_u16(&data, at + 30)? as usize + read_u16(&data, at + 32)? as usize;
+            let name = data.get(at + 46..at + 46 + name_length).ok_or("damaged central directory")?;
+
+            entries.push(ZipEntry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                size: u64::from(read_u32(&data, at + 24)?),
+                method: read_u16(&data, at + 10)?,
+                flags: read_u16(&data, at + 8)?,
+                crc: read_u32(&data, at + 16)?,
+                compressed: u64::from(read_u32(&data, at + 20)?),
+                offset: u64::from(read_u32(&data, at + 42)?),
+            });
+            at += 46 + name_length + skip;
+        }
+
+        Ok(ZipArchive { data, entries })
+    }
+
+    /// The entries in the order they are listed in the archive
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// Decompresses an entry and checks it against its checksum
+    pub fn read(&self, entry: &ZipEntry) -> Result<Vec<u8>, String> {
+        if entry.flags & 1 != 0 { return Err(format!("{} is encrypted", entry.name)); }
+
+        let at = entry.offset as usize;
+        if read_u32(&self.data, at)? != 0x04034b50 { return Err(format!("{} is damaged", entry.name)); }
+        let start = at + 30 + read_u16(&self.data, at + 26)? as usize + read_u16(&self.data, at + 28)? as usize;
+        let stored = self.data.get(start..start + entry.compressed as usize).ok_or_else(|| format!("{} is truncated", entry.name))?;
+
+        let data = match entry.method {
+            0 => stored.to_vec(),
+            8 => {
+                let mut data = Vec::with_capacity(entry.size as usize);
+                DeflateDecoder::new(stored).take(entry.size).read_to_end(&mut data)
+                    .map_err(|error| format!("{} is damaged: {}", entry.name, error))?;
+                data
+            }
+            method => return Err(format!("{} uses an unsupported compression method ({})", entry.name, method)),
+        };
+        if data.len() as u64 != entry.size || crc32fast::hash(&data) != entry.crc {
+            return Err(format!("{} is damaged, its checksum does not match", entry.name));
+        }
+        Ok(data)
+    }
+}
+
+// ------- Helpers -------
+
+fn limit(value: u64) -> io::Result<u32> {
+    u32::try_from(value).map_err(|_| io::Error::other("a zip archive is limited to 4 GiB"))
+}
+
+// (date, time) in MS-DOS format, UTC. Zip has no time zone, and dates before 1980 cannot be stored
+fn dos_time(time: SystemTime) -> (u16, u16) {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0);
+    let (days, of_day) = (seconds / 86400, seconds % 86400);
+
+    // days since 1970-01-01 to a civil date
+    let z = days as i64 + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    if year < 1980 { return ((1 << 5) | 1, 0); }
+    let date = ((year - 1980) << 9) as u16 | (month << 5) as u16 | day as u16;
+    let time = ((of_day / 3600) << 11) as u16 | (((of_day % 3600) / 60) << 5) as u16 | ((of_day % 60) / 2) as u16;
+    (date, time)
+}
+
+// The end of central directory record is last, followed only by a comment of up to 64 KiB
+fn find_end_of_directory(data: &[u8]) -> Option<usize> {
+    let earliest = data.len().saturating_sub(22 + u16::MAX as usize);
+    (earliest..=data.len().checked_sub(22)?).rev().find(|&at| data[at..at + 4] == 0x06054b50u32.to_le_bytes())
+}
+
+fn read_u16(data: &[u8], at: usize) -> Result<u16, String> {
+    data.get(at..at + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]])).ok_or_else(|| "the archive is truncated".to_string())
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32, String> {
+    data.get(at..at + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).ok_or_else(|| "the archive is truncated".to_string())
+}
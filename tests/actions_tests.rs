@@ -0,0 +1,122 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use lopdf::{dictionary, Document, Object};
+    use pdfh::actions::{self, Fit, OpenDestination};
+    use pdfh::sanitize::{self, Removal};
+    use pdfh::utils::SaveOptions;
+    use ::function_name::named;
+
+    use crate::common::{build_filepath, build_outfile_pathbuf};
+
+    // two-pages.pdf opening with a JavaScript action, and /AA on the catalog and second page
+    fn document_with_actions() -> Document {
+        let mut doc = Document::load(build_filepath("two-pages.pdf")).unwrap();
+        let script = doc.add_object(dictionary! { "S" => "JavaScript", "JS" => Object::string_literal("app.alert(1)") });
+        let second_page = *doc.get_pages().get(&2).unwrap();
+        doc.get_object_mut(second_page).unwrap().as_dict_mut().unwrap()
+            .set("AA", dictionary! { "O" => script });
+
+        let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        let catalog = doc.get_object_mut(root).unwrap().as_dict_mut().unwrap();
+        catalog.set("OpenAction", script);
+        catalog.set("AA", dictionary! { "WC" => script });
+        doc
+    }
+
+    #[test]
+    fn parse_open_destinations() {
+        assert_eq!(OpenDestination::parse("page=1,fit"), Ok(OpenDestination { page: 1, fit: Fit::Fit }));
+        assert_eq!(OpenDestination::parse("page=4"), Ok(OpenDestination { page: 4, fit: Fit::Fit }));
+        assert_eq!(OpenDestination::parse("FitH=700, page=2"), Ok(OpenDestination { page: 2, fit: Fit::FitH(Some(700.0)) }));
+        assert_eq!(OpenDestination::parse("page=3,xyz=::1.5"),
+                   Ok(OpenDestination { page: 3, fit: Fit::XYZ(None, None, Some(1.5)) }));
+        assert_eq!(OpenDestination::parse("page=1,fitr=0:0:300:400"),
+                   Ok(OpenDestination { page: 1, fit: Fit::FitR([0.0, 0.0, 300.0, 400.0]) }));
+
+        assert!(OpenDestination::parse("fit").is_err());
+        assert!(OpenDestination::parse("page=0").is_err());
+        assert!(OpenDestination::parse("page=1,fit,fitb").is_err());
+        assert!(OpenDestination::parse("page=1,fitr=0:0").is_err());
+        assert!(OpenDestination::parse("page=1,zoom").is_err());
+    }
+
+    #[test]
+    #[named]
+    fn set_open_action_writes_destination() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        document_with_actions().save(&infile).unwrap();
+
+        let destination = OpenDestination { page: 2, fit: Fit::XYZ(Some(72.0), None, Some(4.0)) };
+        actions::open_action(infile, Some(outfile.clone()), Some(destination), false, false, &SaveOptions::default());
+
+        let doc = Document::load(outfile).unwrap();
+        let second_page = *doc.get_pages().get(&2).unwrap();
+        let open_action = doc.catalog().unwrap().get(b"OpenAction").unwrap().as_array().unwrap();
+        assert_eq!(open_action.len(), 5);
+        assert_eq!(open_action[0].as_reference().unwrap(), second_page);
+        assert_eq!(open_action[1].as_name().unwrap(), b"XYZ");
+        assert_eq!(open_action[2].as_float().unwrap(), 72.0);
+        assert!(matches!(open_action[3], Object::Null));
+        assert_eq!(open_action[4].as_float().unwrap(), 4.0);
+        // additional actions are left alone unless asked for
+        assert!(doc.catalog().unwrap().has(b"AA"));
+    }
+
+    #[test]
+    fn set_open_action_rejects_missing_page() {
+        let mut doc = document_with_actions();
+
+        assert!(actions::set_open_action(&mut doc, &OpenDestination { page: 3, fit: Fit::Fit }).is_err());
+    }
+
+    #[test]
+    #[named]
+    fn clear_open_action_and_additional_actions() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        document_with_actions().save(&infile).unwrap();
+
+        actions::open_action(infile, Some(outfile.clone()), None, true, false, &SaveOptions::default());
+
+        let doc = Document::load(outfile).unwrap();
+        assert!(!doc.catalog().unwrap().has(b"OpenAction"));
+        assert!(!doc.catalog().unwrap().has(b"AA"));
+        assert!(doc.page_iter().all(|id| !doc.get_dictionary(id).unwrap().has(b"AA")));
+    }
+
+    #[test]
+    fn remove_open_action_prunes_its_action() {
+        let mut doc = document_with_actions();
+        let objects_before = doc.objects.len();
+
+        let report = sanitize::remove_open_action(&mut doc);
+
+        assert_eq!(report.entries, 2);
+        // the script is still used by the additional actions
+        assert_eq!(report.objects, 0);
+        assert!(!doc.catalog().unwrap().has(b"OpenAction"));
+
+        let report = sanitize::remove_additional_actions(&mut doc);
+
+        assert_eq!(report.entries, 4);
+        assert_eq!(report.objects, 1);
+        assert_eq!(doc.objects.len(), objects_before - 1);
+    }
+
+    #[test]
+    #[named]
+    fn sanitize_removes_openaction() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        document_with_actions().save(&infile).unwrap();
+
+        sanitize::sanitize(infile, Some(outfile.clone()), vec![Removal::OpenAction], false, &SaveOptions::default()).unwrap();
+
+        let doc = Document::load(outfile).unwrap();
+        assert!(!doc.catalog().unwrap().has(b"OpenAction"));
+        assert!(doc.catalog().unwrap().has(b"AA"));
+    }
+}
@@ -0,0 +1,123 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, time::{Duration, SystemTime}};
+    use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+    use pdfh::attachments;
+    use ::function_name::named;
+
+    use crate::common::{build_outfile_pathbuf, text_document};
+
+    const INVOICE: &[u8] = b"<?xml version=\"1.0\"?><Invoice><Total>42.00</Total></Invoice>";
+
+    fn output_dir(name: &str) -> PathBuf {
+        let dir = build_outfile_pathbuf(name).with_extension("");
+        fs::remove_dir_all(&dir).ok();
+        dir
+    }
+
+    fn filespec(doc: &mut Document, name: &str, data: &[u8], mod_date: Option<&str>) -> ObjectId {
+        let mut stream = Stream::new(dictionary! { "Type" => "EmbeddedFile" }, data.to_vec());
+        if let Some(date) = mod_date {
+            stream.dict.set("Params", dictionary! { "ModDate" => Object::string_literal(date) });
+        }
+        stream.compress().unwrap();
+        let stream_id = doc.add_object(stream);
+        doc.add_object(dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal(name),
+            "EF" => dictionary! { "F" => stream_id },
+        })
+    }
+
+    // a factur-x style invoice, two CSVs with the same name (one in a folder) in the name tree, and
+    // a file attachment annotation on page 2 whose file is also in the name tree
+    fn document_with_attachments() -> Document {
+        let mut doc = text_document(&["BT /F1 12 Tf 72 700 Td (Invoice) Tj ET", "BT /F1 12 Tf 72 700 Td (Terms) Tj ET"]);
+        let invoice = filespec(&mut doc, "factur-x.xml", INVOICE, Some("D:20240301120000+01'00'"));
+        let lines = filespec(&mut doc, "lines.csv", b"sku,qty\nA1,2\n", None);
+        let nested = filespec(&mut doc, "archive/lines.csv", b"sku,qty\nB7,1\n", None);
+        let terms = filespec(&mut doc, "terms.txt", b"Net 30", None);
+
+        let tree = doc.add_object(dictionary! {
+            "Names" => vec![
+                Object::string_literal("factur-x.xml"), invoice.into(),
+                Object::string_literal("lines.csv"), lines.into(),
+                Object::string_literal("nested"), nested.into(),
+                Object::string_literal("terms.txt"), terms.into(),
+            ],
+        });
+        let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        doc.get_object_mut(root).unwrap().as_dict_mut().unwrap()
+            .set("Names", dictionary! { "EmbeddedFiles" => tree });
+
+        let note = filespec(&mut doc, "note.txt", b"signed copy", None);
+        let second_page = *doc.get_pages().get(&2).unwrap();
+        let annots: Vec<Object> = [note, terms].iter()
+            .map(|fs| doc.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "FileAttachment",
+                "Rect" => vec![72.into(), 72.into(), 92.into(), 92.into()],
+                "FS" => *fs,
+            }).into())
+            .collect();
+        doc.get_object_mut(second_page).unwrap().as_dict_mut().unwrap().set("Annots", annots);
+        doc
+    }
+
+    #[test]
+    fn list_attachments_from_name_tree_and_annotations() {
+        let doc = document_with_attachments();
+
+        let found: Vec<(String, Option<u32>)> = attachments::list_attachments(&doc)
+            .into_iter()
+            .map(|a| (a.name, a.page))
+            .collect();
+
+        assert_eq!(found, vec![
+            ("factur-x.xml".to_string(), None),
+            ("lines.csv".to_string(), None),
+            ("archive/lines.csv".to_string(), None),
+            ("terms.txt".to_string(), None),
+            ("note.txt".to_string(), Some(2)),
+        ]);
+    }
+
+    #[test]
+    #[named]
+    fn extract_attachments_writes_every_file() {
+        let infile = build_outfile_pathbuf(function_name!());
+        let dir = output_dir(function_name!());
+        document_with_attachments().save(&infile).unwrap();
+
+        attachments::extract_attachments(infile, dir.clone(), false);
+
+        let mut names: Vec<String> = fs::read_dir(&dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["archive_lines.csv", "factur-x.xml", "lines.csv", "note.txt", "terms.txt"]);
+        assert_eq!(fs::read(dir.join("factur-x.xml")).unwrap(), INVOICE);
+        assert_eq!(fs::read(dir.join("archive_lines.csv")).unwrap(), b"sku,qty\nB7,1\n");
+        assert_eq!(fs::read(dir.join("note.txt")).unwrap(), b"signed copy");
+
+        // 2024-03-01 11:00:00 UTC
+        let modified = fs::metadata(dir.join("factur-x.xml")).unwrap().modified().unwrap();
+        assert_eq!(modified, SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_290_800));
+    }
+
+    #[test]
+    #[named]
+    fn extract_attachments_flattened_names_get_suffixes() {
+        let infile = build_outfile_pathbuf(function_name!());
+        let dir = output_dir(function_name!());
+        document_with_attachments().save(&infile).unwrap();
+
+        attachments::extract_attachments(infile, dir.clone(), true);
+
+        assert_eq!(fs::read(dir.join("lines.csv")).unwrap(), b"sku,qty\nA1,2\n");
+        assert_eq!(fs::read(dir.join("lines-1.csv")).unwrap(), b"sku,qty\nB7,1\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 5);
+    }
+}
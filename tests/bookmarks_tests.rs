@@ -0,0 +1,346 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use lopdf::{dictionary, Document, Object, ObjectId};
+    use pdfh::{bookmarks, dests, links, utils};
+    use pdfh::ops::PageSelection;
+    use pdfh::utils::SaveOptions;
+    use ::function_name::named;
+
+    use crate::common::{build_filepath, build_outfile_pathbuf, text_document};
+
+    // the resolved page number of every outline item, in outline order
+    fn outline_pages(doc: &Document) -> Vec<u32> {
+        let numbers: BTreeMap<ObjectId, u32> = doc.get_pages().into_iter().map(|(n, id)| (id, n)).collect();
+        bookmarks::outline_items(doc)
+            .into_iter()
+            .map(|id| {
+                let item = doc.get_dictionary(id).unwrap();
+                let dest = dests::goto_destination(doc, item).unwrap();
+                numbers[&dests::resolve_destination(doc, &dest).unwrap()]
+            })
+            .collect()
+    }
+
+    fn internal_link_pages(doc: &Document) -> Vec<u32> {
+        links::list_links(doc).into_iter().filter_map(|link| link.target_page).collect()
+    }
+
+    // chapters at 24pt and sections at 16pt over three pages, with 10pt body text and a page number
+    fn report_document() -> Document {
+        text_document(&[
+            "BT /F1 24 Tf 72 700 Td (Chapter One) Tj ET \
+             BT /F1 16 Tf 72 650 Td (Section A) Tj ET \
+             BT /F1 10 Tf 72 600 Td (Body text that is not a heading.) Tj ET",
+            "BT /F1 16 Tf 72 700 Td [(Section) -600 (B)] TJ ET \
+             BT /F1 10 Tf 72 650 Td (More body text.) Tj ET \
+             BT /F1 24 Tf 300 40 Td (2) Tj ET",
+            "BT /F1 12 Tf 2 0 0 2 72 700 Tm (Chapter Two) Tj ET",
+        ])
+    }
+
+    fn outline_titles(doc: &Document) -> Vec<(String, u32)> {
+        let numbers: BTreeMap<ObjectId, u32> = doc.get_pages().into_iter().map(|(n, id)| (id, n)).collect();
+        bookmarks::outline_items(doc)
+            .into_iter()
+            .map(|id| {
+                let item = doc.get_dictionary(id).unwrap();
+                let title = String::from_utf8_lossy(item.get(b"Title").unwrap().as_str().unwrap()).into_owned();
+                let dest = dests::goto_destination(doc, item).unwrap();
+                (title, numbers[&dests::resolve_destination(doc, &dest).unwrap()])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn find_headings_uses_font_size_tiers() {
+        let doc = report_document();
+
+        let headings = bookmarks::find_headings(&doc, 14.0, 2);
+
+        let found: Vec<(usize, u32, &str)> = headings.iter().map(|h| (h.level, h.page, h.title.as_str())).collect();
+        assert_eq!(found, vec![
+            (1, 1, "Chapter One"),
+            (2, 1, "Section A"),
+            (2, 2, "Section B"),
+            (1, 3, "Chapter Two"),
+        ]);
+    }
+
+    #[test]
+    #[named]
+    fn generate_bookmarks_builds_two_level_outline() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        report_document().save(&infile).unwrap();
+
+        bookmarks::generate_bookmarks(infile, Some(outfile.clone()), 14.0, 2, false, false, &SaveOptions::default());
+
+        let doc = Document::load(outfile).unwrap();
+        assert_eq!(outline_titles(&doc), vec![
+            ("Chapter One".to_string(), 1),
+            ("Section A".to_string(), 1),
+            ("Section B".to_string(), 2),
+            ("Chapter Two".to_string(), 3),
+        ]);
+        let items = bookmarks::outline_items(&doc);
+        let parent = |i: usize| doc.get_dictionary(items[i]).unwrap().get(b"Parent").unwrap().as_reference().unwrap();
+        assert_eq!(parent(1), items[0]);
+        assert_eq!(parent(2), items[0]);
+        assert_eq!(parent(3), parent(0));
+    }
+
+    #[test]
+    fn generate_bookmarks_with_one_level() {
+        let mut doc = report_document();
+
+        let headings = bookmarks::find_headings(&doc, 14.0, 1);
+        bookmarks::set_outline(&mut doc, &headings);
+
+        assert_eq!(outline_titles(&doc), vec![("Chapter One".to_string(), 1), ("Chapter Two".to_string(), 3)]);
+    }
+
+    #[test]
+    fn shift_bookmarks_forward_clamps_at_last_page() {
+        let mut doc = Document::load(build_filepath("single-pages-object-multi-page.pdf")).unwrap();
+        let before = outline_pages(&doc);
+        let links_before = internal_link_pages(&doc);
+
+        let report = bookmarks::shift_destinations(&mut doc, 1, false);
+
+        let expected: Vec<u32> = before.iter().map(|p| (p + 1).min(4)).collect();
+        assert_eq!(outline_pages(&doc), expected);
+        assert_eq!(report.bookmarks, before.len());
+        assert_eq!(report.clamped, before.iter().filter(|p| **p == 4).count());
+        assert_eq!(internal_link_pages(&doc), links_before);
+    }
+
+    #[test]
+    fn shift_bookmarks_and_links_back() {
+        let mut doc = Document::load(build_filepath("single-pages-object-multi-page.pdf")).unwrap();
+        let before = outline_pages(&doc);
+        let links_before = internal_link_pages(&doc);
+
+        let report = bookmarks::shift_destinations(&mut doc, -1, true);
+
+        let expected: Vec<u32> = before.iter().map(|p| (p - 1).max(1)).collect();
+        assert_eq!(outline_pages(&doc), expected);
+        let expected_links: Vec<u32> = links_before.iter().map(|p| p - 1).collect();
+        assert_eq!(internal_link_pages(&doc), expected_links);
+        assert_eq!(report.links, links_before.len());
+        assert_eq!(report.dropped, 0);
+    }
+
+    #[test]
+    fn shift_links_past_the_end_drops_them() {
+        let mut doc = Document::load(build_filepath("single-pages-object-multi-page.pdf")).unwrap();
+        let links_before = internal_link_pages(&doc);
+        let web_links = links::list_links(&doc).iter().filter(|link| link.kind == "uri").count();
+
+        let report = bookmarks::shift_destinations(&mut doc, 2, true);
+
+        let expected: Vec<u32> = links_before.iter().filter(|p| **p <= 2).map(|p| p + 2).collect();
+        assert_eq!(internal_link_pages(&doc), expected);
+        assert_eq!(report.dropped, links_before.len() - expected.len());
+        assert_eq!(links::list_links(&doc).iter().filter(|link| link.kind == "uri").count(), web_links);
+    }
+
+    #[test]
+    #[named]
+    fn shift_bookmarks_file() {
+        let infile = build_filepath("single-pages-object-multi-page.pdf");
+        let outfile = build_outfile_pathbuf(function_name!());
+        let before = outline_pages(&Document::load(&infile).unwrap());
+
+        bookmarks::shift_bookmarks(infile, Some(outfile.clone()), 1, true, false, &SaveOptions::default());
+
+        let doc = Document::load(outfile).unwrap();
+        let expected: Vec<u32> = before.iter().map(|p| (p + 1).min(4)).collect();
+        assert_eq!(outline_pages(&doc), expected);
+    }
+
+    fn set_key(doc: &mut Document, id: ObjectId, key: &str, value: Object) {
+        doc.get_object_mut(id).unwrap().as_dict_mut().unwrap().set(key, value);
+    }
+
+    // checks that siblings link both ways, parents point at their children and back, and returns
+    // the /Count of every item (0 when absent)
+    fn checked_counts(doc: &Document, parent: ObjectId) -> Vec<i64> {
+        let node = doc.get_dictionary(parent).unwrap();
+        let mut counts = Vec::new();
+        let mut previous: Option<ObjectId> = None;
+        let mut current = node.get(b"First").and_then(Object::as_reference).ok();
+        while let Some(id) = current {
+            let item = doc.get_dictionary(id).unwrap();
+            assert_eq!(item.get(b"Parent").unwrap().as_reference().unwrap(), parent);
+            assert_eq!(item.get(b"Prev").and_then(Object::as_reference).ok(), previous);
+            counts.push(item.get(b"Count").and_then(Object::as_i64).unwrap_or(0));
+            counts.extend(checked_counts(doc, id));
+            previous = Some(id);
+            current = item.get(b"Next").and_then(Object::as_reference).ok();
+        }
+        assert_eq!(node.get(b"Last").and_then(Object::as_reference).ok(), previous);
+        counts
+    }
+
+    #[test]
+    fn repair_outline_fixes_counts_and_links() {
+        let mut doc = Document::load(build_filepath("single-pages-object-multi-page.pdf")).unwrap();
+        let outlines_id = doc.catalog().unwrap().get(b"Outlines").unwrap().as_reference().unwrap();
+        let items = bookmarks::outline_items(&doc);
+        let counts_before = checked_counts(&doc, outlines_id);
+
+        set_key(&mut doc, outlines_id, "Count", 99.into());
+        doc.get_object_mut(items[1]).unwrap().as_dict_mut().unwrap().remove(b"Parent");
+        set_key(&mut doc, items[2], "Prev", items[0].into());
+        set_key(&mut doc, items[4], "Count", (-9).into());
+        let orphan = doc.add_object(dictionary! {
+            "Title" => Object::string_literal("Orphan"),
+            "Parent" => outlines_id,
+            "Prev" => *items.last().unwrap(),
+        });
+        set_key(&mut doc, outlines_id, "Last", orphan.into());
+
+        let fixes = bookmarks::repair_outline(&mut doc);
+
+        assert_eq!(fixes.len(), 6, "{:#?}", fixes);
+        assert!(doc.get_object(orphan).is_err());
+        assert_eq!(checked_counts(&doc, outlines_id), counts_before);
+        assert_eq!(doc.get_dictionary(outlines_id).unwrap().get(b"Count").unwrap().as_i64().unwrap(), 7);
+        assert!(bookmarks::repair_outline(&mut doc).is_empty());
+    }
+
+    #[test]
+    fn repair_outline_cuts_loops() {
+        let mut doc = Document::load(build_filepath("single-pages-object-multi-page.pdf")).unwrap();
+        let outlines_id = doc.catalog().unwrap().get(b"Outlines").unwrap().as_reference().unwrap();
+        let items = bookmarks::outline_items(&doc);
+        let last = *items.last().unwrap();
+        set_key(&mut doc, last, "Next", items[0].into());
+
+        let fixes = bookmarks::repair_outline(&mut doc);
+
+        assert_eq!(fixes.len(), 2, "{:#?}", fixes);
+        assert_eq!(bookmarks::outline_items(&doc), items);
+        checked_counts(&doc, outlines_id);
+    }
+
+    #[test]
+    #[named]
+    fn extract_drops_links_and_bookmarks_to_other_pages() {
+        let infile = build_filepath("single-pages-object-multi-page.pdf");
+        let outfile = build_outfile_pathbuf(function_name!());
+        let doc = Document::load(&infile).unwrap();
+        let links_before = links::list_links(&doc);
+        let kept_links = links_before.iter()
+            .filter(|l| [1, 3].contains(&l.page) && l.target_page.is_some_and(|p| [1, 3].contains(&p)))
+            .count();
+
+        utils::extract(infile, outfile.clone(), PageSelection::Pages(vec![1, 3]), false, false, false, false, false, false, &SaveOptions::default()).unwrap();
+
+        let doc = Document::load(outfile).unwrap();
+        let links_after = links::list_links(&doc);
+        assert!(links_after.iter().all(|l| !l.broken), "{:#?}", links_after);
+        assert_eq!(links_after.iter().filter(|l| l.kind == "internal").count(), kept_links);
+        let outlines_id = doc.catalog().unwrap().get(b"Outlines").unwrap().as_reference().unwrap();
+        checked_counts(&doc, outlines_id);
+        assert!(outline_pages(&doc).iter().all(|p| [1, 2].contains(p)));
+    }
+
+    #[test]
+    fn remap_to_pages_retargets_to_nearest_kept_page() {
+        let mut doc = Document::load(build_filepath("single-pages-object-multi-page.pdf")).unwrap();
+        let pages = doc.get_pages();
+        let items_before = bookmarks::outline_items(&doc).len();
+        let retargeted = links::list_links(&doc)
+            .into_iter()
+            .filter(|l| [1, 3].contains(&l.page) && l.target_page.is_some_and(|p| [2, 4].contains(&p)))
+            .count();
+        let keep = [pages[&1], pages[&3]].into_iter().collect();
+
+        let report = bookmarks::remap_to_pages(&mut doc, &keep, true);
+
+        assert_eq!(report.links_dropped, 0);
+        assert_eq!(report.bookmarks_dropped, 0);
+        assert_eq!(bookmarks::outline_items(&doc).len(), items_before);
+        // page 2 is as close to 1 as to 3 and goes to the earlier one, page 4 goes to 3
+        assert!(outline_pages(&doc).iter().all(|p| [1, 3].contains(p)));
+        let links_after: Vec<(u32, u32)> = links::list_links(&doc)
+            .into_iter()
+            .filter_map(|l| Some((l.page, l.target_page?)))
+            .filter(|(page, _)| [1, 3].contains(page))
+            .collect();
+        assert!(links_after.iter().all(|(_, target)| [1, 3].contains(target)));
+        assert!(retargeted > 0);
+        assert_eq!(report.links_retargeted, retargeted);
+    }
+
+    // twelve pages in three chapters, 2.1 opened through a named destination
+    fn chapters(path: &std::path::Path) -> std::path::PathBuf {
+        let contents: Vec<String> = (1..=12).map(|page| format!("BT ({}) Tj ET", page)).collect();
+        let mut doc = text_document(&contents.iter().map(String::as_str).collect::<Vec<_>>());
+        let heading = |level, page, title: &str| bookmarks::Heading { level, page, size: 12.0, title: title.to_string() };
+        bookmarks::set_outline(&mut doc, &[heading(1, 1, "1"), heading(2, 2, "1.1"),
+                                           heading(1, 5, "2"), heading(2, 6, "2.1"), heading(2, 7, "2.2"), heading(3, 8, "2.2.1"),
+                                           heading(1, 9, "3")]);
+        let page_6 = doc.get_pages()[&6];
+        let root_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        doc.get_object_mut(root_id).and_then(Object::as_dict_mut).unwrap()
+            .set("Dests", dictionary! { "results" => vec![page_6.into(), "Fit".into()] });
+        let item = bookmarks::outline_items(&doc)[3];
+        let item = doc.get_object_mut(item).and_then(Object::as_dict_mut).unwrap();
+        item.remove(b"Dest");
+        item.remove(b"A");
+        item.set("Dest", Object::Name(b"results".to_vec()));
+        doc.save(path).unwrap();
+        path.to_path_buf()
+    }
+
+    // The title, depth and page of every outline item, in outline order
+    fn titled_outline(doc: &Document) -> Vec<(String, usize, u32)> {
+        let numbers: BTreeMap<ObjectId, u32> = doc.get_pages().into_iter().map(|(n, id)| (id, n)).collect();
+        let root = doc.catalog().unwrap().get(b"Outlines").unwrap().as_reference().unwrap();
+        bookmarks::outline_items(doc).into_iter().map(|id| {
+            let item = doc.get_dictionary(id).unwrap();
+            let title = String::from_utf8_lossy(item.get(b"Title").unwrap().as_str().unwrap()).into_owned();
+            let mut depth = 0;
+            let mut parent = item.get(b"Parent").unwrap().as_reference().unwrap();
+            while parent != root {
+                depth += 1;
+                parent = doc.get_dictionary(parent).unwrap().get(b"Parent").unwrap().as_reference().unwrap();
+            }
+            let dest = dests::goto_destination(doc, item).unwrap();
+            (title, depth, numbers[&dests::resolve_destination(doc, &dest).unwrap()])
+        }).collect()
+    }
+
+    #[test]
+    #[named]
+    fn extract_keeps_the_bookmarks_of_the_chapter() {
+        let infile = chapters(&build_outfile_pathbuf(&format!("{}_in", function_name!())));
+        let outfile = build_outfile_pathbuf(function_name!());
+        let extract = |selection: PageSelection, drop_bookmarks: bool| {
+            utils::extract(infile.clone(), outfile.clone(), selection, false, false, drop_bookmarks, false, false, false, &SaveOptions::default()).unwrap();
+            Document::load(&outfile).unwrap()
+        };
+        let item = |title: &str, depth, page| (title.to_string(), depth, page);
+
+        let doc = extract(PageSelection::Pages(vec![5, 6, 7, 8]), false);
+        assert_eq!(titled_outline(&doc), [item("2", 0, 1), item("2.1", 1, 2), item("2.2", 1, 3), item("2.2.1", 2, 4)]);
+        checked_counts(&doc, doc.catalog().unwrap().get(b"Outlines").unwrap().as_reference().unwrap());
+
+        // the items of pages that are left out go, their children move up
+        let doc = extract(PageSelection::Pages(vec![2, 6, 8]), false);
+        assert_eq!(titled_outline(&doc), [item("1.1", 0, 1), item("2.1", 0, 2), item("2.2.1", 0, 3)]);
+
+        // without any item left there is no outline
+        let doc = extract(PageSelection::Pages(vec![3, 4]), false);
+        assert!(!doc.catalog().unwrap().has(b"Outlines"));
+
+        let doc = extract(PageSelection::Pages(vec![5, 6, 7, 8]), true);
+        assert!(!doc.catalog().unwrap().has(b"Outlines"));
+        assert!(bookmarks::outline_items(&doc).is_empty());
+    }
+}
@@ -0,0 +1,146 @@
+// Shared helpers for the integration tests. Not every test file uses every helper.
+#![allow(dead_code)]
+
+use std::{io, path::PathBuf};
+
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use pdfh::prompt::PasswordPrompt;
+
+pub const DATA_DIR: &str = "test-data";
+
+pub fn build_filepath(filename: &str) -> PathBuf {
+    PathBuf::from(format!("{}/{}", DATA_DIR, filename))
+}
+
+pub fn build_outfile_pathbuf(filename: &str) -> PathBuf {
+    PathBuf::from(format!("{}/output/{}.pdf", DATA_DIR, filename))
+}
+
+/// Adds a link annotation with the given action (or /Dest) entries to a page
+pub fn add_link_annotation(doc: &mut Document, page: u32, rect: [f64; 4], entries: Dictionary) -> ObjectId {
+    let page_id = *doc.get_pages().get(&page).unwrap();
+    let mut annot = dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Link",
+        "Rect" => rect.iter().map(|n| Object::Real(*n)).collect::<Vec<_>>(),
+        "Border" => vec![0.into(), 0.into(), 0.into()],
+    };
+    for (key, value) in entries.iter() {
+        annot.set(key.clone(), value.clone());
+    }
+    let annot_id = doc.add_object(annot);
+
+    let page_dict = doc.get_object_mut(page_id).unwrap().as_dict_mut().unwrap();
+    match page_dict.get_mut(b"Annots") {
+        Ok(Object::Array(annots)) => annots.push(annot_id.into()),
+        _ => page_dict.set("Annots", vec![annot_id.into()]),
+    }
+    annot_id
+}
+
+/// Builds a letter sized document with one page per content stream. The pages share a Courier
+/// font named /F1, whose glyphs are all 600 units wide.
+pub fn text_document(contents: &[&str]) -> Document {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Courier",
+    });
+    let resources_id = doc.add_object(dictionary! {
+        "Font" => dictionary! { "F1" => font_id },
+    });
+
+    let mut kids: Vec<Object> = Vec::new();
+    for content in contents {
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        kids.push(page_id.into());
+    }
+
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Count" => kids.len() as i64,
+        "Kids" => kids,
+        "Resources" => resources_id,
+        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+    }));
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+    doc
+}
+
+/// Answers password prompts from a list, recording each prompt it was shown
+pub struct ScriptedPrompt {
+    pub interactive: bool,
+    pub answers: Vec<String>,
+    pub prompts: Vec<String>,
+}
+
+impl ScriptedPrompt {
+    pub fn new(interactive: bool, answers: &[&str]) -> ScriptedPrompt {
+        ScriptedPrompt {
+            interactive,
+            answers: answers.iter().rev().map(|a| a.to_string()).collect(),
+            prompts: Vec::new(),
+        }
+    }
+}
+
+impl PasswordPrompt for ScriptedPrompt {
+    fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    fn read_password(&mut self, prompt: &str) -> io::Result<String> {
+        self.prompts.push(prompt.to_string());
+        self.answers.pop().ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "no more answers"))
+    }
+}
+
+/// A two page text_document with a signed signature field "Approval" on page one. The signature
+/// dictionary has the shape of a real one, but its /Contents is not a valid PKCS#7 signature.
+pub fn signed_document() -> Document {
+    let mut doc = text_document(&["BT /F1 12 Tf 72 700 Td (Contract) Tj ET", "BT /F1 12 Tf 72 700 Td (Terms) Tj ET"]);
+    let first_page = *doc.get_pages().get(&1).unwrap();
+
+    let signature = doc.add_object(dictionary! {
+        "Type" => "Sig",
+        "Filter" => "Adobe.PPKLite",
+        "SubFilter" => "adbe.pkcs7.detached",
+        "ByteRange" => vec![0.into(), 100.into(), 200.into(), 300.into()],
+        "Contents" => Object::String(vec![0; 64], lopdf::StringFormat::Hexadecimal),
+        "M" => Object::string_literal("D:20240301120000Z"),
+    });
+    let appearance = doc.add_object(Stream::new(dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Form",
+        "BBox" => vec![0.into(), 0.into(), 200.into(), 40.into()],
+    }, b"0 g 0 0 200 2 re f".to_vec()));
+    let field = doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Widget",
+        "FT" => "Sig",
+        "T" => Object::string_literal("Approval"),
+        "V" => signature,
+        "Rect" => vec![72.into(), 100.into(), 272.into(), 140.into()],
+        "P" => first_page,
+        "AP" => dictionary! { "N" => appearance },
+    });
+    doc.get_object_mut(first_page).unwrap().as_dict_mut().unwrap().set("Annots", vec![field.into()]);
+
+    let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+    doc.get_object_mut(root).unwrap().as_dict_mut().unwrap().set("AcroForm", dictionary! {
+        "Fields" => vec![field.into()],
+        "SigFlags" => 3,
+    });
+    doc
+}
@@ -0,0 +1,64 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+    use std::process::Command;
+
+    use lopdf::Document;
+    use pdfh::compat;
+    use ::function_name::named;
+
+    use crate::common::{build_filepath, build_outfile_pathbuf};
+
+    fn os_args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    fn run(args: &[&str]) -> std::process::Output {
+        Command::new(env!("CARGO_BIN_EXE_pdfh")).args(args).output().unwrap()
+    }
+
+    #[test]
+    fn output_keyword_dropped_for_pdftk_verbs() {
+        let args = os_args(&["tpdf", "cat", "a.pdf", "b.pdf", "output", "out.pdf", "-c"]);
+
+        assert_eq!(compat::strip_output_keyword(args), os_args(&["tpdf", "cat", "a.pdf", "b.pdf", "out.pdf", "-c"]));
+    }
+
+    #[test]
+    fn output_keyword_only_with_compat() {
+        let args = os_args(&["tpdf", "merge", "a.pdf", "output", "out.pdf"]);
+        assert_eq!(compat::strip_output_keyword(args.clone()), args);
+
+        let args = os_args(&["tpdf", "--compat", "pdftk", "merge", "a.pdf", "output", "out.pdf"]);
+        assert_eq!(compat::strip_output_keyword(args), os_args(&["tpdf", "--compat", "pdftk", "merge", "a.pdf", "out.pdf"]));
+    }
+
+    #[test]
+    #[named]
+    fn cat_behaves_like_merge() {
+        let inputs = [build_filepath("two-pages.pdf"), build_filepath("one-page-with-image.pdf")];
+        let inputs: Vec<&str> = inputs.iter().map(|path| path.to_str().unwrap()).collect();
+        let merged = build_outfile_pathbuf(&format!("{}_merge", function_name!()));
+        let catted = build_outfile_pathbuf(&format!("{}_cat", function_name!()));
+
+        assert!(run(&["merge", inputs[0], inputs[1], merged.to_str().unwrap()]).status.success());
+        assert!(run(&["cat", inputs[0], inputs[1], "output", catted.to_str().unwrap()]).status.success());
+
+        let merged = Document::load(merged).unwrap();
+        let catted = Document::load(catted).unwrap();
+        assert_eq!(catted.get_pages().len(), merged.get_pages().len());
+        for (a, b) in catted.get_pages().values().zip(merged.get_pages().values()) {
+            assert_eq!(catted.get_page_content(*a).unwrap(), merged.get_page_content(*b).unwrap());
+        }
+    }
+
+    #[test]
+    fn aliases_listed_in_help() {
+        let help = String::from_utf8(run(&["--help"]).stdout).unwrap();
+
+        assert!(help.contains("merge") && help.contains("[aliases: cat]"), "{}", help);
+        assert!(help.contains("[aliases: burst]"), "{}", help);
+    }
+}
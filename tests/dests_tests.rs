@@ -0,0 +1,101 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use lopdf::{dictionary, Document, Object, StringFormat};
+    use pdfh::{dests, names};
+    use pdfh::utils::SaveOptions;
+    use ::function_name::named;
+
+    use crate::common::{build_filepath, build_outfile_pathbuf, text_document};
+
+    // three pages with destinations to the first and last, then the last page deleted the way
+    // other tools do it, leaving its destinations behind
+    fn document_with_dangling_dests(tree_size: usize) -> Document {
+        let mut doc = text_document(&["", "", ""]);
+        let pages = doc.get_pages();
+        let fit = |page: u32| Object::Array(vec![pages[&page].into(), "Fit".into()]);
+
+        let mut names_array = Vec::new();
+        for i in 0..tree_size {
+            names_array.push(Object::String(format!("valid.{:03}", i).into_bytes(), StringFormat::Literal));
+            names_array.push(fit(1));
+        }
+        names_array.push(Object::String(b"zz.dangling".to_vec(), StringFormat::Literal));
+        names_array.push(fit(3));
+        let tree_id = doc.add_object(dictionary! { "Names" => names_array });
+
+        let catalog_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        let catalog = doc.get_object_mut(catalog_id).unwrap().as_dict_mut().unwrap();
+        catalog.set("Names", dictionary! { "Dests" => tree_id });
+        catalog.set("Dests", dictionary! { "old" => fit(3), "intro" => fit(1) });
+
+        doc.delete_pages(&[3]);
+        doc
+    }
+
+    #[test]
+    fn named_destinations_reports_dangling_entries() {
+        let doc = document_with_dangling_dests(1);
+
+        let found: BTreeMap<String, Option<u32>> = dests::named_destinations(&doc)
+            .into_iter()
+            .map(|d| (d.name, d.page))
+            .collect();
+
+        assert_eq!(found.len(), 4);
+        assert_eq!(found["intro"], Some(1));
+        assert_eq!(found["valid.000"], Some(1));
+        assert_eq!(found["old"], None);
+        assert_eq!(found["zz.dangling"], None);
+    }
+
+    #[test]
+    fn remove_dangling_destinations_keeps_valid_ones() {
+        let mut doc = document_with_dangling_dests(1);
+
+        let removed = dests::remove_dangling_destinations(&mut doc);
+
+        assert_eq!(removed, 2);
+        let remaining: Vec<String> = dests::named_destinations(&doc).into_iter().map(|d| d.name).collect();
+        assert_eq!(remaining, vec!["intro", "valid.000"]);
+        assert!(dests::named_destination(&doc, b"valid.000").is_some());
+        assert!(dests::named_destination(&doc, b"zz.dangling").is_none());
+    }
+
+    #[test]
+    fn remove_dangling_destinations_rebuilds_large_trees() {
+        let mut doc = document_with_dangling_dests(150);
+
+        assert_eq!(dests::remove_dangling_destinations(&mut doc), 2);
+
+        let catalog = doc.catalog().unwrap();
+        let names = doc.dereference(catalog.get(b"Names").unwrap()).unwrap().1.as_dict().unwrap();
+        let root = names.get(b"Dests").unwrap().clone();
+        let root_dict = doc.dereference(&root).unwrap().1.as_dict().unwrap();
+        let kids = root_dict.get(b"Kids").unwrap().as_array().unwrap();
+        assert_eq!(kids.len(), 3);
+        let last_leaf = doc.dereference(&kids[2]).unwrap().1.as_dict().unwrap();
+        let limits = last_leaf.get(b"Limits").unwrap().as_array().unwrap();
+        assert_eq!(limits[1].as_str().unwrap(), b"valid.149");
+
+        let entries = names::name_tree_entries(&doc, &root);
+        assert_eq!(entries.len(), 150);
+        assert!(names::name_tree_lookup(&doc, &root, b"valid.075").is_some());
+    }
+
+    #[test]
+    #[named]
+    fn prune_dests_leaves_resolving_destinations_alone() {
+        let infile = build_filepath("single-pages-object-multi-page.pdf");
+        let outfile = build_outfile_pathbuf(function_name!());
+        let before = dests::named_destinations(&Document::load(&infile).unwrap());
+
+        dests::prune_dests(infile, Some(outfile.clone()), false, &SaveOptions::default());
+
+        let after = dests::named_destinations(&Document::load(outfile).unwrap());
+        assert_eq!(after.len(), before.len());
+        assert!(after.iter().all(|d| d.page.is_some()));
+    }
+}
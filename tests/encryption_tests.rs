@@ -0,0 +1,203 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use lopdf::{Document, Object};
+    use pdfh::encryption::{self, DecryptError};
+    use pdfh::error::{Error, LoadError};
+    use pdfh::utils::{self, SaveOptions};
+    use ::function_name::named;
+
+    use crate::common::{build_filepath, build_outfile_pathbuf, ScriptedPrompt};
+
+    // the fixtures have two pages reading "Quarterly report" and "Appendix", and a title in /Info
+    fn check_decrypted(doc: &Document) {
+        assert!(!encryption::is_encrypted(doc));
+        let pages = doc.get_pages();
+        assert_eq!(pages.len(), 2);
+        let first = String::from_utf8(doc.get_page_content(pages[&1]).unwrap()).unwrap();
+        assert!(first.contains("(Quarterly report) Tj"), "{}", first);
+        let second = String::from_utf8(doc.get_page_content(pages[&2]).unwrap()).unwrap();
+        assert!(second.contains("(Appendix) Tj"), "{}", second);
+
+        let info = doc.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let title = doc.get_dictionary(info).unwrap().get(b"Title").and_then(Object::as_str).unwrap();
+        assert_eq!(title, b"Restricted report");
+    }
+
+    #[test]
+    fn decrypt_rc4_restrictions_only() {
+        let data = fs::read(build_filepath("restrictions-only.pdf")).unwrap();
+        assert!(encryption::is_encrypted(&Document::load_mem(&data).unwrap()));
+
+        let doc = encryption::decrypt_pdf(&data, b"").unwrap();
+
+        check_decrypted(&doc);
+    }
+
+    #[test]
+    fn decrypt_aes_with_object_streams() {
+        let data = fs::read(build_filepath("restrictions-only-aes.pdf")).unwrap();
+
+        let doc = encryption::decrypt_pdf(&data, b"").unwrap();
+
+        check_decrypted(&doc);
+    }
+
+    #[test]
+    fn decrypt_with_owner_password() {
+        let data = fs::read(build_filepath("restrictions-only.pdf")).unwrap();
+
+        check_decrypted(&encryption::decrypt_pdf(&data, b"owner").unwrap());
+    }
+
+    #[test]
+    fn decrypt_aes256_needs_user_password() {
+        let data = fs::read(build_filepath("password-protected.pdf")).unwrap();
+
+        assert_eq!(encryption::decrypt_pdf(&data, b"").err(), Some(DecryptError::WrongPassword));
+        assert_eq!(encryption::decrypt_pdf(&data, b"wrong").err(), Some(DecryptError::WrongPassword));
+        check_decrypted(&encryption::decrypt_pdf(&data, b"secret").unwrap());
+        check_decrypted(&encryption::decrypt_pdf(&data, b"owner").unwrap());
+    }
+
+    #[test]
+    fn decrypt_unencrypted_document() {
+        let data = fs::read(build_filepath("two-pages.pdf")).unwrap();
+
+        assert_eq!(encryption::decrypt_pdf(&data, b"").err(), Some(DecryptError::NotEncrypted));
+    }
+
+    #[test]
+    #[named]
+    fn decrypt_restrictions_only_write_out() {
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let mut prompt = ScriptedPrompt::new(true, &[]);
+
+        encryption::decrypt(build_filepath("restrictions-only.pdf"), Some(outfile.clone()), None, &mut prompt, false, &SaveOptions::default());
+
+        assert!(prompt.prompts.is_empty(), "asked for a password that was not needed");
+
+        let doc = Document::load(outfile).unwrap();
+        check_decrypted(&doc);
+    }
+
+    #[test]
+    #[named]
+    #[should_panic(expected = "needs a user password")]
+    fn decrypt_restrictions_only_refuses_user_password() {
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        // stdin is not a terminal, so there is no one to ask
+        encryption::decrypt(build_filepath("password-protected.pdf"), Some(outfile), None, &mut ScriptedPrompt::new(false, &["secret"]), false, &SaveOptions::default());
+    }
+
+    #[test]
+    #[named]
+    fn decrypt_prompts_for_user_password() {
+        let outfile = build_outfile_pathbuf(function_name!());
+        let mut prompt = ScriptedPrompt::new(true, &["secret"]);
+
+        encryption::decrypt(build_filepath("password-protected.pdf"), Some(outfile.clone()), None, &mut prompt, false, &SaveOptions::default());
+
+        assert_eq!(prompt.prompts.len(), 1);
+        check_decrypted(&Document::load(outfile).unwrap());
+    }
+
+    fn run(args: &[&str]) -> std::process::Output {
+        std::process::Command::new(env!("CARGO_BIN_EXE_pdfh")).args(args).env_remove("RUST_BACKTRACE").output().unwrap()
+    }
+
+    #[test]
+    #[named]
+    fn rotate_with_password() {
+        let outfile = build_outfile_pathbuf(function_name!());
+        let infile = build_filepath("password-protected.pdf");
+
+        let output = run(&["rotate", infile.to_str().unwrap(), outfile.to_str().unwrap(), "--degrees", "90", "--password", "secret"]);
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let doc = Document::load(&outfile).unwrap();
+        check_decrypted(&doc);
+        assert!(doc.get_pages().values().all(|id| doc.get_dictionary(*id).unwrap().get(b"Rotate").unwrap().as_i64().unwrap() == 90));
+    }
+
+    #[test]
+    #[named]
+    fn wrong_and_missing_password() {
+        let outfile = build_outfile_pathbuf(function_name!());
+        let infile = build_filepath("password-protected.pdf");
+        let rotate = |extra: &[&str]| {
+            let output = run(&[&["rotate", infile.to_str().unwrap(), outfile.to_str().unwrap(), "--degrees", "90"], extra].concat());
+            assert!(!output.status.success());
+            String::from_utf8_lossy(&output.stderr).into_owned()
+        };
+
+        let missing = rotate(&[]);
+        assert!(missing.contains("the file is encrypted, supply its password with --password"), "{}", missing);
+        let wrong = rotate(&["--password", "wrong"]);
+        assert!(wrong.contains("the password is not correct"), "{}", wrong);
+    }
+
+    #[test]
+    #[named]
+    fn restrictions_only_open_without_password() {
+        let outfile = build_outfile_pathbuf(function_name!());
+        let infile = build_filepath("restrictions-only-aes.pdf");
+
+        let output = run(&["rotate", infile.to_str().unwrap(), outfile.to_str().unwrap(), "--degrees", "90"]);
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        check_decrypted(&Document::load(&outfile).unwrap());
+    }
+
+    #[test]
+    #[named]
+    fn merge_with_password_per_input() {
+        let outfile = build_outfile_pathbuf(function_name!());
+        let protected = build_filepath("password-protected.pdf");
+
+        let output = run(&[
+            "merge", &format!("{}::secret", protected.display()), build_filepath("two-pages.pdf").to_str().unwrap(),
+            &format!("{}::secret:2", protected.display()), outfile.to_str().unwrap(),
+        ]);
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let doc = Document::load(&outfile).unwrap();
+        assert!(!encryption::is_encrypted(&doc));
+        assert_eq!(doc.get_pages().len(), 2 + 2 + 1);
+    }
+
+    #[test]
+    #[named]
+    fn merge_password_with_colons() {
+        let outfile = build_outfile_pathbuf(function_name!());
+        let protected = build_filepath("password-protected.pdf");
+
+        let with_password = |given: String| utils::split_password(Path::new(&given));
+        assert_eq!(with_password(format!("{}::se:cret", protected.display())), Some((protected.clone(), "se:cret".to_string())));
+        assert_eq!(with_password(format!("{}:::", protected.display())), Some((protected.clone(), ":".to_string())));
+        assert_eq!(with_password(format!("{}:secret", protected.display())), None);
+
+        // the whole password is tried, not taken for a path and a shorter password
+        let merged = utils::merge(&vec![PathBuf::from(format!("{}::wrong:one", protected.display()))], outfile, false, false,
+                                  utils::SortOrder::Name, false, false, None, false, &utils::DocumentInfo::default());
+        assert!(matches!(merged, Err(Error::LoadFailed { source: LoadError::WrongPassword, .. })), "{:?}", merged);
+    }
+
+    #[test]
+    #[named]
+    fn incremental_update_of_encrypted_input_refused() {
+        let outfile = build_outfile_pathbuf(function_name!());
+        fs::copy(build_filepath("restrictions-only.pdf"), &outfile).unwrap();
+
+        let output = run(&["rotate", outfile.to_str().unwrap(), "--degrees", "90", "--incremental"]);
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("the input is encrypted"));
+        assert!(encryption::is_encrypted(&Document::load(&outfile).unwrap()));
+    }
+}
@@ -0,0 +1,174 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use pdfh::fetch;
+
+    #[test]
+    fn recognizes_urls() {
+        assert!(fetch::is_url(&PathBuf::from("http://example.com/a.pdf")));
+        assert!(fetch::is_url(&PathBuf::from("HTTPS://example.com/a.pdf")));
+        assert!(!fetch::is_url(&PathBuf::from("test-data/two-pages.pdf")));
+        assert!(!fetch::is_url(&PathBuf::from("http.pdf")));
+    }
+
+    #[test]
+    fn failed_download_is_an_error() {
+        // nothing listens on the discard port, and builds without the http feature refuse URLs
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(["extract", "http://127.0.0.1:9/a.pdf", "test-data/output/failed_download_is_an_error.pdf", "--pages", "1"])
+            .output()
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with("error: Failed to download http://127.0.0.1:9/a.pdf: "), "{}", stderr);
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+}
+
+#[cfg(all(test, feature = "http"))]
+mod http_tests {
+    use std::fs;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    use lopdf::Document;
+    use pdfh::{fetch, utils};
+    use pdfh::error::Error;
+    use ::function_name::named;
+
+    use crate::common::{build_filepath, build_outfile_pathbuf};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // Serves the fixture, redirects, errors and a stalled response on a local port
+    fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" { break; }
+                    request.push(line.trim_end().to_string());
+                }
+                let path = request[0].split(' ').nth(1).unwrap().to_string();
+                let authorized = request.iter().any(|line| line == "Authorization: Basic dXNlcjpwYXNz");
+
+                let (status, headers, body) = match path.as_str() {
+                    "/two-pages.pdf" => ("200 OK", String::new(), fs::read(build_filepath("two-pages.pdf")).unwrap()),
+                    "/moved" => ("302 Found", "Location: /two-pages.pdf\r\n".to_string(), Vec::new()),
+                    "/chunked.pdf" => {
+                        let pdf = fs::read(build_filepath("two-pages.pdf")).unwrap();
+                        let mut body = Vec::new();
+                        for chunk in pdf.chunks(1000) {
+                            body.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+                            body.extend_from_slice(chunk);
+                            body.extend_from_slice(b"\r\n");
+                        }
+                        body.extend_from_slice(b"0\r\n\r\n");
+                        ("200 OK", "Transfer-Encoding: chunked\r\n".to_string(), body)
+                    }
+                    "/private.pdf" if authorized => ("200 OK", String::new(), fs::read(build_filepath("two-pages.pdf")).unwrap()),
+                    "/private.pdf" => ("401 Unauthorized", String::new(), Vec::new()),
+                    "/page.html" => ("200 OK", String::new(), b"<html>not a pdf</html>".to_vec()),
+                    "/stalled.pdf" => {
+                        std::thread::sleep(Duration::from_secs(3));
+                        continue;
+                    }
+                    _ => ("404 Not Found", String::new(), Vec::new()),
+                };
+                let length = if headers.contains("chunked") { String::new() } else { format!("Content-Length: {}\r\n", body.len()) };
+                let _ = stream.write_all(format!("HTTP/1.1 {}\r\n{}{}\r\n", status, headers, length).as_bytes());
+                let _ = stream.write_all(&body);
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    #[test]
+    fn fetches_fixture() {
+        let server = serve();
+        let expected = fs::read(build_filepath("two-pages.pdf")).unwrap();
+
+        assert_eq!(fetch::fetch(&format!("{}/two-pages.pdf", server), TIMEOUT).unwrap(), expected);
+        assert_eq!(fetch::fetch(&format!("{}/moved", server), TIMEOUT).unwrap(), expected);
+        assert_eq!(fetch::fetch(&format!("{}/chunked.pdf", server), TIMEOUT).unwrap(), expected);
+        assert_eq!(fetch::fetch(&format!("{}/private.pdf", server.replace("http://", "http://user:pass@")), TIMEOUT).unwrap(), expected);
+    }
+
+    #[test]
+    fn reports_failures() {
+        let server = serve();
+
+        assert_eq!(fetch::fetch(&format!("{}/missing.pdf", server), TIMEOUT).unwrap_err(), "HTTP 404 Not Found");
+        assert_eq!(fetch::fetch(&format!("{}/private.pdf", server), TIMEOUT).unwrap_err(), "HTTP 401 Unauthorized");
+        assert_eq!(fetch::fetch(&format!("{}/stalled.pdf", server), Duration::from_secs(1)).unwrap_err(), "timed out after 1s");
+    }
+
+    #[test]
+    fn speaks_tls_to_https_urls() {
+        // a server that answers in plain HTTP, the TLS handshake fails on its reply
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+        });
+
+        let error = fetch::fetch(&format!("https://{}/two-pages.pdf", address), TIMEOUT).unwrap_err();
+        assert!(error.contains("corrupt message"), "{}", error);
+    }
+
+    #[test]
+    fn refuses_non_pdf() {
+        let server = serve();
+        match fetch::localize(vec![format!("{}/page.html", server).into()], TIMEOUT, &mut Vec::new()) {
+            Err(Error::DownloadFailed { url, reason }) => {
+                assert_eq!(url, format!("{}/page.html", server));
+                assert_eq!(reason, "the response is not a PDF");
+            }
+            other => panic!("expected the HTML page to be refused, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[named]
+    fn merges_url_with_local_file() {
+        let server = serve();
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let mut downloads = Vec::new();
+        let infiles = fetch::localize(vec![format!("{}/two-pages.pdf", server).into(), build_filepath("two-pages.pdf")], TIMEOUT, &mut downloads).unwrap();
+        utils::merge(&infiles, outfile.clone(), false, false, utils::SortOrder::Name, false, false, None, false, &utils::DocumentInfo::default()).unwrap();
+
+        assert_eq!(Document::load(&outfile).unwrap().get_pages().len(), 4);
+        let local = downloads[0].path().to_path_buf();
+        assert!(local.exists());
+        drop(downloads);
+        assert!(!local.exists(), "the downloaded copy is removed");
+    }
+
+    #[test]
+    fn summarizes_url() {
+        let server = serve();
+        let url = format!("{}/two-pages.pdf", server);
+
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(["--json", "info", &url, &format!("{}/missing.pdf", server)])
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+        let entries: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(entries[0]["path"], url.as_str());
+        assert_eq!(entries[0]["pages"], 2);
+        assert_eq!(entries[1]["error"], format!("Failed to download {}/missing.pdf: HTTP 404 Not Found", server).as_str());
+    }
+}
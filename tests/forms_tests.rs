@@ -0,0 +1,343 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fs};
+    use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+    use pdfh::forms::{self, ExportFormat, FieldValue};
+    use pdfh::utils::SaveOptions;
+    use ::function_name::named;
+
+    use crate::common::{build_outfile_pathbuf, signed_document, text_document};
+
+    fn widget(doc: &mut Document, page: ObjectId, entries: Dictionary) -> ObjectId {
+        let mut widget = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "Rect" => vec![72.into(), 600.into(), 272.into(), 620.into()],
+            "P" => page,
+        };
+        for (key, value) in entries.iter() {
+            widget.set(key.clone(), value.clone());
+        }
+        let id = doc.add_object(widget);
+        let page = doc.get_object_mut(page).unwrap().as_dict_mut().unwrap();
+        match page.get_mut(b"Annots") {
+            Ok(Object::Array(annots)) => annots.push(id.into()),
+            _ => page.set("Annots", vec![id.into()]),
+        }
+        id
+    }
+
+    // normal appearances for the given states of a checkbox or radio button
+    fn states(doc: &mut Document, names: &[&str]) -> Dictionary {
+        let mut normal = Dictionary::new();
+        for name in names {
+            let id = doc.add_object(Stream::new(dictionary! {}, b"0 g 0 0 10 10 re f".to_vec()));
+            normal.set(name.as_bytes().to_vec(), id);
+        }
+        dictionary! { "N" => normal }
+    }
+
+    // a one page form with a text field, two text fields under a parent, a checkbox, a radio
+    // group with two buttons, a combo box with a default, a multi-select list and a push button
+    fn form_document() -> Document {
+        let mut doc = text_document(&["BT /F1 12 Tf 72 700 Td (Application form) Tj ET"]);
+        let page = *doc.get_pages().get(&1).unwrap();
+
+        let name = widget(&mut doc, page, dictionary! { "FT" => "Tx", "T" => Object::string_literal("name") });
+
+        let street = widget(&mut doc, page, dictionary! { "T" => Object::string_literal("street") });
+        let city = widget(&mut doc, page, dictionary! { "T" => Object::string_literal("city") });
+        let address = doc.add_object(dictionary! {
+            "FT" => "Tx",
+            "T" => Object::string_literal("address"),
+            "Kids" => vec![street.into(), city.into()],
+        });
+        for kid in [street, city] {
+            doc.get_object_mut(kid).unwrap().as_dict_mut().unwrap().set("Parent", address);
+        }
+
+        let ap = states(&mut doc, &["Yes", "Off"]);
+        let subscribe = widget(&mut doc, page, dictionary! {
+            "FT" => "Btn", "T" => Object::string_literal("subscribe"), "AS" => "Off", "AP" => ap,
+        });
+
+        let small_ap = states(&mut doc, &["S", "Off"]);
+        let large_ap = states(&mut doc, &["L", "Off"]);
+        let small = widget(&mut doc, page, dictionary! { "AS" => "Off", "AP" => small_ap });
+        let large = widget(&mut doc, page, dictionary! { "AS" => "Off", "AP" => large_ap });
+        let size = doc.add_object(dictionary! {
+            "FT" => "Btn",
+            "Ff" => 1 << 15,
+            "T" => Object::string_literal("size"),
+            "Kids" => vec![small.into(), large.into()],
+        });
+        for kid in [small, large] {
+            doc.get_object_mut(kid).unwrap().as_dict_mut().unwrap().set("Parent", size);
+        }
+
+        let colour = widget(&mut doc, page, dictionary! {
+            "FT" => "Ch",
+            "Ff" => 1 << 17,
+            "T" => Object::string_literal("colour"),
+            "Opt" => vec![Object::string_literal("Red"), Object::string_literal("Green"), Object::string_literal("Blue")],
+            "DV" => Object::string_literal("Green"),
+        });
+        let toppings = widget(&mut doc, page, dictionary! {
+            "FT" => "Ch",
+            "Ff" => 1 << 21,
+            "T" => Object::string_literal("toppings"),
+            "Opt" => vec![
+                vec![Object::string_literal("ham"), Object::string_literal("Ham")].into(),
+                vec![Object::string_literal("pineapple"), Object::string_literal("Pineapple")].into(),
+            ],
+        });
+        let submit = widget(&mut doc, page, dictionary! { "FT" => "Btn", "Ff" => 1 << 16, "T" => Object::string_literal("submit") });
+
+        let fields: Vec<Object> = [name, address, subscribe, size, colour, toppings, submit].into_iter().map(Object::from).collect();
+        let acroform = doc.add_object(dictionary! { "Fields" => fields });
+        let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        doc.get_object_mut(root).unwrap().as_dict_mut().unwrap().set("AcroForm", acroform);
+        doc
+    }
+
+    fn text(value: &str) -> Option<FieldValue> {
+        Some(FieldValue::Text(value.to_string()))
+    }
+
+    fn filled_values() -> BTreeMap<String, Option<FieldValue>> {
+        BTreeMap::from([
+            ("name".to_string(), text("Zoë (Test)")),
+            ("address.city".to_string(), text("Ghent")),
+            ("subscribe".to_string(), text("Yes")),
+            ("size".to_string(), text("L")),
+            ("colour".to_string(), text("Blue")),
+            ("toppings".to_string(), Some(FieldValue::List(vec!["ham".to_string(), "pineapple".to_string()]))),
+        ])
+    }
+
+    fn listing(doc: &Document) -> Vec<(String, String, Option<FieldValue>)> {
+        forms::list_fields(doc).into_iter().map(|f| (f.name, f.kind, f.value)).collect()
+    }
+
+    #[test]
+    fn list_fields_names_kinds_and_options() {
+        let doc = form_document();
+
+        let fields = forms::list_fields(&doc);
+
+        let names: Vec<(&str, &str)> = fields.iter().map(|f| (f.name.as_str(), f.kind.as_str())).collect();
+        assert_eq!(names, vec![
+            ("name", "text"),
+            ("address.street", "text"),
+            ("address.city", "text"),
+            ("subscribe", "checkbox"),
+            ("size", "radio"),
+            ("colour", "choice"),
+            ("toppings", "choice"),
+            ("submit", "pushbutton"),
+        ]);
+        assert!(fields.iter().all(|f| f.value.is_none()));
+        assert_eq!(fields[3].options, vec!["Yes"]);
+        assert_eq!(fields[4].options, vec!["S", "L"]);
+        assert_eq!(fields[5].default, text("Green"));
+        assert_eq!(fields[6].options, vec!["ham", "pineapple"]);
+    }
+
+    #[test]
+    fn fill_fields_sets_values_and_states() {
+        let mut doc = form_document();
+
+        assert_eq!(forms::fill_fields(&mut doc, &filled_values()), Ok(6));
+
+        let values: Vec<Option<FieldValue>> = forms::list_fields(&doc).into_iter().map(|f| f.value).collect();
+        assert_eq!(values, vec![
+            text("Zoë (Test)"), None, text("Ghent"), text("Yes"), text("L"), text("Blue"),
+            Some(FieldValue::List(vec!["ham".to_string(), "pineapple".to_string()])), None,
+        ]);
+        let page = *doc.get_pages().get(&1).unwrap();
+        let appearance_states: Vec<Vec<u8>> = doc.get_dictionary(page).unwrap().get(b"Annots").unwrap().as_array().unwrap()
+            .iter()
+            .map(|annot| doc.get_dictionary(annot.as_reference().unwrap()).unwrap())
+            .filter_map(|annot| annot.get(b"AS").and_then(Object::as_name).ok().map(<[u8]>::to_vec))
+            .collect();
+        assert_eq!(appearance_states, vec![b"Yes".to_vec(), b"Off".to_vec(), b"L".to_vec()]);
+    }
+
+    #[test]
+    fn fill_fields_rejects_unknown_fields_and_values() {
+        let mut doc = form_document();
+
+        assert!(forms::fill_fields(&mut doc, &BTreeMap::from([("nope".to_string(), text("x"))])).is_err());
+        assert!(forms::fill_fields(&mut doc, &BTreeMap::from([("size".to_string(), text("XL"))])).is_err());
+        assert!(forms::fill_fields(&mut doc, &BTreeMap::from([("submit".to_string(), text("x"))])).is_err());
+    }
+
+    #[test]
+    #[named]
+    fn export_and_fill_round_trip() {
+        let blank = build_outfile_pathbuf(&format!("{}_blank", function_name!()));
+        let filled = build_outfile_pathbuf(&format!("{}_filled", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        let data = blank.with_extension("json");
+        form_document().save(&blank).unwrap();
+        let mut doc = form_document();
+        forms::fill_fields(&mut doc, &filled_values()).unwrap();
+        doc.save(&filled).unwrap();
+
+        forms::export_fields(filled.clone(), data.clone(), ExportFormat::Json);
+        forms::fill_form(blank, Some(outfile.clone()), data.clone(), false, &SaveOptions::default());
+
+        let exported: serde_json::Value = serde_json::from_str(&fs::read_to_string(&data).unwrap()).unwrap();
+        assert!(exported["address.street"].is_null());
+        assert!(exported.get("submit").is_none());
+        let original = Document::load(filled).unwrap();
+        let copy = Document::load(outfile).unwrap();
+        assert_eq!(listing(&copy), listing(&original));
+    }
+
+    #[test]
+    #[named]
+    fn export_fdf_nests_fields() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let data = infile.with_extension("fdf");
+        let mut doc = form_document();
+        forms::fill_fields(&mut doc, &filled_values()).unwrap();
+        doc.save(&infile).unwrap();
+
+        forms::export_fields(infile, data.clone(), ExportFormat::Fdf);
+
+        let fdf = fs::read_to_string(data).unwrap();
+        assert!(fdf.starts_with("%FDF-1.2\n"));
+        assert!(fdf.contains("<< /T (address) /Kids [\n<< /T (city) /V (Ghent) >>\n<< /T (street) >>\n] >>"));
+        assert!(fdf.contains("<< /T (name) /V <FEFF005A006F00EB0020002800540065007300740029> >>"));
+        assert!(fdf.contains("<< /T (size) /V /L >>"));
+        assert!(fdf.contains("<< /T (toppings) /V [(ham) (pineapple)] >>"));
+        assert!(!fdf.contains("submit"));
+        assert!(fdf.trim_end().ends_with("%%EOF"));
+    }
+
+    #[test]
+    #[named]
+    fn reset_form_restores_defaults() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        let mut doc = form_document();
+        forms::fill_fields(&mut doc, &filled_values()).unwrap();
+        doc.save(&infile).unwrap();
+
+        forms::reset_form(infile, Some(outfile.clone()), vec![], false, &SaveOptions::default());
+
+        let doc = Document::load(outfile).unwrap();
+        let expected: Vec<(String, String, Option<FieldValue>)> = listing(&form_document())
+            .into_iter()
+            .map(|(name, kind, value)| if name == "colour" { (name, kind, text("Green")) } else { (name, kind, value) })
+            .collect();
+        assert_eq!(listing(&doc), expected);
+        let page = *doc.get_pages().get(&1).unwrap();
+        let off = doc.get_dictionary(page).unwrap().get(b"Annots").unwrap().as_array().unwrap()
+            .iter()
+            .map(|annot| doc.get_dictionary(annot.as_reference().unwrap()).unwrap())
+            .filter_map(|annot| annot.get(b"AS").and_then(Object::as_name).ok())
+            .all(|state| state == b"Off");
+        assert!(off);
+        assert_eq!(forms::list_fields(&doc).len(), 8);
+    }
+
+    #[test]
+    fn reset_fields_only_named() {
+        let mut doc = form_document();
+        forms::fill_fields(&mut doc, &filled_values()).unwrap();
+
+        let reset = forms::reset_fields(&mut doc, &["name".to_string(), "size".to_string()]);
+
+        assert_eq!(reset, Ok(2));
+        let values: BTreeMap<String, Option<FieldValue>> = forms::list_fields(&doc).into_iter().map(|f| (f.name, f.value)).collect();
+        assert_eq!(values["name"], None);
+        assert_eq!(values["size"], None);
+        assert_eq!(values["address.city"], text("Ghent"));
+        assert_eq!(values["subscribe"], text("Yes"));
+        assert!(forms::reset_fields(&mut doc, &["nope".to_string()]).is_err());
+    }
+
+    #[test]
+    fn signed_fields_found() {
+        assert_eq!(forms::signed_fields(&signed_document()), vec!["Approval".to_string()]);
+        assert!(forms::signed_fields(&text_document(&["BT ET"])).is_empty());
+    }
+
+    #[test]
+    #[named]
+    #[should_panic(expected = "is digitally signed (Approval)")]
+    fn rewriting_signed_document_refused() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        signed_document().save(&infile).unwrap();
+
+        forms::reset_form(infile, Some(outfile), vec![], false, &SaveOptions::default());
+    }
+
+    #[test]
+    #[named]
+    fn rewriting_signed_document_with_break_signatures() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        signed_document().save(&infile).unwrap();
+        let save = SaveOptions { break_signatures: true, ..SaveOptions::default() };
+
+        forms::reset_form(infile, Some(outfile.clone()), vec![], false, &save);
+
+        let doc = Document::load(outfile).unwrap();
+        assert_eq!(doc.get_pages().len(), 2);
+    }
+
+    fn has_signature_dictionary(doc: &Document) -> bool {
+        doc.objects.values().any(|object| match object {
+            Object::Dictionary(dict) => dict.has(b"ByteRange"),
+            _ => false,
+        })
+    }
+
+    #[test]
+    #[named]
+    fn remove_signatures_write_out() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        let mut doc = signed_document();
+        let signature = doc.objects.iter()
+            .find(|(_, object)| object.as_dict().is_ok_and(|dict| dict.has(b"ByteRange")))
+            .map(|(id, _)| *id)
+            .unwrap();
+        let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        doc.get_object_mut(root).unwrap().as_dict_mut().unwrap()
+            .set("Perms", dictionary! { "DocMDP" => signature });
+        doc.save(&infile).unwrap();
+
+        forms::remove_signatures(infile, Some(outfile.clone()), false, false, &SaveOptions::default());
+
+        let doc = Document::load(outfile).unwrap();
+        assert!(forms::list_fields(&doc).is_empty());
+        assert!(!has_signature_dictionary(&doc));
+        assert!(!doc.catalog().unwrap().has(b"Perms"));
+        let pages = doc.get_pages();
+        assert_eq!(pages.len(), 2);
+        assert!(!doc.get_dictionary(pages[&1]).unwrap().has(b"Annots"));
+    }
+
+    #[test]
+    fn remove_signatures_flatten_first() {
+        let mut doc = signed_document();
+
+        assert_eq!(forms::remove_signature_fields(&mut doc, true), 1);
+
+        assert!(forms::signed_fields(&doc).is_empty());
+        let first = *doc.get_pages().get(&1).unwrap();
+        let content = String::from_utf8(doc.get_page_content(first).unwrap()).unwrap();
+        assert!(content.contains("(Contract) Tj"), "{}", content);
+        assert!(content.contains("1 0 0 1 72 100 cm /SigAp"), "{}", content);
+        let resources = doc.get_dictionary(first).unwrap().get(b"Resources").unwrap().as_dict().unwrap();
+        assert_eq!(resources.get(b"XObject").unwrap().as_dict().unwrap().len(), 1);
+        assert!(resources.has(b"Font"));
+    }
+}
@@ -0,0 +1,143 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use lopdf::{dictionary, Object};
+    use ::function_name::named;
+
+    use pdfh::bookmarks::{self, Heading};
+    use pdfh::info;
+
+    use crate::common::{build_filepath, build_outfile_pathbuf, text_document};
+
+    fn run(args: &[&str]) -> std::process::Output {
+        Command::new(env!("CARGO_BIN_EXE_pdfh")).args(args).output().unwrap()
+    }
+
+    #[test]
+    fn page_counts_of_the_fixtures() {
+        for (fixture, pages) in [("two-pages.pdf", 2), ("one-page-with-image.pdf", 1), ("single-pages-object-multi-page.pdf", 4),
+                                 ("multiple-pages-objects-multi-page.pdf", 164), ("form-order.pdf", 1)] {
+            let summary = info::summarize(&build_filepath(fixture)).unwrap();
+            assert_eq!(summary.pages, pages, "{}", fixture);
+            assert!(!summary.encrypted);
+        }
+        assert!(info::summarize(&build_filepath("form-order.pdf")).unwrap().has_acroform);
+        assert!(!info::summarize(&build_filepath("two-pages.pdf")).unwrap().has_acroform);
+    }
+
+    #[test]
+    fn encrypted_without_the_password() {
+        let summary = info::summarize(&build_filepath("password-protected.pdf")).unwrap();
+
+        assert!(summary.encrypted);
+        assert_eq!(summary.pages, 2);
+        assert_eq!(summary.title, None);
+    }
+
+    #[test]
+    #[named]
+    fn document_information_and_outline() {
+        let path = build_outfile_pathbuf(function_name!());
+        let mut doc = text_document(&["BT (1) Tj ET", "BT (2) Tj ET", "BT (3) Tj ET"]);
+        doc.trailer.set("Info", dictionary! {
+            "Title" => Object::string_literal("Annual report"),
+            "Author" => Object::string_literal(""),
+            "Producer" => Object::string_literal("scanner 2.1"),
+        });
+        let heading = |level, page, title: &str| Heading { level, page, size: 12.0, title: title.to_string() };
+        bookmarks::set_outline(&mut doc, &[heading(1, 1, "One"), heading(2, 2, "One.One"), heading(1, 3, "Two")]);
+        doc.save(&path).unwrap();
+
+        let summary = info::summarize(&path).unwrap();
+        assert_eq!(summary.title.as_deref(), Some("Annual report"));
+        assert_eq!(summary.producer.as_deref(), Some("scanner 2.1"));
+        // empty and missing fields are left out
+        assert_eq!((summary.author, summary.subject), (None, None));
+        assert_eq!(summary.bookmarks, Some(2));
+
+        let text = info::summarize(&path).unwrap().to_string();
+        assert!(text.starts_with(&format!("{}\n  Pages:        3\n  Version:      1.5\n  Encrypted:    no\n", path.display())), "{}", text);
+        assert!(text.contains("  Title:        Annual report\n"), "{}", text);
+        assert!(text.ends_with("  Bookmarks:    2 top level\n  Form:         no\n"), "{}", text);
+        assert!(!text.contains("Author"), "{}", text);
+    }
+
+    #[test]
+    fn info_cli() {
+        let output = run(&["info", "test-data/two-pages.pdf", "test-data/notapdf.txt", "test-data/form-order.pdf"]);
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let files: Vec<&str> = stdout.split("\n\n").collect();
+        assert_eq!(files.len(), 2, "{}", stdout);
+        assert!(files[0].starts_with("test-data/two-pages.pdf\n  Pages:        2\n"), "{}", stdout);
+        assert!(files[1].starts_with("test-data/form-order.pdf\n  Pages:        1\n"), "{}", stdout);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("skipped test-data/notapdf.txt"));
+
+        let output = run(&["--json", "info", "test-data/single-pages-object-multi-page.pdf"]);
+        let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(summary["pages"], 4);
+        assert_eq!(summary["bookmarks"], 7);
+    }
+
+    #[test]
+    #[named]
+    fn dates_in_rfc3339() {
+        let path = build_outfile_pathbuf(function_name!());
+        let dated = |created: &str, modified: &str| {
+            let mut doc = text_document(&["BT (1) Tj ET"]);
+            doc.trailer.set("Info", dictionary! {
+                "CreationDate" => Object::string_literal(created),
+                "ModDate" => Object::string_literal(modified),
+            });
+            doc.save(&path).unwrap();
+            let summary = info::summarize(&path).unwrap();
+            (summary.created.unwrap(), summary.modified.unwrap())
+        };
+
+        assert_eq!(dated("D:20230415093000+02'00'", "D:20230415093000-05'30"),
+                   ("2023-04-15T09:30:00+02:00".to_string(), "2023-04-15T09:30:00-05:30".to_string()));
+        assert_eq!(dated("D:20230415093000Z", "D:2023"),
+                   ("2023-04-15T09:30:00Z".to_string(), "2023-01-01T00:00:00-00:00".to_string()));
+        // dates that do not parse are kept as they are
+        assert_eq!(dated("D:20231345", "last Tuesday"),
+                   ("D:20231345".to_string(), "last Tuesday".to_string()));
+    }
+
+    #[test]
+    fn info_json() {
+        let output = run(&["info", "test-data/two-pages.pdf", "--json"]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let summary: info::Summary = serde_json::from_slice(&output.stdout).unwrap();
+
+        assert_eq!((summary.pages, summary.version.as_str(), summary.encrypted), (2, "1.4", false));
+        assert_eq!(summary.file_size, std::fs::metadata("test-data/two-pages.pdf").unwrap().len());
+        assert_eq!((summary.title, summary.author), (None, None));
+        assert_eq!(summary.created.as_deref(), Some("2022-06-02T01:32:13+00:00"));
+        assert_eq!(summary.modified.as_deref(), Some("2022-06-02T01:32:13+00:00"));
+        assert!(!summary.has_outline && !summary.has_acroform);
+
+        // absent values are null, not left out
+        let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        for key in ["pages", "version", "encrypted", "title", "author", "created", "modified", "has_outline", "has_acroform", "file_size"] {
+            assert!(value.get(key).is_some(), "{} is missing", key);
+        }
+        assert!(value["title"].is_null());
+
+        // a file that does not load gets an error object and the others are still summarized
+        let output = run(&["--json", "info", "test-data/notapdf.txt", "test-data/form-order.pdf"]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let entries: Vec<info::Entry> = serde_json::from_slice(&output.stdout).unwrap();
+        match entries.as_slice() {
+            [info::Entry::Failed { path, error }, info::Entry::Summary(summary)] => {
+                assert_eq!(path.to_str(), Some("test-data/notapdf.txt"));
+                assert!(!error.is_empty());
+                assert!(summary.has_acroform);
+            }
+            other => panic!("expected an error and a summary, got {:?}", other),
+        }
+    }
+}
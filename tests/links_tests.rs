@@ -0,0 +1,209 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use lopdf::{dictionary, Document, Object};
+    use pdfh::links::{self, LinkTarget, NewLink};
+    use pdfh::utils::SaveOptions;
+    use ::function_name::named;
+
+    use crate::common::{add_link_annotation, build_filepath, build_outfile_pathbuf};
+
+    #[derive(Debug, Default, PartialEq)]
+    struct LinkCounts {
+        uri: usize,
+        internal: usize,
+        other: usize,
+    }
+
+    fn count_links(doc: &Document) -> LinkCounts {
+        let mut counts = LinkCounts::default();
+        for (_, page_id) in doc.get_pages() {
+            let page = doc.get_dictionary(page_id).unwrap();
+            let annots = match page.get(b"Annots") {
+                Ok(annots) => doc.dereference(annots).unwrap().1.as_array().unwrap().clone(),
+                Err(_) => continue,
+            };
+            for annot in annots {
+                let annot = doc.dereference(&annot).unwrap().1.as_dict().unwrap();
+                match links::link_target(doc, annot) {
+                    Some(LinkTarget::Uri(_)) => counts.uri += 1,
+                    Some(LinkTarget::Internal(_)) => counts.internal += 1,
+                    Some(_) => counts.other += 1,
+                    None => {}
+                }
+            }
+        }
+        counts
+    }
+
+    // two-pages.pdf with its links replaced by one web link and one link to the second page
+    fn document_with_two_links() -> Document {
+        let mut doc = Document::load(build_filepath("two-pages.pdf")).unwrap();
+        links::remove_links(&mut doc, false, &[]);
+        let second_page = *doc.get_pages().get(&2).unwrap();
+
+        add_link_annotation(&mut doc, 1, [72.0, 700.0, 300.0, 720.0], dictionary! {
+            "A" => dictionary! { "S" => "URI", "URI" => Object::string_literal("https://example.com/docs") },
+        });
+        add_link_annotation(&mut doc, 1, [72.0, 600.0, 300.0, 620.0], dictionary! {
+            "Dest" => vec![second_page.into(), "Fit".into()],
+        });
+        doc
+    }
+
+    #[test]
+    fn list_links_external_and_internal() {
+        let doc = document_with_two_links();
+
+        let found = links::list_links(&doc);
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].page, 1);
+        assert_eq!(found[0].kind, "uri");
+        assert_eq!(found[0].target.as_deref(), Some("https://example.com/docs"));
+        assert_eq!(found[0].rect, vec![72.0, 700.0, 300.0, 720.0]);
+        assert_eq!(found[1].kind, "internal");
+        assert_eq!(found[1].target_page, Some(2));
+        assert!(!found[1].broken);
+    }
+
+    #[test]
+    fn list_links_resolves_named_destinations() {
+        let doc = Document::load(build_filepath("single-pages-object-multi-page.pdf")).unwrap();
+
+        let found = links::list_links(&doc);
+
+        assert_eq!(found.len(), 17);
+        assert!(found.iter().all(|link| !link.broken));
+        assert_eq!(found[0].target.as_deref(), Some("section.1"));
+        assert_eq!(found[0].target_page, Some(2));
+        let external: Vec<_> = found.iter().filter(|link| link.kind == "uri").collect();
+        assert_eq!(external.len(), 1);
+        assert_eq!(external[0].page, 3);
+    }
+
+    #[test]
+    fn list_links_flags_broken_destinations() {
+        let mut doc = document_with_two_links();
+        add_link_annotation(&mut doc, 2, [72.0, 500.0, 300.0, 520.0], dictionary! {
+            "A" => dictionary! { "S" => "GoTo", "D" => Object::string_literal("no-such-destination") },
+        });
+
+        let found = links::list_links(&doc);
+        let broken: Vec<_> = found.iter().filter(|link| link.broken).collect();
+
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].page, 2);
+        assert_eq!(broken[0].target.as_deref(), Some("no-such-destination"));
+    }
+
+    #[test]
+    fn uri_host_strips_user_info_and_port() {
+        assert_eq!(links::uri_host("https://user:pw@Docs.Example.com:8080/a?b#c"), Some("docs.example.com".to_string()));
+        assert_eq!(links::uri_host("http://example.com"), Some("example.com".to_string()));
+        assert_eq!(links::uri_host("mailto:someone@example.com"), None);
+    }
+
+    #[test]
+    fn remove_links_all() {
+        let mut doc = Document::load(build_filepath("single-pages-object-multi-page.pdf")).unwrap();
+        assert_eq!(count_links(&doc), LinkCounts { uri: 1, internal: 16, other: 0 });
+
+        let removed = links::remove_links(&mut doc, false, &[]);
+
+        assert_eq!(removed, 17);
+        assert_eq!(count_links(&doc), LinkCounts::default());
+    }
+
+    #[test]
+    fn remove_links_external_only_keeps_internal() {
+        let mut doc = Document::load(build_filepath("single-pages-object-multi-page.pdf")).unwrap();
+
+        let removed = links::remove_links(&mut doc, true, &[]);
+
+        assert_eq!(removed, 1);
+        assert_eq!(count_links(&doc), LinkCounts { uri: 0, internal: 16, other: 0 });
+    }
+
+    #[test]
+    fn remove_links_by_domain() {
+        let mut doc = Document::load(build_filepath("two-pages.pdf")).unwrap();
+        let before = count_links(&doc);
+
+        let removed = links::remove_links(&mut doc, false, &["github.com".to_string()]);
+        let after = count_links(&doc);
+
+        assert!(removed > 0);
+        assert_eq!(after.uri, before.uri - removed);
+        assert_eq!(after.internal, before.internal);
+
+        // a second pass has nothing left to remove and other domains survive
+        assert_eq!(links::remove_links(&mut doc, false, &["github.com".to_string()]), 0);
+        assert!(links::remove_links(&mut doc, false, &["docs.rs".to_string()]) > 0);
+    }
+
+    #[test]
+    fn remove_links_domain_ignores_internal_links() {
+        let mut doc = Document::load(build_filepath("single-pages-object-multi-page.pdf")).unwrap();
+
+        let removed = links::remove_links(&mut doc, false, &["example.com".to_string()]);
+
+        assert_eq!(removed, 0);
+        assert_eq!(count_links(&doc), LinkCounts { uri: 1, internal: 16, other: 0 });
+    }
+
+    #[test]
+    #[named]
+    fn strip_links_external_only_write_out() {
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        links::strip_links(build_filepath("single-pages-object-multi-page.pdf"), Some(outfile.clone()), true, vec![], false, &SaveOptions::default());
+
+        let doc = Document::load(outfile).unwrap();
+        assert_eq!(count_links(&doc), LinkCounts { uri: 0, internal: 16, other: 0 });
+    }
+
+    #[test]
+    fn add_link_creates_annots_array() {
+        let mut doc = Document::load(build_filepath("two-pages.pdf")).unwrap();
+        let second_page = *doc.get_pages().get(&2).unwrap();
+        assert!(!doc.get_dictionary(second_page).unwrap().has(b"Annots"));
+
+        links::add_link(&mut doc, 2, [72.0, 700.0, 300.0, 720.0], NewLink::Uri("https://example.com".to_string())).unwrap();
+
+        let on_second_page: Vec<_> = links::list_links(&doc).into_iter().filter(|link| link.page == 2).collect();
+        assert_eq!(on_second_page.len(), 1);
+        assert_eq!(on_second_page[0].kind, "uri");
+        assert_eq!(on_second_page[0].target.as_deref(), Some("https://example.com"));
+        assert_eq!(on_second_page[0].rect, vec![72.0, 700.0, 300.0, 720.0]);
+    }
+
+    #[test]
+    fn add_link_rejects_bad_placement() {
+        let mut doc = Document::load(build_filepath("two-pages.pdf")).unwrap();
+        let uri = NewLink::Uri("https://example.com".to_string());
+
+        assert!(links::add_link(&mut doc, 3, [72.0, 700.0, 300.0, 720.0], uri.clone()).is_err());
+        assert!(links::add_link(&mut doc, 1, [72.0, 700.0, 300.0, 900.0], uri.clone()).is_err());
+        assert!(links::add_link(&mut doc, 1, [72.0, 700.0, 72.0, 720.0], uri).is_err());
+        assert!(links::add_link(&mut doc, 1, [72.0, 700.0, 300.0, 720.0], NewLink::GoTo(5)).is_err());
+    }
+
+    #[test]
+    #[named]
+    fn add_goto_link_write_out() {
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        links::add_link_to_file(build_filepath("single-pages-object-multi-page.pdf"), Some(outfile.clone()),
+                                4, [300.0, 72.0, 72.0, 100.0], NewLink::GoTo(1), &SaveOptions::default());
+
+        let doc = Document::load(outfile).unwrap();
+        let added = links::list_links(&doc).pop().unwrap();
+        assert_eq!(added.page, 4);
+        assert_eq!(added.kind, "internal");
+        assert_eq!(added.target_page, Some(1));
+        // corners are normalized so the rectangle runs lower left to upper right
+        assert_eq!(added.rect, vec![72.0, 72.0, 300.0, 100.0]);
+    }
+}
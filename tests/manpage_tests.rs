@@ -0,0 +1,69 @@
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use pdfh::manpage;
+
+    // The subcommands and their aliases as `tpdf --help` lists them, straight from the CLI
+    fn subcommands() -> Vec<(String, Vec<String>)> {
+        let output = Command::new(env!("CARGO_BIN_EXE_pdfh")).arg("--help").output().unwrap();
+        let help = String::from_utf8(output.stdout).unwrap();
+        let section = &help[help.find("SUBCOMMANDS:").unwrap()..];
+        section.lines().skip(1)
+            // continuation lines of a long about are indented further
+            .filter(|line| line.starts_with("    ") && !line.starts_with("     "))
+            .filter_map(|line| {
+                let name = line.split_whitespace().next()?.to_string();
+                let aliases = line.split_once("[aliases: ")
+                    .map(|(_, rest)| rest.trim_end_matches(']').split(", ").map(str::to_string).collect())
+                    .unwrap_or_default();
+                Some((name, aliases))
+            })
+            // clap adds help when the command is built, the page only documents ours
+            .filter(|(name, _)| name != "help")
+            .collect()
+    }
+
+    fn generate() -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_pdfh")).arg("manpage").output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn manpage_lists_every_subcommand() {
+        let page = generate();
+
+        assert!(page.contains("\n.TH TPDF 1 "));
+        let subcommands = subcommands();
+        assert!(subcommands.len() > 20, "{:?}", subcommands);
+        for (name, aliases) in subcommands {
+            assert!(page.contains(&format!(".SS \"tpdf {}\"", name)), "no section for {}", name);
+            if !aliases.is_empty() {
+                assert!(page.contains(&format!("Aliases: {}", aliases.join(", "))), "no aliases for {}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn manpage_documents_flags_and_examples() {
+        let page = generate();
+
+        for flag in ["\\fB\\-\\-pages\\fR", "\\fB\\-\\-break\\-signature\\fR", "\\fB\\-\\-remove\\-signatures\\fR", "\\fB\\-\\-profile\\fR"] {
+            assert!(page.contains(flag), "{} is missing", flag);
+        }
+        assert!(page.contains("Possible values: strict, standard"));
+        for example in manpage::EXAMPLES {
+            assert!(page.contains(&format!("tpdf {}", example.args.replace('-', "\\-").replace('\'', "\\*(Aq"))), "{}", example.args);
+        }
+    }
+
+    #[test]
+    fn manpage_write_failure_is_an_error() {
+        // a directory cannot be written over
+        let output = Command::new(env!("CARGO_BIN_EXE_pdfh")).args(["manpage", "-o"]).arg(std::env::temp_dir()).output().unwrap();
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to write out file"), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+}
@@ -0,0 +1,650 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    use std::collections::BTreeMap;
+
+    use lopdf::{dictionary, Document, Object, StringFormat};
+    use ::function_name::named;
+
+    use pdfh::{dests, forms, links, names, utils};
+    use pdfh::ops::Position;
+
+    use crate::common::{add_link_annotation, build_filepath, build_outfile_pathbuf, text_document, DATA_DIR};
+
+    fn run(args: &[&str]) -> std::process::Output {
+        Command::new(env!("CARGO_BIN_EXE_pdfh")).args(args).output().unwrap()
+    }
+
+    fn page_count(path: &PathBuf) -> usize {
+        Document::load(path).unwrap().get_pages().len()
+    }
+
+    #[test]
+    #[named]
+    fn last_path_is_the_output() {
+        let inputs = [build_filepath("two-pages.pdf"), build_filepath("single-pages-object-multi-page.pdf"), build_filepath("one-page-with-image.pdf")];
+        let outfile = build_outfile_pathbuf(function_name!());
+        // an existing PDF in the output's place must be replaced, not merged
+        std::fs::copy(&inputs[0], &outfile).unwrap();
+
+        let output = run(&[
+            "merge", inputs[0].to_str().unwrap(), inputs[1].to_str().unwrap(), inputs[2].to_str().unwrap(), outfile.to_str().unwrap(),
+        ]);
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(page_count(&outfile), inputs.iter().map(page_count).sum::<usize>());
+    }
+
+    #[test]
+    #[named]
+    fn directory_with_compress() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for fixture in ["two-pages.pdf", "single-pages-object-multi-page.pdf"] {
+            std::fs::copy(build_filepath(fixture), dir.join(fixture)).unwrap();
+        }
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let output = run(&["merge", dir.to_str().unwrap(), outfile.to_str().unwrap(), "--compress"]);
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(page_count(&outfile), 2 + 4);
+    }
+
+    #[test]
+    fn needs_an_input_and_an_output() {
+        let output = run(&["merge", build_filepath("two-pages.pdf").to_str().unwrap()]);
+
+        assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("At least one input is required, test-data/two-pages.pdf is the file to write to"), "{}", stderr);
+    }
+
+    // the fixture pages have a different width for each file: a 100, b/c 200, b/d/e 300, m 250, z 400
+    fn page_widths(path: &PathBuf) -> Vec<i64> {
+        let doc = Document::load(path).unwrap();
+        doc.get_pages().values()
+            .map(|id| doc.get_dictionary(*id).unwrap().get(b"MediaBox").unwrap().as_array().unwrap()[2].as_i64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    #[named]
+    fn directory_tree_flat_and_recursive() {
+        let tree = build_filepath("tree");
+        let flat = build_outfile_pathbuf(&format!("{}_flat", function_name!()));
+        let recursive = build_outfile_pathbuf(&format!("{}_recursive", function_name!()));
+
+        let output = run(&["merge", tree.to_str().unwrap(), flat.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let output = run(&["merge", "-r", tree.to_str().unwrap(), recursive.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        // .Pdf and .PDF count as PDFs, notes.txt does not
+        assert_eq!(page_widths(&flat), vec![100, 250, 400]);
+        assert_eq!(page_widths(&recursive), vec![100, 200, 300, 250, 400]);
+    }
+
+    #[test]
+    #[named]
+    fn directory_sort_orders() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // page1.pdf is the newest, so that modification time order is the reverse of natural order
+        let now = std::time::SystemTime::now();
+        for (age, (name, fixture)) in [("page1.pdf", "a.pdf"), ("page2.pdf", "b/c.Pdf"), ("page10.pdf", "b/d/e.PDF")].into_iter().enumerate() {
+            let path = dir.join(name);
+            std::fs::copy(build_filepath("tree").join(fixture), &path).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(now - std::time::Duration::from_secs(60 * (age as u64 + 1))).unwrap();
+        }
+        let first = build_filepath("tree/z.pdf");
+
+        let merge = |sort: &str| {
+            let outfile = build_outfile_pathbuf(&format!("{}_{}", function_name!(), sort));
+            let output = run(&["merge", first.to_str().unwrap(), dir.to_str().unwrap(), outfile.to_str().unwrap(), "--sort", sort]);
+            assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+            page_widths(&outfile)
+        };
+
+        // the file given before the directory stays first
+        assert_eq!(merge("name"), vec![400, 100, 300, 200]);
+        assert_eq!(merge("natural"), vec![400, 100, 200, 300]);
+        assert_eq!(merge("mtime"), vec![400, 300, 200, 100]);
+    }
+
+    fn merge_inputs(inputs: &[&str], outfile: &Path) -> Result<utils::SaveReport, pdfh::Error> {
+        let inputs: Vec<PathBuf> = inputs.iter().map(PathBuf::from).collect();
+        utils::merge(&inputs, outfile.to_path_buf(), false, false, utils::SortOrder::Name, false, false, None, false, &utils::DocumentInfo::default())
+    }
+
+    #[test]
+    #[named]
+    fn glob_inputs() {
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        // b is a directory, its PDFs are merged as if it had been given
+        merge_inputs(&["test-data/tree/[a-m]*"], &outfile).unwrap();
+        assert_eq!(page_widths(&outfile), vec![100, 200, 250]);
+
+        merge_inputs(&["test-data/tree/z.pdf", "test-data/tree/?.pdf", "test-data/tree/b/*/*.PDF"], &outfile).unwrap();
+        assert_eq!(page_widths(&outfile), vec![400, 100, 400, 300]);
+    }
+
+    #[test]
+    #[named]
+    fn glob_without_matches() {
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let result = merge_inputs(&["test-data/tree/a.pdf", "test-data/tree/*.docx"], &outfile);
+
+        match result {
+            Err(pdfh::Error::NoMatch { pattern }) => assert_eq!(pattern, "test-data/tree/*.docx"),
+            other => panic!("expected no matches, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[named]
+    fn glob_with_many_stars_is_fast() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let long = format!("{}.pdf", "a".repeat(60));
+        std::fs::copy(build_filepath("tree/a.pdf"), dir.join(&long)).unwrap();
+        std::fs::copy(build_filepath("tree/z.pdf"), dir.join(format!("{}b.pdf", "a".repeat(60)))).unwrap();
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        // backtracking into every * would try billions of ways to fail on the first name
+        let started = std::time::Instant::now();
+        merge_inputs(&[dir.join(format!("{}b*.pdf", "*a".repeat(12))).to_str().unwrap()], &outfile).unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(5), "took {:?}", started.elapsed());
+        assert_eq!(page_widths(&outfile), vec![400]);
+    }
+
+    #[test]
+    #[named]
+    fn existing_path_with_glob_characters_is_literal() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(build_filepath("tree/m.Pdf"), dir.join("[draft] *.pdf")).unwrap();
+        // what the name would match as a pattern
+        std::fs::copy(build_filepath("tree/a.pdf"), dir.join("d x.pdf")).unwrap();
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        merge_inputs(&[dir.join("[draft] *.pdf").to_str().unwrap()], &outfile).unwrap();
+
+        assert_eq!(page_widths(&outfile), vec![250]);
+    }
+
+    #[test]
+    #[named]
+    fn skip_errors() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(build_filepath("tree/a.pdf"), dir.join("1.pdf")).unwrap();
+        std::fs::copy(build_filepath("notapdf.txt"), dir.join("2.pdf")).unwrap();
+        std::fs::copy(build_filepath("tree/z.pdf"), dir.join("3.pdf")).unwrap();
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let output = run(&["merge", dir.to_str().unwrap(), outfile.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("2.pdf: Invalid file header"));
+
+        let output = run(&["merge", dir.to_str().unwrap(), outfile.to_str().unwrap(), "--skip-errors"]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{}", stderr);
+        assert!(stderr.contains("warning: Failed to load") && stderr.contains("2.pdf: Invalid file header, skipped"));
+        assert!(stderr.contains("merged 2 of 3 files (1 skipped)"));
+        assert_eq!(page_widths(&outfile), vec![100, 400]);
+    }
+
+    #[test]
+    #[named]
+    fn skip_errors_fails_when_nothing_loads() {
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let result = merge_inputs(&["test-data/notapdf.txt", "test-data/does-not-exist.pdf"], &outfile);
+        assert!(matches!(result, Err(pdfh::Error::LoadFailed { .. })));
+
+        let inputs = vec![build_filepath("notapdf.txt"), build_filepath("does-not-exist.pdf")];
+        let result = utils::merge(&inputs, outfile, false, false, utils::SortOrder::Name, true, false, None, false, &utils::DocumentInfo::default());
+        assert!(matches!(result, Err(pdfh::Error::AllInputsFailed { count: 2 })));
+    }
+
+    #[test]
+    #[named]
+    fn bookmark_by_file() {
+        let inputs = ["tree/a.pdf", "two-pages.pdf", "tree/z.pdf"].map(build_filepath);
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let output = run(&[
+            "merge", inputs[0].to_str().unwrap(), inputs[1].to_str().unwrap(), inputs[2].to_str().unwrap(), outfile.to_str().unwrap(),
+            "--bookmark-by-file",
+        ]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let doc = Document::load(&outfile).unwrap();
+        let pages = doc.get_pages();
+        let outlines = doc.catalog().unwrap().get(b"Outlines").unwrap().as_reference().unwrap();
+        let mut top_level = Vec::new();
+        let mut next = doc.get_dictionary(outlines).unwrap().get(b"First").ok();
+        while let Some(id) = next.map(|id| id.as_reference().unwrap()) {
+            let item = doc.get_dictionary(id).unwrap();
+            let title = String::from_utf8_lossy(item.get(b"Title").unwrap().as_str().unwrap()).into_owned();
+            let page = item.get(b"Dest").unwrap().as_array().unwrap()[0].as_reference().unwrap();
+            top_level.push((title, pages.iter().find(|(_, id)| **id == page).map(|(n, _)| *n).unwrap()));
+            next = item.get(b"Next").ok();
+        }
+
+        assert_eq!(top_level, vec![("a".to_string(), 1), ("two-pages".to_string(), 2), ("z".to_string(), 4)]);
+    }
+
+    // a contents page linking to the two chapters after it through a name, a GoTo action to a
+    // name and an explicit destination
+    fn contents_document() -> Document {
+        let mut doc = text_document(&["BT ET"; 3]);
+        let pages = doc.get_pages();
+        let name = |text: &str| Object::String(text.as_bytes().to_vec(), StringFormat::Literal);
+        let tree = BTreeMap::from([
+            (b"chapter-1".to_vec(), Object::Array(vec![pages[&2].into(), "Fit".into()])),
+            (b"chapter-2".to_vec(), Object::Array(vec![pages[&3].into(), "Fit".into()])),
+        ]);
+        let tree_id = names::build_name_tree(&mut doc, tree);
+        let catalog_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        let catalog = doc.get_object_mut(catalog_id).and_then(Object::as_dict_mut).unwrap();
+        catalog.set("Names", dictionary! { "Dests" => tree_id });
+
+        add_link_annotation(&mut doc, 1, [72.0, 700.0, 200.0, 712.0], dictionary! { "Dest" => name("chapter-1") });
+        add_link_annotation(&mut doc, 1, [72.0, 680.0, 200.0, 692.0], dictionary! {
+            "A" => dictionary! { "S" => "GoTo", "D" => name("chapter-2") },
+        });
+        add_link_annotation(&mut doc, 1, [72.0, 660.0, 200.0, 672.0], dictionary! {
+            "Dest" => vec![pages[&3].into(), "Fit".into()],
+        });
+        doc
+    }
+
+    #[test]
+    #[named]
+    fn links_and_named_destinations() {
+        let infile = build_outfile_pathbuf(&format!("{}_contents", function_name!()));
+        contents_document().save(&infile).unwrap();
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let output = run(&["merge", infile.to_str().unwrap(), infile.to_str().unwrap(), outfile.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let doc = Document::load(&outfile).unwrap();
+        let targets: Vec<(u32, Option<u32>)> = links::list_links(&doc).into_iter().map(|link| (link.page, link.target_page)).collect();
+        assert_eq!(targets, vec![(1, Some(2)), (1, Some(3)), (1, Some(3)), (4, Some(5)), (4, Some(6)), (4, Some(6))]);
+
+        // the second copy's names were taken, so they get its number
+        let named: Vec<(String, Option<u32>)> = dests::named_destinations(&doc).into_iter().map(|dest| (dest.name, dest.page)).collect();
+        assert_eq!(named, vec![
+            ("chapter-1".to_string(), Some(2)),
+            ("chapter-1-2".to_string(), Some(5)),
+            ("chapter-2".to_string(), Some(3)),
+            ("chapter-2-2".to_string(), Some(6)),
+        ]);
+    }
+
+    #[test]
+    fn no_destinations_leave_the_catalog_alone() {
+        let merged = pdfh::ops::merge(vec![text_document(&["BT ET"]), text_document(&["BT ET"])]).unwrap();
+        let catalog = merged.catalog().unwrap();
+
+        assert!(!catalog.has(b"Dests") && !catalog.has(b"Names"));
+    }
+
+    #[test]
+    #[named]
+    fn form_fields() {
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        merge_inputs(&["test-data/form-contact.pdf", "test-data/form-order.pdf"], &outfile).unwrap();
+
+        let doc = Document::load(&outfile).unwrap();
+        let fields: Vec<String> = forms::list_fields(&doc).into_iter().map(|field| field.name).collect();
+        // the order form's name is renamed so it does not share the contact form's value
+        assert_eq!(fields, vec!["name", "email", "name_2", "quantity", "express"]);
+
+        let form_id = doc.catalog().unwrap().get(b"AcroForm").unwrap().as_reference().unwrap();
+        let form = doc.get_dictionary(form_id).unwrap();
+        let fonts = form.get(b"DR").unwrap().as_dict().unwrap().get(b"Font").unwrap().as_dict().unwrap();
+        assert!(fonts.has(b"Helv") && fonts.has(b"Cour"));
+        assert_eq!(form.get(b"DA").unwrap().as_str().unwrap(), b"/Helv 10 Tf 0 g");
+        assert!(form.get(b"NeedAppearances").unwrap().as_bool().unwrap());
+
+        // the order form's fields keep the appearance they had, as they no longer inherit it
+        let roots = form.get(b"Fields").unwrap().as_array().unwrap();
+        let appearances: Vec<Option<&[u8]>> = roots.iter()
+            .map(|root| doc.get_dictionary(root.as_reference().unwrap()).unwrap().get(b"DA").ok().map(|da| da.as_str().unwrap()))
+            .collect();
+        assert_eq!(appearances, vec![None, None, Some(b"/Cour 10 Tf 0 g".as_slice()), Some(b"/Cour 10 Tf 0 g"), Some(b"/Cour 10 Tf 0 g")]);
+
+        let plain = pdfh::ops::merge(vec![text_document(&["BT ET"]), text_document(&["BT ET"])]).unwrap();
+        assert!(!plain.catalog().unwrap().has(b"AcroForm"));
+    }
+
+    #[test]
+    #[named]
+    fn dedupe_resources() {
+        let single = build_filepath("one-page-with-image.pdf");
+        let outfile = build_outfile_pathbuf(function_name!());
+        let inputs = vec![single.clone(); 10];
+
+        let report = utils::merge(&inputs, outfile.clone(), false, false, utils::SortOrder::Name, false, false, None, true, &utils::DocumentInfo::default()).unwrap();
+
+        assert_eq!(page_count(&outfile), 10);
+        // one copy of the image, plus a few kilobytes for each page
+        let single_size = std::fs::metadata(&single).unwrap().len();
+        assert!(report.bytes_out < single_size + single_size / 20, "{} bytes for a {} byte input", report.bytes_out, single_size);
+    }
+
+    fn write_manifest(name: &str, lines: &[&str]) -> PathBuf {
+        let path = PathBuf::from(format!("{}/output/{}.txt", DATA_DIR, name));
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        path
+    }
+
+    #[test]
+    #[named]
+    fn manifest_inputs_and_pages() {
+        // paths are relative to the manifest, which is in the output directory
+        let manifest = write_manifest(function_name!(), &[
+            "# the cover goes first",
+            "../tree/z.pdf",
+            "",
+            "\"../tree/b/c.Pdf\"",
+            "../single-pages-object-multi-page.pdf 3, 1",
+            "../tree/a.pdf",
+        ]);
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let output = run(&["merge", "--manifest", manifest.to_str().unwrap(), outfile.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let merged = Document::load(&outfile).unwrap();
+        let source = Document::load(build_filepath("single-pages-object-multi-page.pdf")).unwrap();
+        let content = |doc: &Document, page: u32| doc.get_page_content(doc.get_pages()[&page]).unwrap();
+        assert_eq!(merged.get_pages().len(), 5);
+        // the selected pages come in the order listed
+        assert_eq!(content(&merged, 3), content(&source, 3));
+        assert_eq!(content(&merged, 4), content(&source, 1));
+        let width = |page: u32| {
+            let page = merged.get_dictionary(merged.get_pages()[&page]).unwrap();
+            page.get(b"MediaBox").unwrap().as_array().unwrap()[2].as_i64().unwrap()
+        };
+        assert_eq!([1, 2, 5].map(width), [400, 200, 100]);
+    }
+
+    #[test]
+    #[named]
+    fn manifest_pages_descending() {
+        let manifest = write_manifest(function_name!(), &["../single-pages-object-multi-page.pdf 4,3,2,1"]);
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        utils::merge(&Vec::new(), outfile.clone(), false, false, utils::SortOrder::Name, false, false, Some(&manifest), false, &utils::DocumentInfo::default()).unwrap();
+
+        let merged = Document::load(&outfile).unwrap();
+        let source = Document::load(build_filepath("single-pages-object-multi-page.pdf")).unwrap();
+        let content = |doc: &Document, page: u32| doc.get_page_content(doc.get_pages()[&page]).unwrap();
+        assert_eq!(merged.get_pages().len(), 4);
+        for page in 1..=4 {
+            assert_eq!(content(&merged, page), content(&source, 5 - page));
+        }
+    }
+
+    #[test]
+    #[named]
+    fn manifest_errors_name_the_line() {
+        let outfile = build_outfile_pathbuf(function_name!());
+        let merge = |name: &str, lines: &[&str]| {
+            let manifest = write_manifest(&format!("{}_{}", function_name!(), name), lines);
+            utils::merge(&Vec::new(), outfile.clone(), false, false, utils::SortOrder::Name, false, false, Some(&manifest), false, &utils::DocumentInfo::default())
+        };
+
+        let line = |result: Result<utils::SaveReport, pdfh::Error>| match result {
+            Err(pdfh::Error::Manifest { line, reason, .. }) => (line, reason),
+            other => panic!("expected a manifest error, got {:?}", other),
+        };
+        assert_eq!(line(merge("range", &["../tree/a.pdf", "../tree/z.pdf 3-1"])), (2, "`3-1` is a reversed range, did you mean 1-3?".to_string()));
+        assert_eq!(line(merge("quote", &["\"../tree/a.pdf"])), (1, "the closing quote is missing".to_string()));
+        assert_eq!(line(merge("past_the_end", &["# one page", "../tree/a.pdf 2"])).0, 2);
+        assert!(line(merge("missing", &["../tree/missing.pdf"])).1.starts_with("Failed to load"));
+    }
+
+    // the text of each page, in order
+    fn page_texts(path: &Path) -> Vec<String> {
+        let doc = Document::load(path).unwrap();
+        doc.get_pages().values().map(|id| String::from_utf8(doc.get_page_content(*id).unwrap()).unwrap()).collect()
+    }
+
+    fn numbered_pages(name: &str, count: usize) -> PathBuf {
+        let path = build_outfile_pathbuf(name);
+        let contents: Vec<String> = (1..=count).map(|page| format!("page {}", page)).collect();
+        text_document(&contents.iter().map(String::as_str).collect::<Vec<_>>()).save(&path).unwrap();
+        path
+    }
+
+    fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+        PathBuf::from(format!("{}:{}", path.display(), suffix))
+    }
+
+    #[test]
+    #[named]
+    fn page_suffix_reorders_a_single_input() {
+        let input = numbered_pages(&format!("{}_input", function_name!()), 5);
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        utils::merge(&vec![with_suffix(&input, "5,1-2"), with_suffix(&input, "even"), with_suffix(&input, "odd")], outfile.clone(), false, false,
+                     utils::SortOrder::Name, false, false, None, false, &utils::DocumentInfo::default()).unwrap();
+
+        let expected = [5, 1, 2, 2, 4, 1, 3, 5].map(|page| format!("page {}", page));
+        assert_eq!(page_texts(&outfile), expected);
+    }
+
+    #[test]
+    #[named]
+    fn page_suffix_repeats_pages() {
+        let input = numbered_pages(&format!("{}_input", function_name!()), 3);
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        utils::merge(&vec![with_suffix(&input, "1,1"), with_suffix(&input, "2-3,2")], outfile.clone(), false, false,
+                     utils::SortOrder::Name, false, false, None, false, &utils::DocumentInfo::default()).unwrap();
+
+        let expected = [1, 1, 2, 3, 2].map(|page| format!("page {}", page));
+        assert_eq!(page_texts(&outfile), expected);
+        assert_eq!(utils::split_page_suffix(Path::new("missing.pdf:1,1")), Some((PathBuf::from("missing.pdf"), utils::PageSuffix::Pages(vec![1, 1]))));
+    }
+
+    #[test]
+    #[named]
+    fn page_suffix_mixes_full_and_partial_inputs() {
+        let input = numbered_pages(&format!("{}_input", function_name!()), 3);
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let output = run(&[
+            "merge", with_suffix(&input, "3").to_str().unwrap(), build_filepath("tree/z.pdf").to_str().unwrap(),
+            input.to_str().unwrap(), with_suffix(&input, "2,1").to_str().unwrap(), outfile.to_str().unwrap(),
+        ]);
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let texts = page_texts(&outfile);
+        assert_eq!(texts.len(), 1 + 1 + 3 + 2);
+        let numbered: Vec<&str> = texts.iter().map(String::as_str).filter(|text| text.starts_with("page ")).collect();
+        assert_eq!(numbered, ["page 3", "page 1", "page 2", "page 3", "page 2", "page 1"]);
+        assert!(!texts[1].starts_with("page "), "z.pdf comes second");
+    }
+
+    #[test]
+    #[named]
+    fn page_suffix_errors_and_literal_paths() {
+        let input = numbered_pages(&format!("{}_input", function_name!()), 2);
+        let outfile = build_outfile_pathbuf(function_name!());
+        let merge = |inputs: Vec<PathBuf>| utils::merge(&inputs, outfile.clone(), false, false, utils::SortOrder::Name, false, false, None, false, &utils::DocumentInfo::default());
+
+        assert!(matches!(merge(vec![with_suffix(&input, "3")]), Err(pdfh::Error::InvalidSelection(_))));
+
+        // a file whose name ends like a selection is taken as it is
+        let literal = with_suffix(&input, "2");
+        std::fs::copy(&input, &literal).unwrap();
+        merge(vec![literal]).unwrap();
+        assert_eq!(page_texts(&outfile), ["page 1", "page 2"]);
+
+        assert_eq!(utils::split_page_suffix(Path::new("C:\\scans\\a.pdf")), None);
+        assert_eq!(utils::split_page_suffix(Path::new("C:\\scans\\a.pdf:2-3")), Some((PathBuf::from("C:\\scans\\a.pdf"), utils::PageSuffix::Pages(vec![2, 3]))));
+        assert_eq!(utils::split_page_suffix(Path::new("missing.pdf:odd")), Some((PathBuf::from("missing.pdf"), utils::PageSuffix::Odd)));
+    }
+
+    #[test]
+    #[named]
+    fn parallel_loading_matches_sequential_merge() {
+        let fixtures = ["tree/a.pdf", "two-revisions.pdf", "tree/z.pdf", "form-contact.pdf", "tree/m.Pdf", "form-order.pdf"];
+        let inputs: Vec<PathBuf> = (0..60).map(|copy| build_filepath(fixtures[copy % fixtures.len()])).collect();
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        utils::merge(&inputs, outfile.clone(), false, false, utils::SortOrder::Name, false, false, None, false, &utils::DocumentInfo::default()).unwrap();
+
+        // what merge does, one input after the other
+        let sequential = build_outfile_pathbuf(&format!("{}_sequential", function_name!()));
+        let mut doc = pdfh::ops::merge(inputs.iter().map(|path| Document::load(path).unwrap()).collect()).unwrap();
+        doc.prune_objects();
+        doc.adjust_zero_pages();
+        doc.build_outline();
+        doc.delete_zero_length_streams();
+        doc.save(&sequential).unwrap();
+
+        assert_eq!(std::fs::read(&outfile).unwrap(), std::fs::read(&sequential).unwrap());
+    }
+
+    #[test]
+    #[named]
+    fn parallel_loading_reports_the_first_failure() {
+        let outfile = build_outfile_pathbuf(function_name!());
+        let mut inputs: Vec<PathBuf> = (0..30).map(|_| build_filepath("tree/a.pdf")).collect();
+        inputs.insert(12, build_filepath("does-not-exist.pdf"));
+        inputs.push(build_filepath("notapdf.txt"));
+
+        let result = utils::merge(&inputs, outfile.clone(), false, false, utils::SortOrder::Name, false, false, None, false, &utils::DocumentInfo::default());
+        match result {
+            Err(pdfh::Error::LoadFailed { path, .. }) => assert_eq!(path, build_filepath("does-not-exist.pdf")),
+            other => panic!("expected the missing file to fail, got {:?}", other),
+        }
+
+        let report = utils::merge(&inputs, outfile.clone(), false, false, utils::SortOrder::Name, true, false, None, false, &utils::DocumentInfo::default()).unwrap();
+        assert_eq!(report.pages_in, 30);
+        assert_eq!(page_widths(&outfile), vec![100; 30]);
+    }
+
+    fn labelled_pages(name: &str, labels: &[&str]) -> PathBuf {
+        let path = build_outfile_pathbuf(name);
+        text_document(labels).save(&path).unwrap();
+        path
+    }
+
+    #[test]
+    #[named]
+    fn interleave_equal_lengths() {
+        let fronts = labelled_pages(&format!("{}_fronts", function_name!()), &["f1", "f2", "f3"]);
+        let backs = labelled_pages(&format!("{}_backs", function_name!()), &["b1", "b2", "b3"]);
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let report = utils::interleave(fronts, backs, outfile.clone(), false, true, false).unwrap();
+
+        assert_eq!((report.pages_in, report.pages_out), (6, 6));
+        assert_eq!(page_texts(&outfile), ["f1", "b1", "f2", "b2", "f3", "b3"]);
+    }
+
+    #[test]
+    #[named]
+    fn interleave_reverse_second() {
+        // the backs come out of the scanner last sheet first
+        let fronts = labelled_pages(&format!("{}_fronts", function_name!()), &["f1", "f2", "f3"]);
+        let backs = labelled_pages(&format!("{}_backs", function_name!()), &["b3", "b2", "b1"]);
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let output = run(&["interleave", fronts.to_str().unwrap(), backs.to_str().unwrap(), outfile.to_str().unwrap(), "--reverse-second"]);
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(page_texts(&outfile), ["f1", "b1", "f2", "b2", "f3", "b3"]);
+    }
+
+    #[test]
+    #[named]
+    fn interleave_off_by_one() {
+        let three = labelled_pages(&format!("{}_three", function_name!()), &["a1", "a2", "a3"]);
+        let two = labelled_pages(&format!("{}_two", function_name!()), &["b1", "b2"]);
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        utils::interleave(three.clone(), two.clone(), outfile.clone(), false, false, false).unwrap();
+        assert_eq!(page_texts(&outfile), ["a1", "b1", "a2", "b2", "a3"]);
+        utils::interleave(two.clone(), three.clone(), outfile.clone(), false, false, false).unwrap();
+        assert_eq!(page_texts(&outfile), ["b1", "a1", "b2", "a2", "a3"]);
+        utils::interleave(two.clone(), three.clone(), outfile.clone(), true, false, false).unwrap();
+        assert_eq!(page_texts(&outfile), ["b1", "a3", "b2", "a2", "a1"]);
+
+        let output = run(&["interleave", three.to_str().unwrap(), two.to_str().unwrap(), outfile.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("has 3 pages and"));
+
+        match utils::interleave(three, two, outfile, false, true, false) {
+            Err(pdfh::error::Error::InvalidSelection(reason)) => assert!(reason.ends_with("has 2, --strict needs the same number"), "{}", reason),
+            other => panic!("expected --strict to refuse, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[named]
+    fn insert_pages() {
+        let base = labelled_pages(&format!("{}_base", function_name!()), &["a1", "a2", "a3"]);
+        let addendum = labelled_pages(&format!("{}_addendum", function_name!()), &["b1", "b2"]);
+        let outfile = build_outfile_pathbuf(function_name!());
+        let insert = |at| utils::insert(base.clone(), addendum.clone(), outfile.clone(), at, false).map(|_| page_texts(&outfile));
+
+        assert_eq!(insert(Position::Page(1)).unwrap(), ["b1", "b2", "a1", "a2", "a3"]);
+        assert_eq!(insert(Position::Page(3)).unwrap(), ["a1", "a2", "b1", "b2", "a3"]);
+        assert_eq!(insert(Position::Page(4)).unwrap(), ["a1", "a2", "a3", "b1", "b2"]);
+        assert_eq!(insert(Position::End).unwrap(), ["a1", "a2", "a3", "b1", "b2"]);
+
+        match insert(Position::Page(5)) {
+            Err(pdfh::error::Error::InvalidSelection(reason)) => assert_eq!(reason, "cannot insert at page 5, the document has 3 pages, use 1 to 4 or end"),
+            other => panic!("expected page 5 to be refused, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[named]
+    fn insert_pages_cli() {
+        let test_resource = build_filepath("single-pages-object-multi-page.pdf");
+        let addendum = labelled_pages(&format!("{}_addendum", function_name!()), &["b1", "b2"]);
+        let outfile = build_outfile_pathbuf(function_name!());
+        let base = Document::load(&test_resource).unwrap();
+        let base_pages: Vec<_> = base.page_iter().map(|id| base.get_page_content(id).unwrap()).collect();
+
+        let output = run(&["insert", test_resource.to_str().unwrap(), addendum.to_str().unwrap(), outfile.to_str().unwrap(), "--at", "2"]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let doc = Document::load(&outfile).unwrap();
+        let contents: Vec<_> = doc.page_iter().map(|id| doc.get_page_content(id).unwrap()).collect();
+        assert_eq!(contents.len(), base_pages.len() + 2);
+        assert_eq!(contents[0], base_pages[0]);
+        assert_eq!(page_texts(&outfile)[1..3], ["b1", "b2"]);
+        assert_eq!(contents[3..], base_pages[1..]);
+
+        // without --at the pages go at the end
+        let output = run(&["insert", addendum.to_str().unwrap(), addendum.to_str().unwrap(), outfile.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(page_texts(&outfile), ["b1", "b2", "b1", "b2"]);
+
+        assert_eq!(run(&["insert", addendum.to_str().unwrap(), addendum.to_str().unwrap(), outfile.to_str().unwrap(), "--at", "0"]).status.code(), Some(2));
+    }
+}
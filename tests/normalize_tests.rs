@@ -0,0 +1,72 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use lopdf::{Document, Object};
+    use pdfh::{normalize, revisions};
+    use pdfh::Error;
+    use pdfh::utils::SaveOptions;
+    use ::function_name::named;
+
+    use crate::common::{build_filepath, build_outfile_pathbuf};
+
+    fn canonical(path: &std::path::Path) -> Vec<u8> {
+        normalize::canonical_bytes(&mut Document::load(path).unwrap())
+    }
+
+    #[test]
+    #[named]
+    fn normalizing_twice_is_identical() {
+        let once = build_outfile_pathbuf(&format!("{}-once", function_name!()));
+        let twice = build_outfile_pathbuf(&format!("{}-twice", function_name!()));
+
+        normalize::normalize(build_filepath("single-pages-object-multi-page.pdf"), Some(once.clone()), &SaveOptions::default()).unwrap();
+        normalize::normalize(once.clone(), Some(twice.clone()), &SaveOptions::default()).unwrap();
+
+        assert_eq!(fs::read(&once).unwrap(), fs::read(&twice).unwrap());
+        assert_eq!(fs::read(&once).unwrap(), canonical(&build_filepath("single-pages-object-multi-page.pdf")));
+    }
+
+    #[test]
+    #[named]
+    fn rewritten_document_normalizes_the_same() {
+        let infile = build_filepath("two-pages.pdf");
+        let rewritten = build_outfile_pathbuf(function_name!());
+
+        // a save that changes nothing but the object numbers, compression and dates
+        let mut doc = Document::load(&infile).unwrap();
+        doc.renumber_objects_with(40);
+        doc.compress();
+        let info = doc.trailer.get(b"Info").and_then(Object::as_reference).unwrap();
+        doc.get_object_mut(info).unwrap().as_dict_mut().unwrap().set("ModDate", Object::string_literal("D:20260101120000Z"));
+        doc.save(&rewritten).unwrap();
+
+        assert_ne!(fs::read(&infile).unwrap(), fs::read(&rewritten).unwrap());
+        assert_eq!(canonical(&infile), canonical(&rewritten));
+    }
+
+    #[test]
+    #[named]
+    fn incremental_update_normalizes_like_flattened() {
+        let infile = build_filepath("two-revisions.pdf");
+        let flattened = build_outfile_pathbuf(function_name!());
+
+        revisions::revisions(infile.clone(), Some(flattened.clone()), true, false, &SaveOptions::default()).unwrap();
+
+        assert_eq!(canonical(&infile), canonical(&flattened));
+        assert_eq!(revisions::count_revisions(&canonical(&infile)), 1);
+    }
+
+    #[test]
+    #[named]
+    fn refuses_incremental_save() {
+        let outfile = build_outfile_pathbuf(function_name!());
+        let save = SaveOptions { incremental: true, ..SaveOptions::default() };
+
+        let result = normalize::normalize(build_filepath("two-pages.pdf"), Some(outfile.clone()), &save);
+
+        assert!(matches!(result, Err(Error::RewriteRequired(_))), "{:?}", result);
+        assert!(!outfile.exists());
+    }
+}
@@ -0,0 +1,385 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use lopdf::{dictionary, Document, Object, ObjectId};
+    use pdfh::bookmarks::{self, Heading};
+    use pdfh::dests;
+    use pdfh::ops::{self, PageRange, PageSelection};
+    use pdfh::Error;
+
+    use crate::common::{build_filepath, text_document};
+
+    fn rotation(doc: &Document, page: u32) -> Option<i64> {
+        doc.get_dictionary(doc.get_pages()[&page]).unwrap().get(b"Rotate").ok()?.as_i64().ok()
+    }
+
+    fn heading(level: usize, page: u32, title: &str) -> Heading {
+        Heading { level, page, size: 12.0, title: title.to_string() }
+    }
+
+    // the title, depth and page number of every outline item, in outline order
+    fn outline(doc: &Document) -> Vec<(String, usize, u32)> {
+        let numbers: BTreeMap<ObjectId, u32> = doc.get_pages().into_iter().map(|(n, id)| (id, n)).collect();
+        let root = doc.catalog().unwrap().get(b"Outlines").unwrap().as_reference().unwrap();
+        bookmarks::outline_items(doc)
+            .into_iter()
+            .map(|id| {
+                let item = doc.get_dictionary(id).unwrap();
+                let title = String::from_utf8_lossy(item.get(b"Title").unwrap().as_str().unwrap()).into_owned();
+                let mut depth = 0;
+                let mut parent = item.get(b"Parent").unwrap().as_reference().unwrap();
+                while parent != root {
+                    depth += 1;
+                    parent = doc.get_dictionary(parent).unwrap().get(b"Parent").unwrap().as_reference().unwrap();
+                }
+                let dest = dests::goto_destination(doc, item).unwrap();
+                (title, depth, numbers[&dests::resolve_destination(doc, &dest).unwrap()])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compose_in_memory() {
+        let mut first = Document::load(build_filepath("single-pages-object-multi-page.pdf")).unwrap();
+        let second = Document::load(build_filepath("two-pages.pdf")).unwrap();
+        let first_content = first.get_page_content(first.get_pages()[&3]).unwrap();
+
+        ops::rotate_pages(&mut first, 90, &PageSelection::Every(3)).unwrap();
+        ops::delete_pages(&mut first, &PageSelection::Pages(vec![1, 2])).unwrap();
+        ops::reverse(&mut first);
+        let merged = ops::merge(vec![first, second]).unwrap();
+
+        assert_eq!(merged.get_pages().len(), 4);
+        // the third page of the first document is second after deleting two pages and reversing
+        assert_eq!(merged.get_page_content(merged.get_pages()[&2]).unwrap(), first_content);
+        assert_eq!(rotation(&merged, 2), Some(90));
+        assert_eq!(rotation(&merged, 1), None);
+    }
+
+    #[test]
+    fn extract_keeps_the_selection() {
+        let mut doc = text_document(&["BT ET", "BT ET", "BT ET"]);
+        let kept = doc.get_pages()[&2];
+
+        ops::extract_pages(&mut doc, &PageSelection::Pages(vec![2, 7]), false).unwrap();
+
+        assert_eq!(doc.get_pages().into_values().collect::<Vec<_>>(), vec![kept]);
+    }
+
+    #[test]
+    fn extract_in_the_listed_order() {
+        let texts = |doc: &Document| -> Vec<String> {
+            doc.page_iter().map(|id| String::from_utf8(doc.get_page_content(id).unwrap()).unwrap().replace("BT (", "").replace(") Tj ET", "")).collect()
+        };
+        let pages: Vec<String> = (1..=8).map(|page| format!("BT ({}) Tj ET", page)).collect();
+        let pages: Vec<&str> = pages.iter().map(String::as_str).collect();
+        let extract = |selection: PageSelection| {
+            let mut doc = text_document(&pages);
+            ops::extract_pages(&mut doc, &selection, false).unwrap();
+            doc
+        };
+
+        assert_eq!(texts(&extract(PageSelection::Pages(vec![7, 2, 5]))), ["7", "2", "5"]);
+        let repeated = extract(PageSelection::Pages(vec![1, 1, 2, 1]));
+        assert_eq!(texts(&repeated), ["1", "1", "2", "1"]);
+        let ids: std::collections::HashSet<ObjectId> = repeated.page_iter().collect();
+        assert_eq!(ids.len(), 4);
+        let range = |first, last| PageRange { first, last: Some(last) };
+        assert_eq!(texts(&extract(PageSelection::Ranges(vec![range(6, 8), range(1, 2), PageRange { first: 7, last: None }]))),
+                   ["6", "7", "8", "1", "2", "7", "8"]);
+
+        // listed in order, the pages stay where they are in the page tree
+        let mut doc = text_document(&pages);
+        let kept: Vec<ObjectId> = [2, 5, 7].iter().map(|page| doc.get_pages()[page]).collect();
+        ops::extract_pages(&mut doc, &PageSelection::Pages(vec![2, 5, 7]), false).unwrap();
+        assert_eq!(doc.page_iter().collect::<Vec<_>>(), kept);
+    }
+
+    #[test]
+    fn extract_a_page_with_fields_twice() {
+        let mut doc = Document::load(build_filepath("form-contact.pdf")).unwrap();
+
+        ops::extract_pages(&mut doc, &PageSelection::Pages(vec![1, 1]), false).unwrap();
+
+        let pages: Vec<ObjectId> = doc.page_iter().collect();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(doc.get_page_content(pages[0]).unwrap(), doc.get_page_content(pages[1]).unwrap());
+        let names: Vec<String> = pdfh::forms::list_fields(&doc).into_iter().map(|field| field.name).collect();
+        assert_eq!(names, ["name", "email", "name_2", "email_2"]);
+    }
+
+    #[test]
+    fn arrange_pages_needs_every_page() {
+        let mut doc = text_document(&["BT ET"; 3]);
+
+        assert!(matches!(ops::arrange_pages(&mut doc, &[3, 1, 1]), Err(Error::InvalidSelection(_))));
+        ops::arrange_pages(&mut doc, &[3, 1, 2, 3]).unwrap();
+        assert_eq!(doc.get_pages().len(), 4);
+    }
+
+    #[test]
+    fn merge_keeps_bookmarks() {
+        let mut first = text_document(&["BT ET"; 2]);
+        bookmarks::set_outline(&mut first, &[heading(1, 1, "One"), heading(2, 2, "One A")]);
+        let untitled = text_document(&["BT ET"]);
+        let mut second = text_document(&["BT ET"; 2]);
+        bookmarks::set_outline(&mut second, &[heading(1, 2, "Two")]);
+
+        let mut merged = ops::merge(vec![first, untitled, second]).unwrap();
+
+        let expected = [("One", 0, 1), ("One A", 1, 2), ("Two", 0, 5)];
+        assert_eq!(outline(&merged), expected.map(|(title, depth, page)| (title.to_string(), depth, page)));
+        // the links and counts of the joined outline are consistent
+        assert_eq!(bookmarks::repair_outline(&mut merged), Vec::<String>::new());
+    }
+
+    #[test]
+    fn merge_bookmarks_named_and_dangling() {
+        let mut first = text_document(&["BT ET"]);
+        bookmarks::set_outline(&mut first, &[heading(1, 1, "Cover")]);
+        let mut second = text_document(&["BT ET"; 3]);
+        bookmarks::set_outline(&mut second, &[heading(1, 1, "Gone"), heading(2, 3, "Named"), heading(1, 2, "Kept")]);
+
+        // names only the second document defines, and a destination that is not a page
+        let items = bookmarks::outline_items(&second);
+        let third_page = second.get_pages()[&3];
+        let catalog_id = second.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        second.get_object_mut(catalog_id).and_then(Object::as_dict_mut).unwrap().set("Dests", dictionary! {
+            "appendix" => vec![third_page.into(), "Fit".into()],
+        });
+        second.get_object_mut(items[1]).and_then(Object::as_dict_mut).unwrap().set("Dest", Object::Name(b"appendix".to_vec()));
+        let not_a_page = second.add_object(dictionary! {});
+        second.get_object_mut(items[0]).and_then(Object::as_dict_mut).unwrap().set("Dest", vec![not_a_page.into(), "Fit".into()]);
+
+        let merged = ops::merge(vec![first, second]).unwrap();
+
+        // the children of a dropped item move up a level
+        let expected = [("Cover", 0, 1), ("Named", 0, 4), ("Kept", 0, 3)];
+        assert_eq!(outline(&merged), expected.map(|(title, depth, page)| (title.to_string(), depth, page)));
+    }
+
+    #[test]
+    fn merge_with_a_bookmark_per_document() {
+        let mut first = text_document(&["BT ET"; 2]);
+        bookmarks::set_outline(&mut first, &[heading(1, 1, "One"), heading(2, 2, "One A")]);
+        let second = text_document(&["BT ET"]);
+        let titles = ["First".to_string(), "Second".to_string()];
+
+        let mut merged = ops::merge_with_bookmarks(vec![first, second], &titles).unwrap();
+
+        let expected = [("First", 0, 1), ("One", 1, 1), ("One A", 2, 2), ("Second", 0, 3)];
+        assert_eq!(outline(&merged), expected.map(|(title, depth, page)| (title.to_string(), depth, page)));
+        assert_eq!(bookmarks::repair_outline(&mut merged), Vec::<String>::new());
+    }
+
+    // a document whose last two pages sit under an intermediate Pages node with its own size and rotation
+    fn nested_page_tree() -> Document {
+        let mut doc = text_document(&["BT ET"; 3]);
+        let root_id = doc.catalog().unwrap().get(b"Pages").unwrap().as_reference().unwrap();
+        let kids = doc.get_dictionary(root_id).unwrap().get(b"Kids").unwrap().as_array().unwrap().clone();
+        let node_id = doc.add_object(dictionary! {
+            "Type" => "Pages",
+            "Parent" => root_id,
+            "Count" => 2,
+            "Kids" => kids[1..].to_vec(),
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            "Rotate" => 90,
+        });
+        for kid in &kids[1..] {
+            let page = doc.get_object_mut(kid.as_reference().unwrap()).and_then(Object::as_dict_mut).unwrap();
+            page.set("Parent", node_id);
+        }
+        let root = doc.get_object_mut(root_id).and_then(Object::as_dict_mut).unwrap();
+        root.set("Kids", vec![kids[0].clone(), node_id.into()]);
+        doc
+    }
+
+    #[test]
+    fn merge_flattens_page_trees() {
+        let merged = ops::merge(vec![text_document(&["BT ET"]), nested_page_tree()]).unwrap();
+
+        let pages_nodes = merged.objects.values().filter(|object| object.type_name().ok() == Some("Pages")).count();
+        assert_eq!(pages_nodes, 1);
+        let mut widths = Vec::new();
+        for page_id in merged.get_pages().into_values() {
+            // set on the page itself, there is no tree left to inherit them from
+            let page = merged.get_dictionary(page_id).unwrap();
+            assert!(page.has(b"Resources"));
+            widths.push(page.get(b"MediaBox").unwrap().as_array().unwrap()[2].as_i64().unwrap());
+        }
+        assert_eq!(widths, vec![612, 612, 595, 595]);
+        assert_eq!((1..=4).map(|page| rotation(&merged, page)).collect::<Vec<_>>(), vec![None, None, Some(90), Some(90)]);
+    }
+
+    #[test]
+    fn merge_keeps_the_first_catalog() {
+        let with_lang = |lang: &str| {
+            let mut doc = text_document(&["BT ET"]);
+            let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+            doc.get_object_mut(root).unwrap().as_dict_mut().unwrap().set("Lang", Object::string_literal(lang));
+            doc
+        };
+
+        let merged = ops::merge(vec![with_lang("en"), with_lang("de"), with_lang("fr")]).unwrap();
+
+        assert_eq!(merged.catalog().unwrap().get(b"Lang").unwrap().as_str().unwrap(), b"en");
+    }
+
+    #[test]
+    fn merge_multiple_pages_nodes_fixture() {
+        let fixture = Document::load(build_filepath("multiple-pages-objects-multi-page.pdf")).unwrap();
+        let second = Document::load(build_filepath("two-pages.pdf")).unwrap();
+        let contents: Vec<Vec<u8>> = fixture.page_iter().map(|id| fixture.get_page_content(id).unwrap()).collect();
+
+        let merged = ops::merge(vec![fixture, second]).unwrap();
+
+        let pages = merged.get_pages();
+        assert_eq!(pages.len(), 166);
+        for (number, content) in contents.iter().enumerate() {
+            let page_id = pages[&(number as u32 + 1)];
+            let page = merged.get_dictionary(page_id).unwrap();
+            assert!(page.has(b"MediaBox") && page.has(b"Resources"), "page {} lost what it inherited", number + 1);
+            assert_eq!(&merged.get_page_content(page_id).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn dedupe_keeps_streams_that_differ_in_their_dictionary() {
+        let mut doc = text_document(&["BT ET"; 3]);
+        let image = |decode: bool| {
+            let mut dict = dictionary! { "Type" => "XObject", "Subtype" => "Image", "Width" => 1, "Height" => 1, "BitsPerComponent" => 8 };
+            if decode { dict.set("Decode", vec![1.into(), 0.into()]); }
+            lopdf::Stream::new(dict, vec![0x80])
+        };
+        let logos = [image(false), image(false), image(true)].map(|stream| doc.add_object(stream));
+        for (page_id, logo) in doc.get_pages().into_values().zip(logos) {
+            let page = doc.get_object_mut(page_id).and_then(Object::as_dict_mut).unwrap();
+            page.set("Resources", dictionary! { "XObject" => dictionary! { "Im0" => logo } });
+        }
+
+        // two of the three identical content streams, and one of the two identical images
+        assert_eq!(ops::dedupe_resources(&mut doc), 3);
+        let logo = |page: u32| {
+            let page = doc.get_dictionary(doc.get_pages()[&page]).unwrap();
+            page.get(b"Resources").unwrap().as_dict().unwrap().get(b"XObject").unwrap().as_dict().unwrap().get(b"Im0").unwrap().as_reference().unwrap()
+        };
+        assert_eq!(logo(1), logo(2));
+        assert_ne!(logo(1), logo(3));
+    }
+
+    #[test]
+    fn selections() {
+        let doc = text_document(&["BT ET"; 5]);
+
+        assert_eq!(PageSelection::new(None, None).page_numbers(&doc).unwrap(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(PageSelection::new(Some(vec![4, 2, 9]), Some(2)).page_numbers(&doc).unwrap(), vec![2, 4]);
+        assert_eq!(PageSelection::Every(2).inverted(&doc).unwrap(), PageSelection::Pages(vec![1, 3, 5]));
+        assert!(matches!(PageSelection::Every(0).page_numbers(&doc), Err(Error::InvalidSelection(_))));
+        assert!(matches!(ops::merge(Vec::new()), Err(Error::PagesRootMissing)));
+    }
+
+    #[test]
+    fn repeat_pages_copies_pages_annotations_and_fields() {
+        let mut doc = Document::load(build_filepath("form-contact.pdf")).unwrap();
+        let original: Vec<Vec<u8>> = doc.page_iter().map(|id| doc.get_page_content(id).unwrap()).collect();
+
+        ops::repeat_pages(&mut doc, 3, true).unwrap();
+
+        let pages: Vec<ObjectId> = doc.page_iter().collect();
+        assert_eq!(pages.len(), 3 * original.len());
+        for (number, page_id) in pages.iter().enumerate() {
+            assert_eq!(doc.get_page_content(*page_id).unwrap(), original[number % original.len()]);
+        }
+
+        // every copy has widgets of its own, shown on its own page
+        let mut widgets = std::collections::HashSet::new();
+        for page_id in &pages {
+            for annot in doc.get_dictionary(*page_id).unwrap().get(b"Annots").unwrap().as_array().unwrap() {
+                let annot_id = annot.as_reference().unwrap();
+                assert!(widgets.insert(annot_id), "{:?} is on two pages", annot_id);
+                let page = doc.get_dictionary(annot_id).unwrap().get(b"P").and_then(Object::as_reference);
+                assert!(page.map_or(true, |page| page == *page_id));
+            }
+        }
+        let names: Vec<String> = pdfh::forms::list_fields(&doc).into_iter().map(|field| field.name).collect();
+        assert_eq!(names, ["name", "email", "name_2", "email_2", "name_3", "email_3"]);
+
+        assert!(matches!(ops::repeat_pages(&mut doc, 0, true), Err(Error::InvalidSelection(_))));
+    }
+
+    #[test]
+    fn repeat_pages_collated_and_uncollated() {
+        let texts = |doc: &Document| -> Vec<String> {
+            doc.page_iter().map(|id| String::from_utf8(doc.get_page_content(id).unwrap()).unwrap()).collect()
+        };
+        let pages = ["BT (1) Tj ET", "BT (2) Tj ET", "BT (3) Tj ET"];
+
+        let mut collated = text_document(&pages);
+        ops::repeat_pages(&mut collated, 2, true).unwrap();
+        assert_eq!(texts(&collated), [pages, pages].concat());
+
+        let mut uncollated = text_document(&pages);
+        ops::repeat_pages(&mut uncollated, 2, false).unwrap();
+        assert_eq!(texts(&uncollated), [pages[0], pages[0], pages[1], pages[1], pages[2], pages[2]]);
+    }
+
+    // four pages: the first two turned 90 and 270 on their own, the others 180 by the page tree
+    fn pre_rotated() -> Document {
+        let mut doc = text_document(&["BT ET"; 4]);
+        let pages = doc.get_pages();
+        doc.get_object_mut(pages[&1]).unwrap().as_dict_mut().unwrap().set("Rotate", 90);
+        doc.get_object_mut(pages[&2]).unwrap().as_dict_mut().unwrap().set("Rotate", 270);
+        let root = doc.catalog().unwrap().get(b"Pages").unwrap().as_reference().unwrap();
+        doc.get_object_mut(root).unwrap().as_dict_mut().unwrap().set("Rotate", 180);
+        doc
+    }
+
+    #[test]
+    fn rotate_pages_by_adds_to_the_rotation() {
+        let mut doc = pre_rotated();
+        ops::rotate_pages_by(&mut doc, 90, &PageSelection::All).unwrap();
+        assert_eq!((1..=4).map(|page| rotation(&doc, page)).collect::<Vec<_>>(), [Some(180), Some(0), Some(270), Some(270)]);
+
+        let mut doc = pre_rotated();
+        ops::rotate_pages_by(&mut doc, -450, &PageSelection::Pages(vec![1, 3])).unwrap();
+        assert_eq!((1..=4).map(|page| rotation(&doc, page)).collect::<Vec<_>>(), [Some(0), Some(270), Some(90), None]);
+
+        // absolute rotation replaces whatever the page had
+        let mut doc = pre_rotated();
+        ops::rotate_pages(&mut doc, 90, &PageSelection::All).unwrap();
+        assert_eq!((1..=4).map(|page| rotation(&doc, page)).collect::<Vec<_>>(), [Some(90); 4]);
+    }
+
+    #[test]
+    fn normalize_rotation() {
+        let normalized: Vec<i64> = [0, 90, 180, 270, 360, 450, -90, -180, -270, -360, -450, 720, 45].into_iter().map(ops::normalize_rotation).collect();
+        assert_eq!(normalized, [0, 90, 180, 270, 0, 90, 270, 180, 90, 0, 270, 0, 0]);
+    }
+
+    #[test]
+    fn rotate_to_zero_removes_the_key() {
+        let mut doc = text_document(&["BT ET"; 2]);
+        ops::rotate_pages(&mut doc, 90, &PageSelection::All).unwrap();
+        ops::rotate_pages(&mut doc, -360, &PageSelection::Pages(vec![1])).unwrap();
+        assert_eq!((rotation(&doc, 1), rotation(&doc, 2)), (None, Some(90)));
+
+        // unless the page would take a rotation from the page tree instead
+        let mut doc = pre_rotated();
+        ops::rotate_pages(&mut doc, 0, &PageSelection::All).unwrap();
+        assert_eq!((1..=4).map(|page| rotation(&doc, page)).collect::<Vec<_>>(), [Some(0); 4]);
+    }
+
+    #[test]
+    fn rotate_reports_missing_pages() {
+        let mut doc = text_document(&["BT ET"; 3]);
+        let missing = ops::rotate_pages(&mut doc, 90, &PageSelection::Pages(vec![5, 2, 0, 5, 4])).unwrap();
+        assert_eq!(missing, [0, 4, 5]);
+        assert_eq!((1..=3).map(|page| rotation(&doc, page)).collect::<Vec<_>>(), [None, Some(90), None]);
+
+        assert_eq!(ops::rotate_pages_by(&mut doc, 90, &PageSelection::Every(2)).unwrap(), Vec::<u32>::new());
+        assert_eq!(ops::rotate_each_page(&mut doc, &[(1, 270), (7, 90)], false), [7]);
+        assert_eq!((1..=3).map(|page| rotation(&doc, page)).collect::<Vec<_>>(), [Some(270), Some(180), None]);
+    }
+}
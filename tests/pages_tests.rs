@@ -0,0 +1,55 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use lopdf::{dictionary, Document, Object};
+    use pdfh::pages;
+
+    use crate::common::{add_link_annotation, build_filepath, text_document};
+
+    #[test]
+    fn attributes_inherited_from_page_tree() {
+        let mut doc = text_document(&["BT /F1 12 Tf 72 700 Td (first line) Tj 0 -20 Td (second) Tj ET", "BT ET"]);
+        let pages_id = doc.catalog().unwrap().get(b"Pages").unwrap().as_reference().unwrap();
+        let second = doc.get_pages()[&2];
+        doc.get_object_mut(pages_id).unwrap().as_dict_mut().unwrap().set("Rotate", -90);
+        doc.get_object_mut(second).unwrap().as_dict_mut().unwrap()
+            .set("CropBox", vec![600.into(), 780.into(), 12.into(), 12.into()]);
+        add_link_annotation(&mut doc, 1, [72.0, 700.0, 140.0, 712.0], dictionary! { "URI" => Object::string_literal("https://example.com") });
+
+        let all: Vec<_> = pages(&doc).collect();
+        assert_eq!(all.len(), 2);
+
+        let first = &all[0];
+        assert_eq!(first.number, 1);
+        assert_eq!(first.media_box, Some([0.0, 0.0, 612.0, 792.0]));
+        assert_eq!(first.crop_box, first.media_box);
+        assert_eq!(first.rotation, 270);
+        assert!(first.resources().unwrap().has(b"Font"));
+        assert_eq!(first.resources_id, doc.get_dictionary(pages_id).unwrap().get(b"Resources").unwrap().as_reference().ok());
+        assert_eq!(first.content_ids.len(), 1);
+        assert_eq!(first.extract_text().unwrap(), "first line\nsecond");
+        assert_eq!(first.annotations().len(), 1);
+
+        let second = &all[1];
+        assert_eq!(second.crop_box, Some([12.0, 12.0, 600.0, 780.0]));
+        assert_eq!(second.extract_text().unwrap(), "");
+        assert!(second.annotations().is_empty());
+    }
+
+    // intermediate /Pages nodes between the root and the pages
+    #[test]
+    fn multiple_pages_nodes() {
+        let doc = Document::load(build_filepath("multiple-pages-objects-multi-page.pdf")).unwrap();
+
+        let all: Vec<_> = pages(&doc).collect();
+        assert_eq!(all.len(), 164);
+        assert!(all.iter().enumerate().all(|(index, page)| page.number == index as u32 + 1));
+        assert_eq!(all.iter().map(|page| page.id).collect::<Vec<_>>(), doc.get_pages().into_values().collect::<Vec<_>>());
+        for page in &all {
+            assert!(page.media_box.is_some(), "page {} has no media box", page.number);
+            assert!(page.resources().is_some(), "page {} has no resources", page.number);
+            assert!(!page.content_ids.is_empty());
+        }
+    }
+}
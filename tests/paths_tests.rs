@@ -0,0 +1,107 @@
+mod common;
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    use lopdf::Document;
+    use pdfh::{response, utils};
+    use pdfh::ops::PageSelection;
+    use ::function_name::named;
+
+    use crate::common::{build_filepath, DATA_DIR};
+
+    // test-data/output/<test name>-<suffix>, where suffix is not valid UTF-8
+    fn non_utf8_path(test: &str, suffix: &[u8]) -> PathBuf {
+        let mut name = format!("{}-", test).into_bytes();
+        name.extend_from_slice(suffix);
+        PathBuf::from(DATA_DIR).join("output").join(OsString::from_vec(name))
+    }
+
+    #[test]
+    #[named]
+    fn merge_non_utf8_paths() {
+        let infile = non_utf8_path(function_name!(), b"caf\xe9.pdf");
+        let outfile = non_utf8_path(function_name!(), b"\xff\xfe-merged.pdf");
+        assert!(infile.to_str().is_none());
+        std::fs::copy(build_filepath("two-pages.pdf"), &infile).unwrap();
+
+        utils::merge(&vec![infile.clone(), build_filepath("one-page-with-image.pdf")], outfile.clone(), false, false, utils::SortOrder::Name, false, false, None, false, &utils::DocumentInfo::default()).unwrap();
+
+        assert_eq!(Document::load(&outfile).unwrap().get_pages().len(), 3);
+    }
+
+    #[test]
+    #[named]
+    fn cli_and_response_file_keep_non_utf8_paths() {
+        let infile = non_utf8_path(function_name!(), b"\xe9t\xe9 scan.pdf");
+        let outfile = non_utf8_path(function_name!(), b"r\xe9sum\xe9.pdf");
+        let list = non_utf8_path(function_name!(), b"list\xff.txt");
+        std::fs::copy(build_filepath("two-pages.pdf"), &infile).unwrap();
+
+        let mut contents = b"\"".to_vec();
+        contents.extend_from_slice(infile.as_os_str().as_bytes());
+        contents.extend_from_slice(b"\"\n");
+        std::fs::write(&list, contents).unwrap();
+        let mut list_arg = OsString::from("@");
+        list_arg.push(&list);
+
+        let args = response::expand_response_files(vec!["pdfh".into(), "merge".into(), list_arg.clone(), outfile.clone().into()]).unwrap();
+        assert_eq!(args[2], infile.as_os_str());
+
+        let status = Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .arg("merge").arg(list_arg).arg(build_filepath("one-page-with-image.pdf")).arg(&outfile)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(Document::load(&outfile).unwrap().get_pages().len(), 3);
+    }
+
+    #[test]
+    #[named]
+    fn split_non_utf8_outfile() {
+        let outfile = non_utf8_path(function_name!(), b"caf\xe9.pdf");
+
+        utils::split(build_filepath("two-pages.pdf"), &utils::SplitNaming::Outfile(outfile), PageSelection::All, None, false, false, false, false).unwrap();
+
+        for page in [b"001", b"002"] {
+            let mut suffix = b"caf\xe9-".to_vec();
+            suffix.extend_from_slice(page);
+            suffix.extend_from_slice(b".pdf");
+            assert_eq!(Document::load(non_utf8_path(function_name!(), &suffix)).unwrap().get_pages().len(), 1);
+        }
+    }
+
+    #[test]
+    #[named]
+    fn split_pattern_keeps_non_utf8_stem() {
+        let infile = non_utf8_path(function_name!(), b"caf\xe9.pdf");
+        std::fs::copy(build_filepath("two-pages.pdf"), &infile).unwrap();
+        let naming = utils::SplitNaming::Pattern { dir: infile.parent().unwrap().to_path_buf(), pattern: "{stem}-p{page}.pdf".to_string() };
+
+        utils::split(infile, &naming, PageSelection::All, None, false, false, false, false).unwrap();
+
+        for page in [b"1", b"2"] {
+            let mut suffix = b"caf\xe9-p".to_vec();
+            suffix.extend_from_slice(page);
+            suffix.extend_from_slice(b".pdf");
+            assert_eq!(Document::load(non_utf8_path(function_name!(), &suffix)).unwrap().get_pages().len(), 1);
+        }
+    }
+
+    #[test]
+    #[named]
+    fn glob_matches_non_utf8_names() {
+        let dir = PathBuf::from(DATA_DIR).join("output").join(function_name!());
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(build_filepath("two-pages.pdf"), dir.join(OsString::from_vec(b"caf\xe9.pdf".to_vec()))).unwrap();
+        let outfile = PathBuf::from(DATA_DIR).join("output").join(format!("{}.pdf", function_name!()));
+
+        utils::merge(&vec![dir.join("*.pdf")], outfile.clone(), false, false, utils::SortOrder::Name, false, false, None, false, &utils::DocumentInfo::default()).unwrap();
+
+        assert_eq!(Document::load(&outfile).unwrap().get_pages().len(), 2);
+    }
+}
@@ -0,0 +1,24 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use pdfh::prompt;
+
+    use crate::common::ScriptedPrompt;
+
+    #[test]
+    fn password_asked_once() {
+        let mut input = ScriptedPrompt::new(true, &["hunter2"]);
+
+        assert_eq!(prompt::ask_password(&mut input, "Password"), Ok(Some("hunter2".to_string())));
+        assert_eq!(input.prompts, vec!["Password: "]);
+    }
+
+    #[test]
+    fn not_interactive_does_not_prompt() {
+        let mut input = ScriptedPrompt::new(false, &["hunter2"]);
+
+        assert_eq!(prompt::ask_password(&mut input, "Password"), Ok(None));
+        assert!(input.prompts.is_empty());
+    }
+}
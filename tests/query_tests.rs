@@ -0,0 +1,62 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use pdfh::query::{self, Criteria};
+    use ::function_name::named;
+
+    use crate::common::{build_filepath, text_document, DATA_DIR};
+
+    // A directory of PDFs to search, plus a text file and a broken PDF that must be passed over
+    fn query_dir(name: &str) -> PathBuf {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for fixture in ["two-pages.pdf", "one-page-with-image.pdf", "password-protected.pdf", "notapdf.txt"] {
+            std::fs::copy(build_filepath(fixture), dir.join(fixture)).unwrap();
+        }
+        std::fs::copy(build_filepath("notapdf.txt"), dir.join("broken.pdf")).unwrap();
+        text_document(&["BT /F1 12 Tf 72 700 Td (invoice 42) Tj ET"]).save(dir.join("invoice.pdf")).unwrap();
+        dir
+    }
+
+    fn names(dir: &Path, criteria: &Criteria) -> Vec<String> {
+        query::find_matches(&vec![dir.to_path_buf()], criteria)
+            .into_iter()
+            .map(|found| found.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    #[named]
+    fn single_criteria() {
+        let dir = query_dir(function_name!());
+
+        assert_eq!(names(&dir, &Criteria::default()),
+                   vec!["invoice.pdf", "one-page-with-image.pdf", "password-protected.pdf", "two-pages.pdf"]);
+        assert_eq!(names(&dir, &Criteria { min_pages: Some(2), ..Criteria::default() }),
+                   vec!["password-protected.pdf", "two-pages.pdf"]);
+        assert_eq!(names(&dir, &Criteria { encrypted: true, ..Criteria::default() }),
+                   vec!["password-protected.pdf"]);
+        assert_eq!(names(&dir, &Criteria { larger_than: Some(1024 * 1024), ..Criteria::default() }),
+                   vec!["one-page-with-image.pdf"]);
+        assert_eq!(names(&dir, &Criteria { contains: Some("invoice".to_string()), ..Criteria::default() }),
+                   vec!["invoice.pdf"]);
+        assert!(names(&dir, &Criteria { has_forms: true, ..Criteria::default() }).is_empty());
+    }
+
+    #[test]
+    #[named]
+    fn criteria_combine() {
+        let dir = query_dir(function_name!());
+
+        let criteria = Criteria { min_pages: Some(2), smaller_than: Some(10 * 1024), ..Criteria::default() };
+        assert_eq!(names(&dir, &criteria), vec!["password-protected.pdf"]);
+
+        let criteria = Criteria { max_pages: Some(1), contains: Some("invoice".to_string()), larger_than: Some(1024 * 1024), ..Criteria::default() };
+        assert!(names(&dir, &criteria).is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use lopdf::Document;
+    use pdfh::recovery;
+    use ::function_name::named;
+
+    use crate::common::{build_outfile_pathbuf, text_document};
+
+    // A two page document whose startxref points at the wrong offset
+    fn damaged_document() -> Vec<u8> {
+        let mut data = Vec::new();
+        text_document(&["BT /F1 12 Tf 72 700 Td (one) Tj ET", "BT /F1 12 Tf 72 700 Td (two) Tj ET"])
+            .save_to(&mut data)
+            .unwrap();
+
+        let startxref = data.windows(9).rposition(|window| window == b"startxref").unwrap();
+        data.truncate(startxref);
+        data.extend_from_slice(b"startxref\n17\n%%EOF\n");
+        data
+    }
+
+    #[test]
+    fn recover_skips_unreadable_objects() {
+        let mut data = damaged_document();
+        data.extend_from_slice(b"99 0 obj\n<< /Broken (never closed >>\nendobj\n");
+        assert!(Document::load_mem(&data).is_err());
+
+        let (doc, recovery) = recovery::recover(&data).unwrap();
+
+        assert_eq!(doc.get_pages().len(), 2);
+        assert_eq!(recovery.skipped, vec![(99, 0)]);
+    }
+
+    #[test]
+    #[named]
+    fn rotate_damaged_file_only_when_lenient() {
+        let strict = build_outfile_pathbuf(&format!("{}_strict", function_name!()));
+        let lenient = build_outfile_pathbuf(&format!("{}_lenient", function_name!()));
+        std::fs::write(&strict, damaged_document()).unwrap();
+        std::fs::write(&lenient, damaged_document()).unwrap();
+
+        let rotate = |file: &std::path::PathBuf, extra: &[&str]| {
+            Command::new(env!("CARGO_BIN_EXE_pdfh"))
+                .args(extra).arg("rotate").arg(file).args(["--degrees", "90"])
+                .env_remove("RUST_BACKTRACE")
+                .output()
+                .unwrap()
+        };
+
+        let output = rotate(&strict, &[]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains(&format!("Failed to load {}", strict.display())));
+
+        let output = rotate(&lenient, &["--lenient"]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("recovered"));
+        let doc = Document::load(&lenient).unwrap();
+        assert_eq!(doc.get_pages().len(), 2);
+        for page_id in doc.get_pages().values() {
+            assert_eq!(doc.get_dictionary(*page_id).unwrap().get(b"Rotate").unwrap().as_i64().unwrap(), 90);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process::Command};
+
+    use lopdf::Document;
+    use pdfh::redact::{self, Area};
+    use pdfh::utils::SaveOptions;
+    use ::function_name::named;
+
+    use crate::common::{build_filepath, build_outfile_pathbuf, text_document};
+
+    // Courier glyphs are 7.2pt wide at 12pt, so SECRET123 runs from x=158.4 to x=223.2
+    const SECRET_LINE: &str = "BT /F1 12 Tf 72 700 Td (Public text SECRET123 more public) Tj ET";
+
+    fn page_content(doc: &Document, page: u32) -> String {
+        let page_id = doc.get_pages()[&page];
+        String::from_utf8_lossy(&doc.get_page_content(page_id).unwrap()).into_owned()
+    }
+
+    #[test]
+    fn redact_area_removes_text_underneath() {
+        let mut doc = text_document(&[SECRET_LINE]);
+
+        let report = redact::redact_areas(&mut doc, &[Area { page: 1, rect: [155.0, 695.0, 225.0, 715.0] }]).unwrap();
+
+        assert_eq!(report.glyphs, 9);
+        let text = doc.extract_text(&[1]).unwrap();
+        assert!(!text.contains("SECRET"), "{}", text);
+        assert!(text.contains("Public text") && text.contains("more public"), "{}", text);
+        assert!(page_content(&doc, 1).contains("155 695 70 20 re"));
+    }
+
+    #[test]
+    fn redact_keeps_remaining_text_in_place() {
+        let mut doc = text_document(&[SECRET_LINE]);
+        let before = redact::find_text(&doc, "more public");
+
+        redact::redact_areas(&mut doc, &[Area { page: 1, rect: [155.0, 695.0, 225.0, 715.0] }]).unwrap();
+
+        let after = redact::find_text(&doc, "more public");
+        assert_eq!(after.len(), 1);
+        for (b, a) in before[0].rect.iter().zip(after[0].rect.iter()) {
+            assert!((b - a).abs() < 1e-6, "{:?} moved to {:?}", before[0].rect, after[0].rect);
+        }
+    }
+
+    #[test]
+    fn find_text_locates_matches_on_every_page() {
+        let doc = text_document(&[SECRET_LINE, "BT /F1 10 Tf 100 100 Td [(SEC) -50 (RET123)] TJ ET"]);
+
+        let found = redact::find_text(&doc, "SECRET123");
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].page, 1);
+        assert!((found[0].rect[0] - 158.4).abs() < 1e-6 && (found[0].rect[2] - 223.2).abs() < 1e-6);
+        assert_eq!(found[1].page, 2);
+        assert!((found[1].rect[0] - 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    #[named]
+    fn redact_text_from_file() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        let mut doc = text_document(&[SECRET_LINE, "BT /F1 10 Tf 100 100 Td (SECRET123) Tj ET"]);
+        doc.save(&infile).unwrap();
+
+        redact::redact(infile, Some(outfile.clone()), vec![], vec!["SECRET123".to_string()], false, &SaveOptions::default()).unwrap();
+
+        let doc = Document::load(outfile).unwrap();
+        let text = doc.extract_text(&[1, 2]).unwrap();
+        assert!(!text.contains("SECRET"), "{}", text);
+        assert!(text.contains("more public"), "{}", text);
+    }
+
+    #[test]
+    fn redact_keeps_inline_images_and_the_content_after_them() {
+        let content = "q 10 0 0 10 300 300 cm BI /W 1 /H 1 /BPC 8 /CS /G ID \u{7f} EI Q\n\
+                       q 10 0 0 10 400 400 cm BI /W 1 /H 1 /BPC 8 /CS /G ID \u{7f} EI Q\n".to_string() + SECRET_LINE;
+        let mut doc = text_document(&[&content]);
+
+        let report = redact::redact_areas(&mut doc, &[
+            Area { page: 1, rect: [155.0, 695.0, 225.0, 715.0] },
+            Area { page: 1, rect: [295.0, 295.0, 315.0, 315.0] },
+        ]).unwrap();
+
+        assert_eq!(report.images, 1);
+        assert_eq!(page_content(&doc, 1).matches("BI").count(), 1);
+        assert!(redact::find_text(&doc, "SECRET").is_empty());
+        assert_eq!(redact::find_text(&doc, "more public").len(), 1);
+    }
+
+    #[test]
+    fn redact_removes_covered_image() {
+        let mut doc = Document::load(build_filepath("one-page-with-image.pdf")).unwrap();
+        assert!(page_content(&doc, 1).contains(" Do"));
+
+        let report = redact::redact_areas(&mut doc, &[Area { page: 1, rect: [0.0, 0.0, 2000.0, 2000.0] }]).unwrap();
+
+        assert!(report.images >= 1);
+        assert!(!page_content(&doc, 1).contains(" Do"));
+    }
+
+    #[test]
+    fn redact_rejects_missing_page() {
+        let mut doc = text_document(&[SECRET_LINE]);
+
+        let result = redact::redact_areas(&mut doc, &[Area { page: 2, rect: [0.0, 0.0, 10.0, 10.0] }]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[named]
+    fn redacted_text_is_not_left_in_the_file() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        text_document(&[SECRET_LINE]).save(&infile).unwrap();
+        let _ = fs::remove_file(&outfile);
+        let redact = |global: &[&str]| Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(global)
+            .arg("redact").arg(&infile).arg(&outfile)
+            .args(["--text", "SECRET123"])
+            .output()
+            .unwrap();
+
+        // an incremental update would keep the original content in the earlier revision
+        let output = redact(&["--incremental"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("incremental update"));
+        assert!(!outfile.exists());
+
+        assert!(redact(&[]).status.success());
+        let data = fs::read(&outfile).unwrap();
+        assert!(!data.windows(b"SECRET123".len()).any(|window| window == b"SECRET123"));
+    }
+}
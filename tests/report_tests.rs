@@ -0,0 +1,167 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+    use std::process::Command;
+    use std::time::Duration;
+
+    use pdfh::{dests, utils};
+    use pdfh::report::{self, RunSummary, Style};
+    use pdfh::ops::PageSelection;
+    use pdfh::utils::{SaveOptions, SaveReport, SaveStats};
+    use ::function_name::named;
+
+    use crate::common::{build_filepath, build_outfile_pathbuf, text_document};
+
+    fn strip_escapes(text: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                for c in chars.by_ref() {
+                    if c == 'm' { break; }
+                }
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn color_only_adds_escapes() {
+        for style in [Style::Error, Style::Warning, Style::Summary] {
+            let text = "warning: 3 links would point past the ends of the document and were removed";
+            let colored = report::paint(style, text, true);
+
+            assert_ne!(colored, text);
+            assert_eq!(strip_escapes(&colored), report::paint(style, text, false));
+            assert_eq!(report::paint(style, text, false), text);
+        }
+    }
+
+    #[test]
+    fn color_enabled_rules() {
+        assert!(report::color_enabled(false, None, true));
+        assert!(report::color_enabled(false, Some(OsStr::new("")), true));
+        assert!(!report::color_enabled(false, Some(OsStr::new("1")), true));
+        assert!(!report::color_enabled(true, None, true));
+        assert!(!report::color_enabled(false, None, false));
+    }
+
+    #[test]
+    fn errors_are_reported_without_escapes_under_no_color() {
+        let output = Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(["revisions", "test-data/does-not-exist.pdf"])
+            .env("NO_COLOR", "1")
+            .env_remove("RUST_BACKTRACE")
+            .output()
+            .unwrap();
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success());
+        assert!(stderr.starts_with("error: Failed to load test-data/does-not-exist.pdf"), "{}", stderr);
+        assert!(!stderr.contains('\x1b'));
+    }
+
+    #[test]
+    fn summary_line() {
+        let saved = SaveReport { pages_in: 10, pages_out: 8, bytes_in: 2048, bytes_out: 1024 , ..SaveReport::default() };
+
+        assert_eq!(RunSummary::new(saved, Duration::from_millis(1500), 1).to_string(),
+                   "pages 10 -> 8, size 2.0 KiB -> 1.0 KiB (-50.0%), 1.50s, 1 warning");
+    }
+
+    #[test]
+    #[named]
+    fn delete_reports_page_delta() {
+        let infile = build_filepath("single-pages-object-multi-page.pdf");
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let saved = utils::delete(infile.clone(), Some(outfile.clone()), PageSelection::Pages(vec![1, 2]), false, false, false, false, &SaveOptions::default()).unwrap();
+
+        assert_eq!(saved.pages_out, saved.pages_in - 2);
+        assert_eq!(saved.bytes_in, std::fs::metadata(infile).unwrap().len());
+        assert_eq!(saved.bytes_out, std::fs::metadata(outfile).unwrap().len());
+    }
+
+    #[test]
+    #[named]
+    fn compress_reports_size_delta() {
+        let content = "BT /F1 12 Tf 72 700 Td (all work and no play) Tj ET\n".repeat(200);
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        text_document(&[&content, &content]).save(&infile).unwrap();
+
+        let saved = dests::prune_dests(infile, Some(outfile), true, &SaveOptions::default());
+
+        assert_eq!((saved.pages_in, saved.pages_out), (2, 2));
+        assert!(saved.bytes_out < saved.bytes_in / 4, "{:?}", saved);
+    }
+
+    #[test]
+    #[named]
+    fn merge_timings_cover_every_phase() {
+        let inputs = [build_filepath("two-pages.pdf"), build_filepath("one-page-with-image.pdf")];
+        let outfile = build_outfile_pathbuf(function_name!());
+        let output = Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(["--json", "--timings", "merge", "-c"])
+            .args(&inputs)
+            .arg(&outfile)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+
+        let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let timings = summary["timings"].as_array().unwrap();
+        let phases: Vec<&str> = timings.iter().map(|timing| timing["phase"].as_str().unwrap()).collect();
+        assert_eq!(phases, vec![format!("load {}", inputs[0].display()).as_str(),
+                                format!("load {}", inputs[1].display()).as_str(),
+                                "transform", "compress", "save"]);
+
+        let total = summary["seconds"].as_f64().unwrap();
+        let timed: f64 = timings.iter().map(|timing| timing["seconds"].as_f64().unwrap()).sum();
+        assert!(timings.iter().all(|timing| timing["seconds"].as_f64().unwrap() >= 0.0));
+        assert!(timed <= total, "phases took {}s of {}s", timed, total);
+    }
+
+    #[test]
+    fn timings_table_rows() {
+        let saved = SaveReport { pages_in: 2, pages_out: 2, bytes_in: 10, bytes_out: 10 , ..SaveReport::default() };
+        let mut summary = RunSummary::new(saved, Duration::from_millis(1000), 0);
+        assert_eq!(summary.timings_table(), None);
+
+        summary.timings = Some(vec![report::Timing { phase: "load a.pdf".to_string(), seconds: 0.25 },
+                                    report::Timing { phase: "save".to_string(), seconds: 0.5 }]);
+        assert_eq!(summary.timings_table().unwrap(),
+                   "load a.pdf      0.250s\nsave            0.500s\nother           0.250s\ntotal           1.000s\n");
+    }
+
+    #[test]
+    #[named]
+    fn delete_reports_pruned_objects() {
+        let infile = build_filepath("single-pages-object-multi-page.pdf");
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let saved = utils::delete(infile, Some(outfile.clone()), PageSelection::Pages(vec![2, 3, 4]), false, false, false, false, &SaveOptions::default()).unwrap();
+
+        let stats = saved.stats;
+        assert_eq!((saved.pages_in, saved.pages_out), (4, 1));
+        assert_eq!((stats.objects_before, stats.objects_pruned, stats.empty_streams, stats.objects_after), (175, 53, 0, 122));
+        assert_eq!(lopdf::Document::load(&outfile).unwrap().objects.len(), stats.objects_after);
+        assert!(RunSummary::new(saved, Duration::from_millis(200), 0).to_string().starts_with("pages 4 -> 1, pruned 53 objects, size "));
+    }
+
+    #[test]
+    #[named]
+    fn incremental_update_prunes_nothing() {
+        let infile = build_filepath("two-pages.pdf");
+        let outfile = build_outfile_pathbuf(function_name!());
+        let save = SaveOptions { incremental: true, ..SaveOptions::default() };
+
+        let saved = utils::delete(infile, Some(outfile), PageSelection::Pages(vec![2]), false, false, false, false, &save).unwrap();
+
+        assert_eq!(saved.stats, SaveStats::default());
+    }
+}
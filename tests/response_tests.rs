@@ -0,0 +1,79 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    use pdfh::response;
+    use ::function_name::named;
+
+    use crate::common::DATA_DIR;
+
+    fn write_response_file(name: &str, contents: &str) -> String {
+        let path = PathBuf::from(format!("{}/output/{}.txt", DATA_DIR, name));
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn os_args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    #[named]
+    fn expands_quoted_paths() {
+        let list = write_response_file(function_name!(), "a.pdf \"My Scans/page 1.pdf\"\n'b c.pdf'\n\n  d.pdf\n");
+
+        let args = response::expand_response_files(os_args(&["pdfh", "merge", &format!("@{}", list), "out.pdf"])).unwrap();
+
+        assert_eq!(args, os_args(&["pdfh", "merge", "a.pdf", "My Scans/page 1.pdf", "b c.pdf", "d.pdf", "out.pdf"]));
+    }
+
+    #[test]
+    fn leaves_other_arguments_alone() {
+        let args = os_args(&["@pdfh", "merge", "@", "out.pdf"]);
+
+        assert_eq!(response::expand_response_files(args.clone()).unwrap(), args);
+    }
+
+    #[test]
+    fn missing_response_file() {
+        let missing = format!("{}/does-not-exist.txt", DATA_DIR);
+
+        let error = response::expand_response_files(os_args(&["pdfh", "merge", &format!("@{}", missing), "out.pdf"])).unwrap_err();
+
+        assert!(error.starts_with(&format!("Failed to read response file {}", missing)), "{}", error);
+    }
+
+    #[test]
+    #[named]
+    fn nested_response_file_rejected() {
+        let list = write_response_file(function_name!(), "a.pdf @more.txt\n");
+
+        let error = response::expand_response_files(os_args(&["pdfh", "merge", &format!("@{}", list), "out.pdf"])).unwrap_err();
+
+        assert!(error.contains("nested response files are not supported"), "{}", error);
+    }
+
+    #[test]
+    #[named]
+    fn unterminated_quote() {
+        let list = write_response_file(function_name!(), "a.pdf \"b.pdf\n");
+
+        let error = response::expand_response_files(os_args(&["pdfh", "merge", &format!("@{}", list), "out.pdf"])).unwrap_err();
+
+        assert!(error.starts_with("Unterminated \" quote"), "{}", error);
+    }
+
+    #[test]
+    fn missing_response_file_exits_cleanly() {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(["merge", "@test-data/does-not-exist.txt", "out.pdf"])
+            .output()
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to read response file"));
+    }
+}
@@ -0,0 +1,91 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use lopdf::{Document, Object};
+    use pdfh::{forms, revisions, utils};
+    use pdfh::ops::PageSelection;
+    use pdfh::utils::SaveOptions;
+    use ::function_name::named;
+
+    use crate::common::{build_filepath, build_outfile_pathbuf, signed_document};
+
+    // the fixture's incremental update replaces the draft text of page one and the title
+    #[test]
+    fn count_two_revisions() {
+        let data = fs::read(build_filepath("two-revisions.pdf")).unwrap();
+        assert_eq!(revisions::count_revisions(&data), 2);
+    }
+
+    #[test]
+    fn count_single_revision() {
+        let data = fs::read(build_filepath("two-pages.pdf")).unwrap();
+        assert_eq!(revisions::count_revisions(&data), 1);
+    }
+
+    #[test]
+    #[named]
+    fn flatten_keeps_latest_revision() {
+        let infile = build_filepath("two-revisions.pdf");
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        revisions::revisions(infile.clone(), Some(outfile.clone()), true, false, &SaveOptions::default()).unwrap();
+
+        let data = fs::read(&outfile).unwrap();
+        assert_eq!(revisions::count_revisions(&data), 1);
+        assert!(data.len() < fs::read(&infile).unwrap().len());
+        let text = String::from_utf8_lossy(&data);
+        assert!(!text.contains("Draft"), "superseded content left in the file");
+
+        let doc = Document::load_mem(&data).unwrap();
+        let pages = doc.get_pages();
+        let first = String::from_utf8(doc.get_page_content(pages[&1]).unwrap()).unwrap();
+        assert!(first.contains("(Final report) Tj"), "{}", first);
+        let info = doc.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let title = doc.get_dictionary(info).unwrap().get(b"Title").and_then(Object::as_str).unwrap();
+        assert_eq!(title, b"Final report");
+    }
+
+    #[test]
+    #[named]
+    fn incremental_update_keeps_signed_revision() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        signed_document().save(&infile).unwrap();
+        let save = SaveOptions { incremental: true, ..SaveOptions::default() };
+
+        utils::rotate(infile.clone(), Some(outfile.clone()), 90, PageSelection::Pages(vec![2]), false, false, &save).unwrap();
+
+        let original = fs::read(&infile).unwrap();
+        let data = fs::read(&outfile).unwrap();
+        assert!(data.starts_with(&original), "the signed revision was rewritten");
+        assert_eq!(revisions::count_revisions(&data), 2);
+
+        let doc = Document::load_mem(&data).unwrap();
+        let pages = doc.get_pages();
+        let rotation = |page: u32| doc.get_dictionary(pages[&page]).unwrap().get(b"Rotate").and_then(Object::as_i64).ok();
+        assert_eq!(rotation(1), None);
+        assert_eq!(rotation(2), Some(90));
+        assert_eq!(forms::signed_fields(&doc), vec!["Approval".to_string()]);
+    }
+
+    #[test]
+    fn append_update_only_writes_changed_objects() {
+        let data = fs::read(build_filepath("two-pages.pdf")).unwrap();
+        let mut doc = Document::load_mem(&data).unwrap();
+        let info = doc.add_object(lopdf::dictionary! { "Title" => Object::string_literal("Updated") });
+        doc.trailer.set("Info", info);
+
+        let mut updated = data.clone();
+        revisions::append_update(&mut updated, &doc).unwrap();
+
+        let update = String::from_utf8_lossy(&updated[data.len()..]);
+        assert_eq!(update.matches(" obj").count(), 1, "{}", update);
+        let reloaded = Document::load_mem(&updated).unwrap();
+        let info = reloaded.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let title = reloaded.get_dictionary(info).unwrap().get(b"Title").and_then(Object::as_str).unwrap();
+        assert_eq!(title, b"Updated");
+        assert_eq!(reloaded.get_pages().len(), 2);
+    }
+}
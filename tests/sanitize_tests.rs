@@ -0,0 +1,247 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process::Command};
+
+    use lopdf::{dictionary, Document, Object, Stream};
+    use pdfh::{attachments, links};
+    use pdfh::sanitize::{self, Profile, Removal};
+    use pdfh::utils::SaveOptions;
+    use ::function_name::named;
+
+    use crate::common::{add_link_annotation, build_filepath, build_outfile_pathbuf};
+
+    const PRIVATE_PAYLOAD_LEN: usize = 20_000;
+
+    // two-pages.pdf with an Illustrator style PieceInfo on the first page and a SpiderInfo on the catalog
+    fn document_with_private_data() -> Document {
+        let mut doc = Document::load(build_filepath("two-pages.pdf")).unwrap();
+
+        let private_id = doc.add_object(Stream::new(dictionary! {}, vec![b'x'; PRIVATE_PAYLOAD_LEN]));
+        let first_page = *doc.get_pages().get(&1).unwrap();
+        let page = doc.get_object_mut(first_page).unwrap().as_dict_mut().unwrap();
+        page.set("PieceInfo", dictionary! {
+            "Illustrator" => dictionary! {
+                "LastModified" => Object::string_literal("D:20220602013213Z"),
+                "Private" => private_id,
+            },
+        });
+        page.set("LastModified", Object::string_literal("D:20220602013213Z"));
+
+        let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        let catalog = doc.get_object_mut(root).unwrap().as_dict_mut().unwrap();
+        catalog.set("SpiderInfo", dictionary! { "V" => 1.0 });
+
+        doc
+    }
+
+    // document_with_private_data plus metadata, scripts, an attachment and both kinds of link
+    fn dirty_document() -> Document {
+        let mut doc = document_with_private_data();
+
+        let info = doc.add_object(dictionary! { "Author" => Object::string_literal("J. Doe") });
+        doc.trailer.set("Info", info);
+        let xmp = doc.add_object(Stream::new(dictionary! { "Type" => "Metadata", "Subtype" => "XML" },
+                                             b"<x:xmpmeta>J. Doe</x:xmpmeta>".to_vec()));
+        let script = doc.add_object(dictionary! { "S" => "JavaScript", "JS" => Object::string_literal("app.alert(1)") });
+        let attachment = doc.add_object(Stream::new(dictionary! { "Type" => "EmbeddedFile" }, b"secret".to_vec()));
+        let filespec = doc.add_object(dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal("secret.txt"),
+            "EF" => dictionary! { "F" => attachment },
+        });
+
+        let root = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        let catalog = doc.get_object_mut(root).unwrap().as_dict_mut().unwrap();
+        catalog.set("Metadata", xmp);
+        catalog.set("OpenAction", script);
+        catalog.set("AA", dictionary! { "WC" => script });
+        catalog.set("Names", dictionary! {
+            "JavaScript" => dictionary! { "Names" => vec![Object::string_literal("init"), script.into()] },
+            "EmbeddedFiles" => dictionary! { "Names" => vec![Object::string_literal("secret.txt"), filespec.into()] },
+        });
+
+        add_link_annotation(&mut doc, 1, [72.0, 700.0, 200.0, 720.0], dictionary! {
+            "A" => dictionary! { "S" => "URI", "URI" => Object::string_literal("https://example.com") },
+        });
+        let second_page = *doc.get_pages().get(&2).unwrap();
+        add_link_annotation(&mut doc, 2, [72.0, 700.0, 200.0, 720.0], dictionary! {
+            "Dest" => vec![second_page.into(), "Fit".into()],
+        });
+
+        doc
+    }
+
+    fn has_script(doc: &Document) -> bool {
+        doc.objects.values().any(|object| match object {
+            Object::Dictionary(dict) => dict.get(b"S").and_then(Object::as_name).ok() == Some(b"JavaScript"),
+            _ => false,
+        })
+    }
+
+    fn has_private_keys(doc: &Document) -> bool {
+        doc.objects.values().any(|object| {
+            let dict = match object {
+                Object::Dictionary(dict) => dict,
+                Object::Stream(stream) => &stream.dict,
+                _ => return false,
+            };
+            dict.has(b"PieceInfo") || dict.has(b"SpiderInfo") || dict.has(b"LastModified")
+        })
+    }
+
+    #[test]
+    fn remove_private_data_strips_keys_and_payload() {
+        let mut doc = document_with_private_data();
+        let pages = doc.get_pages();
+        let contents_before: Vec<Vec<u8>> = pages.values()
+            .map(|id| doc.get_page_content(*id).unwrap())
+            .collect();
+
+        let report = sanitize::remove_private_data(&mut doc);
+
+        // PieceInfo, page LastModified and SpiderInfo, each recorded as a key and a value
+        assert_eq!(report.entries, 6);
+        assert_eq!(report.objects, 1);
+        assert!(report.bytes >= PRIVATE_PAYLOAD_LEN as u64);
+        assert!(!has_private_keys(&doc));
+
+        let contents_after: Vec<Vec<u8>> = pages.values()
+            .map(|id| doc.get_page_content(*id).unwrap())
+            .collect();
+        assert_eq!(contents_before, contents_after);
+    }
+
+    #[test]
+    fn remove_private_data_clean_document_is_untouched() {
+        let mut doc = Document::load(build_filepath("two-pages.pdf")).unwrap();
+        let objects_before = doc.objects.len();
+
+        let report = sanitize::remove_private_data(&mut doc);
+
+        assert_eq!(report.entries, 0);
+        assert_eq!(report.objects, 0);
+        assert_eq!(doc.objects.len(), objects_before);
+    }
+
+    #[test]
+    #[named]
+    fn sanitize_private_data_reduces_size() {
+        let dirty = build_outfile_pathbuf(&format!("{}-dirty", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        document_with_private_data().save(&dirty).unwrap();
+
+        sanitize::sanitize(dirty.clone(), Some(outfile.clone()), vec![Removal::PrivateData], false, &SaveOptions::default()).unwrap();
+
+        let dirty_len = fs::metadata(&dirty).unwrap().len();
+        let clean_len = fs::metadata(&outfile).unwrap().len();
+        assert!(clean_len + PRIVATE_PAYLOAD_LEN as u64 <= dirty_len);
+
+        let clean = Document::load(&outfile).unwrap();
+        assert!(!has_private_keys(&clean));
+        assert_eq!(clean.get_pages().len(), 2);
+    }
+
+    #[test]
+    #[named]
+    fn sanitize_strict_profile_removes_every_category() {
+        let dirty = build_outfile_pathbuf(&format!("{}-dirty", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        let mut doc = dirty_document();
+        let internal_links = links::list_links(&doc).iter().filter(|link| link.kind == "internal").count();
+        doc.save(&dirty).unwrap();
+
+        sanitize::sanitize(dirty, Some(outfile.clone()), Profile::Strict.removals().to_vec(), false, &SaveOptions::default()).unwrap();
+
+        let clean = Document::load(&outfile).unwrap();
+        assert!(!clean.trailer.has(b"Info"));
+        let catalog = clean.catalog().unwrap();
+        for key in [&b"Metadata"[..], b"OpenAction", b"AA"] {
+            assert!(!catalog.has(key), "{} left in the catalog", String::from_utf8_lossy(key));
+        }
+        assert!(!has_script(&clean));
+        assert!(!has_private_keys(&clean));
+        assert!(attachments::list_attachments(&clean).is_empty());
+
+        let remaining = links::list_links(&clean);
+        assert_eq!(remaining.len(), internal_links);
+        assert!(remaining.iter().all(|link| link.kind == "internal"));
+        assert_eq!(clean.get_pages().len(), 2);
+    }
+
+    #[test]
+    fn standard_profile_keeps_links_and_attachments() {
+        let mut doc = dirty_document();
+        let links_before = links::list_links(&doc).len();
+
+        for category in Profile::Standard.removals() {
+            sanitize::remove_category(&mut doc, *category);
+        }
+
+        assert!(!has_script(&doc));
+        assert_eq!(links::list_links(&doc).len(), links_before);
+        assert_eq!(attachments::list_attachments(&doc).len(), 1);
+    }
+
+    #[test]
+    fn remove_javascript_counts_every_script_entry() {
+        let mut doc = dirty_document();
+
+        let report = sanitize::remove_javascript(&mut doc);
+
+        // the /JavaScript name tree, the /OpenAction and the /WC trigger, each a key and a value
+        assert_eq!(report.entries, 6);
+        assert_eq!(report.objects, 1);
+        assert!(!has_script(&doc));
+    }
+
+    #[test]
+    #[named]
+    fn removed_metadata_is_not_left_in_the_file() {
+        let dirty = build_outfile_pathbuf(&format!("{}-dirty", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        let mut doc = Document::load(build_filepath("two-pages.pdf")).unwrap();
+        let info = doc.add_object(dictionary! { "Author" => Object::string_literal("Jane Secret") });
+        doc.trailer.set("Info", info);
+        doc.save(&dirty).unwrap();
+        let _ = fs::remove_file(&outfile);
+        let sanitize = |global: &[&str]| Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(global)
+            .arg("sanitize").arg(&dirty).arg(&outfile)
+            .args(["--profile", "strict"])
+            .output()
+            .unwrap();
+
+        // an incremental update would keep the original Info dictionary in the earlier revision
+        let output = sanitize(&["--incremental"]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("incremental update"));
+        assert!(!outfile.exists());
+
+        assert!(sanitize(&[]).status.success());
+        let data = fs::read(&outfile).unwrap();
+        assert!(!data.windows(b"Jane Secret".len()).any(|window| window == b"Jane Secret"));
+    }
+
+    #[test]
+    #[named]
+    fn revisions_report_how_many_were_flattened() {
+        let outfile = build_outfile_pathbuf(function_name!());
+        let sanitize = |infile: &str| {
+            let output = Command::new(env!("CARGO_BIN_EXE_pdfh"))
+                .args(["--no-color", "sanitize"])
+                .arg(build_filepath(infile)).arg(&outfile)
+                .args(["--remove", "revisions"])
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            String::from_utf8_lossy(&output.stderr).into_owned()
+        };
+
+        let stderr = sanitize("two-revisions.pdf");
+        assert!(stderr.contains("revisions: flattened 2 revisions into one"), "{}", stderr);
+        let stderr = sanitize("two-pages.pdf");
+        assert!(stderr.contains("revisions: no earlier revisions, nothing to flatten"), "{}", stderr);
+    }
+}
@@ -0,0 +1,187 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use lopdf::{dictionary, Document, Object, ObjectId, StringFormat};
+    use pdfh::{names, structure, utils};
+    use pdfh::ops::PageSelection;
+    use pdfh::utils::SaveOptions;
+    use ::function_name::named;
+
+    use crate::common::{add_link_annotation, build_outfile_pathbuf, text_document};
+
+    const TAGGED_PAGE: &str = "/P <</MCID 0>> BDC BT /F1 12 Tf 72 700 Td (Paragraph) Tj ET EMC";
+
+    // three tagged pages: a Document element holding one paragraph per page, plus a Link element
+    // on page 2 made of marked content and the link annotation itself
+    fn tagged_document() -> Document {
+        let mut doc = text_document(&[TAGGED_PAGE, TAGGED_PAGE, TAGGED_PAGE]);
+        let pages: Vec<ObjectId> = doc.get_pages().into_values().collect();
+        let annot_id = add_link_annotation(&mut doc, 2, [72.0, 690.0, 150.0, 710.0], dictionary! {
+            "A" => dictionary! { "S" => "URI", "URI" => Object::string_literal("https://example.com") },
+            "StructParent" => 3,
+        });
+
+        let root_id = doc.new_object_id();
+        let document_id = doc.new_object_id();
+        let mut paragraphs = Vec::new();
+        for (i, page_id) in pages.iter().enumerate() {
+            paragraphs.push(doc.add_object(dictionary! {
+                "Type" => "StructElem",
+                "S" => "P",
+                "P" => document_id,
+                "Pg" => *page_id,
+                "K" => 0,
+                "ID" => Object::String(format!("para{}", i + 1).into_bytes(), StringFormat::Literal),
+            }));
+            let page = doc.get_object_mut(*page_id).unwrap().as_dict_mut().unwrap();
+            page.set("StructParents", i as i64);
+        }
+        let link_id = doc.add_object(dictionary! {
+            "Type" => "StructElem",
+            "S" => "Link",
+            "P" => document_id,
+            "Pg" => pages[1],
+            "K" => vec![
+                dictionary! { "Type" => "MCR", "MCID" => 1 }.into(),
+                dictionary! { "Type" => "OBJR", "Obj" => annot_id }.into(),
+            ],
+        });
+
+        let mut kids: Vec<Object> = paragraphs.iter().map(|id| Object::Reference(*id)).collect();
+        kids.push(link_id.into());
+        doc.objects.insert(document_id, Object::Dictionary(dictionary! {
+            "Type" => "StructElem",
+            "S" => "Document",
+            "P" => root_id,
+            "K" => kids,
+        }));
+
+        let id_tree: Vec<Object> = paragraphs.iter().enumerate()
+            .flat_map(|(i, id)| [Object::String(format!("para{}", i + 1).into_bytes(), StringFormat::Literal), (*id).into()])
+            .collect();
+        doc.objects.insert(root_id, Object::Dictionary(dictionary! {
+            "Type" => "StructTreeRoot",
+            "K" => document_id,
+            "ParentTree" => dictionary! { "Nums" => vec![
+                0.into(), vec![paragraphs[0].into()].into(),
+                1.into(), vec![paragraphs[1].into(), link_id.into()].into(),
+                2.into(), vec![paragraphs[2].into()].into(),
+                3.into(), link_id.into(),
+            ] },
+            "ParentTreeNextKey" => 4,
+            "IDTree" => dictionary! { "Names" => id_tree },
+        }));
+
+        let catalog_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        let catalog = doc.get_object_mut(catalog_id).unwrap().as_dict_mut().unwrap();
+        catalog.set("StructTreeRoot", root_id);
+        catalog.set("MarkInfo", dictionary! { "Marked" => true });
+        doc
+    }
+
+    fn structure_root(doc: &Document) -> &lopdf::Dictionary {
+        let root = doc.catalog().unwrap().get(b"StructTreeRoot").unwrap();
+        doc.dereference(root).unwrap().1.as_dict().unwrap()
+    }
+
+    // walks the tree checking that every reference resolves and marked content ids have a page,
+    // returning the structure types found
+    fn check_structure(doc: &Document) -> Vec<String> {
+        let pages: HashSet<ObjectId> = doc.page_iter().collect();
+        let mut types = Vec::new();
+        let mut queue = vec![(structure_root(doc).get(b"K").unwrap().clone(), None)];
+
+        while let Some((node, page)) = queue.pop() {
+            let dict = match &node {
+                Object::Reference(id) => doc.get_dictionary(*id).expect("structure references a missing object"),
+                Object::Dictionary(dict) => dict,
+                Object::Array(kids) => {
+                    queue.extend(kids.iter().map(|kid| (kid.clone(), page)));
+                    continue;
+                }
+                Object::Integer(_) => {
+                    assert!(page.is_some(), "marked content without a page");
+                    continue;
+                }
+                _ => continue,
+            };
+            let page = match dict.get(b"Pg") {
+                Ok(pg) => {
+                    let pg = pg.as_reference().unwrap();
+                    assert!(pages.contains(&pg), "structure element on a page that is gone");
+                    Some(pg)
+                }
+                Err(_) => page,
+            };
+            if let Ok(obj) = dict.get(b"Obj") {
+                assert!(page.is_some());
+                doc.get_object(obj.as_reference().unwrap()).expect("OBJR to a missing object");
+            }
+            if let Ok(s) = dict.get(b"S") {
+                types.push(String::from_utf8_lossy(s.as_name().unwrap()).into_owned());
+            }
+            if let Ok(kids) = dict.get(b"K") {
+                queue.push((kids.clone(), page));
+            }
+        }
+
+        types.sort();
+        types
+    }
+
+    #[test]
+    fn prune_structure_tree_keeps_intact_tree() {
+        let mut doc = tagged_document();
+
+        assert_eq!(structure::prune_structure_tree(&mut doc), 0);
+        assert_eq!(check_structure(&doc), vec!["Document", "Link", "P", "P", "P"]);
+    }
+
+    #[test]
+    #[named]
+    fn delete_prunes_structure_tree() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        tagged_document().save(&infile).unwrap();
+
+        utils::delete(infile, Some(outfile.clone()), PageSelection::Pages(vec![2]), false, false, false, false, &SaveOptions::default()).unwrap();
+
+        let doc = Document::load(outfile).unwrap();
+        assert_eq!(check_structure(&doc), vec!["Document", "P", "P"]);
+
+        let root = structure_root(&doc);
+        let parent_tree = names::number_tree_entries(&doc, root.get(b"ParentTree").unwrap());
+        assert_eq!(parent_tree.keys().copied().collect::<Vec<_>>(), vec![0, 2]);
+        let id_tree = names::name_tree_entries(&doc, root.get(b"IDTree").unwrap());
+        assert_eq!(id_tree.keys().cloned().collect::<Vec<_>>(), vec![b"para1".to_vec(), b"para3".to_vec()]);
+    }
+
+    #[test]
+    fn prune_structure_tree_after_removing_pages() {
+        let mut doc = tagged_document();
+        doc.delete_pages(&[1, 2]);
+
+        assert_eq!(structure::prune_structure_tree(&mut doc), 3);
+        assert_eq!(check_structure(&doc), vec!["Document", "P"]);
+    }
+
+    #[test]
+    #[named]
+    fn extract_can_strip_structure_tree() {
+        let infile = build_outfile_pathbuf(&format!("{}_in", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+        tagged_document().save(&infile).unwrap();
+
+        utils::extract(infile, outfile.clone(), PageSelection::Pages(vec![2]), false, false, false, true, false, false, &SaveOptions::default()).unwrap();
+
+        let doc = Document::load(outfile).unwrap();
+        let catalog = doc.catalog().unwrap();
+        assert!(catalog.get(b"StructTreeRoot").is_err());
+        assert!(catalog.get(b"MarkInfo").is_err());
+        for page_id in doc.page_iter() {
+            assert!(doc.get_dictionary(page_id).unwrap().get(b"StructParents").is_err());
+        }
+    }
+}
@@ -1,11 +1,21 @@
-#[cfg(test)]
+mod common;
 
+#[cfg(test)]
 mod tests {
-    use std::{path::PathBuf, os::unix::prelude::FileTypeExt};
+    use std::ffi::OsStr;
+    use std::path::{Path, PathBuf};
 
+    use lopdf::dictionary;
+    use pdfh::bookmarks::{self, Heading};
     use pdfh::utils;
+    use pdfh::ops::{PageRange, PageSelection, PageSize, Position};
+    use pdfh::utils::SaveOptions;
+    use pdfh::error::{Error, LoadError};
+    use std::io::ErrorKind;
     use ::function_name::named;
 
+    use crate::common::text_document;
+
 
     const DATA_DIR: &str = "test-data";
 
@@ -49,7 +59,6 @@ mod tests {
     // }
 
     #[test]
-    #[should_panic(expected = "Failed to load document: \n Invalid file header")]
     fn invalid_pdf_file() {
         let test_resource: TestResources = TestResources::new();
 
@@ -57,13 +66,14 @@ mod tests {
         let pages = Some(vec![1,3]);
         let outfile = None;
 
-        utils::delete(test_resource.file_bad_header, outfile, pages, every, false, false)
+        let result = utils::delete(test_resource.file_bad_header.clone(), outfile, PageSelection::new(pages, every), false, false, false, false, &SaveOptions::default());
+        match result {
+            Err(Error::LoadFailed { path, source: LoadError::Parse(lopdf::Error::Header) }) => assert_eq!(path, test_resource.file_bad_header),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
     }
 
     #[test]
-    #[should_panic(
-        expected = "Failed to load document: \n No such file or directory (os error 2)"
-    )]
     fn infile_not_found() {
         let test_resource: TestResources = TestResources::new();
 
@@ -71,11 +81,11 @@ mod tests {
         let pages = Some(vec![1,3]);
         let outfile = None;
 
-        utils::delete(test_resource.file_does_not_exist, outfile, pages, every, false, false)
+        let result = utils::delete(test_resource.file_does_not_exist, outfile, PageSelection::new(pages, every), false, false, false, false, &SaveOptions::default());
+        assert!(matches!(result, Err(Error::LoadFailed { source: LoadError::Io(ref error), .. }) if error.kind() == ErrorKind::NotFound));
     }
 
     #[test]
-    #[should_panic(expected = "Failed to write out file: No such file or directory (os error 2)")]
     fn cannot_write_outfile() {
         let test_resource: TestResources = TestResources::new();
 
@@ -83,7 +93,18 @@ mod tests {
         let pages = Some(vec![1,3]);
         let outfile = Some(test_resource.outfile_cannot_write);
 
-        utils::delete(test_resource.two_pages, outfile, pages, every, false, false)
+        let result = utils::delete(test_resource.two_pages, outfile, PageSelection::new(pages, every), false, false, false, false, &SaveOptions::default());
+        assert!(matches!(result, Err(Error::SaveFailed { ref source, .. }) if source.kind() == ErrorKind::NotFound));
+    }
+
+    #[test]
+    #[named]
+    fn every_zero_is_invalid_selection() {
+        let test_resource: TestResources = TestResources::new();
+
+        let outfile = Some(build_outfile_pathbuf(function_name!()));
+        let result = utils::delete(test_resource.two_pages, outfile, PageSelection::Every(0), false, false, false, false, &SaveOptions::default());
+        assert!(matches!(result, Err(Error::InvalidSelection(_))));
     }
 
     #[test]
@@ -94,7 +115,7 @@ mod tests {
         let pages = Some(vec![1,3]);
         let outfile = Some(test_resource.outfile_valid);
 
-        utils::delete(test_resource.multi_page_single_page_obj, outfile, pages, every, false, false)
+        utils::delete(test_resource.multi_page_single_page_obj, outfile, PageSelection::new(pages, every), false, false, false, false, &SaveOptions::default()).unwrap();
     }
 
     #[test]
@@ -103,7 +124,7 @@ mod tests {
         let test_resource: TestResources = TestResources::new();
 
         let outfile = Some(build_outfile_pathbuf(function_name!()));
-        utils::reverse(test_resource.two_pages, outfile);
+        utils::reverse(test_resource.two_pages, outfile, &SaveOptions::default()).unwrap();
     }
 
     // Visual inspection is required of the output of these tests
@@ -116,7 +137,7 @@ mod tests {
         let pages = Some(vec![1,3]);
         let outfile = Some(build_outfile_pathbuf(function_name!()));
 
-        utils::delete(test_resource.multi_page_single_page_obj, outfile, pages, every, false, false)
+        utils::delete(test_resource.multi_page_single_page_obj, outfile, PageSelection::new(pages, every), false, false, false, false, &SaveOptions::default()).unwrap();
     }
 
     #[test]
@@ -127,7 +148,7 @@ mod tests {
         let outfile = Some(build_outfile_pathbuf(function_name!()));
         let every = Some(25);
         let pages = None;
-        utils::delete(test_resource.multi_page_single_page_obj, outfile, pages, every, false, false)
+        utils::delete(test_resource.multi_page_single_page_obj, outfile, PageSelection::new(pages, every), false, false, false, false, &SaveOptions::default()).unwrap();
     }
 
     #[test]
@@ -136,7 +157,7 @@ mod tests {
         let test_resource: TestResources = TestResources::new();
 
         let outfile = Some(build_outfile_pathbuf(function_name!()));
-        utils::reverse(test_resource.multi_page_multiple_pages_obj, outfile);
+        utils::reverse(test_resource.multi_page_multiple_pages_obj, outfile, &SaveOptions::default()).unwrap();
     }
 
     #[test]
@@ -145,7 +166,7 @@ mod tests {
         let test_resource: TestResources = TestResources::new();
 
         let outfile = Some(build_outfile_pathbuf(function_name!()));
-        utils::reverse(test_resource.multi_page_single_page_obj, outfile);
+        utils::reverse(test_resource.multi_page_single_page_obj, outfile, &SaveOptions::default()).unwrap();
     }
 
     #[test]
@@ -154,7 +175,7 @@ mod tests {
         let test_resource: TestResources = TestResources::new();
 
         let outfile = Some(build_outfile_pathbuf(function_name!()));
-        utils::reverse(test_resource.single_page, outfile);
+        utils::reverse(test_resource.single_page, outfile, &SaveOptions::default()).unwrap();
     }
 
     // Extract
@@ -167,7 +188,7 @@ mod tests {
         let outfile = build_outfile_pathbuf(function_name!());
         let every = None;
         let pages = Some(vec![2,3,5]);
-        utils::extract(test_resource.multi_page_single_page_obj, outfile, pages, every);
+        utils::extract(test_resource.multi_page_single_page_obj, outfile, PageSelection::new(pages, every), false, false, false, false, false, false, &SaveOptions::default()).unwrap();
     }
 
     #[test]
@@ -178,18 +199,1289 @@ mod tests {
         let outfile = build_outfile_pathbuf(function_name!());
         let every = Some(25);
         let pages = None;
-        utils::extract(test_resource.multi_page_multiple_pages_obj, outfile, pages, every);
+        utils::extract(test_resource.multi_page_multiple_pages_obj, outfile, PageSelection::new(pages, every), false, false, false, false, false, false, &SaveOptions::default()).unwrap();
     }
 
     #[test]
     #[named]
-    #[should_panic(expected = "Resulting document would have no pages.")]
     fn extract_single_page_document_page_out_of_bounds() {
         let test_resource: TestResources = TestResources::new();
 
         let outfile = build_outfile_pathbuf(function_name!());
         let every = None;
         let pages = Some(vec![2,3]);
-        utils::extract(test_resource.single_page, outfile, pages, every);
+        let result = utils::extract(test_resource.single_page, outfile, PageSelection::new(pages, every), false, false, false, false, false, false, &SaveOptions::default());
+        assert!(matches!(result, Err(Error::NoPagesRemaining)));
+    }
+
+    // Pages file
+
+    #[test]
+    #[named]
+    fn extract_pages_file_matches_inline_selection() {
+        let test_resource: TestResources = TestResources::new();
+
+        let pages_file = PathBuf::from(format!("{}/output/{}.txt", DATA_DIR, function_name!()));
+        std::fs::write(&pages_file, "# front matter\n2 3\n\n5 # appendix\n").unwrap();
+        let pages = utils::read_pages_file(&pages_file).unwrap();
+        assert_eq!(pages, vec![PageRange { first: 2, last: Some(2) }, PageRange { first: 3, last: Some(3) }, PageRange { first: 5, last: Some(5) }]);
+
+        let from_file = build_outfile_pathbuf(&format!("{}_file", function_name!()));
+        let inline = build_outfile_pathbuf(&format!("{}_inline", function_name!()));
+        utils::extract(test_resource.multi_page_single_page_obj.clone(), from_file.clone(), PageSelection::Ranges(pages), false, false, false, false, false, false, &SaveOptions::default()).unwrap();
+        utils::extract(test_resource.multi_page_single_page_obj, inline.clone(), PageSelection::Pages(vec![2,3,5]), false, false, false, false, false, false, &SaveOptions::default()).unwrap();
+
+        let from_file = lopdf::Document::load(from_file).unwrap();
+        let inline = lopdf::Document::load(inline).unwrap();
+        assert_eq!(from_file.get_pages().len(), inline.get_pages().len());
+        for (a, b) in from_file.get_pages().values().zip(inline.get_pages().values()) {
+            assert_eq!(from_file.get_page_content(*a).unwrap(), inline.get_page_content(*b).unwrap());
+        }
+    }
+
+    #[test]
+    #[named]
+    fn pages_file_empty() {
+        let pages_file = PathBuf::from(format!("{}/output/{}.txt", DATA_DIR, function_name!()));
+        std::fs::write(&pages_file, "# nothing selected\n\n").unwrap();
+        match utils::read_pages_file(&pages_file) {
+            Err(Error::InvalidSelection(reason)) => assert!(reason.ends_with("does not list any pages"), "{}", reason),
+            other => panic!("expected an empty pages file to be refused, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[named]
+    fn pages_file_bad_token() {
+        let pages_file = PathBuf::from(format!("{}/output/{}.txt", DATA_DIR, function_name!()));
+        std::fs::write(&pages_file, "1 two 3\n").unwrap();
+        match utils::read_pages_file(&pages_file) {
+            Err(Error::InvalidSelection(reason)) => assert!(reason.ends_with("`two` is not a page number"), "{}", reason),
+            other => panic!("expected `two` to be refused, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn pages_file_missing() {
+        let pages_file = PathBuf::from(format!("{}/output/no-such-pages-file.txt", DATA_DIR));
+        assert!(matches!(utils::read_pages_file(&pages_file), Err(Error::LoadFailed { source: LoadError::Io(_), .. })));
+
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(["extract", "test-data/two-pages.pdf", "test-data/output/pages_file_missing.pdf", "--pages-file", pages_file.to_str().unwrap()])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with("error: Failed to load"), "{}", stderr);
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+
+    // Page ranges
+
+    #[test]
+    fn page_spec_ranges() {
+        assert_eq!(utils::parse_page_spec("1-5,8,10-12").unwrap(), vec![1, 2, 3, 4, 5, 8, 10, 11, 12]);
+        assert_eq!(utils::parse_page_spec("4 2, 7-7").unwrap(), vec![4, 2, 7]);
+        // overlapping selections list each page once
+        assert_eq!(utils::parse_page_spec("3-6,5,1-4").unwrap(), vec![3, 4, 5, 6, 1, 2]);
+        assert_eq!(utils::join_page_specs(vec![vec![1, 2], vec![2, 3]]), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn page_spec_errors() {
+        assert_eq!(utils::parse_page_spec("9-3").unwrap_err(), "`9-3` is a reversed range, did you mean 3-9?");
+        assert_eq!(utils::parse_page_spec("1,x-4").unwrap_err(), "`x-4` is not a page number");
+        assert_eq!(utils::parse_page_spec("2-").unwrap_err(), "`2-` is not a page number");
+        assert_eq!(utils::parse_page_spec("0-2").unwrap_err(), "`0-2` selects page 0, pages are numbered from 1");
+    }
+
+    #[test]
+    #[named]
+    fn delete_and_extract_ranges() {
+        let test_resource: TestResources = TestResources::new();
+        let deleted = build_outfile_pathbuf(&format!("{}_delete", function_name!()));
+        let extracted = build_outfile_pathbuf(&format!("{}_extract", function_name!()));
+
+        let pages = utils::parse_page_spec("2-3").unwrap();
+        utils::delete(test_resource.multi_page_single_page_obj.clone(), Some(deleted.clone()), PageSelection::Pages(pages), false, false, false, false, &SaveOptions::default()).unwrap();
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(["extract".as_ref(), test_resource.multi_page_single_page_obj.as_os_str(), extracted.as_os_str(), "--pages".as_ref(), "1,4".as_ref()])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+
+        let deleted = lopdf::Document::load(deleted).unwrap();
+        let extracted = lopdf::Document::load(extracted).unwrap();
+        assert_eq!(deleted.get_pages().len(), 2);
+        assert_eq!(extracted.get_pages().len(), 2);
+        for (a, b) in deleted.get_pages().values().zip(extracted.get_pages().values()) {
+            assert_eq!(deleted.get_page_content(*a).unwrap(), extracted.get_page_content(*b).unwrap());
+        }
+    }
+
+    #[test]
+    #[named]
+    fn pages_file_ranges() {
+        let pages_file = PathBuf::from(format!("{}/output/{}.txt", DATA_DIR, function_name!()));
+        std::fs::write(&pages_file, "1-3 # intro\n2,5-6\n-2 9-\n").unwrap();
+        assert_eq!(utils::read_pages_file(&pages_file).unwrap(), vec![
+            PageRange { first: 1, last: Some(3) },
+            PageRange { first: 2, last: Some(2) },
+            PageRange { first: 5, last: Some(6) },
+            PageRange { first: 1, last: Some(2) },
+            PageRange { first: 9, last: None },
+        ]);
+    }
+
+    #[test]
+    #[named]
+    fn extract_pages_file_keeps_order_and_repeats() {
+        let test_resource: TestResources = TestResources::new();
+        let pages_file = PathBuf::from(format!("{}/output/{}.txt", DATA_DIR, function_name!()));
+        std::fs::write(&pages_file, "3-\n1\n3\n").unwrap();
+        let from_file = build_outfile_pathbuf(&format!("{}_file", function_name!()));
+        let inline = build_outfile_pathbuf(&format!("{}_inline", function_name!()));
+
+        let extract = |outfile: &PathBuf, selection: &[&str]| std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .arg("extract").arg(&test_resource.multi_page_single_page_obj).arg(outfile)
+            .args(selection)
+            .output()
+            .unwrap();
+        assert!(extract(&from_file, &["--pages-file", pages_file.to_str().unwrap()]).status.success());
+        assert!(extract(&inline, &["--pages", "3-", "1", "3"]).status.success());
+
+        let from_file = lopdf::Document::load(from_file).unwrap();
+        let inline = lopdf::Document::load(inline).unwrap();
+        let count = lopdf::Document::load(&test_resource.multi_page_single_page_obj).unwrap().get_pages().len();
+        // pages 3 to the end, then 1, then 3 again
+        assert_eq!(from_file.get_pages().len(), (count - 2) + 2);
+        for (a, b) in from_file.get_pages().values().zip(inline.get_pages().values()) {
+            assert_eq!(from_file.get_page_content(*a).unwrap(), inline.get_page_content(*b).unwrap());
+        }
+    }
+
+    // Preserve
+
+    #[test]
+    #[named]
+    fn rotate_inplace_preserves_mode_and_mtime() {
+        let test_resource: TestResources = TestResources::new();
+        let file = build_outfile_pathbuf(function_name!());
+        std::fs::copy(test_resource.two_pages, &file).unwrap();
+
+        let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+        std::fs::File::options().write(true).open(&file).unwrap().set_modified(mtime).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o640)).unwrap();
+        }
+
+        let save = SaveOptions { preserve: true, ..SaveOptions::default() };
+        utils::rotate(file.clone(), None, 90, PageSelection::All, false, false, &save).unwrap();
+
+        let metadata = std::fs::metadata(&file).unwrap();
+        assert_eq!(metadata.modified().unwrap(), mtime);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        }
+    }
+
+    // Output paths
+
+    #[test]
+    fn output_path_precedence() {
+        let command_dir = Some(Path::new("chapters"));
+        let global_dir = Some(Path::new("out"));
+        let absolute = std::env::temp_dir().join("a.pdf");
+
+        assert_eq!(utils::resolve_output_path(&absolute, command_dir, global_dir), absolute);
+        assert_eq!(utils::resolve_output_path(Path::new("a.pdf"), command_dir, global_dir), PathBuf::from("chapters/a.pdf"));
+        assert_eq!(utils::resolve_output_path(Path::new("a.pdf"), None, global_dir), PathBuf::from("out/a.pdf"));
+        assert_eq!(utils::resolve_output_path(Path::new("a.pdf"), None, None), PathBuf::from("a.pdf"));
+        assert_eq!(utils::output_dir(None, None), PathBuf::from("."));
+    }
+
+    #[test]
+    #[named]
+    fn global_output_dir_created_and_inplace_unaffected() {
+        let test_resource: TestResources = TestResources::new();
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let inplace = build_outfile_pathbuf(function_name!());
+        std::fs::copy(&test_resource.two_pages, &inplace).unwrap();
+
+        let run = |args: &[&std::ffi::OsStr]| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_pdfh")).args(args).status().unwrap()
+        };
+        let extract = run(&["extract".as_ref(), test_resource.two_pages.as_os_str(), "first.pdf".as_ref(),
+                            "--pages".as_ref(), "1".as_ref(), "--output-dir".as_ref(), dir.join("nested").as_os_str()]);
+        let rotate = run(&["--output-dir".as_ref(), dir.as_os_str(), "rotate".as_ref(), inplace.as_os_str(), "--degrees".as_ref(), "90".as_ref()]);
+
+        assert!(extract.success() && rotate.success());
+        assert!(dir.join("nested/first.pdf").is_file());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    fn info_field(doc: &lopdf::Document, key: &[u8]) -> Option<String> {
+        let info = doc.trailer.get(b"Info").and_then(|info| info.as_reference()).ok()?;
+        let bytes = doc.get_dictionary(info).ok()?.get(key).ok()?.as_str().ok()?;
+        if bytes.starts_with(&[0xFE, 0xFF]) {
+            let units: Vec<u16> = bytes[2..].chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+            return Some(String::from_utf16_lossy(&units));
+        }
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    #[test]
+    #[named]
+    fn merge_keeps_first_input_info() {
+        let test_resource: TestResources = TestResources::new();
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        utils::merge(&vec![test_resource.two_pages, test_resource.multi_page_single_page_obj], outfile.clone(), false, false, utils::SortOrder::Name, false, false, None, false, &utils::DocumentInfo::default()).unwrap();
+
+        let merged = lopdf::Document::load(&outfile).unwrap();
+        assert_eq!(info_field(&merged, b"Producer").as_deref(), Some("Skia/PDF m102"));
+        assert_eq!(info_field(&merged, b"Title"), None);
+    }
+
+    #[test]
+    #[named]
+    fn merge_sets_info() {
+        let test_resource: TestResources = TestResources::new();
+        let outfile = build_outfile_pathbuf(function_name!());
+        let info = utils::DocumentInfo {
+            title: Some("Annual Report".to_string()),
+            author: Some("Zoë Brontë".to_string()),
+            keywords: Some("finance, 2026".to_string()),
+            ..utils::DocumentInfo::default()
+        };
+
+        utils::merge(&vec![test_resource.multi_page_single_page_obj, test_resource.two_pages], outfile.clone(), false, false, utils::SortOrder::Name, false, false, None, false, &info).unwrap();
+
+        let merged = lopdf::Document::load(&outfile).unwrap();
+        assert_eq!(info_field(&merged, b"Title").as_deref(), Some("Annual Report"));
+        assert_eq!(info_field(&merged, b"Author").as_deref(), Some("Zoë Brontë"));
+        assert_eq!(info_field(&merged, b"Keywords").as_deref(), Some("finance, 2026"));
+        // fields that are not set keep the first input's value
+        assert_eq!(info_field(&merged, b"Subject").as_deref(), Some("Programming Assignment I, Section 00001 & 00002 (Winter 2022)"));
+    }
+
+    #[test]
+    #[named]
+    fn info_file() {
+        let json = PathBuf::from(format!("{}/output/{}.json", DATA_DIR, function_name!()));
+        std::fs::write(&json, r#"{"title": "Deliverable", "subject": "Q3"}"#).unwrap();
+
+        let info = utils::read_info_file(&json).unwrap();
+        assert_eq!(info.title.as_deref(), Some("Deliverable"));
+        assert_eq!(info.subject.as_deref(), Some("Q3"));
+        assert_eq!(info.author, None);
+    }
+
+    #[test]
+    #[named]
+    fn info_file_unknown_field() {
+        let json = PathBuf::from(format!("{}/output/{}.json", DATA_DIR, function_name!()));
+        std::fs::write(&json, r#"{"creator": "me"}"#).unwrap();
+
+        let error = utils::read_info_file(&json).unwrap_err();
+        assert!(error.to_string().contains("unknown field `creator`"), "{}", error);
+    }
+
+    #[test]
+    #[named]
+    fn info_file_missing() {
+        let json = PathBuf::from(format!("{}/output/{}.json", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_file(&json);
+
+        assert!(matches!(utils::read_info_file(&json), Err(Error::LoadFailed { .. })));
+    }
+
+    #[test]
+    #[named]
+    fn split_each_page() {
+        let test_resource: TestResources = TestResources::new();
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let report = utils::split(test_resource.multi_page_single_page_obj, &utils::SplitNaming::Outfile(dir.join("out.pdf")), PageSelection::All, None, false, false, false, false).unwrap();
+
+        assert_eq!(report.pages_out, 4);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 4);
+        for page in 1..=4 {
+            let doc = lopdf::Document::load(dir.join(format!("out-00{}.pdf", page))).unwrap();
+            let pages = doc.get_pages();
+            assert_eq!(pages.len(), 1);
+            // the page keeps what it inherited from the page tree
+            let page_dict = doc.get_dictionary(pages[&1]).unwrap();
+            assert!(page_dict.has(b"MediaBox") && page_dict.has(b"Resources"));
+        }
+    }
+
+    #[test]
+    #[named]
+    fn split_file_counts() {
+        let test_resource: TestResources = TestResources::new();
+        let inputs = [(test_resource.single_page, 1), (test_resource.two_pages, 2)];
+
+        for (index, (infile, count)) in inputs.into_iter().enumerate() {
+            let dir = PathBuf::from(format!("{}/output/{}-{}", DATA_DIR, function_name!(), index));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+
+            let report = utils::split(infile, &utils::SplitNaming::Outfile(dir.join("out.pdf")), PageSelection::All, None, false, false, true, false).unwrap();
+
+            assert_eq!(report.pages_out, count);
+            let mut files: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+            files.sort();
+            assert_eq!(files.len(), count);
+            assert_eq!(files[0], dir.join("out-001.pdf"));
+            for file in files {
+                assert_eq!(lopdf::Document::load(&file).unwrap().get_pages().len(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn split_path_width() {
+        let outfile = Path::new("dir/report.pdf");
+        assert_eq!(utils::split_path(outfile, 7, 12), PathBuf::from("dir/report-007.pdf"));
+        assert_eq!(utils::split_path(outfile, 7, 1200), PathBuf::from("dir/report-0007.pdf"));
+        assert_eq!(utils::split_path(Path::new("pages"), 1, 1), PathBuf::from("pages-001.pdf"));
+    }
+
+    #[test]
+    #[named]
+    fn dupe_three_times() {
+        let test_resource: TestResources = TestResources::new();
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let report = utils::dupe(test_resource.two_pages, outfile.clone(), 3, true, false).unwrap();
+
+        assert_eq!(report.pages_out, 6);
+        assert_eq!(lopdf::Document::load(&outfile).unwrap().get_pages().len(), 6);
+    }
+
+    #[test]
+    #[named]
+    fn dupe_zero_copies_rejected() {
+        let test_resource: TestResources = TestResources::new();
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(["dupe".as_ref(), test_resource.two_pages.as_os_str(), outfile.as_os_str(), "--num".as_ref(), "0".as_ref()])
+            .output()
+            .unwrap();
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("the output needs at least one copy"));
+        assert!(!outfile.exists());
+    }
+
+    #[test]
+    #[named]
+    fn dupe_shares_everything_but_the_pages() {
+        let test_resource: TestResources = TestResources::new();
+        let once = build_outfile_pathbuf(&format!("{}_once", function_name!()));
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let single = utils::dupe(test_resource.multi_page_multiple_pages_obj.clone(), once, 1, true, false).unwrap();
+        let report = utils::dupe(test_resource.multi_page_multiple_pages_obj, outfile.clone(), 50, true, false).unwrap();
+
+        assert_eq!(report.pages_out, 50 * single.pages_out);
+        let doc = lopdf::Document::load(&outfile).unwrap();
+        assert_eq!(doc.get_pages().len(), 50 * single.pages_out);
+        // 49 more copies of the page dictionaries, not of their contents and images
+        assert!(report.bytes_out < 2 * single.bytes_out, "{} bytes for 50 copies, {} for one", report.bytes_out, single.bytes_out);
+    }
+
+    #[test]
+    #[named]
+    fn dupe_uncollated() {
+        let test_resource: TestResources = TestResources::new();
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(["dupe".as_ref(), test_resource.multi_page_single_page_obj.as_os_str(), outfile.as_os_str(), "--num".as_ref(), "3".as_ref(), "--uncollated".as_ref()])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let contents = |path: &PathBuf| {
+            let doc = lopdf::Document::load(path).unwrap();
+            doc.page_iter().map(|id| doc.get_page_content(id).unwrap()).collect::<Vec<Vec<u8>>>()
+        };
+        let original = contents(&test_resource.multi_page_single_page_obj);
+        let expected: Vec<Vec<u8>> = original.iter().flat_map(|page| [page.clone(), page.clone(), page.clone()]).collect();
+        assert_eq!(contents(&outfile), expected);
+    }
+
+    // a 12 page report.pdf, each page showing its number
+    fn twelve_pages(dir: &Path) -> PathBuf {
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir_all(dir).unwrap();
+        let contents: Vec<String> = (1..=12).map(|page| format!("BT ({}) Tj ET", page)).collect();
+        let infile = dir.join("report.pdf");
+        text_document(&contents.iter().map(String::as_str).collect::<Vec<_>>()).save(&infile).unwrap();
+        infile
+    }
+
+    #[test]
+    #[named]
+    fn split_with_pattern() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let out_dir = dir.join("pages");
+
+        let naming = utils::SplitNaming::Pattern { dir: out_dir.clone(), pattern: "{stem}-{page:03}.pdf".to_string() };
+        let report = utils::split(infile, &naming, PageSelection::All, None, true, false, false, false).unwrap();
+
+        assert_eq!(report.pages_out, 12);
+        let mut names: Vec<String> = std::fs::read_dir(&out_dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        names.sort();
+        let expected: Vec<String> = (1..=12).map(|page| format!("report-{:03}.pdf", page)).collect();
+        assert_eq!(names, expected);
+        let last = lopdf::Document::load(out_dir.join("report-012.pdf")).unwrap();
+        assert_eq!(last.get_page_content(last.page_iter().next().unwrap()).unwrap(), b"BT (12) Tj ET");
+    }
+
+    #[test]
+    #[named]
+    fn split_pattern_errors() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let split = |pattern: &str, create_dir: bool| {
+            let naming = utils::SplitNaming::Pattern { dir: dir.join("pages"), pattern: pattern.to_string() };
+            utils::split(infile.clone(), &naming, PageSelection::All, None, create_dir, false, false, false)
+        };
+
+        match split("{stem}-{page}.pdf", false) {
+            Err(Error::OutputDirMissing(missing)) => assert_eq!(missing, dir.join("pages")),
+            other => panic!("expected the missing directory, got {:?}", other),
+        }
+        // nothing is written when two pages would get the same name
+        match split("{stem}-of-{total}.pdf", true) {
+            Err(Error::OutputPattern(reason)) => assert!(reason.starts_with("pages 1 and 2 would both be written to"), "{}", reason),
+            other => panic!("expected duplicate names, got {:?}", other),
+        }
+        assert_eq!(std::fs::read_dir(dir.join("pages")).unwrap().count(), 0);
+        assert!(matches!(split("{name}-{page}.pdf", true), Err(Error::OutputPattern(_))));
+    }
+
+    #[test]
+    fn pattern_names() {
+        assert_eq!(utils::pattern_name("{stem}-{page:03}.pdf", OsStr::new("report"), 7, 12).unwrap(), "report-007.pdf");
+        assert_eq!(utils::pattern_name("{page}-of-{total:2}", OsStr::new("report"), 7, 12).unwrap(), "7-of-12");
+        assert_eq!(utils::pattern_name("{{{stem}}}", OsStr::new("a"), 1, 1).unwrap(), "{a}");
+        assert_eq!(utils::pattern_name("{page:x}", OsStr::new("a"), 1, 1).unwrap_err(), "`x` in `{page:x}` is not a width");
+        assert_eq!(utils::pattern_name("{page", OsStr::new("a"), 1, 1).unwrap_err(), "`{page` has an unmatched brace");
+        assert_eq!(utils::pattern_name("{title}.pdf", OsStr::new("a"), 1, 1).unwrap_err(), "`{title}` is not one of {stem}, {page} or {total}");
+    }
+
+    #[test]
+    #[named]
+    fn split_pattern_cli() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let out_dir = dir.join("pages");
+
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(["split".as_ref(), infile.as_os_str(), "--out-dir".as_ref(), out_dir.as_os_str(), "--pattern".as_ref(), "{page:02}.pdf".as_ref()])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("does not exist, add --mkdir to create it"));
+
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(["split".as_ref(), infile.as_os_str(), "--out-dir".as_ref(), out_dir.as_os_str(), "--pattern".as_ref(), "{page:02}.pdf".as_ref(), "--mkdir".as_ref()])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert!(out_dir.join("01.pdf").exists() && out_dir.join("12.pdf").exists());
+    }
+
+    fn page_numbers(path: &Path) -> Vec<String> {
+        let doc = lopdf::Document::load(path).unwrap();
+        doc.page_iter().map(|id| String::from_utf8(doc.get_page_content(id).unwrap()).unwrap().replace("BT (", "").replace(") Tj ET", "")).collect()
+    }
+
+    #[test]
+    #[named]
+    fn split_ranges_with_gap_and_open_end() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let out_dir = dir.join("parts");
+
+        let report = utils::split_ranges(infile, &out_dir, "1-4:cover.pdf, 6-8:body.pdf,9-:appendix.pdf", true, false, false).unwrap();
+
+        assert_eq!(report.pages_out, 4 + 3 + 4);
+        assert_eq!(page_numbers(&out_dir.join("cover.pdf")), ["1", "2", "3", "4"]);
+        assert_eq!(page_numbers(&out_dir.join("body.pdf")), ["6", "7", "8"]);
+        assert_eq!(page_numbers(&out_dir.join("appendix.pdf")), ["9", "10", "11", "12"]);
+        assert_eq!(std::fs::read_dir(&out_dir).unwrap().count(), 3);
+    }
+
+    #[test]
+    #[named]
+    fn split_ranges_rejected() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let out_dir = dir.join("parts");
+        let split = |spec: &str| match utils::split_ranges(infile.clone(), &out_dir, spec, true, false, false) {
+            Err(Error::InvalidSelection(reason)) => reason,
+            other => panic!("expected {} to be rejected, got {:?}", spec, other),
+        };
+
+        assert_eq!(split("1-5:a.pdf,5-6:b.pdf"), "`5-6:b.pdf` overlaps `1-5:a.pdf`");
+        assert_eq!(split("7-:a.pdf,1-8:b.pdf"), "`1-8:b.pdf` overlaps `7-:a.pdf`");
+        assert_eq!(split("1-4:a.pdf,10-13:b.pdf"), "`10-13:b.pdf` goes past the last page, 12");
+        assert_eq!(split("13-:a.pdf"), "`13-:a.pdf` goes past the last page, 12");
+        // nothing was written
+        assert!(std::fs::read_dir(&out_dir).map_or(true, |mut entries| entries.next().is_none()));
+    }
+
+    #[test]
+    fn parse_split_ranges() {
+        let ranges = utils::parse_split_ranges("1-4:cover.pdf,5:one.pdf,31-:appendix.pdf").unwrap();
+        let spans: Vec<(u32, Option<u32>, &str)> = ranges.iter().map(|range| (range.first, range.last, range.name.to_str().unwrap())).collect();
+        assert_eq!(spans, [(1, Some(4), "cover.pdf"), (5, Some(5), "one.pdf"), (31, None, "appendix.pdf")]);
+
+        assert_eq!(utils::parse_split_ranges("1-4").unwrap_err(), "`1-4` needs a file name, like 1-4:cover.pdf");
+        assert_eq!(utils::parse_split_ranges("1-4:a.pdf,x-9:b.pdf").unwrap_err(), "`x-9:b.pdf` is not a page number");
+        assert_eq!(utils::parse_split_ranges("9-4:a.pdf").unwrap_err(), "`9-4:a.pdf` is a reversed range, did you mean 4-9?");
+        assert_eq!(utils::parse_split_ranges("1:a.pdf,2:a.pdf").unwrap_err(), "`2:a.pdf` writes to a.pdf as well as `1:a.pdf`");
+    }
+
+    // twelve pages with three chapters, the outline lists the last one before the second
+    fn three_chapters(dir: &Path) -> PathBuf {
+        let infile = twelve_pages(dir);
+        let heading = |level, page, title: &str| Heading { level, page, size: 12.0, title: title.to_string() };
+        let mut doc = lopdf::Document::load(&infile).unwrap();
+        bookmarks::set_outline(&mut doc, &[heading(1, 1, "Introduction"),
+                                           heading(2, 3, "Background"),
+                                           heading(1, 9, "Results/Discussion"),
+                                           heading(1, 5, "Methods")]);
+        doc.save(&infile).unwrap();
+        infile
+    }
+
+    #[test]
+    #[named]
+    fn split_by_bookmarks() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = three_chapters(&dir);
+        let out_dir = dir.join("chapters");
+
+        let report = utils::split_bookmarks(infile, &out_dir, true, false, false).unwrap();
+
+        assert_eq!(report.pages_out, 12);
+        assert_eq!(page_numbers(&out_dir.join("Introduction.pdf")), ["1", "2", "3", "4"]);
+        assert_eq!(page_numbers(&out_dir.join("Methods.pdf")), ["5", "6", "7", "8"]);
+        assert_eq!(page_numbers(&out_dir.join("Results_Discussion.pdf")), ["9", "10", "11", "12"]);
+        assert_eq!(std::fs::read_dir(&out_dir).unwrap().count(), 3);
+    }
+
+    #[test]
+    #[named]
+    fn split_by_bookmarks_without_outline() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+
+        let error = utils::split_bookmarks(infile.clone(), &dir.join("chapters"), true, false, false).unwrap_err();
+        assert!(matches!(&error, Error::NoBookmarks(path) if *path == infile));
+        assert!(error.to_string().ends_with("has no bookmarks to split at, split it into single pages or use --ranges instead"));
+    }
+
+    #[test]
+    fn bookmark_file_names() {
+        let titles = ["Intro", "a/b", "A\\B", "intro", "Intro-2", "", "  .hidden. ", "Intro"].map(String::from);
+        assert_eq!(utils::bookmark_file_names(&titles),
+                   ["Intro.pdf", "a_b.pdf", "A_B-2.pdf", "intro-2.pdf", "Intro-2-2.pdf", "untitled.pdf", "hidden.pdf", "Intro-3.pdf"]);
+    }
+
+    // the content of every page, in page order
+    fn page_contents(path: &Path) -> Vec<Vec<u8>> {
+        let doc = lopdf::Document::load(path).unwrap();
+        doc.page_iter().map(|id| doc.get_page_content(id).unwrap()).collect()
+    }
+
+    #[test]
+    #[named]
+    fn split_odd_even() {
+        let test_resource: TestResources = TestResources::new();
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        for infile in [test_resource.two_pages, test_resource.multi_page_multiple_pages_obj] {
+            let (odd, even) = (dir.join("odd.pdf"), dir.join("even.pdf"));
+            let report = utils::split_odd_even(infile.clone(), Some(odd.clone()), Some(even.clone()), true, false, false).unwrap();
+
+            let pages = page_contents(&infile);
+            assert_eq!(report.pages_out, pages.len());
+            assert_eq!(page_contents(&odd), pages.iter().step_by(2).cloned().collect::<Vec<_>>());
+            assert_eq!(page_contents(&even), pages.iter().skip(1).step_by(2).cloned().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    #[named]
+    fn split_odd_even_one_page() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let infile = dir.join("one.pdf");
+        text_document(&["BT (1) Tj ET"]).save(&infile).unwrap();
+        let (odd, even) = (dir.join("odd.pdf"), dir.join("even.pdf"));
+
+        let report = utils::split_odd_even(infile, Some(odd.clone()), Some(even.clone()), false, false, false).unwrap();
+
+        assert_eq!(report.pages_out, 1);
+        assert_eq!(page_numbers(&odd), ["1"]);
+        assert!(!even.exists());
+    }
+
+    #[test]
+    #[named]
+    fn split_odd_even_refuses_to_overwrite() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let same = dir.join(".").join("report.pdf");
+        let odd = dir.join("odd.pdf");
+
+        let error = utils::split_odd_even(infile.clone(), Some(odd.clone()), Some(same.clone()), false, false, false).unwrap_err();
+        assert!(matches!(&error, Error::OutputIsInput(path) if *path == same));
+        assert!(error.to_string().ends_with("is the input, write to another file"));
+
+        assert!(matches!(utils::split_odd_even(infile.clone(), Some(odd.clone()), Some(odd.clone()), false, false, false),
+                         Err(Error::OutputPattern(_))));
+        assert!(matches!(utils::split_odd_even(infile.clone(), None, None, false, false, false), Err(Error::InvalidSelection(_))));
+        // nothing was written and the input is untouched
+        assert!(!odd.exists());
+        assert_eq!(page_numbers(&infile).len(), 12);
+    }
+
+    #[test]
+    #[named]
+    fn rotate_negative_degrees_same_as_positive() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let rotate = |degrees: &str, outfile: &str| {
+            let outfile = dir.join(outfile);
+            let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+                .args(["rotate".as_ref(), infile.as_os_str(), outfile.as_os_str(), "-d".as_ref(), degrees.as_ref()])
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+            std::fs::read(outfile).unwrap()
+        };
+
+        let counterclockwise = rotate("-90", "ccw.pdf");
+        assert_eq!(counterclockwise, rotate("270", "cw.pdf"));
+        assert_eq!(counterclockwise, rotate("-450", "ccw-450.pdf"));
+        assert!(String::from_utf8_lossy(&counterclockwise).contains("/Rotate 270"));
+    }
+
+    fn rotations(path: &Path) -> Vec<Option<i64>> {
+        let doc = lopdf::Document::load(path).unwrap();
+        doc.page_iter().map(|id| doc.get_dictionary(id).unwrap().get(b"Rotate").ok().map(|rotate| rotate.as_i64().unwrap())).collect()
+    }
+
+    #[test]
+    #[named]
+    fn rotate_with_spec() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let outfile = dir.join("rotated.pdf");
+
+        utils::rotate_spec(infile.clone(), Some(outfile.clone()), "3:90, 4-6:-90,12:180,5:270", false, false, &SaveOptions::default()).unwrap();
+        assert_eq!(rotations(&outfile), [None, None, Some(90), Some(270), Some(270), Some(270), None, None, None, None, None, Some(180)]);
+
+        utils::rotate_spec(outfile.clone(), None, "3:90,4:90,12:90", true, false, &SaveOptions::default()).unwrap();
+        assert_eq!(rotations(&outfile), [None, None, Some(180), None, Some(270), Some(270), None, None, None, None, None, Some(270)]);
+
+        match utils::rotate_spec(infile, None, "1:90,13:90", false, true, &SaveOptions::default()) {
+            Err(Error::InvalidSelection(reason)) => assert_eq!(reason, "the document has 12 pages, there is no page 13"),
+            other => panic!("expected page 13 to be rejected, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_rotation_spec() {
+        assert_eq!(utils::parse_rotation_spec("3:90,5-6:-90,1:180").unwrap(), [(1, 180), (3, 90), (5, -90), (6, -90)]);
+        assert_eq!(utils::parse_rotation_spec("2:90,1-3:450").unwrap(), [(1, 450), (2, 90), (3, 450)]);
+
+        assert_eq!(utils::parse_rotation_spec("3").unwrap_err(), "`3` needs the degrees to rotate by, like 3:90");
+        assert_eq!(utils::parse_rotation_spec("3:45").unwrap_err(), "`3:45` does not rotate by a multiple of 90");
+        assert_eq!(utils::parse_rotation_spec("3:left").unwrap_err(), "`3:left` does not rotate by a multiple of 90");
+        assert_eq!(utils::parse_rotation_spec("4-:90").unwrap_err(), "`4-:90` needs the last page of the range");
+        assert_eq!(utils::parse_rotation_spec("6-4:90").unwrap_err(), "`6-4:90` is a reversed range, did you mean 4-6?");
+        assert_eq!(utils::parse_rotation_spec("0:90").unwrap_err(), "`0:90` selects page 0, pages are numbered from 1");
+        assert_eq!(utils::parse_rotation_spec("4-6:90,5:180").unwrap_err(), "`4-6:90` and `5:180` rotate page 5 differently");
+        assert_eq!(utils::parse_rotation_spec(" , ").unwrap_err(), "no rotations given");
+    }
+
+    #[test]
+    #[named]
+    fn rotate_missing_pages() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let outfile = dir.join("rotated.pdf");
+        let rotate = |extra: &[&str]| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+                .args([&["rotate", infile.to_str().unwrap(), outfile.to_str().unwrap(), "-d", "90", "--pages", "3", "31", "14"], extra].concat())
+                .output()
+                .unwrap()
+        };
+
+        let strict = rotate(&["--strict"]);
+        assert_eq!(strict.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&strict.stderr).contains("the document has 12 pages, there is no page 14, 31"));
+        assert!(!outfile.exists());
+
+        let lenient = rotate(&[]);
+        assert!(lenient.status.success(), "{}", String::from_utf8_lossy(&lenient.stderr));
+        assert!(String::from_utf8_lossy(&lenient.stderr).contains("warning: the document has 12 pages, there is no page 14, 31, it was skipped"));
+        assert_eq!(rotations(&outfile)[..4], [None, None, Some(90), None]);
+    }
+
+    #[test]
+    #[named]
+    fn rotate_odd_and_even() {
+        let test_resource: TestResources = TestResources::new();
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let outfile = dir.join("rotated.pdf");
+        let run = |args: &[&str]| std::process::Command::new(env!("CARGO_BIN_EXE_pdfh")).args(args).output().unwrap();
+
+        let infile = test_resource.multi_page_multiple_pages_obj.to_str().unwrap();
+        let output = run(&["rotate", infile, outfile.to_str().unwrap(), "--even", "-d", "180"]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let rotated = rotations(&outfile);
+        assert!(rotated.len() > 2);
+        for (i, rotation) in rotated.iter().enumerate() {
+            assert_eq!(*rotation, if i % 2 == 1 { Some(180) } else { None }, "page {}", i + 1);
+        }
+
+        let output = run(&["rotate", outfile.to_str().unwrap(), "--odd", "-d", "90", "--relative"]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        for (i, rotation) in rotations(&outfile).iter().enumerate() {
+            assert_eq!(*rotation, Some(if i % 2 == 1 { 180 } else { 90 }), "page {}", i + 1);
+        }
+
+        for conflicting in [&["--pages", "1"][..], &["--every", "2"], &["--even"]] {
+            let output = run(&[&["rotate", infile, outfile.to_str().unwrap(), "--odd", "-d", "90"], conflicting].concat());
+            assert_eq!(output.status.code(), Some(2), "{:?}", conflicting);
+        }
+    }
+
+    #[test]
+    #[named]
+    fn delete_and_extract_pages_outside_the_document() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let outfile = dir.join("out.pdf");
+        let delete = |pages: Vec<u32>, strict: bool| {
+            let _ = std::fs::remove_file(&outfile);
+            utils::delete(infile.clone(), Some(outfile.clone()), PageSelection::Pages(pages), false, false, false, strict, &SaveOptions::default())
+        };
+        let extract = |pages: Vec<u32>, strict: bool| {
+            let _ = std::fs::remove_file(&outfile);
+            utils::extract(infile.clone(), outfile.clone(), PageSelection::Pages(pages), false, false, false, false, false, strict, &SaveOptions::default())
+        };
+        let rejected = |result: Result<utils::SaveReport, Error>| match result {
+            Err(Error::InvalidSelection(reason)) => reason,
+            other => panic!("expected the selection to be rejected, got {:?}", other),
+        };
+
+        // valid
+        assert_eq!(delete(vec![2, 12], true).unwrap().pages_out, 10);
+        assert_eq!(extract(vec![2, 12], true).unwrap().pages_out, 2);
+
+        // partly outside, the others are still deleted or extracted unless strict
+        assert_eq!(delete(vec![2, 14, 13], false).unwrap().pages_out, 11);
+        assert_eq!(page_numbers(&outfile)[..2], ["1", "3"]);
+        assert_eq!(extract(vec![13, 2], false).unwrap().pages_out, 1);
+        assert_eq!(rejected(delete(vec![2, 14, 13], true)), "the document has 12 pages, there is no page 13-14");
+        assert_eq!(rejected(extract(vec![13, 2], true)), "the document has 12 pages, there is no page 13");
+        assert!(!outfile.exists());
+
+        // all outside
+        assert_eq!(delete(vec![14], false).unwrap().pages_out, 12);
+        assert!(matches!(extract(vec![14], false), Err(Error::NoPagesRemaining)));
+        assert_eq!(rejected(delete(vec![14], true)), "the document has 12 pages, there is no page 14");
+        assert_eq!(rejected(extract(vec![14], true)), "the document has 12 pages, there is no page 14");
+
+        // page 0, strict or not
+        assert_eq!(rejected(delete(vec![0, 1], false)), "page 0 is listed, pages are numbered from 1");
+        assert_eq!(rejected(extract(vec![0, 1], false)), "page 0 is listed, pages are numbered from 1");
+    }
+
+    #[test]
+    #[named]
+    fn delete_missing_pages_cli() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let outfile = dir.join("out.pdf");
+        let delete = |extra: &[&str]| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+                .args([&["delete", infile.to_str().unwrap(), outfile.to_str().unwrap(), "--pages", "14"], extra].concat())
+                .output()
+                .unwrap()
+        };
+
+        let strict = delete(&["--strict"]);
+        assert_eq!(strict.status.code(), Some(1));
+        assert!(!outfile.exists());
+
+        let lenient = delete(&[]);
+        assert!(lenient.status.success(), "{}", String::from_utf8_lossy(&lenient.stderr));
+        assert!(String::from_utf8_lossy(&lenient.stderr).contains("warning: the document has 12 pages, there is no page 14"));
+    }
+
+    #[test]
+    #[named]
+    fn open_ended_page_ranges() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let outfile = dir.join("out.pdf");
+        let run = |args: &[&str]| {
+            let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+                .args([&args[..1], &[infile.to_str().unwrap(), outfile.to_str().unwrap()], &args[1..]].concat())
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+            page_numbers(&outfile)
+        };
+        let numbers = |pages: std::ops::RangeInclusive<u32>| pages.map(|page| page.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(run(&["delete", "--pages", "5-"]), numbers(1..=4));
+        assert_eq!(run(&["delete", "--pages", "-5"]), numbers(6..=12));
+        assert_eq!(run(&["extract", "--pages", "10-"]), numbers(10..=12));
+        assert_eq!(run(&["extract", "--pages", "-3", "11-"]), ["1", "2", "3", "11", "12"]);
+        // --negate keeps the listed pages
+        assert_eq!(run(&["delete", "--pages", "10-", "--negate"]), numbers(10..=12));
+        assert_eq!(run(&["delete", "--pages", "-9", "--negate"]), numbers(1..=9));
+    }
+
+    #[test]
+    fn open_ended_ranges_past_the_end() {
+        let doc = text_document(&["BT ET"; 12]);
+        let open = |first, last| PageRange { first, last };
+
+        let selection = PageSelection::Ranges(vec![open(11, None), open(20, None), open(1, Some(2)), open(12, Some(14))]);
+        assert_eq!(selection.page_numbers(&doc).unwrap(), [1, 2, 11, 12]);
+        assert_eq!(selection.missing_pages(&doc), [13, 14, 20]);
+        assert_eq!(PageSelection::Ranges(vec![open(13, None)]).page_numbers(&doc).unwrap(), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn parse_page_ranges() {
+        let open = |first, last| PageRange { first, last };
+        assert_eq!(utils::parse_page_ranges("5-").unwrap(), [open(5, None)]);
+        assert_eq!(utils::parse_page_ranges("-5").unwrap(), [open(1, Some(5))]);
+        assert_eq!(utils::parse_page_ranges("1,3-4 20-").unwrap(), [open(1, Some(1)), open(3, Some(4)), open(20, None)]);
+
+        assert_eq!(utils::parse_page_ranges("-").unwrap_err(), "`-` is not a page number");
+        assert_eq!(utils::parse_page_ranges("0-").unwrap_err(), "`0-` selects page 0, pages are numbered from 1");
+        assert_eq!(utils::parse_page_ranges("-x").unwrap_err(), "`-x` is not a page number");
+        assert_eq!(utils::parse_page_ranges("5-3").unwrap_err(), "`5-3` is a reversed range, did you mean 3-5?");
+    }
+
+    // text on pages 1, 3, 6 and 8, a long drawing on page 7, blanks in between
+    const BLANKS: [&str; 8] = [
+        "BT (1) Tj ET",
+        "",
+        "BT (3) Tj ET",
+        "q 0.5 0 0 0.5 0 0 cm 1 g 0 0 612 792 re W n Q",
+        "1 g 0 0 612 792 re f",
+        "BT (6) Tj ET",
+        "0 0 m 100 100 l 200 50 l 300 300 l 400 20 l 500 500 l 600 10 l 610 780 l S",
+        "BT (8) Tj ET",
+    ];
+
+    #[test]
+    #[named]
+    fn delete_blank_pages() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let infile = dir.join("scan.pdf");
+        text_document(&BLANKS).save(&infile).unwrap();
+        let outfile = dir.join("out.pdf");
+        let delete = |threshold: usize, negate: bool| {
+            utils::delete(infile.clone(), Some(outfile.clone()), PageSelection::Blank(threshold), negate, false, false, false, &SaveOptions::default())
+                .map(|_| page_contents(&outfile).into_iter().map(|content| String::from_utf8(content).unwrap()).collect::<Vec<_>>())
+        };
+
+        assert_eq!(delete(64, false).unwrap(), [BLANKS[0], BLANKS[2], BLANKS[5], BLANKS[6], BLANKS[7]]);
+        assert_eq!(delete(64, true).unwrap(), [BLANKS[1], BLANKS[3], BLANKS[4]]);
+        // any line or fill counts without a threshold, a larger one takes the drawing too
+        assert_eq!(delete(0, true).unwrap(), [BLANKS[1], BLANKS[3]]);
+        assert_eq!(delete(100, false).unwrap(), [BLANKS[0], BLANKS[2], BLANKS[5], BLANKS[7]]);
+
+        let all_blank = dir.join("blank.pdf");
+        text_document(&["", "BT ET"]).save(&all_blank).unwrap();
+        match utils::delete(all_blank, None, PageSelection::Blank(64), false, false, false, false, &SaveOptions::default()) {
+            Err(Error::InvalidSelection(reason)) => assert_eq!(reason, "every page is blank, deleting them would leave none"),
+            other => panic!("expected every page to be blank, got {:?}", other),
+        }
+        let twelve = twelve_pages(&dir.join("twelve"));
+        match utils::delete(twelve, None, PageSelection::Blank(64), true, false, false, false, &SaveOptions::default()) {
+            Err(Error::InvalidSelection(reason)) => assert_eq!(reason, "no page is blank, keeping only the blank ones would leave none"),
+            other => panic!("expected no blank pages, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[named]
+    fn delete_blank_cli() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let infile = dir.join("scan.pdf");
+        text_document(&BLANKS).save(&infile).unwrap();
+        let outfile = dir.join("out.pdf");
+        let run = |args: &[&str]| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+                .args([&["delete", infile.to_str().unwrap(), outfile.to_str().unwrap()], args].concat())
+                .output()
+                .unwrap()
+        };
+
+        let output = run(&["--blank", "--blank-threshold", "100"]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("4 of 8 pages are blank"));
+        assert_eq!(page_numbers(&outfile), ["1", "3", "6", "8"]);
+
+        assert_eq!(run(&["--blank", "--pages", "1"]).status.code(), Some(2));
+        assert_eq!(run(&["--pages", "1", "--blank-threshold", "10"]).status.code(), Some(2));
+    }
+
+    #[test]
+    #[named]
+    fn extract_negate() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let outfile = dir.join("out.pdf");
+        let extract = |selection: PageSelection, negate: bool| {
+            utils::extract(infile.clone(), outfile.clone(), selection, negate, false, false, false, false, false, &SaveOptions::default()).unwrap();
+            page_numbers(&outfile)
+        };
+
+        assert_eq!(extract(PageSelection::Pages(vec![3, 7]), false), ["3", "7"]);
+        assert_eq!(extract(PageSelection::Pages(vec![3, 7]), true), ["1", "2", "4", "5", "6", "8", "9", "10", "11", "12"]);
+        assert_eq!(extract(PageSelection::Every(2), false), ["2", "4", "6", "8", "10", "12"]);
+        assert_eq!(extract(PageSelection::Every(2), true), ["1", "3", "5", "7", "9", "11"]);
+    }
+
+    #[test]
+    #[named]
+    fn extract_negate_cli() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let outfile = dir.join("out.pdf");
+        let run = |args: &[&str]| {
+            let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+                .args([&["extract", infile.to_str().unwrap(), outfile.to_str().unwrap()], args].concat())
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+            page_numbers(&outfile)
+        };
+
+        assert_eq!(run(&["--pages", "3", "7", "--negate"]), ["1", "2", "4", "5", "6", "8", "9", "10", "11", "12"]);
+        assert_eq!(run(&["--pages", "-10", "--negate"]), ["11", "12"]);
+        assert_eq!(run(&["--every", "3", "--negate"]), ["1", "2", "4", "5", "7", "8", "10", "11"]);
+    }
+
+    #[test]
+    #[named]
+    fn extract_in_the_listed_order_cli() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let outfile = dir.join("out.pdf");
+        let run = |args: &[&str]| {
+            let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+                .args([&["extract", infile.to_str().unwrap(), outfile.to_str().unwrap()], args].concat())
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+            page_numbers(&outfile)
+        };
+
+        assert_eq!(run(&["--pages", "7", "2", "5"]), ["7", "2", "5"]);
+        assert_eq!(run(&["--pages", "1", "1", "2"]), ["1", "1", "2"]);
+        assert_eq!(run(&["--pages", "11-,1-2"]), ["11", "12", "1", "2"]);
+        // a page that does not exist is left out of the order too
+        assert_eq!(run(&["--pages", "12", "20", "3"]), ["12", "3"]);
+    }
+
+    #[test]
+    #[named]
+    fn split_selected_pages() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let naming = utils::SplitNaming::Outfile(dir.join("out").join("out.pdf"));
+        let written = || {
+            let mut names: Vec<String> = std::fs::read_dir(dir.join("out")).unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let report = utils::split(infile.clone(), &naming, PageSelection::Pages(vec![9, 2, 5]), None, true, false, false, false).unwrap();
+        assert_eq!(written(), ["out-002.pdf", "out-005.pdf", "out-009.pdf"]);
+        assert_eq!((report.pages_in, report.pages_out), (12, 3));
+        for page in ["002", "005", "009"] {
+            assert_eq!(page_numbers(&dir.join("out").join(format!("out-{}.pdf", page))), [page.trim_start_matches('0')]);
+        }
+
+        std::fs::remove_dir_all(dir.join("out")).unwrap();
+        utils::split(infile.clone(), &naming, PageSelection::Every(5), None, true, false, false, false).unwrap();
+        assert_eq!(written(), ["out-005.pdf", "out-010.pdf"]);
+
+        match utils::split(infile.clone(), &naming, PageSelection::Pages(vec![3, 13]), None, true, false, false, true) {
+            Err(Error::InvalidSelection(reason)) => assert_eq!(reason, "the document has 12 pages, there is no page 13"),
+            other => panic!("expected page 13 to be missing, got {:?}", other),
+        }
+        match utils::split(infile, &naming, PageSelection::Pages(vec![13]), None, true, false, false, false) {
+            Err(Error::InvalidSelection(reason)) => assert_eq!(reason, "no page is selected, there is nothing to write"),
+            other => panic!("expected nothing to be written, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[named]
+    fn split_selected_pages_cli() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let run = |args: &[&str]| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+                .args([&["split", infile.to_str().unwrap(), dir.join("part.pdf").to_str().unwrap()], args].concat())
+                .output()
+                .unwrap()
+        };
+
+        let output = run(&["--pages", "2", "5", "9"]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let mut parts: Vec<String> = std::fs::read_dir(&dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("part-"))
+            .collect();
+        parts.sort();
+        assert_eq!(parts, ["part-002.pdf", "part-005.pdf", "part-009.pdf"]);
+        assert!(parts.iter().all(|part| lopdf::Document::load(dir.join(part)).unwrap().get_pages().len() == 1));
+
+        assert_eq!(run(&["--pages", "2", "--odd", "odd.pdf"]).status.code(), Some(2));
+        assert_eq!(run(&["--every", "2", "--ranges", "1-3:a.pdf"]).status.code(), Some(2));
+    }
+
+    // three pages, titled in an information dictionary held directly by the trailer, with XMP
+    fn titled_document(path: &Path) -> PathBuf {
+        let mut doc = text_document(&["BT (1) Tj ET", "BT (2) Tj ET", "BT (3) Tj ET"]);
+        doc.trailer.set("Info", dictionary! {
+            "Title" => lopdf::Object::string_literal("Annual report"),
+            "Producer" => lopdf::Object::string_literal("scanner 2.1"),
+        });
+        let xmp = doc.add_object(lopdf::Stream::new(dictionary! { "Type" => "Metadata", "Subtype" => "XML" }, b"<x:xmpmeta/>".to_vec()));
+        let root_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        doc.get_object_mut(root_id).and_then(lopdf::Object::as_dict_mut).unwrap().set("Metadata", xmp);
+        doc.save(path).unwrap();
+        path.to_path_buf()
+    }
+
+    // The value of a key of the information dictionary, and whether the catalog has XMP metadata
+    fn document_metadata(path: &Path, key: &[u8]) -> (Option<String>, bool) {
+        let doc = lopdf::Document::load(path).unwrap();
+        let value = doc.trailer.get(b"Info").ok()
+            .and_then(|info| doc.dereference(info).ok())
+            .and_then(|(_, info)| info.as_dict().ok()?.get(key).ok()?.as_str().ok())
+            .map(|value| String::from_utf8_lossy(value).into_owned());
+        (value, doc.catalog().unwrap().has(b"Metadata"))
+    }
+
+    #[test]
+    #[named]
+    fn extract_and_split_keep_the_document_information() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let infile = titled_document(&dir.join("report.pdf"));
+        let titled = (Some("Annual report".to_string()), true);
+
+        let extracted = dir.join("page.pdf");
+        utils::extract(infile.clone(), extracted.clone(), PageSelection::Pages(vec![2]), false, false, false, false, false, false, &SaveOptions::default()).unwrap();
+        assert_eq!(document_metadata(&extracted, b"Title"), titled);
+        assert_eq!(document_metadata(&extracted, b"Producer").0.as_deref(), Some("scanner 2.1"));
+
+        utils::split(infile.clone(), &utils::SplitNaming::Outfile(dir.join("part.pdf")), PageSelection::All, None, false, false, false, false).unwrap();
+        assert_eq!(document_metadata(&dir.join("part-003.pdf"), b"Title"), titled);
+
+        // merge takes the information of the first input, setting a field keeps the others
+        let merged = dir.join("merged.pdf");
+        let info = utils::DocumentInfo { author: Some("Finance".to_string()), ..utils::DocumentInfo::default() };
+        utils::merge(&vec![infile.clone(), extracted.clone()], merged.clone(), false, false, utils::SortOrder::Name, false, false, None, false, &info).unwrap();
+        assert_eq!(document_metadata(&merged, b"Title"), titled);
+        assert_eq!(document_metadata(&merged, b"Author").0.as_deref(), Some("Finance"));
+
+        utils::extract(infile.clone(), extracted.clone(), PageSelection::Pages(vec![2]), false, false, false, false, true, false, &SaveOptions::default()).unwrap();
+        assert_eq!(document_metadata(&extracted, b"Title"), (None, false));
+        utils::split_ranges(infile, &dir, "1-2:front.pdf", false, true, false).unwrap();
+        assert_eq!(document_metadata(&dir.join("front.pdf"), b"Title"), (None, false));
+    }
+
+    #[test]
+    #[named]
+    fn strip_metadata_cli() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let infile = titled_document(&dir.join("report.pdf"));
+        let run = |args: &[&str]| {
+            let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh")).args(args).output().unwrap();
+            assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        };
+        let (infile, page) = (infile.to_str().unwrap(), dir.join("page.pdf"));
+
+        run(&["extract", infile, page.to_str().unwrap(), "--pages", "1"]);
+        assert_eq!(document_metadata(&page, b"Title"), (Some("Annual report".to_string()), true));
+        run(&["extract", infile, page.to_str().unwrap(), "--pages", "1", "--strip-metadata"]);
+        assert_eq!(document_metadata(&page, b"Title"), (None, false));
+        run(&["split", infile, "--odd", dir.join("odd.pdf").to_str().unwrap(), "--strip-metadata"]);
+        assert_eq!(document_metadata(&dir.join("odd.pdf"), b"Title"), (None, false));
+    }
+
+    #[test]
+    #[named]
+    fn move_pages() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let outfile = dir.join("out.pdf");
+        let run = |spec: &str, to: &str| {
+            let range = utils::parse_page_run(spec).unwrap();
+            let to = utils::parse_position(to).unwrap();
+            utils::move_pages(infile.clone(), Some(outfile.clone()), range, to, &SaveOptions::default()).map(|_| page_numbers(&outfile))
+        };
+
+        assert_eq!(run("10-12", "1").unwrap(), ["10", "11", "12", "1", "2", "3", "4", "5", "6", "7", "8", "9"]);
+        assert_eq!(run("10-12", "3").unwrap(), ["1", "2", "10", "11", "12", "3", "4", "5", "6", "7", "8", "9"]);
+        assert_eq!(run("2-3", "end").unwrap(), ["1", "4", "5", "6", "7", "8", "9", "10", "11", "12", "2", "3"]);
+        // positions count the pages left once the moved ones are taken out
+        assert_eq!(run("-2", "10").unwrap(), ["3", "4", "5", "6", "7", "8", "9", "10", "11", "1", "2", "12"]);
+        assert_eq!(run("11-", "11").unwrap(), ["1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12"]);
+        assert_eq!(run("5", "12").unwrap(), ["1", "2", "3", "4", "6", "7", "8", "9", "10", "11", "12", "5"]);
+
+        assert!(matches!(run("5", "13"), Err(Error::InvalidSelection(_))));
+        assert!(matches!(run("11-14", "1"), Err(Error::InvalidSelection(_))));
+        assert_eq!(utils::parse_page_run("1-2,5").unwrap_err(), "`1-2,5` is more than one run of pages, move them one run at a time");
+        assert_eq!(utils::parse_position("0").unwrap_err(), "`0` selects page 0, pages are numbered from 1");
+        assert_eq!(utils::parse_position("End").unwrap(), Position::End);
+    }
+
+    #[test]
+    #[named]
+    fn move_pages_of_a_nested_page_tree() {
+        let test_resource: TestResources = TestResources::new();
+        let outfile = build_outfile_pathbuf(function_name!());
+        let before: Vec<_> = lopdf::Document::load(&test_resource.multi_page_multiple_pages_obj).unwrap().get_pages().into_values().collect();
+
+        let range = PageRange { first: 1, last: Some(2) };
+        utils::move_pages(test_resource.multi_page_multiple_pages_obj, Some(outfile.clone()), range, Position::End, &SaveOptions::default()).unwrap();
+
+        let after: Vec<_> = lopdf::Document::load(&outfile).unwrap().get_pages().into_values().collect();
+        assert_eq!(after, [&before[2..], &before[..2]].concat());
+    }
+
+    #[test]
+    #[named]
+    fn move_pages_cli() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let run = |args: &[&str]| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+                .args([&["move", infile.to_str().unwrap()], args].concat())
+                .output()
+                .unwrap()
+        };
+
+        // edits the file inplace without an outfile
+        let output = run(&["--pages", "-3", "--to", "end"]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(page_numbers(&infile), ["4", "5", "6", "7", "8", "9", "10", "11", "12", "1", "2", "3"]);
+
+        assert_eq!(run(&["--pages", "1,3", "--to", "2"]).status.code(), Some(2));
+        assert_eq!(run(&["--pages", "1", "--to", "first"]).status.code(), Some(2));
+        assert!(!run(&["--pages", "1", "--to", "13"]).status.success());
+    }
+
+    fn media_boxes(path: &Path) -> Vec<Vec<f64>> {
+        let doc = lopdf::Document::load(path).unwrap();
+        doc.page_iter().map(|id| {
+            let page = doc.get_dictionary(id).unwrap();
+            page.get(b"MediaBox").unwrap().as_array().unwrap().iter().map(|v| v.as_float().unwrap()).collect()
+        }).collect()
+    }
+
+    #[test]
+    #[named]
+    fn insert_blank_pages() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        // page 3 in landscape, for the blanks that match it
+        let mut doc = lopdf::Document::load(&infile).unwrap();
+        let third = doc.page_iter().nth(2).unwrap();
+        doc.get_object_mut(third).and_then(lopdf::Object::as_dict_mut).unwrap().set("MediaBox", vec![0.into(), 0.into(), 842.into(), 595.into()]);
+        doc.save(&infile).unwrap();
+        let outfile = dir.join("out.pdf");
+
+        let report = utils::insert_blank_pages(infile.clone(), Some(outfile.clone()), &[3, 7], &[], 2, PageSize::Match, &SaveOptions::default()).unwrap();
+        assert_eq!((report.pages_in, report.pages_out), (12, 16));
+        let pages = page_numbers(&outfile);
+        assert_eq!(pages[..10], ["1", "2", "3", "", "", "4", "5", "6", "7", ""]);
+        let boxes = media_boxes(&outfile);
+        assert_eq!(boxes[3], [0.0, 0.0, 842.0, 595.0]);
+        assert_eq!(boxes[4], [0.0, 0.0, 842.0, 595.0]);
+
+        utils::insert_blank_pages(infile.clone(), Some(outfile.clone()), &[12], &[1], 1, PageSize::Letter, &SaveOptions::default()).unwrap();
+        let pages = page_numbers(&outfile);
+        assert_eq!(pages.len(), 14);
+        assert_eq!((pages[0].as_str(), pages[1].as_str(), pages[13].as_str()), ("", "1", ""));
+        let boxes = media_boxes(&outfile);
+        assert_eq!(boxes[0], [0.0, 0.0, 612.0, 792.0]);
+        assert_eq!(boxes[13], [0.0, 0.0, 612.0, 792.0]);
+
+        assert!(matches!(utils::insert_blank_pages(infile.clone(), Some(outfile.clone()), &[13], &[], 1, PageSize::A4, &SaveOptions::default()),
+                         Err(Error::InvalidSelection(_))));
+    }
+
+    #[test]
+    #[named]
+    fn insert_blank_pages_cli() {
+        let dir = PathBuf::from(format!("{}/output/{}", DATA_DIR, function_name!()));
+        let infile = twelve_pages(&dir);
+        let outfile = dir.join("out.pdf");
+        let run = |args: &[&str]| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+                .args([&["blank", infile.to_str().unwrap(), outfile.to_str().unwrap()], args].concat())
+                .output()
+                .unwrap()
+        };
+
+        let output = run(&["--after", "3", "--after", "7", "--count", "1", "--size", "A4"]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(page_numbers(&outfile).len(), 14);
+        assert_eq!(media_boxes(&outfile)[3], [0.0, 0.0, 595.276, 841.89]);
+
+        assert!(!run(&["--after", "20"]).status.success());
+        assert_eq!(run(&["--size", "A4"]).status.code(), Some(2));
+        assert_eq!(run(&["--after", "3", "--size", "A5"]).status.code(), Some(2));
+        assert_eq!(run(&["--after", "3", "--count", "0"]).status.code(), Some(2));
     }
-}
\ No newline at end of file
+}
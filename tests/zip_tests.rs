@@ -0,0 +1,191 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::DeflateDecoder;
+    use lopdf::Document;
+    use pdfh::utils;
+    use pdfh::zip::{ZipArchive, ZipCompression, ZipWriter};
+    use ::function_name::named;
+
+    use crate::common::{build_filepath, build_outfile_pathbuf, text_document};
+
+    // (name, contents) of every entry, read from the local headers and checked against the
+    // central directory
+    fn unzip(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let u16_at = |at: usize| u16::from_le_bytes([archive[at], archive[at + 1]]) as usize;
+        let u32_at = |at: usize| u32::from_le_bytes(archive[at..at + 4].try_into().unwrap()) as usize;
+
+        let mut entries = Vec::new();
+        let mut at = 0;
+        while u32_at(at) == 0x04034b50 {
+            let method = u16_at(at + 8);
+            let crc = u32_at(at + 14) as u32;
+            let compressed = u32_at(at + 18);
+            let name_length = u16_at(at + 26);
+            let start = at + 30 + name_length + u16_at(at + 28);
+
+            let name = String::from_utf8(archive[at + 30..at + 30 + name_length].to_vec()).unwrap();
+            let stored = &archive[start..start + compressed];
+            let data = match method {
+                0 => stored.to_vec(),
+                8 => {
+                    let mut data = Vec::new();
+                    DeflateDecoder::new(stored).read_to_end(&mut data).unwrap();
+                    data
+                }
+                other => panic!("unexpected method {}", other),
+            };
+            assert_eq!(data.len(), u32_at(at + 22));
+            assert_eq!(crc32fast::hash(&data), crc);
+            entries.push((name, data));
+            at = start + compressed;
+        }
+
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(end), 0x06054b50);
+        assert_eq!(u16_at(end + 10), entries.len());
+        assert_eq!(u32_at(end + 16), at, "central directory follows the entries");
+        entries
+    }
+
+    fn page(text: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        text_document(&[&format!("BT /F1 12 Tf 72 700 Td ({}) Tj ET", text)]).save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn archive(compression: ZipCompression) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Vec::new(), compression);
+        zip.add("pg_0001.pdf", &page("one")).unwrap();
+        zip.add("pages/pg_0002.pdf", &page("two")).unwrap();
+        zip.add("manifest.json", b"[]").unwrap();
+        zip.finish().unwrap()
+    }
+
+    #[test]
+    fn entries_load_as_single_pages() {
+        for compression in [ZipCompression::Store, ZipCompression::Deflate] {
+            let entries = unzip(&archive(compression));
+
+            let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+            assert_eq!(names, vec!["pg_0001.pdf", "pages/pg_0002.pdf", "manifest.json"]);
+            for (_, data) in &entries[..2] {
+                assert_eq!(Document::load_mem(data).unwrap().get_pages().len(), 1);
+            }
+            assert_eq!(entries[2].1, b"[]");
+        }
+    }
+
+    #[test]
+    fn deflate_is_smaller() {
+        assert!(archive(ZipCompression::Deflate).len() < archive(ZipCompression::Store).len());
+    }
+
+    #[test]
+    fn empty_archive() {
+        let archive = ZipWriter::new(Vec::new(), ZipCompression::Store).finish().unwrap();
+        assert!(unzip(&archive).is_empty());
+        assert_eq!(archive.len(), 22);
+    }
+
+    #[test]
+    fn archive_reads_back() {
+        for compression in [ZipCompression::Store, ZipCompression::Deflate] {
+            let archive = ZipArchive::new(archive(compression)).unwrap();
+
+            let names: Vec<&str> = archive.entries().iter().map(|entry| entry.name.as_str()).collect();
+            assert_eq!(names, vec!["pg_0001.pdf", "pages/pg_0002.pdf", "manifest.json"]);
+            assert_eq!(archive.read(&archive.entries()[0]).unwrap(), page("one"));
+            assert_eq!(archive.read(&archive.entries()[2]).unwrap(), b"[]");
+        }
+    }
+
+    #[test]
+    fn damaged_entry_is_reported() {
+        let mut data = archive(ZipCompression::Store);
+        data[60] ^= 0xFF;
+        let archive = ZipArchive::new(data).unwrap();
+
+        assert_eq!(archive.read(&archive.entries()[0]).unwrap_err(), "pg_0001.pdf is damaged, its checksum does not match");
+        assert!(ZipArchive::new(b"not a zip".to_vec()).is_err());
+    }
+
+    #[test]
+    #[named]
+    fn merge_zip_with_loose_file() {
+        let zipfile = build_outfile_pathbuf(&format!("{}-input", function_name!())).with_extension("zip");
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        // natural order puts 2.pdf before 10.pdf, nested and non-PDF entries are left out
+        let mut zip = ZipWriter::new(std::fs::File::create(&zipfile).unwrap(), ZipCompression::Deflate);
+        zip.add("10.pdf", &std::fs::read(build_filepath("two-pages.pdf")).unwrap()).unwrap();
+        zip.add("2.pdf", &page("second")).unwrap();
+        zip.add("nested/1.pdf", &page("nested")).unwrap();
+        zip.add("readme.txt", b"scanned").unwrap();
+        zip.finish().unwrap();
+
+        let report = utils::merge(&vec![zipfile, build_filepath("single-pages-object-multi-page.pdf")], outfile.clone(), false, false, utils::SortOrder::Name, false, false, None, false, &utils::DocumentInfo::default()).unwrap();
+
+        let merged = Document::load(&outfile).unwrap();
+        assert_eq!(merged.get_pages().len(), 1 + 2 + 4);
+        assert_eq!(report.pages_in, 7);
+        assert!(merged.extract_text(&[1]).unwrap().contains("second"));
+        assert!(!merged.extract_text(&[2]).unwrap().contains("second"));
+    }
+
+    #[test]
+    #[named]
+    fn merge_zip_recursive() {
+        let zipfile = build_outfile_pathbuf(&format!("{}-input", function_name!())).with_extension("zip");
+        let outfile = build_outfile_pathbuf(function_name!());
+
+        // each directory in natural order, a subdirectory's PDFs where its name sorts
+        let mut zip = ZipWriter::new(std::fs::File::create(&zipfile).unwrap(), ZipCompression::Deflate);
+        zip.add("c.pdf", &page("fourth")).unwrap();
+        zip.add("b/10.pdf", &page("third")).unwrap();
+        zip.add("b/2.pdf", &page("second")).unwrap();
+        zip.add("a.pdf", &page("first")).unwrap();
+        zip.add("d/e/1.pdf", &page("fifth")).unwrap();
+        zip.finish().unwrap();
+
+        utils::merge(&vec![zipfile], outfile.clone(), false, true, utils::SortOrder::Name, false, false, None, false, &utils::DocumentInfo::default()).unwrap();
+
+        let merged = Document::load(&outfile).unwrap();
+        assert_eq!(merged.get_pages().len(), 5);
+        for (number, text) in ["first", "second", "third", "fourth", "fifth"].iter().enumerate() {
+            assert!(merged.extract_text(&[number as u32 + 1]).unwrap().contains(text), "page {} is not {}", number + 1, text);
+        }
+    }
+
+    #[test]
+    #[named]
+    fn split_into_zip() {
+        let zipfile = build_outfile_pathbuf(function_name!()).with_extension("zip");
+        let infile = build_filepath("single-pages-object-multi-page.pdf");
+        let count = Document::load(&infile).unwrap().get_pages().len();
+
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_pdfh"))
+            .args(["--json", "split"]).arg(&infile)
+            .args(["--pattern", "pages/{stem}-{page:02}.pdf", "--zip"]).arg(&zipfile)
+            .args(["--zip-compression", "deflate"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let entries = unzip(&std::fs::read(&zipfile).unwrap());
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        let mut expected: Vec<String> = (1..=count).map(|page| format!("pages/single-pages-object-multi-page-{:02}.pdf", page)).collect();
+        expected.push("manifest.json".to_string());
+        assert_eq!(names, expected);
+        for (_, data) in &entries[..count] {
+            assert_eq!(Document::load_mem(data).unwrap().get_pages().len(), 1);
+        }
+        let manifest: serde_json::Value = serde_json::from_slice(&entries[count].1).unwrap();
+        assert_eq!(manifest[1]["name"], expected[1].as_str());
+        assert_eq!(manifest[1]["page"], 2);
+        assert!(!std::path::Path::new("pages").exists(), "no loose files are written");
+    }
+}